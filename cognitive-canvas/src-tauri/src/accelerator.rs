@@ -0,0 +1,353 @@
+use serde::Serialize;
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Modifier {
+    /// Cmd on macOS, Ctrl elsewhere
+    CmdOrCtrl,
+    Cmd,
+    Ctrl,
+    Alt,
+    Shift,
+}
+
+/// A parsed shortcut like "Cmd+Shift+F": modifiers plus exactly one key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Accelerator {
+    pub modifiers: Vec<Modifier>,
+    pub key: String,
+}
+
+/// Most accelerators a chord may chain, e.g. "Ctrl+K Ctrl+S" has two.
+pub const MAX_CHORD_STEPS: usize = 3;
+
+/// A shortcut of one or more accelerators pressed one after another,
+/// written space-separated like "Ctrl+K Ctrl+S".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeySequence {
+    pub steps: Vec<Accelerator>,
+}
+
+const NAMED_KEYS: &[&str] = &[
+    "Enter", "Escape", "Tab", "Space", "Backspace", "Delete", "Insert", "Home", "End", "PageUp",
+    "PageDown", "Up", "Down", "Left", "Right", "Plus",
+];
+
+const PUNCTUATION_KEYS: &str = "=-[]\\;',./`";
+
+fn parse_modifier(name: &str) -> Option<Modifier> {
+    match name.to_lowercase().as_str() {
+        "cmdorctrl" | "commandorcontrol" => Some(Modifier::CmdOrCtrl),
+        "cmd" | "command" | "super" | "meta" => Some(Modifier::Cmd),
+        "ctrl" | "control" => Some(Modifier::Ctrl),
+        "alt" | "option" => Some(Modifier::Alt),
+        "shift" => Some(Modifier::Shift),
+        _ => None,
+    }
+}
+
+/// Normalizes a key name, e.g. "esc" -> "Escape", "a" -> "A", "f5" -> "F5".
+fn parse_key(name: &str) -> Option<String> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_alphanumeric() {
+            return Some(c.to_ascii_uppercase().to_string());
+        }
+        if PUNCTUATION_KEYS.contains(c) {
+            return Some(c.to_string());
+        }
+        return None;
+    }
+
+    let lower = name.to_lowercase();
+    if let Some(number) = lower.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+        return (1..=24).contains(&number).then(|| format!("F{}", number));
+    }
+    let alias = match lower.as_str() {
+        "esc" => "escape",
+        "return" => "enter",
+        "del" => "delete",
+        "arrowup" => "up",
+        "arrowdown" => "down",
+        "arrowleft" => "left",
+        "arrowright" => "right",
+        other => other,
+    };
+    NAMED_KEYS
+        .iter()
+        .find(|key| key.to_lowercase() == alias)
+        .map(|key| key.to_string())
+}
+
+impl Accelerator {
+    /// Parses and validates an accelerator for use on `platform` (as in
+    /// `std::env::consts::OS`).
+    pub fn parse(value: &str, platform: &str) -> AppResult<Self> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Err(AppError::validation("Shortcut can't be empty"));
+        }
+
+        // "Cmd++" means Cmd and the plus key
+        let parts: Vec<&str> = match value.strip_suffix("++") {
+            Some(rest) => rest.split('+').chain(["Plus"]).collect(),
+            None => value.split('+').collect(),
+        };
+        let (key_name, modifier_names) = parts.split_last().ok_or_else(|| AppError::validation("Shortcut can't be empty"))?;
+
+        let mut modifiers = Vec::new();
+        for name in modifier_names {
+            let modifier = parse_modifier(name.trim()).ok_or_else(|| match parse_key(name.trim()) {
+                Some(_) => AppError::validation(format!("Only one key is allowed, found {} before {}", name.trim(), key_name.trim())),
+                None => AppError::validation(format!("Unknown modifier {}", name.trim())),
+            })?;
+            if modifiers.contains(&modifier) {
+                return Err(AppError::validation(format!("{} is listed twice", name.trim())));
+            }
+            modifiers.push(modifier);
+        }
+        modifiers.sort();
+
+        let key = parse_key(key_name.trim()).ok_or_else(|| match parse_modifier(key_name.trim()) {
+            Some(_) => AppError::validation("A shortcut needs a key besides its modifiers"),
+            None => AppError::validation(format!("Unknown key {}", key_name.trim())),
+        })?;
+
+        let accelerator = Accelerator { modifiers, key };
+        accelerator.check_platform(platform)?;
+        Ok(accelerator)
+    }
+
+    /// Like `parse`, but also accepts accelerators saved on another
+    /// platform: macOS's Cmd is read as CmdOrCtrl, so it becomes Ctrl here.
+    pub fn parse_portable(value: &str, platform: &str) -> AppResult<Self> {
+        let error = match Self::parse(value, platform) {
+            Ok(accelerator) => return Ok(accelerator),
+            Err(error) => error,
+        };
+        let accelerator = Self::parse(value, "macos").map_err(|_| error)?.portable("macos");
+        accelerator.check_platform(platform)?;
+        Ok(accelerator)
+    }
+
+    fn has(&self, modifier: Modifier) -> bool {
+        self.modifiers.contains(&modifier)
+    }
+
+    fn check_platform(&self, platform: &str) -> AppResult<()> {
+        let function_key = self.key.starts_with('F') && self.key.len() > 1;
+        let only_shift = self.modifiers.iter().all(|m| *m == Modifier::Shift);
+        // Plain or shifted keys would fire while typing
+        if only_shift && !function_key && self.key != "Escape" {
+            return Err(AppError::validation("Add Cmd, Ctrl or Alt so the shortcut doesn't trigger while typing"));
+        }
+
+        let macos = platform == "macos";
+        if !macos && self.has(Modifier::Cmd) {
+            return Err(AppError::validation("Cmd only exists on macOS; use CmdOrCtrl or Ctrl"));
+        }
+        if !macos && self.has(Modifier::CmdOrCtrl) && self.has(Modifier::Ctrl) {
+            return Err(AppError::validation("CmdOrCtrl already means Ctrl on this platform"));
+        }
+        if macos && self.has(Modifier::CmdOrCtrl) && self.has(Modifier::Cmd) {
+            return Err(AppError::validation("CmdOrCtrl already means Cmd on macOS"));
+        }
+
+        Ok(())
+    }
+
+    /// The same shortcut with CmdOrCtrl spelled out for `platform`, so two
+    /// bindings can be compared.
+    pub fn resolve(&self, platform: &str) -> Accelerator {
+        let command = if platform == "macos" { Modifier::Cmd } else { Modifier::Ctrl };
+        let mut modifiers: Vec<Modifier> = self
+            .modifiers
+            .iter()
+            .map(|m| if *m == Modifier::CmdOrCtrl { command } else { *m })
+            .collect();
+        modifiers.sort();
+        modifiers.dedup();
+        Accelerator { modifiers, key: self.key.clone() }
+    }
+
+    /// The reverse of `resolve`: `platform`'s command key becomes CmdOrCtrl,
+    /// which is how shortcuts are stored so they work on every platform.
+    pub fn portable(&self, platform: &str) -> Accelerator {
+        let command = if platform == "macos" { Modifier::Cmd } else { Modifier::Ctrl };
+        let mut modifiers: Vec<Modifier> = self
+            .modifiers
+            .iter()
+            .map(|m| if *m == command { Modifier::CmdOrCtrl } else { *m })
+            .collect();
+        modifiers.sort();
+        modifiers.dedup();
+        Accelerator { modifiers, key: self.key.clone() }
+    }
+
+    /// Whether the OS handles this shortcut itself, so the app would never
+    /// see it.
+    pub fn is_reserved(&self, platform: &str) -> bool {
+        let resolved = self.resolve(platform);
+        let key = resolved.key.as_str();
+        if platform == "macos" {
+            // Quit, app switcher, Spotlight, hide, minimize
+            resolved.modifiers == [Modifier::Cmd] && ["Q", "Tab", "Space", "H", "M"].contains(&key)
+        } else {
+            // Close window, task switcher, task manager, lock screen
+            (resolved.modifiers == [Modifier::Alt] && ["F4", "Tab"].contains(&key))
+                || (resolved.modifiers == [Modifier::Ctrl, Modifier::Alt] && key == "Delete")
+                || (resolved.modifiers == [Modifier::Ctrl, Modifier::Shift] && key == "Escape")
+                || (resolved.modifiers == [Modifier::Cmd] && key == "L")
+        }
+    }
+}
+
+impl std::fmt::Display for Accelerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for modifier in &self.modifiers {
+            let name = match modifier {
+                Modifier::CmdOrCtrl => "CmdOrCtrl",
+                Modifier::Cmd => "Cmd",
+                Modifier::Ctrl => "Ctrl",
+                Modifier::Alt => "Alt",
+                Modifier::Shift => "Shift",
+            };
+            write!(f, "{}+", name)?;
+        }
+        f.write_str(&self.key)
+    }
+}
+
+impl KeySequence {
+    pub fn parse(value: &str, platform: &str) -> AppResult<Self> {
+        Self::parse_steps(value, |step| Accelerator::parse(step, platform))
+    }
+
+    /// `Accelerator::parse_portable` for every step.
+    pub fn parse_portable(value: &str, platform: &str) -> AppResult<Self> {
+        Self::parse_steps(value, |step| Accelerator::parse_portable(step, platform))
+    }
+
+    fn parse_steps<F>(value: &str, parse_step: F) -> AppResult<Self>
+    where
+        F: Fn(&str) -> AppResult<Accelerator>,
+    {
+        // "Ctrl + K" is one step; only spaces between accelerators separate steps
+        let value = value.split('+').map(str::trim).collect::<Vec<_>>().join("+");
+        let parts: Vec<&str> = value.split_whitespace().collect();
+        if parts.is_empty() {
+            return Err(AppError::validation("Shortcut can't be empty"));
+        }
+        if parts.len() > MAX_CHORD_STEPS {
+            return Err(AppError::validation(format!("A chord can have at most {} steps", MAX_CHORD_STEPS)));
+        }
+
+        let chord = parts.len() > 1;
+        let steps = parts
+            .iter()
+            .enumerate()
+            .map(|(i, part)| parse_step(part).map_err(|e| if chord { AppError::validation(format!("Step {}: {}", i + 1, e)) } else { e }))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(KeySequence { steps })
+    }
+
+    pub fn is_chord(&self) -> bool {
+        self.steps.len() > 1
+    }
+
+    pub fn resolve(&self, platform: &str) -> KeySequence {
+        KeySequence { steps: self.steps.iter().map(|s| s.resolve(platform)).collect() }
+    }
+
+    pub fn portable(&self, platform: &str) -> KeySequence {
+        KeySequence { steps: self.steps.iter().map(|s| s.portable(platform)).collect() }
+    }
+
+    /// Only the first step can be taken by the OS; once a chord has started
+    /// the app sees the rest.
+    pub fn is_reserved(&self, platform: &str) -> bool {
+        self.steps.first().is_some_and(|step| step.is_reserved(platform))
+    }
+
+    /// Whether `prefix` is this sequence or its first steps.
+    pub fn starts_with(&self, prefix: &KeySequence) -> bool {
+        self.steps.starts_with(&prefix.steps)
+    }
+}
+
+impl std::fmt::Display for KeySequence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, step) in self.steps.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_accelerators() {
+        let parsed = Accelerator::parse("shift+cmd+f", "macos").unwrap();
+        assert_eq!(parsed.to_string(), "Cmd+Shift+F");
+        assert_eq!(Accelerator::parse("Cmd++", "macos").unwrap().key, "Plus");
+        assert_eq!(Accelerator::parse("Ctrl+esc", "linux").unwrap().key, "Escape");
+        assert!(Accelerator::parse("F5", "windows").is_ok());
+
+        assert!(Accelerator::parse("Shift+A", "macos").is_err());
+        assert!(Accelerator::parse("Cmd+A+B", "macos").is_err());
+        assert!(Accelerator::parse("Ctrl+Shift", "linux").is_err());
+        assert!(Accelerator::parse("Ctrl+Ctrl+S", "linux").is_err());
+        assert!(Accelerator::parse("Cmd+S", "windows").is_err());
+    }
+
+    #[test]
+    fn test_resolve_and_reserved() {
+        let quit = Accelerator::parse("CmdOrCtrl+Q", "macos").unwrap();
+        assert_eq!(quit.resolve("macos").to_string(), "Cmd+Q");
+        assert_eq!(quit.resolve("linux").to_string(), "Ctrl+Q");
+        assert!(quit.is_reserved("macos"));
+        assert!(!quit.is_reserved("linux"));
+        assert!(Accelerator::parse("Alt+F4", "windows").unwrap().is_reserved("windows"));
+    }
+
+    #[test]
+    fn test_portable_accelerators() {
+        let save = Accelerator::parse("Ctrl+Shift+S", "linux").unwrap();
+        assert_eq!(save.portable("linux").to_string(), "CmdOrCtrl+Shift+S");
+        let tab = Accelerator::parse("Ctrl+Tab", "macos").unwrap();
+        assert_eq!(tab.portable("macos").to_string(), "Ctrl+Tab");
+
+        let from_mac = Accelerator::parse_portable("Cmd+Alt+F", "windows").unwrap();
+        assert_eq!(from_mac.resolve("windows").to_string(), "Ctrl+Alt+F");
+        assert_eq!(Accelerator::parse_portable("Cmd+S", "macos").unwrap().to_string(), "Cmd+S");
+        assert!(Accelerator::parse_portable("Cmd+Ctrl+S", "linux").is_err());
+        assert!(Accelerator::parse_portable("Shift+S", "linux").is_err());
+    }
+
+    #[test]
+    fn test_key_sequences() {
+        let chord = KeySequence::parse("ctrl+k  Ctrl + S", "linux").unwrap();
+        assert!(chord.is_chord());
+        assert_eq!(chord.to_string(), "Ctrl+K Ctrl+S");
+        assert_eq!(KeySequence::parse("Cmd++", "macos").unwrap().to_string(), "Cmd+Plus");
+
+        let prefix = KeySequence::parse("CmdOrCtrl+K", "linux").unwrap();
+        assert!(chord.starts_with(&prefix.resolve("linux")));
+        assert!(!prefix.starts_with(&chord));
+
+        assert_eq!(KeySequence::parse("Ctrl+K Q", "linux").unwrap_err().to_string(), "Step 2: Add Cmd, Ctrl or Alt so the shortcut doesn't trigger while typing");
+        assert!(KeySequence::parse("Ctrl+A Ctrl+B Ctrl+C Ctrl+D", "linux").is_err());
+        assert!(KeySequence::parse("   ", "linux").is_err());
+
+        let from_mac = KeySequence::parse_portable("Cmd+K Cmd+S", "windows").unwrap();
+        assert_eq!(from_mac.resolve("windows").to_string(), "Ctrl+K Ctrl+S");
+        assert_eq!(from_mac.portable("windows").to_string(), "CmdOrCtrl+K CmdOrCtrl+S");
+    }
+}
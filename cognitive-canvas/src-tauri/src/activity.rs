@@ -0,0 +1,172 @@
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::settings_manager::{self, SettingsState};
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    DocumentOpened,
+    DocumentCreated,
+    WordsAdded,
+}
+
+/// One line of activity_journal.jsonl.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityEntry {
+    pub timestamp: DateTime<Local>,
+    /// Identifies the app launch, so entries can be grouped by session
+    pub session: String,
+    pub kind: ActivityKind,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub words: Option<i64>,
+}
+
+/// Inclusive date range; an open end includes everything on that side.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ActivityRange {
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+}
+
+impl ActivityRange {
+    fn contains(&self, date: NaiveDate) -> bool {
+        self.start.is_none_or(|start| date >= start) && self.end.is_none_or(|end| date <= end)
+    }
+}
+
+pub struct ActivityState {
+    session: String,
+    /// Serializes appends so concurrent saves don't interleave lines
+    write_lock: Mutex<()>,
+}
+
+impl Default for ActivityState {
+    fn default() -> Self {
+        Self {
+            session: Local::now().format("%Y%m%dT%H%M%S").to_string(),
+            write_lock: Mutex::new(()),
+        }
+    }
+}
+
+fn get_journal_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir.join("activity_journal.jsonl"))
+}
+
+/// Whether the user turned the journal on.
+pub fn enabled(app_handle: &AppHandle) -> bool {
+    let settings_state = app_handle.state::<SettingsState>();
+    settings_manager::current_settings(app_handle, &settings_state).is_ok_and(|settings| settings.activity_journal)
+}
+
+/// Appends an entry to the journal if the user turned it on. Nothing is
+/// recorded otherwise.
+pub fn record(app_handle: &AppHandle, kind: ActivityKind, path: &str, words: Option<i64>) -> AppResult<()> {
+    if !enabled(app_handle) {
+        return Ok(());
+    }
+
+    let state = app_handle.state::<ActivityState>();
+    let entry = ActivityEntry {
+        timestamp: Local::now(),
+        session: state.session.clone(),
+        kind,
+        path: path.to_string(),
+        words,
+    };
+    let line = serde_json::to_string(&entry)
+        .map_err(|e| AppError::io(format!("Failed to serialize activity: {}", e)))?;
+
+    let journal_path = get_journal_path(app_handle)?;
+    let _guard = state.write_lock.lock()?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&journal_path)
+        .map_err(|e| AppError::io(format!("Failed to open activity journal: {}", e)))?;
+    writeln!(file, "{}", line).map_err(|e| AppError::io(format!("Failed to write activity journal: {}", e)))
+}
+
+/// Records what a save did: creating the document, and how many words it
+/// added compared to `previous_words`.
+pub fn record_save(app_handle: &AppHandle, path: &str, created: bool, previous_words: usize, words: usize) -> AppResult<()> {
+    if created {
+        record(app_handle, ActivityKind::DocumentCreated, path, None)?;
+    }
+    let added = words as i64 - previous_words as i64;
+    if added > 0 {
+        record(app_handle, ActivityKind::WordsAdded, path, Some(added))?;
+    }
+    Ok(())
+}
+
+/// Copies the journal entries within `range` to `dest` as JSONL. Returns
+/// how many were exported.
+pub fn export_journal(app_handle: &AppHandle, range: &ActivityRange, dest: &Path) -> AppResult<usize> {
+    let journal_path = get_journal_path(app_handle)?;
+    if !journal_path.exists() {
+        std::fs::write(dest, "").map_err(|e| AppError::io(format!("Failed to write {}: {}", dest.display(), e)))?;
+        return Ok(0);
+    }
+    let journal = std::fs::File::open(&journal_path)
+        .map_err(|e| AppError::io(format!("Failed to open activity journal: {}", e)))?;
+    export_lines(BufReader::new(journal), range, dest)
+}
+
+fn export_lines<R: BufRead>(journal: R, range: &ActivityRange, dest: &Path) -> AppResult<usize> {
+    let mut output = std::io::BufWriter::new(
+        std::fs::File::create(dest).map_err(|e| AppError::io(format!("Failed to create {}: {}", dest.display(), e)))?,
+    );
+
+    let mut count = 0;
+    for line in journal.lines() {
+        let line = line.map_err(|e| AppError::io(format!("Failed to read activity journal: {}", e)))?;
+        // A line cut short by a crash is skipped rather than failing the export
+        let Ok(entry) = serde_json::from_str::<ActivityEntry>(&line) else {
+            continue;
+        };
+        if range.contains(entry.timestamp.date_naive()) {
+            writeln!(output, "{}", line).map_err(|e| AppError::io(format!("Failed to write {}: {}", dest.display(), e)))?;
+            count += 1;
+        }
+    }
+
+    output.flush().map_err(|e| AppError::io(format!("Failed to write {}: {}", dest.display(), e)))?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_filters_by_date() {
+        let journal = [
+            r#"{"timestamp":"2024-03-01T09:00:00+00:00","session":"a","kind":"document_opened","path":"x.md"}"#,
+            r#"{"timestamp":"2024-03-05T09:00:00+00:00","session":"a","kind":"words_added","path":"x.md","words":12}"#,
+            r#"{"timestamp":"2024-03-0"#,
+        ]
+        .join("\n");
+
+        let dest = std::env::temp_dir().join("test_activity_export.jsonl");
+        let range = ActivityRange { start: NaiveDate::from_ymd_opt(2024, 3, 2), end: None };
+        assert_eq!(export_lines(journal.as_bytes(), &range, &dest).unwrap(), 1);
+        assert!(std::fs::read_to_string(&dest).unwrap().contains("\"words\":12"));
+
+        let _ = std::fs::remove_file(&dest);
+    }
+}
@@ -0,0 +1,736 @@
+use reqwest::{Client, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{AppHandle, Emitter, Manager};
+use std::path::PathBuf;
+use std::time::Duration;
+use crate::config_parser::ConfigParser;
+use crate::local_llm;
+use crate::error::{AppError, AppResult};
+use crate::operations::Operation;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 60;
+const DEFAULT_MAX_RETRIES: u32 = 2;
+const DEFAULT_MAX_TOKENS: u32 = 1024;
+/// First wait before retrying; doubled after each attempt.
+const RETRY_BASE_DELAY_MS: u64 = 500;
+/// Longest `Retry-After` that's honoured before giving up instead.
+const MAX_RETRY_AFTER_SECS: u64 = 30;
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiConfig {
+    /// "openai", "anthropic" or "ollama". Any OpenAI-compatible server works
+    /// with "openai" and its `base_url`; "ollama" also covers a local
+    /// llama.cpp server and needs no key
+    pub provider: String,
+    /// Falls back to OPENAI_API_KEY or ANTHROPIC_API_KEY when empty
+    pub api_key: String,
+    /// Empty for the provider's default
+    pub model: String,
+    /// Empty for the provider's public API
+    pub base_url: String,
+    pub timeout_secs: u64,
+    /// Further attempts after a timeout, rate limit or server error
+    pub max_retries: u32,
+    /// Empty for the provider's default
+    pub embedding_model: String,
+    /// Embed documents as they're saved, for semantic search. Sends their
+    /// text to the provider
+    pub semantic_index: bool,
+}
+
+impl Default for AiConfig {
+    fn default() -> Self {
+        Self {
+            provider: "openai".to_string(),
+            api_key: String::new(),
+            model: String::new(),
+            base_url: String::new(),
+            timeout_secs: DEFAULT_TIMEOUT_SECS,
+            max_retries: DEFAULT_MAX_RETRIES,
+            embedding_model: String::new(),
+            semantic_index: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Provider {
+    OpenAi,
+    Anthropic,
+    /// A server on this machine speaking the OpenAI API
+    Ollama,
+}
+
+impl Provider {
+    pub(crate) fn parse(name: &str) -> AppResult<Self> {
+        match name {
+            "openai" | "" => Ok(Provider::OpenAi),
+            "anthropic" => Ok(Provider::Anthropic),
+            "ollama" => Ok(Provider::Ollama),
+            other => Err(AppError::config(format!("Unknown AI provider: {}", other))),
+        }
+    }
+
+    fn default_base_url(self) -> &'static str {
+        match self {
+            Provider::OpenAi => "https://api.openai.com/v1",
+            Provider::Anthropic => "https://api.anthropic.com/v1",
+            Provider::Ollama => local_llm::DEFAULT_BASE_URL,
+        }
+    }
+
+    fn default_model(self) -> &'static str {
+        match self {
+            Provider::OpenAi => "gpt-4o-mini",
+            Provider::Anthropic => "claude-3-5-haiku-latest",
+            Provider::Ollama => "llama3.2",
+        }
+    }
+
+    fn default_embedding_model(self) -> Option<&'static str> {
+        match self {
+            Provider::OpenAi => Some("text-embedding-3-small"),
+            Provider::Anthropic => None,
+            Provider::Ollama => Some("nomic-embed-text"),
+        }
+    }
+
+    fn api_key_var(self) -> Option<&'static str> {
+        match self {
+            Provider::OpenAi => Some("OPENAI_API_KEY"),
+            Provider::Anthropic => Some("ANTHROPIC_API_KEY"),
+            Provider::Ollama => None,
+        }
+    }
+}
+
+/// Per-request overrides of the configured defaults.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CompletionOptions {
+    pub model: Option<String>,
+    /// Instructions sent ahead of the prompt
+    pub system: Option<String>,
+    pub max_tokens: Option<u32>,
+    pub temperature: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Completion {
+    pub text: String,
+    /// The model that answered, which may be a dated version of the one asked for
+    pub model: String,
+    pub usage: TokenUsage,
+    /// Why generation stopped, e.g. "stop", "length" or "end_turn"
+    pub finish_reason: Option<String>,
+}
+
+fn get_config_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir.join("ai.conf"))
+}
+
+pub fn load_ai_config(app_handle: &AppHandle) -> AppResult<AiConfig> {
+    let config_path = get_config_path(app_handle)?;
+    let config_path_str = config_path.to_str()
+        .ok_or_else(|| AppError::config("Invalid config path"))?;
+
+    if !config_path.exists() {
+        return Ok(AiConfig::default());
+    }
+
+    let mut parser = ConfigParser::new(config_path_str);
+    parser.set_range("timeout_secs", 1.0, 600.0);
+    parser.set_range("max_retries", 0.0, 10.0);
+    parser.load()?;
+
+    let defaults = AiConfig::default();
+    let value = |key: &str| parser.get_str(key).cloned().unwrap_or_default();
+    let provider = value("provider");
+    Ok(AiConfig {
+        provider: if provider.is_empty() { defaults.provider } else { provider },
+        api_key: value("api_key"),
+        model: value("model"),
+        base_url: value("base_url"),
+        timeout_secs: parser.get_int("timeout_secs").map_or(defaults.timeout_secs, |t| t as u64),
+        max_retries: parser.get_int("max_retries").map_or(defaults.max_retries, |r| r as u32),
+        embedding_model: value("embedding_model"),
+        semantic_index: parser.get_bool("semantic_index").unwrap_or(defaults.semantic_index),
+    })
+}
+
+pub fn save_ai_config(app_handle: &AppHandle, config: &AiConfig) -> AppResult<()> {
+    Provider::parse(&config.provider)?;
+    let config_path = get_config_path(app_handle)?;
+    let config_path_str = config_path.to_str()
+        .ok_or_else(|| AppError::config("Invalid config path"))?;
+
+    let mut parser = ConfigParser::new(config_path_str);
+    parser.set_range("timeout_secs", 1.0, 600.0);
+    parser.set_range("max_retries", 0.0, 10.0);
+
+    parser.set_str("provider", &config.provider);
+    parser.set_str("api_key", &config.api_key);
+    parser.set_str("model", &config.model);
+    parser.set_str("base_url", &config.base_url);
+    parser.set_int("timeout_secs", config.timeout_secs as i64)?;
+    parser.set_int("max_retries", i64::from(config.max_retries))?;
+    parser.set_str("embedding_model", &config.embedding_model);
+    parser.set_bool("semantic_index", config.semantic_index);
+
+    parser.set_comment("provider", "openai (or any OpenAI-compatible server), anthropic, or ollama for a local server");
+    parser.set_comment("api_key", "Leave empty to use OPENAI_API_KEY or ANTHROPIC_API_KEY");
+    parser.set_comment("model", "Leave empty for the provider's default");
+    parser.set_comment("base_url", "Leave empty for the provider's API, e.g. https://openrouter.ai/api/v1");
+    parser.set_comment("semantic_index", "Embed documents when saved for semantic search; sends their text to the provider");
+
+    parser.save()
+}
+
+pub(crate) fn base_url(provider: Provider, config: &AiConfig) -> &str {
+    if config.base_url.is_empty() {
+        provider.default_base_url()
+    } else {
+        config.base_url.trim_end_matches('/')
+    }
+}
+
+/// The endpoint, headers and JSON body for one completion.
+struct ApiRequest {
+    url: String,
+    headers: Vec<(&'static str, String)>,
+    body: Value,
+}
+
+fn build_request(provider: Provider, config: &AiConfig, api_key: &str, prompt: &str, options: &CompletionOptions) -> ApiRequest {
+    let base_url = base_url(provider, config);
+    let model = options
+        .model
+        .clone()
+        .filter(|m| !m.is_empty())
+        .or_else(|| Some(config.model.clone()).filter(|m| !m.is_empty()))
+        .unwrap_or_else(|| provider.default_model().to_string());
+    let max_tokens = options.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS);
+
+    match provider {
+        Provider::OpenAi | Provider::Ollama => {
+            let mut messages = Vec::new();
+            if let Some(system) = &options.system {
+                messages.push(json!({ "role": "system", "content": system }));
+            }
+            messages.push(json!({ "role": "user", "content": prompt }));
+            let mut body = json!({ "model": model, "messages": messages, "max_tokens": max_tokens });
+            if let Some(temperature) = options.temperature {
+                body["temperature"] = json!(temperature);
+            }
+            let mut headers = Vec::new();
+            if !api_key.is_empty() {
+                headers.push(("Authorization", format!("Bearer {}", api_key)));
+            }
+            ApiRequest { url: format!("{}/chat/completions", base_url), headers, body }
+        }
+        Provider::Anthropic => {
+            let mut body = json!({
+                "model": model,
+                "max_tokens": max_tokens,
+                "messages": [{ "role": "user", "content": prompt }],
+            });
+            if let Some(system) = &options.system {
+                body["system"] = json!(system);
+            }
+            if let Some(temperature) = options.temperature {
+                body["temperature"] = json!(temperature);
+            }
+            let headers = vec![("x-api-key", api_key.to_string()), ("anthropic-version", ANTHROPIC_VERSION.to_string())];
+            ApiRequest { url: format!("{}/messages", base_url), headers, body }
+        }
+    }
+}
+
+fn parse_response(provider: Provider, body: &Value) -> AppResult<Completion> {
+    let number = |value: &Value| value.as_u64().unwrap_or(0);
+    let model = body["model"].as_str().unwrap_or_default().to_string();
+
+    match provider {
+        Provider::OpenAi | Provider::Ollama => {
+            let choice = &body["choices"][0];
+            let text = choice["message"]["content"]
+                .as_str()
+                .ok_or_else(|| AppError::network("AI response had no message"))?;
+            Ok(Completion {
+                text: text.to_string(),
+                model,
+                usage: TokenUsage {
+                    input_tokens: number(&body["usage"]["prompt_tokens"]),
+                    output_tokens: number(&body["usage"]["completion_tokens"]),
+                },
+                finish_reason: choice["finish_reason"].as_str().map(String::from),
+            })
+        }
+        Provider::Anthropic => {
+            let blocks = body["content"].as_array().ok_or_else(|| AppError::network("AI response had no content"))?;
+            let text: String = blocks
+                .iter()
+                .filter(|block| block["type"] == "text")
+                .filter_map(|block| block["text"].as_str())
+                .collect();
+            Ok(Completion {
+                text,
+                model,
+                usage: TokenUsage {
+                    input_tokens: number(&body["usage"]["input_tokens"]),
+                    output_tokens: number(&body["usage"]["output_tokens"]),
+                },
+                finish_reason: body["stop_reason"].as_str().map(String::from),
+            })
+        }
+    }
+}
+
+/// The provider's own explanation from an error body, which both APIs put
+/// in `error.message`.
+fn error_message(status: StatusCode, body: &str) -> String {
+    let message = serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|value| value["error"]["message"].as_str().map(String::from))
+        .unwrap_or_else(|| body.trim().chars().take(200).collect());
+    format!("AI request failed ({}): {}", status, message)
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::REQUEST_TIMEOUT || status.is_server_error()
+}
+
+fn retry_delay(attempt: u32, retry_after: Option<u64>) -> Duration {
+    match retry_after {
+        Some(secs) => Duration::from_secs(secs),
+        None => Duration::from_millis(RETRY_BASE_DELAY_MS << attempt.min(6)),
+    }
+}
+
+fn api_key(provider: Provider, config: &AiConfig) -> AppResult<String> {
+    let api_key = match provider.api_key_var() {
+        Some(var) if config.api_key.is_empty() => std::env::var(var).unwrap_or_default(),
+        _ => config.api_key.clone(),
+    };
+    // Local servers usually don't need a key
+    if api_key.is_empty() && config.base_url.is_empty() && provider != Provider::Ollama {
+        return Err(AppError::config(format!("No API key configured for {}", config.provider)));
+    }
+    Ok(api_key)
+}
+
+/// POSTs `body`, retrying timeouts, rate limits and server errors with
+/// exponential backoff, and returns the first successful response.
+async fn send_with_retries(client: &Client, api_request: &ApiRequest, max_retries: u32) -> AppResult<Response> {
+    let mut attempt = 0;
+    loop {
+        let mut request = client
+            .post(&api_request.url)
+            .header("Content-Type", "application/json")
+            .body(api_request.body.to_string());
+        for (name, value) in &api_request.headers {
+            request = request.header(*name, value);
+        }
+
+        let (error, retry_after) = match request.send().await {
+            Ok(response) if response.status().is_success() => return Ok(response),
+            Ok(response) => {
+                let status = response.status();
+                let retry_after = response
+                    .headers()
+                    .get("retry-after")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse::<u64>().ok());
+                let message = AppError::network(error_message(status, &response.text().await.unwrap_or_default()));
+                if !is_retryable(status) || retry_after.is_some_and(|secs| secs > MAX_RETRY_AFTER_SECS) {
+                    return Err(message);
+                }
+                (message, retry_after)
+            }
+            Err(e) if e.is_timeout() || e.is_connect() => (AppError::network(format!("AI request failed: {}", e)), None),
+            Err(e) => return Err(AppError::network(format!("AI request failed: {}", e))),
+        };
+
+        if attempt >= max_retries {
+            return Err(error);
+        }
+        tokio::time::sleep(retry_delay(attempt, retry_after)).await;
+        attempt += 1;
+    }
+}
+
+/// Sends `prompt` to the configured provider and returns its answer with
+/// the tokens it used.
+pub async fn complete(config: &AiConfig, prompt: &str, options: &CompletionOptions) -> AppResult<Completion> {
+    let provider = Provider::parse(&config.provider)?;
+    let api_key = api_key(provider, config)?;
+    let request = build_request(provider, config, &api_key, prompt, options);
+    let client = Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs.max(1)))
+        .build()
+        .map_err(|e| AppError::network(format!("Failed to create HTTP client: {}", e)))?;
+
+    let response = send_with_retries(&client, &request, config.max_retries).await?;
+    let text = response.text().await.map_err(|e| AppError::network(format!("Failed to read AI response: {}", e)))?;
+    let value: Value = serde_json::from_str(&text).map_err(|e| AppError::network(format!("Failed to parse AI response: {}", e)))?;
+    parse_response(provider, &value)
+}
+
+/// The model `embed` uses, which vectors are only comparable within.
+pub fn embedding_model(config: &AiConfig) -> AppResult<String> {
+    let provider = Provider::parse(&config.provider)?;
+    if !config.embedding_model.is_empty() {
+        return Ok(config.embedding_model.clone());
+    }
+    provider
+        .default_embedding_model()
+        .map(String::from)
+        .ok_or_else(|| AppError::config(format!("{} has no embeddings API; set embedding_model for a compatible server", config.provider)))
+}
+
+/// Embedding vectors for `inputs`, in order, through the OpenAI-style
+/// `/embeddings` endpoint that Ollama and llama.cpp also have.
+pub async fn embed(config: &AiConfig, inputs: &[String]) -> AppResult<Vec<Vec<f32>>> {
+    let provider = Provider::parse(&config.provider)?;
+    let model = embedding_model(config)?;
+    let api_key = api_key(provider, config)?;
+    let mut headers = Vec::new();
+    if !api_key.is_empty() {
+        headers.push(("Authorization", format!("Bearer {}", api_key)));
+    }
+    let request = ApiRequest {
+        url: format!("{}/embeddings", base_url(provider, config)),
+        headers,
+        body: json!({ "model": model, "input": inputs }),
+    };
+    let client = Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs.max(1)))
+        .build()
+        .map_err(|e| AppError::network(format!("Failed to create HTTP client: {}", e)))?;
+
+    let response = send_with_retries(&client, &request, config.max_retries).await?;
+    let text = response.text().await.map_err(|e| AppError::network(format!("Failed to read AI response: {}", e)))?;
+    let value: Value = serde_json::from_str(&text).map_err(|e| AppError::network(format!("Failed to parse AI response: {}", e)))?;
+    parse_embeddings(&value, inputs.len())
+}
+
+fn parse_embeddings(value: &Value, count: usize) -> AppResult<Vec<Vec<f32>>> {
+    let mut vectors = vec![Vec::new(); count];
+    for item in value["data"].as_array().into_iter().flatten() {
+        let index = item["index"].as_u64().unwrap_or(0) as usize;
+        let vector = item["embedding"]
+            .as_array()
+            .ok_or_else(|| AppError::network("AI response had no embedding"))?
+            .iter()
+            .map(|x| x.as_f64().unwrap_or(0.0) as f32)
+            .collect();
+        if let Some(slot) = vectors.get_mut(index) {
+            *slot = vector;
+        }
+    }
+    if vectors.iter().any(Vec::is_empty) {
+        return Err(AppError::network(format!("AI response had fewer than {} embeddings", count)));
+    }
+    Ok(vectors)
+}
+
+/// A piece of a streamed answer, emitted as `ai-token`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AiToken {
+    pub request_id: String,
+    pub text: String,
+}
+
+/// Splits a server-sent event stream into the `data:` payload of each
+/// event. Chunks can end anywhere, even inside a UTF-8 character.
+#[derive(Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+    data: String,
+}
+
+impl SseParser {
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    events.push(std::mem::take(&mut self.data));
+                }
+            } else if let Some(data) = line.strip_prefix("data:") {
+                if !self.data.is_empty() {
+                    self.data.push('\n');
+                }
+                self.data.push_str(data.strip_prefix(' ').unwrap_or(data));
+            }
+        }
+        events
+    }
+}
+
+/// Builds up the final completion from stream events, returning the text
+/// each one adds.
+struct StreamAccumulator {
+    provider: Provider,
+    completion: Completion,
+}
+
+impl StreamAccumulator {
+    fn new(provider: Provider) -> Self {
+        Self {
+            provider,
+            completion: Completion { text: String::new(), model: String::new(), usage: TokenUsage::default(), finish_reason: None },
+        }
+    }
+
+    fn handle(&mut self, event: &Value) -> AppResult<Option<String>> {
+        let completion = &mut self.completion;
+        if let Some(message) = event["error"]["message"].as_str() {
+            return Err(AppError::network(format!("AI request failed: {}", message)));
+        }
+
+        let delta = match self.provider {
+            Provider::OpenAi | Provider::Ollama => {
+                if let Some(model) = event["model"].as_str() {
+                    completion.model = model.to_string();
+                }
+                // Sent last, with no choices, when usage was asked for
+                if let Some(usage) = event["usage"].as_object() {
+                    completion.usage.input_tokens = usage.get("prompt_tokens").and_then(Value::as_u64).unwrap_or(0);
+                    completion.usage.output_tokens = usage.get("completion_tokens").and_then(Value::as_u64).unwrap_or(0);
+                }
+                let choice = &event["choices"][0];
+                if let Some(reason) = choice["finish_reason"].as_str() {
+                    completion.finish_reason = Some(reason.to_string());
+                }
+                choice["delta"]["content"].as_str().map(String::from)
+            }
+            Provider::Anthropic => match event["type"].as_str() {
+                Some("message_start") => {
+                    let message = &event["message"];
+                    completion.model = message["model"].as_str().unwrap_or_default().to_string();
+                    completion.usage.input_tokens = message["usage"]["input_tokens"].as_u64().unwrap_or(0);
+                    None
+                }
+                Some("content_block_delta") if event["delta"]["type"] == "text_delta" => {
+                    event["delta"]["text"].as_str().map(String::from)
+                }
+                Some("message_delta") => {
+                    completion.finish_reason = event["delta"]["stop_reason"].as_str().map(String::from);
+                    completion.usage.output_tokens = event["usage"]["output_tokens"].as_u64().unwrap_or(0);
+                    None
+                }
+                _ => None,
+            },
+        };
+
+        let delta = delta.filter(|text| !text.is_empty());
+        if let Some(text) = &delta {
+            completion.text.push_str(text);
+        }
+        Ok(delta)
+    }
+}
+
+/// Like `complete`, but emits the answer as `ai-token` events while it's
+/// generated. Returns the whole completion once done, or what was generated
+/// so far with `finish_reason` "cancelled" if `operation` was cancelled.
+pub async fn complete_stream(
+    app_handle: &AppHandle,
+    operation: &Operation,
+    config: &AiConfig,
+    request_id: &str,
+    prompt: &str,
+    options: &CompletionOptions,
+) -> AppResult<Completion> {
+    let provider = Provider::parse(&config.provider)?;
+    let api_key = api_key(provider, config)?;
+    let mut request = build_request(provider, config, &api_key, prompt, options);
+    request.body["stream"] = json!(true);
+    if provider != Provider::Anthropic {
+        request.body["stream_options"] = json!({ "include_usage": true });
+    }
+
+    stream_tokens(app_handle, provider, config, request_id, &request, operation).await
+}
+
+async fn stream_tokens(
+    app_handle: &AppHandle,
+    provider: Provider,
+    config: &AiConfig,
+    request_id: &str,
+    request: &ApiRequest,
+    operation: &Operation,
+) -> AppResult<Completion> {
+    // A long answer can take minutes, so the timeout applies to connecting
+    // and to each wait for more tokens rather than the whole request
+    let timeout = Duration::from_secs(config.timeout_secs.max(1));
+    let client = Client::builder()
+        .connect_timeout(timeout)
+        .build()
+        .map_err(|e| AppError::network(format!("Failed to create HTTP client: {}", e)))?;
+
+    let mut accumulator = StreamAccumulator::new(provider);
+    let mut response = tokio::select! {
+        response = send_with_retries(&client, request, config.max_retries) => response?,
+        _ = operation.cancelled() => {
+            accumulator.completion.finish_reason = Some("cancelled".to_string());
+            return Ok(accumulator.completion);
+        }
+    };
+
+    let mut parser = SseParser::default();
+    loop {
+        let chunk = tokio::select! {
+            chunk = tokio::time::timeout(timeout, response.chunk()) => chunk
+                .map_err(|_| AppError::network("AI response timed out"))?
+                .map_err(|e| AppError::network(format!("Failed to read AI response: {}", e)))?,
+            _ = operation.cancelled() => {
+                accumulator.completion.finish_reason = Some("cancelled".to_string());
+                return Ok(accumulator.completion);
+            }
+        };
+        let Some(chunk) = chunk else {
+            return Ok(accumulator.completion);
+        };
+
+        for data in parser.push(&chunk) {
+            if data == "[DONE]" {
+                return Ok(accumulator.completion);
+            }
+            let Ok(event) = serde_json::from_str::<Value>(&data) else {
+                continue;
+            };
+            if let Some(text) = accumulator.handle(&event)? {
+                let _ = app_handle.emit("ai-token", AiToken { request_id: request_id.to_string(), text });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_request() {
+        let config = AiConfig { model: "gpt-4o".to_string(), ..AiConfig::default() };
+        let options = CompletionOptions { system: Some("Be brief".to_string()), temperature: Some(0.2), ..Default::default() };
+        let ApiRequest { url, headers, body } = build_request(Provider::OpenAi, &config, "sk-test", "Hi", &options);
+        assert_eq!(url, "https://api.openai.com/v1/chat/completions");
+        assert_eq!(headers, vec![("Authorization", "Bearer sk-test".to_string())]);
+        assert_eq!(body["model"], "gpt-4o");
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["messages"][1]["content"], "Hi");
+        assert_eq!(body["temperature"], 0.2);
+
+        let config = AiConfig { provider: "anthropic".to_string(), base_url: "http://localhost:8080/v1/".to_string(), ..AiConfig::default() };
+        let ApiRequest { url, body, .. } = build_request(Provider::Anthropic, &config, "key", "Hi", &options);
+        assert_eq!(url, "http://localhost:8080/v1/messages");
+        assert_eq!(body["model"], "claude-3-5-haiku-latest");
+        assert_eq!(body["system"], "Be brief");
+        assert_eq!(body["max_tokens"], DEFAULT_MAX_TOKENS);
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let openai = json!({
+            "model": "gpt-4o-2024-08-06",
+            "choices": [{ "message": { "role": "assistant", "content": "Hello!" }, "finish_reason": "stop" }],
+            "usage": { "prompt_tokens": 9, "completion_tokens": 3 }
+        });
+        let completion = parse_response(Provider::OpenAi, &openai).unwrap();
+        assert_eq!(completion.text, "Hello!");
+        assert_eq!(completion.usage, TokenUsage { input_tokens: 9, output_tokens: 3 });
+        assert_eq!(completion.finish_reason.as_deref(), Some("stop"));
+
+        let anthropic = json!({
+            "model": "claude-3-5-haiku-20241022",
+            "content": [{ "type": "text", "text": "Hello" }, { "type": "text", "text": " there" }],
+            "stop_reason": "end_turn",
+            "usage": { "input_tokens": 12, "output_tokens": 4 }
+        });
+        let completion = parse_response(Provider::Anthropic, &anthropic).unwrap();
+        assert_eq!(completion.text, "Hello there");
+        assert_eq!(completion.usage.output_tokens, 4);
+
+        assert!(parse_response(Provider::OpenAi, &json!({ "choices": [] })).is_err());
+    }
+
+    #[test]
+    fn test_stream_events() {
+        let mut parser = SseParser::default();
+        // A chunk boundary inside "é" and another inside an event
+        let stream = "data: {\"choices\":[{\"delta\":{\"content\":\"Caf\u{e9} \"}}]}\n\ndata: {\"model\":\"gpt-4o\",\"choices\":[{\"delta\":{\"content\":\"olé\"},\"finish_reason\":\"stop\"}]}\r\n\r\ndata: {\"choices\":[],\"usage\":{\"prompt_tokens\":5,\"completion_tokens\":2}}\n\ndata: [DONE]\n\n";
+        let bytes = stream.as_bytes();
+        let split = stream.find("ol").unwrap() + 3;
+        let mut events = parser.push(&bytes[..split]);
+        events.extend(parser.push(&bytes[split..]));
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[3], "[DONE]");
+
+        let mut accumulator = StreamAccumulator::new(Provider::OpenAi);
+        let deltas: Vec<String> = events[..3]
+            .iter()
+            .filter_map(|data| accumulator.handle(&serde_json::from_str(data).unwrap()).unwrap())
+            .collect();
+        assert_eq!(deltas, vec!["Café ", "olé"]);
+        assert_eq!(accumulator.completion.model, "gpt-4o");
+        assert_eq!(accumulator.completion.usage, TokenUsage { input_tokens: 5, output_tokens: 2 });
+
+        let mut accumulator = StreamAccumulator::new(Provider::Anthropic);
+        let events = [
+            json!({ "type": "message_start", "message": { "model": "claude", "usage": { "input_tokens": 7 } } }),
+            json!({ "type": "content_block_delta", "delta": { "type": "text_delta", "text": "Hi" } }),
+            json!({ "type": "message_delta", "delta": { "stop_reason": "end_turn" }, "usage": { "output_tokens": 1 } }),
+        ];
+        for event in &events {
+            accumulator.handle(event).unwrap();
+        }
+        assert_eq!(accumulator.completion.text, "Hi");
+        assert_eq!(accumulator.completion.usage, TokenUsage { input_tokens: 7, output_tokens: 1 });
+        assert_eq!(accumulator.completion.finish_reason.as_deref(), Some("end_turn"));
+        assert!(accumulator.handle(&json!({ "type": "error", "error": { "message": "Overloaded" } })).is_err());
+    }
+
+    #[test]
+    fn test_parse_embeddings() {
+        // Entries may come back out of order
+        let value = json!({ "data": [
+            { "index": 1, "embedding": [0.5, -1.0] },
+            { "index": 0, "embedding": [0.25, 0.0] }
+        ] });
+        assert_eq!(parse_embeddings(&value, 2).unwrap(), vec![vec![0.25, 0.0], vec![0.5, -1.0]]);
+        assert!(parse_embeddings(&value, 3).is_err());
+        assert!(embedding_model(&AiConfig { provider: "anthropic".to_string(), ..AiConfig::default() }).is_err());
+    }
+
+    #[test]
+    fn test_error_message() {
+        let body = r#"{"error":{"type":"invalid_request_error","message":"model not found"}}"#;
+        assert_eq!(error_message(StatusCode::NOT_FOUND, body), "AI request failed (404 Not Found): model not found");
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_retryable(StatusCode::UNAUTHORIZED));
+        assert_eq!(retry_delay(2, None), Duration::from_millis(2000));
+        assert_eq!(retry_delay(0, Some(3)), Duration::from_secs(3));
+    }
+}
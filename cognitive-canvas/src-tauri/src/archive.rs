@@ -0,0 +1,493 @@
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use crate::{assets, sidecar, workspace};
+use crate::error::{AppError, AppResult};
+use crate::operations::Operation;
+
+/// In a hidden folder, so an archive unzipped by hand into a workspace
+/// doesn't show its settings in listings.
+pub const MANIFEST_NAME: &str = ".canvas-archive/manifest.json";
+pub const SETTINGS_PREFIX: &str = ".canvas-archive/settings/";
+/// Where a workspace keeps track of the archives imported into it.
+const IMPORTS_NAME: &str = ".canvas-archive/imports.json";
+pub const ARCHIVE_FORMAT: u32 = 1;
+
+/// App settings worth carrying to another machine. ai.conf is left out as
+/// it holds an API key.
+pub const SETTINGS_FILES: &[&str] = &["settings.conf", "shortcuts.conf"];
+
+/// Formats that don't get smaller by compressing them again.
+const STORED_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "zip", "mp3", "m4a", "ogg", "mp4", "pdf"];
+
+/// What goes in besides the documents; everything unless turned off.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveOptions {
+    pub include_assets: Option<bool>,
+    /// Comments, metadata and the like kept next to each document
+    pub include_sidecars: Option<bool>,
+    pub include_settings: Option<bool>,
+}
+
+/// Describes an archive, so importing knows what it's looking at.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub format: u32,
+    pub created_at: DateTime<Utc>,
+    pub documents: usize,
+    pub assets: usize,
+    pub settings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveProgress {
+    pub dest: String,
+    pub done: usize,
+    pub total: usize,
+    /// Path within the archive of the file being added
+    pub current: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveSummary {
+    pub path: String,
+    pub documents: usize,
+    pub assets: usize,
+    /// Everything in the archive, sidecars and settings included
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Where an import came from, kept in the workspace it went into.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportRecord {
+    pub archive: String,
+    /// Of the archive file, to tell whether the same one was imported twice
+    pub sha256: String,
+    pub imported_at: DateTime<Utc>,
+    /// From the manifest, when the archive has one
+    pub exported_at: Option<DateTime<Utc>>,
+    pub documents: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportedArchive {
+    pub dest_dir: String,
+    /// None for zips that weren't made by `export_archive`
+    pub manifest: Option<ArchiveManifest>,
+    /// Relative to `dest_dir`
+    pub documents: Vec<String>,
+    /// Everything extracted, sidecars, assets and settings included
+    pub files: usize,
+    /// Extracted settings files, which aren't applied by importing
+    pub settings: Vec<String>,
+    /// Entries not extracted because a file was already there
+    pub skipped: Vec<String>,
+}
+
+/// `path` relative to `root` with `/` separators, as zip entries are named.
+fn entry_name(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn entry_options(source: &Path) -> SimpleFileOptions {
+    let stored = source
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| STORED_EXTENSIONS.contains(&e.to_lowercase().as_str()));
+    let method = if stored { CompressionMethod::Stored } else { CompressionMethod::Deflated };
+    let options = SimpleFileOptions::default().compression_method(method);
+
+    // Keep when each file was last changed, which zip can only store from 1980
+    let modified = std::fs::metadata(source).and_then(|m| m.modified()).ok().map(DateTime::<Utc>::from);
+    let time = modified.and_then(|t| {
+        zip::DateTime::from_date_and_time(t.year() as u16, t.month() as u8, t.day() as u8, t.hour() as u8, t.minute() as u8, t.second() as u8).ok()
+    });
+    match time {
+        Some(time) => options.last_modified_time(time),
+        None => options,
+    }
+}
+
+/// Zips the documents below `dir`, with their sidecars, the workspace's
+/// assets and the app settings in `settings_dir`, into `dest`. Ignored
+/// files are left out. `on_progress` is called before each file; an error
+/// from it stops the export and removes the partial archive.
+pub fn export_archive<F>(dir: &Path, dest: &Path, options: &ArchiveOptions, settings_dir: &Path, mut on_progress: F) -> AppResult<ArchiveSummary>
+where
+    F: FnMut(&ArchiveProgress) -> AppResult<()>,
+{
+    let documents = workspace::document_files(dir)?;
+    let mut entries: Vec<(PathBuf, String)> = Vec::new();
+    for document in &documents {
+        entries.push((document.clone(), entry_name(dir, document)));
+        if options.include_sidecars.unwrap_or(true) {
+            for (kind, path) in sidecar::sidecars(document) {
+                if kind != "lock" {
+                    entries.push((path.clone(), entry_name(dir, &path)));
+                }
+            }
+        }
+    }
+    let asset_files = if options.include_assets.unwrap_or(true) { assets::asset_files(dir)? } else { Vec::new() };
+    entries.extend(asset_files.iter().map(|path| (path.clone(), entry_name(dir, path))));
+    let mut settings = Vec::new();
+    if options.include_settings.unwrap_or(true) {
+        for name in SETTINGS_FILES {
+            let path = settings_dir.join(name);
+            if path.is_file() {
+                entries.push((path, format!("{}{}", SETTINGS_PREFIX, name)));
+                settings.push(name.to_string());
+            }
+        }
+    }
+
+    // Written next to `dest` and renamed when done, so a failed export
+    // doesn't leave a broken archive behind
+    let file_name = dest.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| "workspace.zip".to_string());
+    let partial = dest.with_file_name(format!("{}.part", file_name));
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| AppError::io(format!("Failed to create {}: {}", parent.display(), e)))?;
+    }
+    let file = File::create(&partial).map_err(|e| AppError::io(format!("Failed to create {}: {}", partial.display(), e)))?;
+    let mut zip = ZipWriter::new(file);
+
+    let result = (|| {
+        let total = entries.len();
+        for (done, (source, name)) in entries.iter().enumerate() {
+            on_progress(&ArchiveProgress { dest: dest.to_string_lossy().to_string(), done, total, current: name.clone() })?;
+            let mut reader = File::open(source).map_err(|e| AppError::io(format!("Failed to read {}: {}", source.display(), e)))?;
+            zip.start_file(name.as_str(), entry_options(source)).map_err(|e| AppError::io(format!("Failed to write archive: {}", e)))?;
+            std::io::copy(&mut reader, &mut zip).map_err(|e| AppError::io(format!("Failed to write archive: {}", e)))?;
+        }
+
+        let manifest = ArchiveManifest {
+            format: ARCHIVE_FORMAT,
+            created_at: Utc::now(),
+            documents: documents.len(),
+            assets: asset_files.len(),
+            settings,
+        };
+        let json = serde_json::to_vec_pretty(&manifest).map_err(|e| AppError::io(format!("Failed to write archive: {}", e)))?;
+        zip.start_file(MANIFEST_NAME, SimpleFileOptions::default()).map_err(|e| AppError::io(format!("Failed to write archive: {}", e)))?;
+        std::io::Write::write_all(&mut zip, &json).map_err(|e| AppError::io(format!("Failed to write archive: {}", e)))?;
+        zip.finish().map_err(|e| AppError::io(format!("Failed to write archive: {}", e)))?;
+        on_progress(&ArchiveProgress { dest: dest.to_string_lossy().to_string(), done: total, total, current: String::new() })?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e);
+    }
+
+    std::fs::rename(&partial, dest).map_err(|e| AppError::io(format!("Failed to save {}: {}", dest.display(), e)))?;
+    Ok(ArchiveSummary {
+        path: dest.to_string_lossy().to_string(),
+        documents: documents.len(),
+        assets: asset_files.len(),
+        files: entries.len() + 1,
+        bytes: std::fs::metadata(dest).map(|m| m.len()).unwrap_or(0),
+    })
+}
+
+/// Where an entry goes below the destination, or None when its name could
+/// lead outside it: absolute paths, drive letters, `..` and the like.
+pub fn safe_entry_path(name: &str) -> Option<PathBuf> {
+    if name.starts_with('/') || name.contains('\\') {
+        return None;
+    }
+    let parts: Vec<&str> = name.trim_end_matches('/').split('/').collect();
+    if parts.iter().any(|part| part.is_empty() || *part == "." || *part == ".." || part.contains(':')) {
+        return None;
+    }
+    Some(parts.iter().collect())
+}
+
+/// Zip times have no time zone; exported ones are UTC.
+fn entry_modified(time: zip::DateTime) -> Option<SystemTime> {
+    let date = NaiveDate::from_ymd_opt(time.year() as i32, time.month() as u32, time.day() as u32)?;
+    let time = date.and_hms_opt(time.hour() as u32, time.minute() as u32, time.second() as u32)?;
+    Some(time.and_utc().into())
+}
+
+fn record_import(zip_path: &Path, imported: &ImportedArchive) -> AppResult<()> {
+    let mut file = File::open(zip_path).map_err(|e| AppError::io(format!("Failed to read {}: {}", zip_path.display(), e)))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).map_err(|e| AppError::io(format!("Failed to read {}: {}", zip_path.display(), e)))?;
+
+    let path = Path::new(&imported.dest_dir).join(IMPORTS_NAME);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| AppError::io(format!("Failed to create {}: {}", parent.display(), e)))?;
+    }
+    let mut records: Vec<ImportRecord> = sidecar::read_json(&path)?;
+    records.push(ImportRecord {
+        archive: zip_path.to_string_lossy().to_string(),
+        sha256: hex::encode(hasher.finalize()),
+        imported_at: Utc::now(),
+        exported_at: imported.manifest.as_ref().map(|manifest| manifest.created_at),
+        documents: imported.documents.clone(),
+    });
+    sidecar::write_json(&path, &records)
+}
+
+/// Extracts an archive made by `export_archive`, or any zip of documents,
+/// into `dest_dir`. Nothing is extracted if any entry would land outside
+/// it, and existing files are left alone. Settings are extracted next to
+/// the manifest rather than applied. `on_progress` is called before each
+/// file; if it or an extraction fails, the files extracted so far are
+/// removed again.
+pub fn import_archive<F>(zip_path: &Path, dest_dir: &Path, mut on_progress: F) -> AppResult<ImportedArchive>
+where
+    F: FnMut(&ArchiveProgress) -> AppResult<()>,
+{
+    let read_error = |e: zip::result::ZipError| AppError::io(format!("Failed to read {}: {}", zip_path.display(), e));
+    let file = File::open(zip_path).map_err(|e| AppError::io(format!("Failed to read {}: {}", zip_path.display(), e)))?;
+    let mut archive = ZipArchive::new(file).map_err(read_error)?;
+
+    let mut entries = Vec::new();
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index).map_err(read_error)?;
+        let Some(relative) = safe_entry_path(entry.name()) else {
+            return Err(AppError::validation(format!("Refusing to import {}: {} would be outside the destination", zip_path.display(), entry.name())));
+        };
+        if !entry.is_dir() && entry.name() != MANIFEST_NAME && entry.name() != IMPORTS_NAME {
+            entries.push((index, entry.name().to_string(), relative));
+        }
+    }
+
+    let manifest = match archive.by_name(MANIFEST_NAME) {
+        Ok(mut entry) => {
+            let mut json = String::new();
+            entry.read_to_string(&mut json).map_err(|e| AppError::io(format!("Failed to read {}: {}", zip_path.display(), e)))?;
+            let manifest: ArchiveManifest = serde_json::from_str(&json).map_err(|e| AppError::validation(format!("Failed to parse the archive manifest: {}", e)))?;
+            Some(manifest)
+        }
+        Err(_) => None,
+    };
+    if manifest.as_ref().is_some_and(|manifest| manifest.format > ARCHIVE_FORMAT) {
+        return Err(AppError::unsupported(format!("{} was exported by a newer version", zip_path.display())));
+    }
+
+    std::fs::create_dir_all(dest_dir).map_err(|e| AppError::io(format!("Failed to create {}: {}", dest_dir.display(), e)))?;
+    let root = dest_dir.canonicalize().map_err(|e| AppError::io(format!("Failed to read {}: {}", dest_dir.display(), e)))?;
+    let mut imported = ImportedArchive {
+        dest_dir: dest_dir.to_string_lossy().to_string(),
+        manifest,
+        documents: Vec::new(),
+        files: 0,
+        settings: Vec::new(),
+        skipped: Vec::new(),
+    };
+
+    let mut created = Vec::new();
+    let result = (|| {
+        let total = entries.len();
+        for (done, (index, name, relative)) in entries.iter().enumerate() {
+            on_progress(&ArchiveProgress { dest: imported.dest_dir.clone(), done, total, current: name.clone() })?;
+            let target = dest_dir.join(relative);
+            if target.exists() {
+                imported.skipped.push(name.clone());
+                continue;
+            }
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| AppError::io(format!("Failed to create {}: {}", parent.display(), e)))?;
+                // A folder already in the destination may be a link to elsewhere
+                if !parent.canonicalize().is_ok_and(|parent| parent.starts_with(&root)) {
+                    return Err(AppError::validation(format!("Refusing to import {}: {} would be outside the destination", zip_path.display(), name)));
+                }
+            }
+
+            let mut entry = archive.by_index(*index).map_err(read_error)?;
+            let mut file = File::create(&target).map_err(|e| AppError::io(format!("Failed to create {}: {}", target.display(), e)))?;
+            created.push(target.clone());
+            std::io::copy(&mut entry, &mut file).map_err(|e| AppError::io(format!("Failed to write {}: {}", target.display(), e)))?;
+            if let Some(modified) = entry.last_modified().and_then(entry_modified) {
+                let _ = file.set_modified(modified);
+            }
+
+            imported.files += 1;
+            if name.starts_with(SETTINGS_PREFIX) {
+                imported.settings.push(target.to_string_lossy().to_string());
+            } else if workspace::is_document(&target) {
+                imported.documents.push(name.clone());
+            }
+        }
+        on_progress(&ArchiveProgress { dest: imported.dest_dir.clone(), done: total, total, current: String::new() })
+    })();
+    if let Err(e) = result {
+        for path in &created {
+            let _ = std::fs::remove_file(path);
+        }
+        return Err(e);
+    }
+
+    record_import(zip_path, &imported)?;
+    Ok(imported)
+}
+
+/// `export_archive` with the app's settings, emitting "archive-progress"
+/// and reporting to `operation` as it goes.
+pub fn export_workspace_archive(
+    app_handle: &AppHandle,
+    dir: &Path,
+    dest: &Path,
+    options: &ArchiveOptions,
+    operation: &Operation,
+) -> AppResult<ArchiveSummary> {
+    let settings_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+    export_archive(dir, dest, options, &settings_dir, |progress| {
+        let _ = app_handle.emit("archive-progress", progress);
+        operation.step(progress.done, progress.total, progress.current.clone());
+        operation.check_cancelled()
+    })
+}
+
+/// `import_archive`, emitting "archive-progress" and reporting to
+/// `operation` as it goes.
+pub fn import_workspace_archive(app_handle: &AppHandle, zip_path: &Path, dest_dir: &Path, operation: &Operation) -> AppResult<ImportedArchive> {
+    import_archive(zip_path, dest_dir, |progress| {
+        let _ = app_handle.emit("archive-progress", progress);
+        operation.step(progress.done, progress.total, progress.current.clone());
+        operation.check_cancelled()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+
+    #[test]
+    fn test_export_archive() {
+        let dir = std::env::temp_dir().join("test_archive_export");
+        let _ = fs::remove_dir_all(&dir);
+        let (workspace, settings_dir) = (dir.join("workspace"), dir.join("app"));
+        fs::create_dir_all(workspace.join("notes")).unwrap();
+        fs::create_dir_all(workspace.join("assets")).unwrap();
+        fs::create_dir_all(&settings_dir).unwrap();
+        fs::write(workspace.join("notes/plan.md"), "# Plan").unwrap();
+        fs::write(sidecar::sidecar_path(&workspace.join("notes/plan.md"), "comments"), "[]").unwrap();
+        fs::write(sidecar::sidecar_path(&workspace.join("notes/plan.md"), "lock"), "{}").unwrap();
+        fs::write(workspace.join("assets/0123.png"), [0x89, b'P', b'N', b'G']).unwrap();
+        fs::write(settings_dir.join("settings.conf"), "theme=dark\n").unwrap();
+        fs::write(settings_dir.join("ai.conf"), "api_key=secret\n").unwrap();
+
+        let dest = dir.join("backup.zip");
+        let mut progress = Vec::new();
+        let summary = export_archive(&workspace, &dest, &ArchiveOptions::default(), &settings_dir, |p| {
+            progress.push(p.done);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!((summary.documents, summary.assets, summary.files), (1, 1, 5));
+        assert_eq!(progress, vec![0, 1, 2, 3, 4]);
+        assert!(!dir.join("backup.zip.part").exists());
+
+        let mut archive = zip::ZipArchive::new(File::open(&dest).unwrap()).unwrap();
+        let mut names: Vec<String> = (0..archive.len()).map(|i| archive.by_index(i).unwrap().name().to_string()).collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                ".canvas-archive/manifest.json",
+                ".canvas-archive/settings/settings.conf",
+                "assets/0123.png",
+                "notes/.plan.md.comments.json",
+                "notes/plan.md",
+            ]
+        );
+        let mut manifest = String::new();
+        archive.by_name(MANIFEST_NAME).unwrap().read_to_string(&mut manifest).unwrap();
+        let manifest: ArchiveManifest = serde_json::from_str(&manifest).unwrap();
+        assert_eq!(manifest.settings, vec!["settings.conf"]);
+
+        let options = ArchiveOptions { include_sidecars: Some(false), include_settings: Some(false), ..Default::default() };
+        let summary = export_archive(&workspace, &dest, &options, &settings_dir, |_| Ok(())).unwrap();
+        assert_eq!(summary.files, 3);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_safe_entry_path() {
+        assert_eq!(safe_entry_path("notes/plan.md"), Some(PathBuf::from("notes").join("plan.md")));
+        assert_eq!(safe_entry_path("notes/"), Some(PathBuf::from("notes")));
+        for name in ["../evil.md", "notes/../../evil.md", "/etc/passwd", "C:/evil.md", "notes\\..\\evil.md", "a//b", "./a", ""] {
+            assert_eq!(safe_entry_path(name), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_import_archive() {
+        let dir = std::env::temp_dir().join("test_archive_import");
+        let _ = fs::remove_dir_all(&dir);
+        let (workspace, settings_dir, dest) = (dir.join("workspace"), dir.join("app"), dir.join("imported"));
+        fs::create_dir_all(workspace.join("notes")).unwrap();
+        fs::create_dir_all(&settings_dir).unwrap();
+        fs::create_dir_all(dest.join("notes")).unwrap();
+        fs::write(workspace.join("notes/plan.md"), "# Plan").unwrap();
+        fs::write(workspace.join("notes/ideas.md"), "# Ideas").unwrap();
+        fs::write(sidecar::sidecar_path(&workspace.join("notes/plan.md"), "comments"), "[]").unwrap();
+        fs::write(settings_dir.join("settings.conf"), "theme=dark\n").unwrap();
+        fs::write(dest.join("notes/ideas.md"), "# Mine").unwrap();
+
+        let zip_path = dir.join("backup.zip");
+        export_archive(&workspace, &zip_path, &ArchiveOptions::default(), &settings_dir, |_| Ok(())).unwrap();
+        let imported = import_archive(&zip_path, &dest, |_| Ok(())).unwrap();
+        assert_eq!(imported.documents, vec!["notes/plan.md"]);
+        assert_eq!(imported.skipped, vec!["notes/ideas.md"]);
+        assert_eq!(imported.files, 3);
+        assert_eq!(imported.manifest.as_ref().map(|m| m.documents), Some(2));
+        assert_eq!(fs::read_to_string(dest.join("notes/ideas.md")).unwrap(), "# Mine");
+        assert!(sidecar::sidecar_path(&dest.join("notes/plan.md"), "comments").exists());
+        assert!(dest.join(SETTINGS_PREFIX).join("settings.conf").exists());
+
+        let records: Vec<ImportRecord> = sidecar::read_json(&dest.join(IMPORTS_NAME)).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].documents, vec!["notes/plan.md"]);
+        assert_eq!(records[0].sha256.len(), 64);
+
+        // Stopping partway removes what was extracted or written so far
+        let stop_after_first = |p: &ArchiveProgress| if p.done == 1 { Err(AppError::cancelled("Stopped")) } else { Ok(()) };
+        let cancelled = dir.join("cancelled");
+        assert!(matches!(import_archive(&zip_path, &cancelled, stop_after_first), Err(AppError::Cancelled(_))));
+        assert!(!cancelled.join("notes/plan.md").exists());
+        assert!(!cancelled.join("notes/ideas.md").exists());
+        assert!(!sidecar::sidecar_path(&cancelled.join("notes/plan.md"), "comments").exists());
+        assert!(!cancelled.join(SETTINGS_PREFIX).join("settings.conf").exists());
+        let stopped_zip = dir.join("stopped.zip");
+        assert!(export_archive(&workspace, &stopped_zip, &ArchiveOptions::default(), &settings_dir, stop_after_first).is_err());
+        assert!(!stopped_zip.exists());
+        assert!(!dir.join("stopped.zip.part").exists());
+
+        // One bad entry and nothing is extracted
+        let evil = dir.join("evil.zip");
+        let mut zip = ZipWriter::new(File::create(&evil).unwrap());
+        zip.start_file("fine.md", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"fine").unwrap();
+        zip.start_file("../escaped.md", SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"gotcha").unwrap();
+        zip.finish().unwrap();
+        assert!(import_archive(&evil, &dest, |_| Ok(())).is_err());
+        assert!(!dest.join("fine.md").exists());
+        assert!(!dir.join("escaped.md").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,280 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use crate::{document_version, sidecar};
+use crate::error::{AppError, AppResult};
+
+/// How deep `locate_asset` looks below the directory it's given.
+const LOCATE_MAX_DEPTH: usize = 6;
+
+/// A file used by documents but left where it is, e.g. a large video or
+/// dataset outside the workspace. The hash identifies the content, so a
+/// moved or edited target can be told apart from the original.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetReference {
+    pub id: String,
+    pub path: String,
+    pub content_hash: String,
+    pub size: u64,
+    pub modified: Option<u64>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetStatus {
+    Ok,
+    /// The file is there but its content differs from when it was linked
+    Changed,
+    /// Nothing at the path anymore; use `locate_asset` or `relink_asset`
+    Missing,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AssetCheck {
+    pub asset: AssetReference,
+    pub status: AssetStatus,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct AssetIndex {
+    assets: Vec<AssetReference>,
+}
+
+fn get_index_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir.join("asset_references.json"))
+}
+
+fn generate_id() -> String {
+    format!("asset-{}", Utc::now().timestamp_nanos_opt().unwrap_or_default())
+}
+
+/// Hashes a file without reading it into memory at once; the targets are
+/// often too large for that.
+fn hash_file(path: &Path) -> AppResult<(String, u64)> {
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| AppError::io(format!("Failed to open {}: {}", path.display(), e)))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1024 * 1024];
+    let mut size = 0;
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| AppError::io(format!("Failed to read {}: {}", path.display(), e)))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+    Ok((hex::encode(hasher.finalize()), size))
+}
+
+fn find_asset<'a>(index: &'a mut AssetIndex, id: &str) -> AppResult<&'a mut AssetReference> {
+    index
+        .assets
+        .iter_mut()
+        .find(|asset| asset.id == id)
+        .ok_or_else(|| AppError::not_found(format!("No asset found for {}", id)))
+}
+
+fn new_reference(path: &Path) -> AppResult<AssetReference> {
+    let (content_hash, size) = hash_file(path)?;
+    Ok(AssetReference {
+        id: generate_id(),
+        path: path.to_string_lossy().to_string(),
+        content_hash,
+        size,
+        modified: document_version::modified_millis(path),
+        created_at: Utc::now(),
+    })
+}
+
+/// Whether the target is still there and unchanged. The file is only
+/// rehashed when its size or mtime moved; a touched but identical file has
+/// its new mtime recorded, in which case this returns `true` alongside.
+fn asset_status(asset: &mut AssetReference) -> AppResult<(AssetStatus, bool)> {
+    let path = PathBuf::from(&asset.path);
+    let Ok(metadata) = std::fs::metadata(&path) else {
+        return Ok((AssetStatus::Missing, false));
+    };
+    let modified = document_version::modified_millis(&path);
+    if metadata.len() == asset.size && modified == asset.modified {
+        return Ok((AssetStatus::Ok, false));
+    }
+
+    let (content_hash, _) = hash_file(&path)?;
+    if content_hash != asset.content_hash {
+        return Ok((AssetStatus::Changed, false));
+    }
+    asset.modified = modified;
+    Ok((AssetStatus::Ok, true))
+}
+
+/// Points `asset` at `new_path`, adopting whatever content is there.
+fn relink(asset: &mut AssetReference, new_path: &Path) -> AppResult<AssetStatus> {
+    let (content_hash, size) = hash_file(new_path)?;
+    let status = if content_hash == asset.content_hash { AssetStatus::Ok } else { AssetStatus::Changed };
+    asset.path = new_path.to_string_lossy().to_string();
+    asset.content_hash = content_hash;
+    asset.size = size;
+    asset.modified = document_version::modified_millis(new_path);
+    Ok(status)
+}
+
+/// Files below `search_root` with the asset's exact content. Only files of
+/// the right size are hashed.
+fn find_copies(asset: &AssetReference, search_root: &Path) -> Vec<String> {
+    let mut matches = Vec::new();
+    let mut pending = vec![(search_root.to_path_buf(), 0)];
+    while let Some((dir, depth)) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                if depth < LOCATE_MAX_DEPTH && !entry.file_name().to_string_lossy().starts_with('.') {
+                    pending.push((path, depth + 1));
+                }
+            } else if metadata.len() == asset.size
+                && hash_file(&path).is_ok_and(|(hash, _)| hash == asset.content_hash)
+            {
+                matches.push(path.to_string_lossy().to_string());
+            }
+        }
+    }
+
+    matches.sort();
+    matches
+}
+
+/// Links `path` as a reference asset without copying it.
+pub fn add_reference(app_handle: &AppHandle, path: &Path) -> AppResult<AssetReference> {
+    let asset = new_reference(path)?;
+
+    let index_path = get_index_path(app_handle)?;
+    let mut index: AssetIndex = sidecar::read_json(&index_path)?;
+    index.assets.push(asset.clone());
+    sidecar::write_json(&index_path, &index)?;
+    Ok(asset)
+}
+
+/// Checks whether the target is still there and unchanged.
+pub fn check_asset(app_handle: &AppHandle, id: &str) -> AppResult<AssetCheck> {
+    let index_path = get_index_path(app_handle)?;
+    let mut index: AssetIndex = sidecar::read_json(&index_path)?;
+    let asset = find_asset(&mut index, id)?;
+    let (status, updated) = asset_status(asset)?;
+    let asset = asset.clone();
+    // Remember a touched file's new mtime to skip hashing it next time
+    if updated {
+        sidecar::write_json(&index_path, &index)?;
+    }
+    Ok(AssetCheck { asset, status })
+}
+
+/// Points a reference at `new_path`. If the content there is different,
+/// the reference adopts it and the status says so.
+pub fn relink_asset(app_handle: &AppHandle, id: &str, new_path: &Path) -> AppResult<AssetCheck> {
+    let index_path = get_index_path(app_handle)?;
+    let mut index: AssetIndex = sidecar::read_json(&index_path)?;
+    let asset = find_asset(&mut index, id)?;
+    let status = relink(asset, new_path)?;
+
+    let asset = asset.clone();
+    sidecar::write_json(&index_path, &index)?;
+    Ok(AssetCheck { asset, status })
+}
+
+/// Looks below `search_root` for files with the asset's exact content, to
+/// find where a missing target was moved.
+pub fn locate_asset(app_handle: &AppHandle, id: &str, search_root: &Path) -> AppResult<Vec<String>> {
+    let index: AssetIndex = sidecar::read_json(&get_index_path(app_handle)?)?;
+    let asset = index
+        .assets
+        .iter()
+        .find(|asset| asset.id == id)
+        .ok_or_else(|| AppError::not_found(format!("No asset found for {}", id)))?;
+    Ok(find_copies(asset, search_root))
+}
+
+pub fn list_assets(app_handle: &AppHandle) -> AppResult<Vec<AssetReference>> {
+    let index: AssetIndex = sidecar::read_json(&get_index_path(app_handle)?)?;
+    Ok(index.assets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_moved_asset_is_found_and_relinked() {
+        let dir = std::env::temp_dir().join("test_asset_refs_moved");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("archive/2024")).unwrap();
+        fs::write(dir.join("clip.mov"), b"frames").unwrap();
+        fs::write(dir.join("archive/other.mov"), b"others").unwrap();
+
+        let mut asset = new_reference(&dir.join("clip.mov")).unwrap();
+        assert_eq!(asset_status(&mut asset).unwrap(), (AssetStatus::Ok, false));
+
+        let moved = dir.join("archive/2024/clip-renamed.mov");
+        fs::rename(dir.join("clip.mov"), &moved).unwrap();
+        assert_eq!(asset_status(&mut asset).unwrap().0, AssetStatus::Missing);
+
+        // Same size, different content: not a match
+        assert_eq!(find_copies(&asset, &dir), vec![moved.to_string_lossy().to_string()]);
+
+        assert_eq!(relink(&mut asset, &moved).unwrap(), AssetStatus::Ok);
+        assert_eq!(asset.path, moved.to_string_lossy());
+        assert_eq!(asset_status(&mut asset).unwrap(), (AssetStatus::Ok, false));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_changed_asset_is_flagged() {
+        let dir = std::env::temp_dir().join("test_asset_refs_changed");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.csv");
+        fs::write(&path, "a,b\n1,2\n").unwrap();
+
+        let mut asset = new_reference(&path).unwrap();
+        let original_hash = asset.content_hash.clone();
+        fs::write(&path, "a,b\n1,2\n3,4\n").unwrap();
+        assert_eq!(asset_status(&mut asset).unwrap(), (AssetStatus::Changed, false));
+        assert_eq!(asset.content_hash, original_hash);
+
+        // Relinking to a different file adopts its content but says so
+        let other = dir.join("other.csv");
+        fs::write(&other, "x\n").unwrap();
+        assert_eq!(relink(&mut asset, &other).unwrap(), AssetStatus::Changed);
+        assert_ne!(asset.content_hash, original_hash);
+        assert_eq!(asset.size, 2);
+
+        // Rewritten with the same bytes: hashed again, still fine, and the
+        // new mtime is recorded
+        asset.modified = Some(0);
+        assert_eq!(asset_status(&mut asset).unwrap(), (AssetStatus::Ok, true));
+        assert_eq!(asset.modified, document_version::modified_millis(&other));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,148 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use crate::{document_format, workspace};
+use crate::error::{AppError, AppResult};
+
+/// Where images and attachments embedded in documents are kept, relative
+/// to the workspace.
+pub const ASSETS_DIR: &str = "assets";
+
+/// Hex digits of the content hash used as an asset's file name.
+const NAME_HASH_LENGTH: usize = 16;
+
+/// A file in the workspace's assets folder.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Asset {
+    pub name: String,
+    /// Relative to the workspace, with forward slashes, as documents embed it
+    pub path: String,
+    pub size: u64,
+    /// Documents referring to it, relative to the workspace
+    pub used_by: Vec<String>,
+}
+
+fn relative(name: &str) -> String {
+    format!("{}/{}", ASSETS_DIR, name)
+}
+
+/// The name an asset with these bytes is stored under: the same content
+/// always gets the same name, so it's only stored once.
+pub fn asset_name(bytes: &[u8], extension: &str) -> String {
+    let hash = &hex::encode(Sha256::digest(bytes))[..NAME_HASH_LENGTH];
+    if extension.is_empty() {
+        hash.to_string()
+    } else {
+        format!("{}.{}", hash, extension.to_lowercase())
+    }
+}
+
+/// Stores `bytes` as an asset and returns its path relative to the
+/// workspace.
+pub fn store_bytes(workspace: &Path, bytes: &[u8], extension: &str) -> AppResult<String> {
+    let name = asset_name(bytes, extension);
+    let dir = workspace.join(ASSETS_DIR);
+    let dest = dir.join(&name);
+    if !dest.exists() {
+        std::fs::create_dir_all(&dir).map_err(|e| AppError::io(format!("Failed to create {}: {}", dir.display(), e)))?;
+        std::fs::write(&dest, bytes).map_err(|e| AppError::io(format!("Failed to write {}: {}", dest.display(), e)))?;
+    }
+    Ok(relative(&name))
+}
+
+/// Copies a file into the assets folder and returns its path relative to
+/// the workspace. Importing the same content again returns the same path.
+pub fn import_asset(workspace: &Path, source: &Path) -> AppResult<String> {
+    let bytes = std::fs::read(source).map_err(|e| AppError::io(format!("Failed to read {}: {}", source.display(), e)))?;
+    let extension = source.extension().and_then(|e| e.to_str()).unwrap_or_default();
+    store_bytes(workspace, &bytes, extension)
+}
+
+pub(crate) fn asset_files(workspace: &Path) -> AppResult<Vec<PathBuf>> {
+    let dir = workspace.join(ASSETS_DIR);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let entries = std::fs::read_dir(&dir).map_err(|e| AppError::io(format!("Failed to read {}: {}", dir.display(), e)))?;
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .filter(|path| !path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with('.')))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Every asset with the documents that mention it by name.
+pub fn list_assets(workspace: &Path) -> AppResult<Vec<Asset>> {
+    let documents: Vec<(String, String)> = workspace::document_files(workspace)?
+        .into_iter()
+        .filter_map(|path| {
+            let content = document_format::read_document(&path).ok()?;
+            let name = path.strip_prefix(workspace).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+            Some((name, content))
+        })
+        .collect();
+
+    Ok(asset_files(workspace)?
+        .into_iter()
+        .map(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            Asset {
+                path: relative(&name),
+                size: std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+                used_by: documents
+                    .iter()
+                    .filter(|(_, content)| content.contains(&name))
+                    .map(|(document, _)| document.clone())
+                    .collect(),
+                name,
+            }
+        })
+        .collect())
+}
+
+/// Deletes assets no document mentions and returns their paths.
+pub fn delete_unused_assets(workspace: &Path) -> AppResult<Vec<String>> {
+    let mut deleted = Vec::new();
+    for asset in list_assets(workspace)?.into_iter().filter(|asset| asset.used_by.is_empty()) {
+        let path = workspace.join(ASSETS_DIR).join(&asset.name);
+        std::fs::remove_file(&path).map_err(|e| AppError::io(format!("Failed to delete {}: {}", path.display(), e)))?;
+        deleted.push(asset.path);
+    }
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_import_and_clean_up() {
+        let dir = std::env::temp_dir().join("test_assets");
+        let _ = fs::remove_dir_all(&dir);
+        let workspace = dir.join("workspace");
+        fs::create_dir_all(&workspace).unwrap();
+        fs::write(dir.join("Photo.JPG"), "jpeg bytes").unwrap();
+        fs::write(dir.join("copy.jpg"), "jpeg bytes").unwrap();
+        fs::write(dir.join("chart.png"), "png bytes").unwrap();
+
+        let photo = import_asset(&workspace, &dir.join("Photo.JPG")).unwrap();
+        assert!(photo.starts_with("assets/") && photo.ends_with(".jpg"));
+        assert_eq!(import_asset(&workspace, &dir.join("copy.jpg")).unwrap(), photo);
+        let chart = import_asset(&workspace, &dir.join("chart.png")).unwrap();
+
+        fs::write(workspace.join("trip.md"), format!("# Trip\n\n![photo]({})", photo)).unwrap();
+        let assets = list_assets(&workspace).unwrap();
+        assert_eq!(assets.len(), 2);
+        let used = assets.iter().find(|a| a.path == photo).unwrap();
+        assert_eq!(used.used_by, vec!["trip.md"]);
+
+        assert_eq!(delete_unused_assets(&workspace).unwrap(), vec![chart.clone()]);
+        assert!(workspace.join(&photo).exists());
+        assert!(!workspace.join(&chart).exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,202 @@
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use serde::Serialize;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use crate::assets::{self, ASSETS_DIR};
+use crate::error::{AppError, AppResult};
+
+type SharedWriter = Arc<Mutex<Option<hound::WavWriter<BufWriter<File>>>>>;
+
+/// A finished voice memo.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AudioNote {
+    /// Relative to the workspace, like other assets
+    pub path: String,
+    pub duration_ms: u64,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+struct ActiveRecording {
+    workspace: PathBuf,
+    stop: mpsc::Sender<()>,
+    /// The recording thread; it owns the input stream, which can't be moved
+    /// between threads, and returns the temp file once it stopped
+    thread: JoinHandle<AppResult<PathBuf>>,
+}
+
+/// The one recording that can run at a time.
+#[derive(Default)]
+pub struct RecordingState {
+    active: Mutex<Option<ActiveRecording>>,
+}
+
+fn write_samples<T>(data: &[T], writer: &SharedWriter)
+where
+    T: cpal::Sample,
+    i16: cpal::FromSample<T>,
+{
+    if let Ok(mut guard) = writer.lock() {
+        if let Some(writer) = guard.as_mut() {
+            for &sample in data {
+                if writer.write_sample(sample.to_sample::<i16>()).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Records from the default microphone into `temp` until told to stop.
+/// Whether recording started is reported through `started`.
+fn record(temp: PathBuf, started: mpsc::Sender<AppResult<()>>, stop: mpsc::Receiver<()>) -> AppResult<PathBuf> {
+    let setup = || -> AppResult<(cpal::Stream, SharedWriter)> {
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or_else(|| AppError::not_found("No microphone found"))?;
+        let supported = device
+            .default_input_config()
+            .map_err(|e| AppError::io(format!("Failed to open microphone: {}", e)))?;
+        let sample_format = supported.sample_format();
+        let config: cpal::StreamConfig = supported.into();
+
+        let spec = hound::WavSpec {
+            channels: config.channels,
+            sample_rate: config.sample_rate.0,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let writer = hound::WavWriter::create(&temp, spec).map_err(|e| AppError::io(format!("Failed to create {}: {}", temp.display(), e)))?;
+        let writer: SharedWriter = Arc::new(Mutex::new(Some(writer)));
+
+        let on_error = |e| tracing::error!("Recording error: {}", e);
+        let shared = writer.clone();
+        let stream = match sample_format {
+            cpal::SampleFormat::F32 => {
+                device.build_input_stream(&config, move |data: &[f32], _: &_| write_samples(data, &shared), on_error, None)
+            }
+            cpal::SampleFormat::I16 => {
+                device.build_input_stream(&config, move |data: &[i16], _: &_| write_samples(data, &shared), on_error, None)
+            }
+            cpal::SampleFormat::U16 => {
+                device.build_input_stream(&config, move |data: &[u16], _: &_| write_samples(data, &shared), on_error, None)
+            }
+            other => return Err(AppError::unsupported(format!("Unsupported microphone sample format {:?}", other))),
+        }
+        .map_err(|e| AppError::io(format!("Failed to open microphone: {}", e)))?;
+        stream.play().map_err(|e| AppError::io(format!("Failed to start recording: {}", e)))?;
+        Ok((stream, writer))
+    };
+
+    let (stream, writer) = match setup() {
+        Ok(recording) => {
+            let _ = started.send(Ok(()));
+            recording
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&temp);
+            let _ = started.send(Err(e.clone()));
+            return Err(e);
+        }
+    };
+
+    // Stopped explicitly, or the state was dropped with the app
+    let _ = stop.recv();
+    drop(stream);
+    let writer = writer.lock()?.take();
+    if let Some(writer) = writer {
+        writer.finalize().map_err(|e| AppError::io(format!("Failed to finish recording: {}", e)))?;
+    }
+    Ok(temp)
+}
+
+/// Starts recording from the default microphone. The memo goes into the
+/// workspace's assets when stopped.
+pub fn start_recording(state: &RecordingState, workspace: &Path) -> AppResult<()> {
+    let mut active = state.active.lock()?;
+    if active.is_some() {
+        return Err(AppError::conflict("Already recording"));
+    }
+
+    let dir = workspace.join(ASSETS_DIR);
+    std::fs::create_dir_all(&dir).map_err(|e| AppError::io(format!("Failed to create {}: {}", dir.display(), e)))?;
+    let temp = dir.join(format!(".recording-{}.wav", chrono::Utc::now().timestamp_millis()));
+
+    let (started_tx, started_rx) = mpsc::channel();
+    let (stop_tx, stop_rx) = mpsc::channel();
+    let thread = std::thread::Builder::new()
+        .name("audio-recording".to_string())
+        .spawn(move || record(temp, started_tx, stop_rx))
+        .map_err(|e| AppError::io(format!("Failed to start recording: {}", e)))?;
+
+    started_rx.recv().map_err(|_| AppError::internal("Recording stopped unexpectedly"))??;
+    *active = Some(ActiveRecording { workspace: workspace.to_path_buf(), stop: stop_tx, thread });
+    Ok(())
+}
+
+/// Stops the recording and stores it as an asset named by its content.
+pub fn stop_recording(state: &RecordingState) -> AppResult<AudioNote> {
+    let recording = state
+        .active
+        .lock()?
+        .take()
+        .ok_or_else(|| AppError::conflict("Not recording"))?;
+    let _ = recording.stop.send(());
+    let temp = recording
+        .thread
+        .join()
+        .map_err(|_| AppError::internal("Recording thread panicked"))??;
+
+    let path = assets::import_asset(&recording.workspace, &temp);
+    let _ = std::fs::remove_file(&temp);
+    let path = path?;
+    audio_info(&recording.workspace, &path)
+}
+
+pub fn is_recording(state: &RecordingState) -> bool {
+    state.active.lock().is_ok_and(|active| active.is_some())
+}
+
+/// Duration and format of a WAV asset; `path` is relative to `workspace`.
+pub fn audio_info(workspace: &Path, path: &str) -> AppResult<AudioNote> {
+    let full = workspace.join(path);
+    let reader = hound::WavReader::open(&full).map_err(|e| AppError::io(format!("Failed to read {}: {}", full.display(), e)))?;
+    let spec = reader.spec();
+    Ok(AudioNote {
+        path: path.to_string(),
+        // `duration` counts frames, i.e. samples per channel
+        duration_ms: u64::from(reader.duration()) * 1000 / u64::from(spec.sample_rate.max(1)),
+        sample_rate: spec.sample_rate,
+        channels: spec.channels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audio_info() {
+        let workspace = std::env::temp_dir().join("test_audio");
+        let _ = std::fs::remove_dir_all(&workspace);
+        std::fs::create_dir_all(workspace.join(ASSETS_DIR)).unwrap();
+
+        let spec = hound::WavSpec { channels: 2, sample_rate: 8000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut writer = hound::WavWriter::create(workspace.join("assets/memo.wav"), spec).unwrap();
+        // Half a second of stereo silence
+        for _ in 0..8000 {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let info = audio_info(&workspace, "assets/memo.wav").unwrap();
+        assert_eq!(info.duration_ms, 500);
+        assert_eq!(info.channels, 2);
+
+        let _ = std::fs::remove_dir_all(&workspace);
+    }
+}
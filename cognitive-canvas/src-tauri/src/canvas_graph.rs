@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use crate::node_types::NodeError;
+
+/// A node of a canvas document's Lexical tree.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CanvasNode {
+    /// The node's own `id` field if it has one, otherwise where it is,
+    /// e.g. "root.children[2].children[0]"
+    pub id: String,
+    pub node_type: String,
+    pub parent: Option<String>,
+    pub depth: usize,
+    /// Text of text nodes
+    pub text: Option<String>,
+    /// Every other property except `children`
+    pub fields: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// From a node to one of its children
+    Child,
+    /// A connector drawn between two nodes, from the document's `edges`
+    Connection,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CanvasEdge {
+    pub from: String,
+    pub to: String,
+    pub kind: EdgeKind,
+    pub label: Option<String>,
+}
+
+/// A connector as stored next to `root` in a canvas file.
+#[derive(Debug, Clone, Deserialize)]
+struct StoredEdge {
+    from: String,
+    to: String,
+    #[serde(default)]
+    label: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CanvasGraph {
+    pub nodes: Vec<CanvasNode>,
+    pub edges: Vec<CanvasEdge>,
+}
+
+/// Which nodes `find` returns; every field given has to match.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct NodeQuery {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub node_type: Option<String>,
+    /// Case-insensitive substring of the node's text
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+impl NodeQuery {
+    fn matches(&self, node: &CanvasNode) -> bool {
+        self.id.as_ref().is_none_or(|id| &node.id == id)
+            && self.node_type.as_ref().is_none_or(|t| &node.node_type == t)
+            && self.text.as_ref().is_none_or(|text| {
+                node.text.as_ref().is_some_and(|t| t.to_lowercase().contains(&text.to_lowercase()))
+            })
+    }
+}
+
+struct Builder {
+    graph: CanvasGraph,
+    errors: Vec<NodeError>,
+    ids: HashSet<String>,
+}
+
+impl Builder {
+    fn error(&mut self, path: &str, node_type: &str, message: String) {
+        self.errors.push(NodeError {
+            path: path.to_string(),
+            node_type: node_type.to_string(),
+            message,
+        });
+    }
+
+    fn visit(&mut self, node: &Value, path: &str, parent: Option<&str>, depth: usize) {
+        let Some(object) = node.as_object() else {
+            self.error(path, "", "Node isn't an object".to_string());
+            return;
+        };
+        let node_type = match object.get("type").and_then(|t| t.as_str()) {
+            Some(node_type) => node_type.to_string(),
+            None if depth == 0 => "root".to_string(),
+            None => {
+                self.error(path, "", "Node has no type".to_string());
+                String::new()
+            }
+        };
+
+        let id = object.get("id").and_then(|id| id.as_str()).map(str::to_string).unwrap_or_else(|| path.to_string());
+        if !self.ids.insert(id.clone()) {
+            self.error(path, &node_type, format!("Duplicate node id {}", id));
+        }
+
+        let children = match object.get("children") {
+            None => &[][..],
+            Some(Value::Array(children)) => children.as_slice(),
+            Some(_) => {
+                self.error(path, &node_type, "children should be an array".to_string());
+                &[][..]
+            }
+        };
+
+        self.graph.nodes.push(CanvasNode {
+            id: id.clone(),
+            node_type,
+            parent: parent.map(str::to_string),
+            depth,
+            text: object.get("text").and_then(|t| t.as_str()).map(str::to_string),
+            fields: object
+                .iter()
+                .filter(|(key, _)| !matches!(key.as_str(), "children" | "type" | "text" | "id"))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        });
+
+        for (i, child) in children.iter().enumerate() {
+            let child_path = format!("{}.children[{}]", path, i);
+            if child.is_object() {
+                let child_id = child
+                    .get("id")
+                    .and_then(|id| id.as_str())
+                    .map(str::to_string)
+                    .unwrap_or_else(|| child_path.clone());
+                self.graph.edges.push(CanvasEdge {
+                    from: id.clone(),
+                    to: child_id,
+                    kind: EdgeKind::Child,
+                    label: None,
+                });
+            }
+            self.visit(child, &child_path, Some(&id), depth + 1);
+        }
+    }
+
+    fn connections(&mut self, edges: &Value) {
+        let Some(edges) = edges.as_array() else {
+            self.error("edges", "", "edges should be an array".to_string());
+            return;
+        };
+        for (i, edge) in edges.iter().enumerate() {
+            let path = format!("edges[{}]", i);
+            let edge: StoredEdge = match serde_json::from_value(edge.clone()) {
+                Ok(edge) => edge,
+                Err(e) => {
+                    self.error(&path, "edge", format!("Invalid edge: {}", e));
+                    continue;
+                }
+            };
+            for end in [&edge.from, &edge.to] {
+                if !self.ids.contains(end) {
+                    self.error(&path, "edge", format!("Edge refers to missing node {}", end));
+                }
+            }
+            self.graph.edges.push(CanvasEdge {
+                from: edge.from,
+                to: edge.to,
+                kind: EdgeKind::Connection,
+                label: edge.label,
+            });
+        }
+    }
+}
+
+fn build(content: &str) -> Option<(CanvasGraph, Vec<NodeError>)> {
+    let value: Value = serde_json::from_str(content).ok()?;
+    let root = value.get("root")?;
+
+    let mut builder = Builder { graph: CanvasGraph::default(), errors: Vec::new(), ids: HashSet::new() };
+    builder.visit(root, "root", None, 0);
+    if let Some(edges) = value.get("edges") {
+        builder.connections(edges);
+    }
+    Some((builder.graph, builder.errors))
+}
+
+impl CanvasGraph {
+    /// Reads the nodes and edges of a canvas document, skipping what's
+    /// malformed. Content that isn't Lexical JSON has no graph.
+    pub fn parse(content: &str) -> Option<CanvasGraph> {
+        build(content).map(|(graph, _)| graph)
+    }
+
+    pub fn node_count(&self, node_type: Option<&str>) -> usize {
+        self.nodes.iter().filter(|node| node_type.is_none_or(|t| node.node_type == t)).count()
+    }
+
+    /// The first node in document order that matches `query`.
+    pub fn find(&self, query: &NodeQuery) -> Option<&CanvasNode> {
+        self.nodes.iter().find(|node| query.matches(node))
+    }
+}
+
+/// Structural problems that would stop the editor from loading a canvas
+/// document: nodes that aren't objects or lack a type, `children` that
+/// aren't arrays, duplicate ids, and connectors to missing nodes.
+pub fn validate(content: &str) -> Vec<NodeError> {
+    build(content).map(|(_, errors)| errors).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_graph() {
+        let doc = r#"{"root":{"children":[
+            {"type":"paragraph","id":"intro","children":[{"type":"text","text":"Hello World"}]},
+            {"type":"image","id":"pic","src":"a.png"}
+        ]},"edges":[{"from":"intro","to":"pic","label":"shows"}]}"#;
+
+        let graph = CanvasGraph::parse(doc).unwrap();
+        assert_eq!(graph.node_count(None), 4);
+        assert_eq!(graph.node_count(Some("text")), 1);
+        let text = graph.find(&NodeQuery { text: Some("world".to_string()), ..Default::default() }).unwrap();
+        assert_eq!(text.id, "root.children[0].children[0]");
+        assert_eq!(text.parent.as_deref(), Some("intro"));
+        assert_eq!(graph.find(&NodeQuery { id: Some("pic".to_string()), ..Default::default() }).unwrap().fields["src"], "a.png");
+
+        let connections: Vec<&CanvasEdge> = graph.edges.iter().filter(|e| e.kind == EdgeKind::Connection).collect();
+        assert_eq!(connections.len(), 1);
+        assert_eq!(graph.edges.len(), 4);
+        assert!(validate(doc).is_empty());
+        assert!(CanvasGraph::parse("# markdown").is_none());
+    }
+
+    #[test]
+    fn test_validate_structure() {
+        let doc = r#"{"root":{"children":[
+            {"type":"paragraph","id":"a","children":"oops"},
+            {"id":"a"},
+            3
+        ]},"edges":[{"from":"a","to":"b"},{"from":"a"}]}"#;
+
+        let messages: Vec<String> = validate(doc).into_iter().map(|e| format!("{}: {}", e.path, e.message)).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "root.children[0]: children should be an array",
+                "root.children[1]: Node has no type",
+                "root.children[1]: Duplicate node id a",
+                "root.children[2]: Node isn't an object",
+                "edges[0]: Edge refers to missing node b",
+                "edges[1]: Invalid edge: missing field `to`",
+            ]
+        );
+    }
+}
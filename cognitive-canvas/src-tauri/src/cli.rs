@@ -0,0 +1,185 @@
+use chrono::Local;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::{deep_link, document_text, retention, workspace};
+use crate::error::{AppError, AppResult};
+
+/// Where `capture` appends, relative to the workspace.
+const INBOX_FILE: &str = "Inbox.md";
+
+/// What the app was asked to do from the command line.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum CliCommand {
+    Open { paths: Vec<String> },
+    New { title: String },
+    Capture { text: String },
+    Search { query: String },
+}
+
+/// Commands received before the frontend was listening, handed over by
+/// `take_pending`.
+#[derive(Default)]
+pub struct CliState {
+    pending: Mutex<Vec<CliCommand>>,
+}
+
+/// Parses the process arguments, the first being the executable. Relative
+/// paths are resolved against `cwd`, which for a forwarded command is the
+/// second instance's. Returns `None` when there's nothing to do beyond
+/// launching.
+pub fn parse(args: &[String], cwd: &Path) -> AppResult<Option<CliCommand>> {
+    let Some(first) = args.get(1) else {
+        return Ok(None);
+    };
+    // How Windows and Linux hand over a `cognitivecanvas://` link
+    if deep_link::is_deep_link(first) {
+        return deep_link::parse(first).map(Some);
+    }
+    let text = args[2..].join(" ");
+    let argument = |name: &str| {
+        if text.trim().is_empty() {
+            Err(AppError::validation(format!("Usage: cognitive-canvas {} \"{}\"", first, name)))
+        } else {
+            Ok(text.clone())
+        }
+    };
+
+    match first.as_str() {
+        "new" => Ok(Some(CliCommand::New { title: argument("Title")? })),
+        "capture" => Ok(Some(CliCommand::Capture { text: argument("text")? })),
+        "search" => Ok(Some(CliCommand::Search { query: argument("query")? })),
+        _ => {
+            // Anything else is files to open; flags are for the OS or webview
+            let paths: Vec<String> = args[1..]
+                .iter()
+                .filter(|arg| !arg.starts_with('-'))
+                .map(|arg| cwd.join(arg).to_string_lossy().to_string())
+                .collect();
+            Ok((!paths.is_empty()).then_some(CliCommand::Open { paths }))
+        }
+    }
+}
+
+/// Hands a command to the frontend as a "cli-command" event and brings the
+/// window forward. It's also queued, in case the frontend isn't listening
+/// yet.
+pub fn dispatch(app_handle: &AppHandle, command: CliCommand) {
+    let state = app_handle.state::<CliState>();
+    if let Ok(mut pending) = state.pending.lock() {
+        pending.push(command.clone());
+    }
+    let _ = app_handle.emit("cli-command", &command);
+    focus_main_window(app_handle);
+}
+
+/// Brings the window forward, whether it was minimized or hidden.
+pub fn focus_main_window(app_handle: &AppHandle) {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+    }
+}
+
+/// Returns the commands not yet taken; each is delivered once.
+pub fn take_pending(state: &CliState) -> AppResult<Vec<CliCommand>> {
+    let mut pending = state.pending.lock()?;
+    Ok(std::mem::take(&mut *pending))
+}
+
+pub(crate) fn workspace_root(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    retention::workspace_dir(app_handle)?.ok_or_else(|| AppError::config("No workspace directory configured"))
+}
+
+/// Runs `command` without the UI, for when no instance was running.
+/// Returns the lines to print.
+pub fn run_headless(app_handle: &AppHandle, command: &CliCommand) -> AppResult<Vec<String>> {
+    match command {
+        CliCommand::Open { .. } => Ok(Vec::new()),
+        CliCommand::New { title } => {
+            let path = create_document(&workspace_root(app_handle)?, title)?;
+            Ok(vec![path.to_string_lossy().to_string()])
+        }
+        CliCommand::Capture { text } => {
+            let path = capture(&workspace_root(app_handle)?, text)?;
+            Ok(vec![format!("Captured to {}", path.display())])
+        }
+        CliCommand::Search { query } => Ok(search(&workspace_root(app_handle)?, query)?
+            .into_iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect()),
+    }
+}
+
+/// Creates a Markdown document titled `title`, numbering the file name if
+/// it's taken.
+fn create_document(root: &Path, title: &str) -> AppResult<PathBuf> {
+    let name: String = title
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == ' ' || *c == '-' || *c == '_')
+        .collect();
+    let name = if name.trim().is_empty() { "Untitled" } else { name.trim() };
+
+    let mut path = root.join(format!("{}.md", name));
+    let mut n = 2;
+    while path.exists() {
+        path = root.join(format!("{} {}.md", name, n));
+        n += 1;
+    }
+
+    std::fs::write(&path, format!("# {}\n", title))
+        .map_err(|e| AppError::io(format!("Failed to create {}: {}", path.display(), e)))?;
+    Ok(path)
+}
+
+fn capture(root: &Path, text: &str) -> AppResult<PathBuf> {
+    let path = root.join(INBOX_FILE);
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| AppError::io(format!("Failed to open {}: {}", path.display(), e)))?;
+    writeln!(file, "- [{}] {}", Local::now().format("%Y-%m-%d %H:%M"), text)
+        .map_err(|e| AppError::io(format!("Failed to write {}: {}", path.display(), e)))?;
+    Ok(path)
+}
+
+/// Documents whose text contains `query`, ignoring case.
+fn search(root: &Path, query: &str) -> AppResult<Vec<PathBuf>> {
+    let query = query.to_lowercase();
+    Ok(workspace::document_files(root)?
+        .into_iter()
+        .filter(|path| {
+            std::fs::read_to_string(path)
+                .is_ok_and(|content| document_text::plain_text(&content).to_lowercase().contains(&query))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        std::iter::once("cognitive-canvas").chain(list.iter().copied()).map(String::from).collect()
+    }
+
+    #[test]
+    fn test_parse_subcommands() {
+        let cwd = Path::new("/home/me");
+        assert_eq!(parse(&args(&[]), cwd).unwrap(), None);
+        assert_eq!(
+            parse(&args(&["new", "Weekly", "review"]), cwd).unwrap(),
+            Some(CliCommand::New { title: "Weekly review".to_string() })
+        );
+        assert!(parse(&args(&["capture"]), cwd).is_err());
+        assert_eq!(
+            parse(&args(&["notes.md", "--flag"]), cwd).unwrap(),
+            Some(CliCommand::Open { paths: vec![cwd.join("notes.md").to_string_lossy().to_string()] })
+        );
+    }
+}
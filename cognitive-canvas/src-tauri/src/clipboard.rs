@@ -0,0 +1,330 @@
+use base64::Engine;
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+use crate::assets;
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LinkPreview {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+}
+
+/// What the clipboard holds, along with the payload best suited to turn it
+/// into a canvas node.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ClipboardContent {
+    Url {
+        url: String,
+        domain: String,
+        preview: Option<LinkPreview>,
+    },
+    Image {
+        width: usize,
+        height: usize,
+        data_url: String,
+    },
+    Table {
+        delimiter: char,
+        headers: Vec<String>,
+        rows: Vec<Vec<String>>,
+    },
+    Code {
+        language: Option<String>,
+        code: String,
+    },
+    RichText {
+        html: String,
+        text: String,
+    },
+    Text {
+        text: String,
+    },
+    Empty,
+}
+
+pub async fn classify_clipboard() -> AppResult<ClipboardContent> {
+    // Read everything up front so the clipboard handle isn't held across the preview fetch
+    let (text, html) = {
+        let mut clipboard = arboard::Clipboard::new()
+            .map_err(|e| AppError::io(format!("Failed to access clipboard: {}", e)))?;
+
+        if let Ok(image) = clipboard.get_image() {
+            let png = encode_png(image.width as u32, image.height as u32, &image.bytes)?;
+            return Ok(ClipboardContent::Image {
+                width: image.width,
+                height: image.height,
+                data_url: format!(
+                    "data:image/png;base64,{}",
+                    base64::engine::general_purpose::STANDARD.encode(png)
+                ),
+            });
+        }
+
+        let html = clipboard.get().html().ok();
+        (clipboard.get_text().unwrap_or_default(), html)
+    };
+
+    let mut content = classify_text(&text, html);
+    if let ClipboardContent::Url { url, preview, .. } = &mut content {
+        *preview = fetch_link_preview(url).await;
+    }
+
+    Ok(content)
+}
+
+pub fn encode_png(width: u32, height: u32, rgba: &[u8]) -> AppResult<Vec<u8>> {
+    let mut png = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut png, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder
+            .write_header()
+            .map_err(|e| AppError::io(format!("Failed to encode image: {}", e)))?;
+        writer
+            .write_image_data(rgba)
+            .map_err(|e| AppError::io(format!("Failed to encode image: {}", e)))?;
+    }
+    Ok(png)
+}
+
+/// Saves the image on the clipboard as a PNG in the workspace's assets
+/// folder and returns its path relative to the workspace, e.g.
+/// `assets/3f2a9c1e5b7d4a60.png`. The name comes from the content, so
+/// pasting the same image twice doesn't store it twice.
+pub fn paste_image(workspace: &Path) -> AppResult<String> {
+    let image = arboard::Clipboard::new()
+        .map_err(|e| AppError::io(format!("Failed to access clipboard: {}", e)))?
+        .get_image()
+        .map_err(|_| AppError::not_found("The clipboard doesn't hold an image"))?;
+    let png = encode_png(image.width as u32, image.height as u32, &image.bytes)?;
+    assets::store_bytes(workspace, &png, "png")
+}
+
+/// Picks the most specific interpretation of pasted text. `html` is the
+/// rich-text flavor of the same clipboard entry, if the source offered one.
+pub fn classify_text(text: &str, html: Option<String>) -> ClipboardContent {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return match html {
+            Some(html) if !html.trim().is_empty() => ClipboardContent::RichText { html, text: String::new() },
+            _ => ClipboardContent::Empty,
+        };
+    }
+
+    if let Some(domain) = url_domain(trimmed) {
+        return ClipboardContent::Url {
+            url: trimmed.to_string(),
+            domain,
+            preview: None,
+        };
+    }
+
+    if let Some((delimiter, mut rows)) = parse_table(trimmed) {
+        let headers = rows.remove(0);
+        return ClipboardContent::Table { delimiter, headers, rows };
+    }
+
+    if looks_like_code(trimmed) {
+        return ClipboardContent::Code {
+            language: guess_language(trimmed),
+            code: text.trim_matches('\n').to_string(),
+        };
+    }
+
+    match html {
+        Some(html) if has_formatting(&html) => ClipboardContent::RichText { html, text: text.to_string() },
+        _ => ClipboardContent::Text { text: text.to_string() },
+    }
+}
+
+fn url_domain(text: &str) -> Option<String> {
+    if text.contains(char::is_whitespace) {
+        return None;
+    }
+    let rest = ["https://", "http://", "ftp://"]
+        .iter()
+        .find_map(|scheme| text.strip_prefix(scheme))?;
+    let domain = rest.split(['/', '?', '#']).next()?;
+    if domain.is_empty() || !domain.contains('.') && !domain.starts_with("localhost") {
+        return None;
+    }
+    Some(domain.to_string())
+}
+
+/// Detects delimited tables: at least two rows, all with the same number
+/// (two or more) of columns.
+fn parse_table(text: &str) -> Option<(char, Vec<Vec<String>>)> {
+    let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+    if lines.len() < 2 {
+        return None;
+    }
+
+    for delimiter in ['\t', ',', ';'] {
+        let rows: Vec<Vec<String>> = lines.iter().map(|line| split_row(line, delimiter)).collect();
+        let columns = rows[0].len();
+        if columns >= 2 && rows.iter().all(|row| row.len() == columns) {
+            return Some((delimiter, rows));
+        }
+    }
+
+    None
+}
+
+fn split_row(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            c if c == delimiter && !in_quotes => {
+                fields.push(field.trim().to_string());
+                field.clear();
+            }
+            c => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+
+    fields
+}
+
+fn looks_like_code(text: &str) -> bool {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut score = 0;
+
+    for line in &lines {
+        let line = line.trim();
+        if line.ends_with(';') || line.ends_with('{') || line == "}" || line.ends_with("):") {
+            score += 2;
+        }
+        if CODE_PREFIXES.iter().any(|prefix| line.starts_with(prefix)) {
+            score += 2;
+        }
+        if line.contains("=>") || line.contains("->") || line.contains("==") || line.contains("::") {
+            score += 1;
+        }
+    }
+
+    let indented = lines.iter().filter(|l| l.starts_with("    ") || l.starts_with('\t')).count();
+    if lines.len() > 1 && indented * 3 >= lines.len() {
+        score += 2;
+    }
+
+    score >= 3
+}
+
+const CODE_PREFIXES: &[&str] = &[
+    "fn ", "pub ", "use ", "impl ", "let ", "const ", "def ", "class ", "import ", "from ",
+    "function ", "export ", "return ", "#include", "package ", "func ", "SELECT ", "//", "/*",
+];
+
+fn guess_language(code: &str) -> Option<String> {
+    let checks: &[(&str, &[&str])] = &[
+        ("rust", &["fn ", "let mut ", "impl ", "pub fn", "::new("]),
+        ("python", &["def ", "import ", "elif ", "self.", "):\n"]),
+        ("typescript", &["interface ", ": string", ": number", "export type "]),
+        ("javascript", &["function ", "const ", "=> {", "console.log"]),
+        ("go", &["func ", "package ", ":= "]),
+        ("c", &["#include", "int main(", "printf("]),
+        ("sql", &["SELECT ", "INSERT INTO", "CREATE TABLE", "WHERE "]),
+        ("html", &["<div", "<html", "</"]),
+        ("json", &["\": ", "{\n  \""]),
+    ];
+
+    checks
+        .iter()
+        .map(|(language, markers)| (language, markers.iter().filter(|m| code.contains(*m)).count()))
+        .filter(|(_, hits)| *hits > 0)
+        .max_by_key(|(_, hits)| *hits)
+        .map(|(language, _)| language.to_string())
+}
+
+/// Browsers wrap even plain selections in a `<meta>`/`<span>`, so only treat
+/// HTML as rich text when it carries real structure or emphasis.
+fn has_formatting(html: &str) -> bool {
+    let lower = html.to_lowercase();
+    ["<b", "<strong", "<i>", "<em", "<u>", "<a ", "<h1", "<h2", "<h3", "<ul", "<ol", "<table", "<code"]
+        .iter()
+        .any(|tag| lower.contains(tag))
+}
+
+async fn fetch_link_preview(url: &str) -> Option<LinkPreview> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(3))
+        .build()
+        .ok()?;
+    let html = client.get(url).send().await.ok()?.text().await.ok()?;
+
+    let title = meta_content(&html, "og:title").or_else(|| {
+        let start = html.find("<title")?;
+        let after = &html[start..];
+        let open_end = after.find('>')?;
+        let close = after.find("</title>")?;
+        (open_end < close).then(|| after[open_end + 1..close].trim().to_string())
+    });
+
+    Some(LinkPreview {
+        title,
+        description: meta_content(&html, "og:description").or_else(|| meta_content(&html, "description")),
+        image: meta_content(&html, "og:image"),
+    })
+}
+
+fn meta_content(html: &str, name: &str) -> Option<String> {
+    let needles = [format!("property=\"{}\"", name), format!("name=\"{}\"", name)];
+    for tag in html.split("<meta").skip(1) {
+        let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+        if needles.iter().any(|n| tag.contains(n.as_str())) {
+            let start = tag.find("content=\"")? + "content=\"".len();
+            let end = tag[start..].find('"')? + start;
+            return Some(tag[start..end].to_string());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_text() {
+        assert!(matches!(
+            classify_text("https://example.com/page?x=1", None),
+            ClipboardContent::Url { ref domain, .. } if domain == "example.com"
+        ));
+
+        match classify_text("name,age\nAda,36\n\"Smith, J\",41", None) {
+            ClipboardContent::Table { delimiter, headers, rows } => {
+                assert_eq!(delimiter, ',');
+                assert_eq!(headers, vec!["name", "age"]);
+                assert_eq!(rows[1], vec!["Smith, J", "41"]);
+            }
+            other => panic!("expected table, got {:?}", other),
+        }
+
+        match classify_text("fn main() {\n    let x = 1;\n}", None) {
+            ClipboardContent::Code { language, .. } => assert_eq!(language.as_deref(), Some("rust")),
+            other => panic!("expected code, got {:?}", other),
+        }
+
+        assert!(matches!(
+            classify_text("Just a sentence.", Some("<b>Just</b> a sentence.".to_string())),
+            ClipboardContent::RichText { .. }
+        ));
+        assert!(matches!(classify_text("Just a sentence.", None), ClipboardContent::Text { .. }));
+        assert_eq!(classify_text("  ", None), ClipboardContent::Empty);
+    }
+}
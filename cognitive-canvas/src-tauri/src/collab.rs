@@ -0,0 +1,479 @@
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc, watch};
+use tokio_tungstenite::tungstenite::Message;
+use crate::error::{AppError, AppResult};
+
+const HOST_PEER_ID: &str = "host";
+/// Letters and digits that can't be mistaken for each other when read out.
+/// There are 32, so a random byte maps onto one without bias.
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+/// 50 bits, far more than can be guessed at `MAX_FAILED_JOINS` a minute
+const CODE_LENGTH: usize = 10;
+const MAX_FAILED_JOINS: usize = 5;
+const FAILED_JOIN_WINDOW: Duration = Duration::from_secs(60);
+/// How long connecting and exchanging join messages may take, either way
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Wire format shared by the host and every guest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CollabMessage {
+    Join { code: String, name: String },
+    Welcome { peer_id: String, document: String },
+    Rejected { reason: String },
+    Edit { peer_id: String, payload: Value },
+    PeerJoined { peer_id: String, name: String },
+    PeerLeft { peer_id: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CollabSessionInfo {
+    pub role: String,
+    pub document: String,
+    pub code: String,
+    pub port: u16,
+    pub addresses: Vec<String>,
+}
+
+enum ActiveSession {
+    Host {
+        info: CollabSessionInfo,
+        tx: broadcast::Sender<CollabMessage>,
+        shutdown: watch::Sender<bool>,
+    },
+    Guest {
+        info: CollabSessionInfo,
+        peer_id: String,
+        outgoing: mpsc::UnboundedSender<CollabMessage>,
+        shutdown: watch::Sender<bool>,
+    },
+    /// Connecting to a host. Holds the slot so nothing else starts meanwhile
+    Joining,
+}
+
+/// What every connection to a hosted session shares.
+struct Host {
+    info: CollabSessionInfo,
+    limiter: Mutex<JoinLimiter>,
+}
+
+#[derive(Default)]
+pub struct CollabState {
+    session: Mutex<Option<ActiveSession>>,
+}
+
+fn generate_code() -> AppResult<String> {
+    let mut bytes = [0u8; CODE_LENGTH];
+    getrandom::getrandom(&mut bytes).map_err(|e| AppError::internal(format!("Failed to generate a session code: {}", e)))?;
+    Ok(bytes.iter().map(|byte| CODE_ALPHABET[*byte as usize % CODE_ALPHABET.len()] as char).collect())
+}
+
+/// Failed joins by address. One that failed `MAX_FAILED_JOINS` times
+/// within `FAILED_JOIN_WINDOW` is turned away until the oldest expires.
+#[derive(Default)]
+struct JoinLimiter {
+    failures: HashMap<IpAddr, VecDeque<Instant>>,
+}
+
+impl JoinLimiter {
+    fn allows(&mut self, ip: IpAddr, now: Instant) -> bool {
+        let Some(failures) = self.failures.get_mut(&ip) else {
+            return true;
+        };
+        while failures.front().is_some_and(|failed| now.duration_since(*failed) >= FAILED_JOIN_WINDOW) {
+            failures.pop_front();
+        }
+        if failures.is_empty() {
+            self.failures.remove(&ip);
+            return true;
+        }
+        failures.len() < MAX_FAILED_JOINS
+    }
+
+    fn record_failure(&mut self, ip: IpAddr, now: Instant) {
+        self.failures.entry(ip).or_default().push_back(now);
+    }
+}
+
+/// Best guess at this machine's LAN address. Connecting a UDP socket sends
+/// nothing; it just makes the OS pick the outbound interface.
+fn lan_addresses() -> Vec<String> {
+    let mut addresses = vec!["127.0.0.1".to_string()];
+    if let Ok(socket) = std::net::UdpSocket::bind("0.0.0.0:0") {
+        if socket.connect("8.8.8.8:80").is_ok() {
+            if let Ok(addr) = socket.local_addr() {
+                if !addr.ip().is_loopback() && !addr.ip().is_unspecified() {
+                    addresses.insert(0, addr.ip().to_string());
+                }
+            }
+        }
+    }
+    addresses
+}
+
+fn to_ws_message(message: &CollabMessage) -> Option<Message> {
+    serde_json::to_string(message).ok().map(Message::Text)
+}
+
+fn parse_ws_message(message: &Message) -> Option<CollabMessage> {
+    match message {
+        Message::Text(text) => serde_json::from_str(text).ok(),
+        _ => None,
+    }
+}
+
+/// Starts hosting `document`. The slot is checked and filled under one
+/// lock, so two calls can't both start a server.
+pub fn start_session(app_handle: &AppHandle, state: &CollabState, document: String) -> AppResult<CollabSessionInfo> {
+    let mut session = state.session.lock()?;
+    if session.is_some() {
+        return Err(AppError::conflict("A collaboration session is already running"));
+    }
+
+    let listener = std::net::TcpListener::bind(("0.0.0.0", 0))
+        .and_then(|listener| {
+            listener.set_nonblocking(true)?;
+            TcpListener::from_std(listener)
+        })
+        .map_err(|e| AppError::network(format!("Failed to start collaboration server: {}", e)))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| AppError::network(format!("Failed to start collaboration server: {}", e)))?
+        .port();
+
+    let info = CollabSessionInfo {
+        role: "host".to_string(),
+        document,
+        code: generate_code()?,
+        port,
+        addresses: lan_addresses(),
+    };
+
+    let (tx, _) = broadcast::channel(256);
+    let (shutdown, shutdown_rx) = watch::channel(false);
+
+    tauri::async_runtime::spawn(accept_peers(
+        listener,
+        Arc::new(Host { info: info.clone(), limiter: Mutex::new(JoinLimiter::default()) }),
+        tx.clone(),
+        shutdown_rx,
+        app_handle.clone(),
+    ));
+
+    *session = Some(ActiveSession::Host {
+        info: info.clone(),
+        tx,
+        shutdown,
+    });
+
+    Ok(info)
+}
+
+async fn accept_peers(
+    listener: TcpListener,
+    host: Arc<Host>,
+    tx: broadcast::Sender<CollabMessage>,
+    mut shutdown: watch::Receiver<bool>,
+    app_handle: AppHandle,
+) {
+    let mut next_peer = 1u32;
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                if let Ok((stream, addr)) = accepted {
+                    // Dropping the stream closes it before any handshake
+                    let allowed = host.limiter.lock().is_ok_and(|mut limiter| limiter.allows(addr.ip(), Instant::now()));
+                    if !allowed {
+                        continue;
+                    }
+                    let peer_id = format!("peer-{}", next_peer);
+                    next_peer += 1;
+                    tauri::async_runtime::spawn(handle_peer(
+                        stream,
+                        addr,
+                        peer_id,
+                        host.clone(),
+                        tx.clone(),
+                        shutdown.clone(),
+                        app_handle.clone(),
+                    ));
+                }
+            }
+            _ = shutdown.changed() => break,
+        }
+    }
+}
+
+async fn handle_peer(
+    stream: TcpStream,
+    addr: SocketAddr,
+    peer_id: String,
+    host: Arc<Host>,
+    tx: broadcast::Sender<CollabMessage>,
+    mut shutdown: watch::Receiver<bool>,
+    app_handle: AppHandle,
+) {
+    let record_failure = || {
+        if let Ok(mut limiter) = host.limiter.lock() {
+            limiter.record_failure(addr.ip(), Instant::now());
+        }
+    };
+    let ws = match tokio::time::timeout(HANDSHAKE_TIMEOUT, tokio_tungstenite::accept_async(stream)).await {
+        Ok(Ok(ws)) => ws,
+        _ => {
+            record_failure();
+            return;
+        }
+    };
+    let (mut sink, mut source) = ws.split();
+
+    // The first message has to be a join carrying the session code
+    let first = tokio::time::timeout(HANDSHAKE_TIMEOUT, source.next()).await.ok().flatten().and_then(|m| m.ok());
+    let name = match first.as_ref().and_then(parse_ws_message) {
+        Some(CollabMessage::Join { code, name }) if code.eq_ignore_ascii_case(&host.info.code) => name,
+        _ => {
+            record_failure();
+            let rejected = CollabMessage::Rejected { reason: "Invalid session code".to_string() };
+            if let Some(message) = to_ws_message(&rejected) {
+                let _ = sink.send(message).await;
+            }
+            let _ = sink.close().await;
+            return;
+        }
+    };
+
+    let mut rx = tx.subscribe();
+    let welcome = CollabMessage::Welcome { peer_id: peer_id.clone(), document: host.info.document.clone() };
+    if let Some(message) = to_ws_message(&welcome) {
+        if sink.send(message).await.is_err() {
+            return;
+        }
+    }
+
+    let joined = CollabMessage::PeerJoined { peer_id: peer_id.clone(), name };
+    let _ = app_handle.emit("collab-peer-joined", &joined);
+    let _ = tx.send(joined);
+
+    loop {
+        tokio::select! {
+            incoming = source.next() => match incoming {
+                Some(Ok(message)) => {
+                    // Guests can only send edits; stamp them with the sender's id
+                    if let Some(CollabMessage::Edit { payload, .. }) = parse_ws_message(&message) {
+                        let edit = CollabMessage::Edit { peer_id: peer_id.clone(), payload };
+                        let _ = app_handle.emit("collab-edit", &edit);
+                        let _ = tx.send(edit);
+                    } else if let Message::Close(_) = message {
+                        break;
+                    }
+                }
+                _ => break,
+            },
+            outgoing = rx.recv() => match outgoing {
+                Ok(message) => {
+                    let from_self = matches!(
+                        &message,
+                        CollabMessage::Edit { peer_id: sender, .. }
+                            | CollabMessage::PeerJoined { peer_id: sender, .. }
+                            if *sender == peer_id
+                    );
+                    if from_self {
+                        continue;
+                    }
+                    if let Some(message) = to_ws_message(&message) {
+                        if sink.send(message).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = shutdown.changed() => break,
+        }
+    }
+
+    let _ = sink.close().await;
+    let left = CollabMessage::PeerLeft { peer_id };
+    let _ = app_handle.emit("collab-peer-left", &left);
+    let _ = tx.send(left);
+}
+
+pub async fn join_session(
+    app_handle: &AppHandle,
+    state: &CollabState,
+    host: String,
+    port: u16,
+    code: String,
+    name: String,
+) -> AppResult<CollabSessionInfo> {
+    {
+        let mut session = state.session.lock()?;
+        if session.is_some() {
+            return Err(AppError::conflict("A collaboration session is already running"));
+        }
+        *session = Some(ActiveSession::Joining);
+    }
+
+    let handshake = async {
+        let (ws, _) = tokio_tungstenite::connect_async(format!("ws://{}:{}", host, port))
+            .await
+            .map_err(|e| AppError::network(format!("Failed to connect to {}:{}: {}", host, port, e)))?;
+        let (mut sink, mut source) = ws.split();
+
+        let join = CollabMessage::Join { code: code.clone(), name };
+        sink.send(to_ws_message(&join).ok_or_else(|| AppError::internal("Failed to encode join request"))?)
+            .await
+            .map_err(|e| AppError::network(format!("Failed to join session: {}", e)))?;
+
+        match source.next().await.and_then(|m| m.ok()).as_ref().and_then(parse_ws_message) {
+            Some(CollabMessage::Welcome { peer_id, document }) => Ok((sink, source, peer_id, document)),
+            Some(CollabMessage::Rejected { reason }) => Err(AppError::validation(reason)),
+            _ => Err(AppError::network("The host did not respond to the join request")),
+        }
+    };
+    let result = tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake)
+        .await
+        .unwrap_or_else(|_| Err(AppError::network(format!("Timed out joining {}:{}", host, port))));
+
+    let mut session = state.session.lock()?;
+    // `stop_session` empties the slot when it's called while joining
+    if !matches!(*session, Some(ActiveSession::Joining)) {
+        return Err(AppError::cancelled("The collaboration session was stopped while joining"));
+    }
+    let (mut sink, mut source, peer_id, document) = match result {
+        Ok(joined) => joined,
+        Err(e) => {
+            *session = None;
+            return Err(e);
+        }
+    };
+
+    let info = CollabSessionInfo {
+        role: "guest".to_string(),
+        document,
+        code,
+        port,
+        addresses: vec![host],
+    };
+
+    let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<CollabMessage>();
+    let (shutdown, mut shutdown_rx) = watch::channel(false);
+    let app = app_handle.clone();
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::select! {
+                incoming = source.next() => match incoming {
+                    Some(Ok(message)) => match parse_ws_message(&message) {
+                        Some(edit @ CollabMessage::Edit { .. }) => { let _ = app.emit("collab-edit", &edit); }
+                        Some(joined @ CollabMessage::PeerJoined { .. }) => { let _ = app.emit("collab-peer-joined", &joined); }
+                        Some(left @ CollabMessage::PeerLeft { .. }) => { let _ = app.emit("collab-peer-left", &left); }
+                        _ => {}
+                    },
+                    _ => break,
+                },
+                Some(message) = outgoing_rx.recv() => {
+                    if let Some(message) = to_ws_message(&message) {
+                        if sink.send(message).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                _ = shutdown_rx.changed() => break,
+            }
+        }
+        let _ = sink.close().await;
+        let _ = app.emit("collab-session-ended", ());
+    });
+
+    *session = Some(ActiveSession::Guest {
+        info: info.clone(),
+        peer_id,
+        outgoing,
+        shutdown,
+    });
+
+    Ok(info)
+}
+
+/// Relays a local edit to every other participant.
+pub fn send_edit(state: &CollabState, payload: Value) -> AppResult<()> {
+    let session = state.session.lock()?;
+    match session.as_ref() {
+        Some(ActiveSession::Host { tx, .. }) => {
+            // No receivers just means nobody has joined yet
+            let _ = tx.send(CollabMessage::Edit { peer_id: HOST_PEER_ID.to_string(), payload });
+            Ok(())
+        }
+        Some(ActiveSession::Guest { peer_id, outgoing, .. }) => outgoing
+            .send(CollabMessage::Edit { peer_id: peer_id.clone(), payload })
+            .map_err(|_| AppError::not_found("The collaboration session has ended")),
+        Some(ActiveSession::Joining) | None => Err(AppError::not_found("No collaboration session is running")),
+    }
+}
+
+pub fn session_info(state: &CollabState) -> AppResult<Option<CollabSessionInfo>> {
+    let session = state.session.lock()?;
+    Ok(session.as_ref().and_then(|s| match s {
+        ActiveSession::Host { info, .. } | ActiveSession::Guest { info, .. } => Some(info.clone()),
+        ActiveSession::Joining => None,
+    }))
+}
+
+pub fn stop_session(state: &CollabState) -> AppResult<()> {
+    let session = state.session.lock()?.take();
+    match session {
+        Some(ActiveSession::Host { shutdown, .. }) | Some(ActiveSession::Guest { shutdown, .. }) => {
+            let _ = shutdown.send(true);
+            Ok(())
+        }
+        Some(ActiveSession::Joining) | None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_code() {
+        let code = generate_code().unwrap();
+        assert_eq!(code.len(), CODE_LENGTH);
+        assert!(code.bytes().all(|c| CODE_ALPHABET.contains(&c)));
+        assert_ne!(code, generate_code().unwrap());
+    }
+
+    #[test]
+    fn test_join_limiter() {
+        let mut limiter = JoinLimiter::default();
+        let (guesser, other): (IpAddr, IpAddr) = ("192.168.1.20".parse().unwrap(), "192.168.1.21".parse().unwrap());
+        let start = Instant::now();
+        for _ in 0..MAX_FAILED_JOINS {
+            assert!(limiter.allows(guesser, start));
+            limiter.record_failure(guesser, start);
+        }
+        assert!(!limiter.allows(guesser, start + Duration::from_secs(1)));
+        assert!(limiter.allows(other, start));
+        assert!(limiter.allows(guesser, start + FAILED_JOIN_WINDOW));
+        assert!(limiter.failures.is_empty());
+    }
+
+    #[test]
+    fn test_message_format() {
+        let join = CollabMessage::Join { code: "ABCD234567".to_string(), name: "Sam".to_string() };
+        let text = serde_json::to_string(&join).unwrap();
+        assert_eq!(text, r#"{"type":"join","code":"ABCD234567","name":"Sam"}"#);
+        assert!(matches!(parse_ws_message(&Message::Text(text)), Some(CollabMessage::Join { .. })));
+        assert!(parse_ws_message(&Message::Text("not json".to_string())).is_none());
+        assert!(parse_ws_message(&Message::Binary(vec![1, 2])).is_none());
+    }
+}
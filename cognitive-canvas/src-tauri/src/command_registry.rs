@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use crate::fuzzy::fuzzy_match;
+use crate::shortcuts_manager::{self, Shortcuts};
+use crate::error::{AppError, AppResult};
+
+/// How many results `list_commands` returns unless asked for more.
+pub const DEFAULT_LIMIT: usize = 50;
+
+/// Extra search terms for built-in commands, by action id.
+const BUILTIN_KEYWORDS: &[(&str, &[&str])] = &[
+    ("command_palette", &["commands", "actions"]),
+    ("new_document", &["create", "file", "note"]),
+    ("open", &["file", "browse"]),
+    ("save", &["write", "file"]),
+    ("close_document", &["tab", "file"]),
+    ("find", &["search", "text"]),
+    ("search", &["find", "grep", "files"]),
+    ("zoom_in", &["bigger", "larger", "scale"]),
+    ("zoom_out", &["smaller", "scale"]),
+    ("zoom_reset", &["actual size", "100%"]),
+    ("toggle_sidebar", &["panel", "files", "explorer"]),
+    ("toggle_fullscreen", &["window", "maximize"]),
+];
+
+/// A command the palette can run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandInfo {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// Filled in from the keymap for built-in commands
+    #[serde(default)]
+    pub shortcut: Option<String>,
+    #[serde(default, skip_deserializing)]
+    pub builtin: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandMatch {
+    #[serde(flatten)]
+    pub command: CommandInfo,
+    pub score: i64,
+    /// Character positions in `title` that matched the query, for highlighting
+    pub matched: Vec<usize>,
+}
+
+/// Commands registered at runtime, e.g. by plugins. Built-in commands come
+/// from the keymap's actions and aren't stored here.
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: RwLock<BTreeMap<String, CommandInfo>>,
+}
+
+impl CommandRegistry {
+    /// Adds a command, replacing an earlier one with the same id.
+    pub fn register(&self, mut command: CommandInfo) -> AppResult<()> {
+        command.id = command.id.trim().to_string();
+        if command.id.is_empty() || command.title.trim().is_empty() {
+            return Err(AppError::validation("A command needs an id and a title"));
+        }
+        if shortcuts_manager::actions().any(|(action, _)| action == command.id) {
+            return Err(AppError::conflict(format!("{} is a built-in command", command.id)));
+        }
+        command.builtin = false;
+
+        let mut commands = self.commands.write()?;
+        commands.insert(command.id.clone(), command);
+        Ok(())
+    }
+
+    pub fn unregister(&self, id: &str) -> bool {
+        self.commands.write().is_ok_and(|mut commands| commands.remove(id).is_some())
+    }
+
+    /// Every command, built-in ones first.
+    pub fn all(&self, shortcuts: &Shortcuts) -> Vec<CommandInfo> {
+        let mut commands: Vec<CommandInfo> = shortcuts_manager::actions()
+            .map(|(id, title)| CommandInfo {
+                id: id.to_string(),
+                title: title.to_string(),
+                keywords: BUILTIN_KEYWORDS
+                    .iter()
+                    .find(|(action, _)| *action == id)
+                    .map(|(_, keywords)| keywords.iter().map(|k| k.to_string()).collect())
+                    .unwrap_or_default(),
+                shortcut: shortcuts.get(id).map(|s| s.to_string()),
+                builtin: true,
+            })
+            .collect();
+        if let Ok(registered) = self.commands.read() {
+            commands.extend(registered.values().cloned());
+        }
+        commands
+    }
+
+    /// Commands matching `query`, best first. An empty query lists every
+    /// command by title.
+    pub fn search(&self, shortcuts: &Shortcuts, query: &str, limit: usize) -> Vec<CommandMatch> {
+        let query = query.trim();
+        let mut matches: Vec<CommandMatch> = self
+            .all(shortcuts)
+            .into_iter()
+            .filter_map(|command| {
+                if query.is_empty() {
+                    return Some(CommandMatch { command, score: 0, matched: Vec::new() });
+                }
+                let (score, matched) = match_command(query, &command)?;
+                Some(CommandMatch { command, score, matched })
+            })
+            .collect();
+
+        matches.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.command.title.cmp(&b.command.title)));
+        matches.truncate(limit);
+        matches
+    }
+}
+
+/// Scores `query` against a command's title, then its id and keywords at a
+/// discount. Only title matches are highlighted.
+fn match_command(query: &str, command: &CommandInfo) -> Option<(i64, Vec<usize>)> {
+    let title = fuzzy_match(query, &command.title);
+    let other = std::iter::once(command.id.replace('_', " "))
+        .chain(command.keywords.iter().cloned())
+        .filter_map(|text| fuzzy_match(query, &text))
+        .map(|(score, _)| score / 2)
+        .max();
+
+    match (title, other) {
+        (Some((score, _)), Some(other)) if other > score => Some((other, Vec::new())),
+        (Some(title), _) => Some(title),
+        (None, other) => other.map(|score| (score, Vec::new())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_commands() {
+        let registry = CommandRegistry::default();
+        registry
+            .register(CommandInfo {
+                id: "plugin.word_count".to_string(),
+                title: "Show word count".to_string(),
+                keywords: vec!["statistics".to_string()],
+                shortcut: None,
+                builtin: false,
+            })
+            .unwrap();
+        assert!(registry
+            .register(CommandInfo { id: "save".to_string(), title: "Mine".to_string(), keywords: Vec::new(), shortcut: None, builtin: false })
+            .is_err());
+
+        let shortcuts = Shortcuts::default();
+        let results = registry.search(&shortcuts, "save", 5);
+        assert_eq!(results[0].command.id, "save");
+        assert_eq!(results[0].command.shortcut.as_deref(), Some("CmdOrCtrl+S"));
+
+        let results = registry.search(&shortcuts, "stats", 5);
+        assert_eq!(results[0].command.id, "plugin.word_count");
+        assert!(results[0].matched.is_empty());
+
+        assert_eq!(registry.search(&shortcuts, "", 100).len(), shortcuts.bindings().count() + 1);
+        assert_eq!(registry.search(&shortcuts, "", 3).len(), 3);
+    }
+}
@@ -0,0 +1,205 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use std::path::{Path, PathBuf};
+use crate::sidecar;
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Comment {
+    pub id: String,
+    pub author: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommentThread {
+    pub id: String,
+    /// Key of the Lexical node the thread is attached to
+    pub node_id: String,
+    pub comments: Vec<Comment>,
+    pub resolved: bool,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CommentsFile {
+    threads: Vec<CommentThread>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewComment {
+    pub node_id: String,
+    pub author: String,
+    pub body: String,
+    /// Reply to an existing thread instead of starting a new one
+    pub thread_id: Option<String>,
+}
+
+fn get_index_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir.join("comment_threads.json"))
+}
+
+fn generate_id(prefix: &str) -> String {
+    format!("{}-{}", prefix, Utc::now().timestamp_nanos_opt().unwrap_or_default())
+}
+
+/// Keeps threads addressable by id after their document was renamed.
+pub fn rename_document(app_handle: &AppHandle, old_path: &Path, new_path: &Path) -> AppResult<()> {
+    sidecar::move_owners(&get_index_path(app_handle)?, old_path, new_path)
+}
+
+pub fn list_comments(document_path: &Path) -> AppResult<Vec<CommentThread>> {
+    let file: CommentsFile = sidecar::read_json(&sidecar::sidecar_path(document_path, "comments"))?;
+    Ok(file.threads)
+}
+
+pub fn add_comment(app_handle: &AppHandle, document_path: &Path, comment: NewComment) -> AppResult<CommentThread> {
+    add(&get_index_path(app_handle)?, document_path, comment)
+}
+
+pub fn resolve_thread(app_handle: &AppHandle, thread_id: &str, resolved: bool) -> AppResult<CommentThread> {
+    resolve(&get_index_path(app_handle)?, thread_id, resolved)
+}
+
+fn add(index_path: &Path, document_path: &Path, comment: NewComment) -> AppResult<CommentThread> {
+    let path = sidecar::sidecar_path(document_path, "comments");
+    let mut file: CommentsFile = sidecar::read_json(&path)?;
+    let now = Utc::now();
+
+    let entry = Comment {
+        id: generate_id("comment"),
+        author: comment.author,
+        body: comment.body,
+        created_at: now,
+    };
+
+    let thread = match comment.thread_id {
+        Some(thread_id) => {
+            let thread = file
+                .threads
+                .iter_mut()
+                .find(|t| t.id == thread_id)
+                .ok_or_else(|| AppError::not_found(format!("Comment thread not found: {}", thread_id)))?;
+            thread.comments.push(entry);
+            // Replying reopens a resolved discussion
+            thread.resolved = false;
+            thread.resolved_at = None;
+            thread.clone()
+        }
+        None => {
+            let thread = CommentThread {
+                id: generate_id("thread"),
+                node_id: comment.node_id,
+                comments: vec![entry],
+                resolved: false,
+                resolved_at: None,
+                created_at: now,
+            };
+            file.threads.push(thread.clone());
+
+            sidecar::register_owner(index_path, &thread.id, document_path)?;
+
+            thread
+        }
+    };
+
+    sidecar::write_json(&path, &file)?;
+    Ok(thread)
+}
+
+fn resolve(index_path: &Path, thread_id: &str, resolved: bool) -> AppResult<CommentThread> {
+    let document_path = sidecar::find_owner(index_path, thread_id)?;
+    let path = sidecar::sidecar_path(&document_path, "comments");
+    let mut file: CommentsFile = sidecar::read_json(&path)?;
+
+    let thread = file
+        .threads
+        .iter_mut()
+        .find(|t| t.id == thread_id)
+        .ok_or_else(|| AppError::not_found(format!("Comment thread not found: {}", thread_id)))?;
+    thread.resolved = resolved;
+    thread.resolved_at = if resolved { Some(Utc::now()) } else { None };
+    let thread = thread.clone();
+
+    sidecar::write_json(&path, &file)?;
+    Ok(thread)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn comment(thread_id: Option<&str>, body: &str) -> NewComment {
+        NewComment {
+            node_id: "p1".to_string(),
+            author: "Ana".to_string(),
+            body: body.to_string(),
+            thread_id: thread_id.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_add_and_reply() {
+        let dir = std::env::temp_dir().join("test_comments_add");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let (index, document) = (dir.join("comment_threads.json"), dir.join("notes.canvas"));
+
+        assert!(list_comments(&document).unwrap().is_empty());
+
+        let thread = add(&index, &document, comment(None, "First")).unwrap();
+        assert!(dir.join(".notes.canvas.comments.json").exists());
+        assert_eq!(list_comments(&document).unwrap().len(), 1);
+
+        let resolved = resolve(&index, &thread.id, true).unwrap();
+        assert!(resolved.resolved && resolved.resolved_at.is_some());
+
+        // Replying reopens the thread and keeps it in the same sidecar
+        let reply = add(&index, &document, comment(Some(&thread.id), "Second")).unwrap();
+        assert!(!reply.resolved);
+        let threads = list_comments(&document).unwrap();
+        assert_eq!(threads.len(), 1);
+        let bodies: Vec<&str> = threads[0].comments.iter().map(|c| c.body.as_str()).collect();
+        assert_eq!(bodies, vec!["First", "Second"]);
+        assert_eq!(threads[0].node_id, "p1");
+
+        assert!(matches!(
+            add(&index, &document, comment(Some("thread-missing"), "Lost")),
+            Err(AppError::NotFound(_))
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_after_rename() {
+        let dir = std::env::temp_dir().join("test_comments_rename");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let index = dir.join("comment_threads.json");
+        let (old, new) = (dir.join("draft.canvas"), dir.join("final.canvas"));
+
+        let thread = add(&index, &old, comment(None, "Fix intro")).unwrap();
+
+        fs::rename(sidecar::sidecar_path(&old, "comments"), sidecar::sidecar_path(&new, "comments")).unwrap();
+        sidecar::move_owners(&index, &old, &new).unwrap();
+
+        assert!(list_comments(&old).unwrap().is_empty());
+        assert!(resolve(&index, &thread.id, true).unwrap().resolved);
+        assert!(list_comments(&new).unwrap()[0].resolved);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,40 +1,141 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Display;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::error::{AppError, AppResult};
 
 #[derive(Debug, Clone)]
 pub struct ConfigParser {
     data: HashMap<String, String>,
     comments: HashMap<String, String>,
+    /// Values currently provided by @include'd files, which aren't written
+    /// back unless changed
+    included: HashMap<String, String>,
+    ranges: HashMap<String, (f64, f64)>,
+    validators: HashMap<String, Validator>,
+    /// The file as it was loaded, so saving keeps its layout
+    lines: Vec<Line>,
+    trailing_newline: bool,
     file_path: String,
 }
 
+/// Checks a raw value, returning a message describing what's wrong.
+pub type Validator = fn(&str) -> AppResult<()>;
+
+#[derive(Debug, Clone)]
+enum Line {
+    Blank,
+    Comment(String),
+    Section(String),
+    Include(String),
+    Entry {
+        key: String,
+        raw: String,
+        value: String,
+        comment: Option<String>,
+        is_list_item: bool,
+        /// Written as `key = [a, b]`
+        is_array: bool,
+    },
+}
+
 impl ConfigParser {
     pub fn new(file_path: &str) -> Self {
         Self {
             data: HashMap::new(),
             comments: HashMap::new(),
+            included: HashMap::new(),
+            ranges: HashMap::new(),
+            validators: HashMap::new(),
+            lines: Vec::new(),
+            trailing_newline: false,
             file_path: file_path.to_string(),
         }
     }
 
-    pub fn load(&mut self) -> Result<(), String> {
+    pub fn load(&mut self) -> AppResult<()> {
         if !Path::new(&self.file_path).exists() {
             // Create default config file if it doesn't exist
             self.create_default_config()?;
         }
 
         let content = fs::read_to_string(&self.file_path)
-            .map_err(|e| format!("Failed to read config file: {}", e))?;
+            .map_err(|e| AppError::io(format!("Failed to read config file: {}", e)))?;
 
         self.parse_content(&content)?;
         Ok(())
     }
 
-    pub fn save(&self) -> Result<(), String> {
+    pub fn save(&self) -> AppResult<()> {
         let content = self.generate_content();
         fs::write(&self.file_path, content)
-            .map_err(|e| format!("Failed to write config file: {}", e))?;
+            .map_err(|e| AppError::io(format!("Failed to write config file: {}", e)))?;
+        Ok(())
+    }
+
+    /// Applies `edit`, validates the result and saves it, or leaves both the
+    /// parser and the file as they were. The current file is copied to
+    /// `<file>.bak` first and put back if writing fails, and the new content
+    /// is written to a temporary file and renamed into place, so a crash
+    /// can't leave a half-written config behind.
+    pub fn transaction<F>(&mut self, edit: F) -> AppResult<()>
+    where
+        F: FnOnce(&mut ConfigParser) -> AppResult<()>,
+    {
+        let snapshot = self.clone();
+
+        let result = edit(self).and_then(|_| self.validate()).and_then(|_| self.save_atomic());
+        if result.is_err() {
+            *self = snapshot;
+        }
+        result
+    }
+
+    /// Checks every value against the registered ranges and validators.
+    pub fn validate(&self) -> AppResult<()> {
+        for (key, (min, max)) in &self.ranges {
+            if let Some(value) = self.data.get(key) {
+                let number: f64 = value
+                    .parse()
+                    .map_err(|_| AppError::validation(format!("{} must be a number, got {}", key, value)))?;
+                if !(number >= *min && number <= *max) {
+                    return Err(AppError::validation(format!("{} must be between {} and {}, got {}", key, min, max, value)));
+                }
+            }
+        }
+        for (key, validator) in &self.validators {
+            if let Some(value) = self.data.get(key) {
+                validator(value).map_err(|e| AppError::validation(format!("Invalid value for {}: {}", key, e)))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_validator(&mut self, key: &str, validator: Validator) {
+        self.validators.insert(key.to_string(), validator);
+    }
+
+    fn save_atomic(&self) -> AppResult<()> {
+        let path = Path::new(&self.file_path);
+        let backup_path = PathBuf::from(format!("{}.bak", self.file_path));
+        let temp_path = PathBuf::from(format!("{}.tmp", self.file_path));
+
+        if path.exists() {
+            fs::copy(path, &backup_path)
+                .map_err(|e| AppError::io(format!("Failed to back up config file: {}", e)))?;
+        }
+
+        let written = fs::write(&temp_path, self.generate_content())
+            .and_then(|_| fs::rename(&temp_path, path));
+
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp_path);
+            if backup_path.exists() {
+                let _ = fs::copy(&backup_path, path);
+            }
+            return Err(AppError::io(format!("Failed to write config file: {}", e)));
+        }
         Ok(())
     }
 
@@ -50,6 +151,12 @@ impl ConfigParser {
         self.data.get(key)
     }
 
+    /// Like `get`, but with `~` and `${VAR}` expanded. The stored value is
+    /// left untouched, so saving writes back what the user typed.
+    pub fn get_expanded(&self, key: &str) -> Option<String> {
+        self.data.get(key).map(|value| expand_value(value))
+    }
+
     pub fn get_bool(&self, key: &str) -> Option<bool> {
         self.data.get(key).and_then(|v| match v.to_lowercase().as_str() {
             "true" | "1" | "yes" | "on" => Some(true),
@@ -66,84 +173,453 @@ impl ConfigParser {
         self.data.insert(key.to_string(), value.to_string());
     }
 
+    /// Reads a list written either as `key=a, b, c` or as repeated
+    /// `key[]=a` lines.
+    pub fn get_list(&self, key: &str) -> Option<Vec<String>> {
+        let value = self.data.get(key)?;
+        let separator = if value.contains(LIST_SEPARATOR) { LIST_SEPARATOR } else { ',' };
+        Some(
+            value
+                .split(separator)
+                .map(|item| item.trim())
+                .filter(|item| !item.is_empty())
+                .map(|item| item.to_string())
+                .collect(),
+        )
+    }
+
+    /// Stores a list. It's saved comma-separated unless an item contains a
+    /// comma or quote, in which case each item gets its own `key[]=` line.
+    pub fn set_list<S: AsRef<str>>(&mut self, key: &str, items: &[S]) {
+        let items: Vec<&str> = items.iter().map(|item| item.as_ref()).collect();
+        let value = if items.iter().any(|item| item.contains([',', '"', '\''])) {
+            // Keep a trailing separator so a one-item list stays a list
+            items.iter().map(|item| format!("{}{}", item, LIST_SEPARATOR)).collect()
+        } else {
+            items.join(", ")
+        };
+        self.data.insert(key.to_string(), value);
+    }
+
+    /// Restricts a numeric key to `min..=max`. Out-of-range values read back
+    /// as `None` and are refused by the setters.
+    pub fn set_range(&mut self, key: &str, min: f64, max: f64) {
+        self.ranges.insert(key.to_string(), (min, max));
+    }
+
+    pub fn get_int(&self, key: &str) -> Option<i64> {
+        let value = self.data.get(key)?.parse::<i64>().ok()?;
+        self.in_range(key, value as f64).then_some(value)
+    }
+
+    pub fn get_float(&self, key: &str) -> Option<f64> {
+        let value = self.data.get(key)?.parse::<f64>().ok()?;
+        (value.is_finite() && self.in_range(key, value)).then_some(value)
+    }
+
+    pub fn get_enum<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.data.get(key)?.parse::<T>().ok()
+    }
+
+    pub fn set_int(&mut self, key: &str, value: i64) -> AppResult<()> {
+        self.check_range(key, value as f64)?;
+        self.data.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    pub fn set_float(&mut self, key: &str, value: f64) -> AppResult<()> {
+        if !value.is_finite() {
+            return Err(AppError::validation(format!("Invalid value for {}: {}", key, value)));
+        }
+        self.check_range(key, value)?;
+        self.data.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    pub fn set_enum<T: Display>(&mut self, key: &str, value: T) {
+        self.data.insert(key.to_string(), value.to_string());
+    }
+
+    fn in_range(&self, key: &str, value: f64) -> bool {
+        self.ranges
+            .get(key)
+            .is_none_or(|(min, max)| value >= *min && value <= *max)
+    }
+
+    fn check_range(&self, key: &str, value: f64) -> AppResult<()> {
+        match self.ranges.get(key) {
+            Some((min, max)) if !self.in_range(key, value) => {
+                Err(AppError::validation(format!("{} must be between {} and {}, got {}", key, min, max, value)))
+            }
+            _ => Ok(()),
+        }
+    }
+
     pub fn set_comment(&mut self, key: &str, comment: &str) {
         self.comments.insert(key.to_string(), comment.to_string());
     }
 
-    fn parse_content(&mut self, content: &str) -> Result<(), String> {
+    /// Returns the keys under `section` without the section prefix.
+    pub fn section_keys(&self, section: &str) -> Vec<String> {
+        let prefix = format!("{}.", section);
+        let mut keys: Vec<String> = self
+            .data
+            .keys()
+            .filter_map(|key| key.strip_prefix(&prefix))
+            .filter(|key| !key.contains('.'))
+            .map(|key| key.to_string())
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.comments.remove(key);
+        self.data.remove(key)
+    }
+
+    fn parse_content(&mut self, content: &str) -> AppResult<()> {
         self.data.clear();
         self.comments.clear();
+        self.included.clear();
+        self.lines.clear();
+        self.trailing_newline = false;
 
-        for line in content.lines() {
+        let path = Path::new(&self.file_path).to_path_buf();
+        let mut chain = vec![path.clone()];
+        self.parse_file(content, &path, &mut chain)?;
+
+        self.trailing_newline = content.ends_with('\n');
+
+        Ok(())
+    }
+
+    /// Parses one file. Only the top-level file (the first in `chain`) is
+    /// recorded in the line model; included files just contribute values.
+    fn parse_file(&mut self, content: &str, path: &Path, chain: &mut Vec<PathBuf>) -> AppResult<()> {
+        let is_root = chain.len() == 1;
+
+        // Keys below a [section] header are stored as "section.key"
+        let mut section = String::new();
+
+        let lines: Vec<&str> = content.lines().collect();
+        let mut index = 0;
+
+        while index < lines.len() {
+            let line = lines[index];
+            index += 1;
             let trimmed = line.trim();
             
-            // Skip empty lines and comment-only lines
-            if trimmed.is_empty() || trimmed.starts_with('#') {
+            // Blank lines and comment-only lines are kept so saving
+            // reproduces them
+            if trimmed.is_empty() {
+                if is_root {
+                    self.lines.push(Line::Blank);
+                }
+                continue;
+            }
+            if trimmed.starts_with('#') {
+                if is_root {
+                    self.lines.push(Line::Comment(line.to_string()));
+                }
+                continue;
+            }
+
+            // Values from an included file override what came before the
+            // @include line and are overridden by what comes after it
+            if let Some(target) = trimmed.strip_prefix("@include") {
+                let target = target.split('#').next().unwrap_or("").trim().trim_matches('"');
+                self.include(path, target, chain)?;
+                if is_root {
+                    self.lines.push(Line::Include(line.to_string()));
+                }
+                continue;
+            }
+
+            if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                section = trimmed[1..trimmed.len() - 1].trim().to_string();
+                if is_root {
+                    self.lines.push(Line::Section(section.clone()));
+                }
                 continue;
             }
 
             // Handle key=value with optional inline comment
             if let Some(equals_pos) = trimmed.find('=') {
-                let key = trimmed[..equals_pos].trim().to_string();
-                let rest = &trimmed[equals_pos + 1..];
-                
-                // Split value and comment
-                let (value, comment) = if let Some(hash_pos) = rest.find('#') {
-                    let value = rest[..hash_pos].trim();
-                    let comment = rest[hash_pos + 1..].trim();
-                    (value, Some(comment))
+                let key = trimmed[..equals_pos].trim();
+                let (key, is_list_item) = match key.strip_suffix("[]") {
+                    Some(key) => (key.trim_end(), true),
+                    None => (key, false),
+                };
+                let key = if section.is_empty() {
+                    key.to_string()
                 } else {
-                    (rest.trim(), None)
+                    format!("{}.{}", section, key)
                 };
+                let mut rest = trimmed[equals_pos + 1..].to_string();
+
+                // Triple-quoted strings, arrays and backslash continuations
+                // can span several lines; they're kept together as one entry
+                let extra = continuation_lines(&rest, &lines[index..]);
+                let raw = lines[index - 1..index + extra].join("\n");
+                for next in &lines[index..index + extra] {
+                    rest.push('\n');
+                    rest.push_str(next);
+                }
+                index += extra;
 
-                // Remove quotes if present
-                let value = if (value.starts_with('"') && value.ends_with('"')) ||
-                             (value.starts_with('\'') && value.ends_with('\'')) {
-                    &value[1..value.len()-1]
+                let (value, comment, is_array) = parse_multiline_value(&rest);
+                let value = value.as_str();
+
+                if is_list_item {
+                    let items = self.data.entry(key.clone()).or_default();
+                    items.push_str(value);
+                    items.push(LIST_SEPARATOR);
                 } else {
-                    value
-                };
+                    self.data.insert(key.clone(), value.to_string());
+                }
 
-                self.data.insert(key.clone(), value.to_string());
+                if !is_root {
+                    self.included.insert(key.clone(), self.data[&key].clone());
+                    continue;
+                }
+                self.included.remove(&key);
                 
                 if let Some(comment_text) = comment {
-                    self.comments.insert(key, comment_text.to_string());
+                    self.comments.insert(key.clone(), comment_text.to_string());
                 }
+
+                self.lines.push(Line::Entry {
+                    key,
+                    raw,
+                    value: value.to_string(),
+                    comment: comment.map(|c| c.to_string()),
+                    is_list_item,
+                    is_array,
+                });
             }
         }
 
         Ok(())
     }
 
+    /// Merges `target`, resolved relative to the including file. A missing
+    /// file is skipped so optional per-machine overrides can be listed
+    /// unconditionally; include cycles are an error.
+    fn include(&mut self, from: &Path, target: &str, chain: &mut Vec<PathBuf>) -> AppResult<()> {
+        let target = PathBuf::from(expand_value(target));
+        let path = match from.parent() {
+            Some(dir) if target.is_relative() => dir.join(target),
+            _ => target,
+        };
+
+        if chain.iter().any(|p| p == &path) {
+            return Err(AppError::config(format!("Config include cycle: {} is already being read", path.display())));
+        }
+        if chain.len() > MAX_INCLUDE_DEPTH {
+            return Err(AppError::config(format!("Config includes nested too deeply at {}", path.display())));
+        }
+        if !path.exists() {
+            return Ok(());
+        }
+
+        let content = fs::read_to_string(&path)
+            .map_err(|e| AppError::io(format!("Failed to read included config {}: {}", path.display(), e)))?;
+
+        chain.push(path.clone());
+        let result = self.parse_file(&content, &path, chain);
+        chain.pop();
+        result
+    }
+
+    /// Writes the file back in its original layout: untouched entries keep
+    /// their exact text, changed ones are rewritten in place, removed ones
+    /// are dropped and new ones go after the last entry of their section.
     fn generate_content(&self) -> String {
         let mut lines = Vec::new();
         
-        // Add header comment
-        lines.push("# Cognitive Canvas Configuration".to_string());
-        lines.push("# This file stores user preferences in a simple key=value format".to_string());
-        lines.push("# Lines starting with # are comments and will be ignored".to_string());
-        lines.push("".to_string());
-
-        // Sort keys for consistent output
-        let mut keys: Vec<_> = self.data.keys().collect();
-        keys.sort();
+        if self.lines.is_empty() {
+            // Add header comment
+            lines.push("# Cognitive Canvas Configuration".to_string());
+            lines.push("# This file stores user preferences in a simple key=value format".to_string());
+            lines.push("# Lines starting with # are comments and will be ignored".to_string());
+            lines.push("".to_string());
+        }
 
-        for key in keys {
-            if let Some(value) = self.data.get(key) {
-                let comment = self.comments.get(key);
-                
-                if let Some(comment_text) = comment {
-                    lines.push(format!("{}={} # {}", key, value, comment_text));
-                } else {
-                    lines.push(format!("{}={}", key, value));
+        // Keys that have no line yet, grouped by section
+        let known: HashSet<&str> = self
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                Line::Entry { key, .. } => Some(key.as_str()),
+                _ => None,
+            })
+            .collect();
+        let mut pending: BTreeMap<&str, Vec<&String>> = BTreeMap::new();
+        // Changed values that an include currently provides have to be
+        // written after the include to take effect
+        let mut overrides: BTreeMap<&str, Vec<&String>> = BTreeMap::new();
+        for (key, value) in &self.data {
+            match self.included.get(key) {
+                Some(included) if included == value => {}
+                Some(_) if !split_section(key).0.is_empty() => {
+                    overrides.entry(split_section(key).0).or_default().push(key)
                 }
+                _ if known.contains(key.as_str()) && !self.included.contains_key(key) => {}
+                _ => pending.entry(split_section(key).0).or_default().push(key),
             }
         }
+        for keys in pending.values_mut().chain(overrides.values_mut()) {
+            keys.sort();
+        }
+
+        let mut last_entries: HashMap<&str, usize> = HashMap::new();
+        for (index, line) in self.lines.iter().enumerate() {
+            if let Line::Entry { key, is_list_item: false, .. } = line {
+                last_entries.insert(key, index);
+            }
+        }
+
+        let mut written: HashSet<&str> = HashSet::new();
+        let mut current_section = "";
+        let mut insert_at = lines.len();
+        let mut seen_entry = false;
+
+        for (index, line) in self.lines.iter().enumerate() {
+            match line {
+                Line::Blank => lines.push("".to_string()),
+                Line::Comment(text) => {
+                    lines.push(text.clone());
+                    // New top-level keys go below the file's leading comments
+                    if !seen_entry && current_section.is_empty() {
+                        insert_at = lines.len();
+                    }
+                }
+                Line::Include(raw) => {
+                    lines.push(raw.clone());
+                    if current_section.is_empty() {
+                        insert_at = lines.len();
+                    }
+                }
+                Line::Section(section) => {
+                    self.insert_pending(&mut lines, insert_at, pending.remove(current_section));
+                    lines.push(format!("[{}]", section));
+                    current_section = section;
+                    insert_at = lines.len();
+                }
+                Line::Entry { key, raw, value, comment, is_list_item, is_array } => {
+                    let current = match self.data.get(key) {
+                        Some(current) => current,
+                        None => continue,
+                    };
+                    seen_entry = true;
+
+                    if *is_list_item {
+                        // All items are written together at the first one
+                        if written.insert(key) {
+                            lines.extend(self.format_entry(key));
+                            insert_at = lines.len();
+                        }
+                        continue;
+                    }
+
+                    // Earlier lines for a repeated key are shadowed and kept
+                    // as written, as is a line an include overrides
+                    let effective = last_entries.get(key.as_str()) == Some(&index);
+                    let unchanged = current == value && self.comments.get(key) == comment.as_ref();
+                    if !effective || self.included.contains_key(key) || unchanged {
+                        lines.push(raw.clone());
+                    } else if *is_array {
+                        lines.push(self.format_array(key));
+                    } else {
+                        lines.extend(self.format_entry(key));
+                    }
+                    insert_at = lines.len();
+                }
+            }
+        }
+        self.insert_pending(&mut lines, insert_at, pending.remove(current_section));
+
+        // Sections that don't exist in the file yet go at the end; top-level
+        // keys always have a place above, so "" never reaches here
+        for (section, keys) in pending.into_iter().chain(overrides) {
+            lines.push("".to_string());
+            lines.push(format!("[{}]", section));
+            for key in keys {
+                lines.extend(self.format_entry(key));
+            }
+        }
+
+        if self.trailing_newline {
+            lines.push("".to_string());
+        }
 
         lines.join("\n")
     }
 
-    fn create_default_config(&mut self) -> Result<(), String> {
+    fn insert_pending(&self, lines: &mut Vec<String>, at: usize, keys: Option<Vec<&String>>) {
+        let new_lines: Vec<String> = keys
+            .into_iter()
+            .flatten()
+            .flat_map(|key| self.format_entry(key))
+            .collect();
+        lines.splice(at..at, new_lines);
+    }
+
+    fn format_entry(&self, key: &str) -> Vec<String> {
+        let (_, name) = split_section(key);
+        let value = match self.data.get(key) {
+            Some(value) => value,
+            None => return Vec::new(),
+        };
+        let comment = self.comments.get(key);
+
+        if value.contains(LIST_SEPARATOR) {
+            return value
+                .split_terminator(LIST_SEPARATOR)
+                .enumerate()
+                .map(|(i, item)| match comment {
+                    Some(comment_text) if i == 0 => format!("{}[]={} # {}", name, quote_value(item), comment_text),
+                    _ => format!("{}[]={}", name, quote_value(item)),
+                })
+                .collect();
+        }
+
+        // Multi-line text reads best as a block
+        let value = if value.contains('\n') && !value.contains(TRIPLE_QUOTE) {
+            format!("{}\n{}\n{}", TRIPLE_QUOTE, value, TRIPLE_QUOTE)
+        } else {
+            quote_value(value)
+        };
+
+        match comment {
+            Some(comment_text) => vec![format!("{}={} # {}", name, value, comment_text)],
+            None => vec![format!("{}={}", name, value)],
+        }
+    }
+
+    fn format_array(&self, key: &str) -> String {
+        let (_, name) = split_section(key);
+        let items: Vec<String> = self
+            .get_list(key)
+            .unwrap_or_default()
+            .iter()
+            .map(|item| {
+                if item.contains([',', '[', ']']) {
+                    quote(item)
+                } else {
+                    quote_value(item)
+                }
+            })
+            .collect();
+
+        match self.comments.get(key) {
+            Some(comment_text) => format!("{}=[{}] # {}", name, items.join(", "), comment_text),
+            None => format!("{}=[{}]", name, items.join(", ")),
+        }
+    }
+
+    fn create_default_config(&mut self) -> AppResult<()> {
         // Set default values with comments
         self.set_bool("window_decorations", true);
         self.set_comment("window_decorations", "Show native window title bar and decorations");
@@ -154,14 +630,288 @@ impl ConfigParser {
         self.set_bool("window_fullscreen", false);
         self.set_comment("window_fullscreen", "Start window in fullscreen mode (overrides maximized)");
 
-        self.set("command_palette", "Cmd+P");
-        self.set_comment("command_palette", "Open the command palette");
-
         self.save()?;
         Ok(())
     }
 }
 
+const MAX_INCLUDE_DEPTH: usize = 8;
+
+/// Joins the items of a list written as repeated `key[]=` lines. The ASCII
+/// unit separator doesn't occur in hand-written config values.
+const LIST_SEPARATOR: char = '\u{1f}';
+
+/// Splits the text after `=` into the value and an optional inline comment.
+///
+/// Values in double quotes may contain `#`, `=`, surrounding spaces and the
+/// escapes `\"`, `\\`, `\n`, `\r` and `\t`. Single-quoted values are taken
+/// literally. In unquoted values `#` only starts a comment at the beginning
+/// or after whitespace, so `Ctrl+#` needs no quotes.
+fn parse_value(rest: &str) -> (String, Option<&str>) {
+    let rest = rest.trim();
+    let mut chars = rest.char_indices();
+
+    let quote = match rest.chars().next() {
+        Some(quote @ ('"' | '\'')) => {
+            chars.next();
+            quote
+        }
+        _ => {
+            let hash = rest
+                .char_indices()
+                .find(|&(i, c)| c == '#' && (i == 0 || rest[..i].ends_with(char::is_whitespace)))
+                .map(|(i, _)| i);
+            return match hash {
+                Some(i) => (rest[..i].trim().to_string(), Some(rest[i + 1..].trim())),
+                None => (rest.to_string(), None),
+            };
+        }
+    };
+
+    let mut value = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == quote => {
+                let after = rest[i + 1..].trim_start();
+                let comment = after.strip_prefix('#').map(|comment| comment.trim());
+                return (value, comment);
+            }
+            '\\' if quote == '"' => match chars.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, 'r')) => value.push('\r'),
+                Some((_, 't')) => value.push('\t'),
+                Some((_, other)) => value.push(other),
+                None => value.push('\\'),
+            },
+            c => value.push(c),
+        }
+    }
+
+    // No closing quote: keep the text as written
+    (rest.to_string(), None)
+}
+
+const TRIPLE_QUOTE: &str = "\"\"\"";
+
+/// How many of the `following` lines belong to a value starting with
+/// `rest`:
+///
+/// - `"""` runs up to the next `"""`
+/// - `[` runs up to the matching `]`
+/// - a trailing `\` continues onto the next line if that line is indented
+///
+/// An unterminated `"""` or `[` is read as a plain single-line value.
+fn continuation_lines(rest: &str, following: &[&str]) -> usize {
+    let start = rest.trim();
+
+    if let Some(body) = start.strip_prefix(TRIPLE_QUOTE) {
+        if body.contains(TRIPLE_QUOTE) {
+            return 0;
+        }
+        return following
+            .iter()
+            .position(|line| line.contains(TRIPLE_QUOTE))
+            .map_or(0, |i| i + 1);
+    }
+
+    if let Some(inner) = start.strip_prefix('[') {
+        if find_outside_quotes(inner, &[']']).is_some() {
+            return 0;
+        }
+        return following
+            .iter()
+            .position(|line| find_outside_quotes(line, &[']']).is_some())
+            .map_or(0, |i| i + 1);
+    }
+
+    let mut count = 0;
+    let mut current = start;
+    while current.ends_with('\\') && count < following.len() && following[count].starts_with(char::is_whitespace) {
+        current = following[count].trim();
+        count += 1;
+    }
+    count
+}
+
+/// Parses a possibly multi-line value into its text, inline comment and
+/// whether it was written as an array. Array items are joined the way
+/// repeated `key[]=` lines are, so `get_list` reads both.
+fn parse_multiline_value(rest: &str) -> (String, Option<&str>, bool) {
+    let start = rest.trim_start();
+
+    if let Some(body) = start.strip_prefix(TRIPLE_QUOTE) {
+        if let Some(end) = body.find(TRIPLE_QUOTE) {
+            let mut text = &body[..end];
+            // Delimiters on their own lines aren't part of the value
+            text = text.strip_prefix("\r\n").or_else(|| text.strip_prefix('\n')).unwrap_or(text);
+            if let Some(last_break) = text.rfind('\n') {
+                if text[last_break + 1..].trim().is_empty() {
+                    text = text[..last_break].strip_suffix('\r').unwrap_or(&text[..last_break]);
+                }
+            }
+            let comment = body[end + 3..].trim_start().strip_prefix('#').map(|c| c.trim());
+            return (text.to_string(), comment, false);
+        }
+    }
+
+    if let Some(inner) = start.strip_prefix('[') {
+        // Only a bracket at the end (or before a comment) makes an array,
+        // so a value like "[Draft] Notes" stays text
+        let close = find_outside_quotes(inner, &[']']).filter(|&close| {
+            let after = inner[close + 1..].trim_start();
+            after.is_empty() || after.starts_with('#')
+        });
+        if let Some(close) = close {
+            let mut items = String::new();
+            for item in split_outside_quotes(&inner[..close], &[',', '\n']) {
+                let (item, _) = parse_value(item);
+                if !item.is_empty() {
+                    items.push_str(&item);
+                    items.push(LIST_SEPARATOR);
+                }
+            }
+            let comment = inner[close + 1..].trim_start().strip_prefix('#').map(|c| c.trim());
+            return (items, comment, true);
+        }
+    }
+
+    if rest.contains('\n') {
+        let lines: Vec<&str> = rest.lines().collect();
+        let last = lines.len() - 1;
+        let joined: Vec<&str> = lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let line = line.trim();
+                if i < last { line.strip_suffix('\\').unwrap_or(line).trim_end() } else { line }
+            })
+            .collect();
+        let (value, _) = parse_value(&joined.join(" "));
+        // The comment can only be on the last line
+        let (_, comment) = parse_value(lines[last]);
+        return (value, comment, false);
+    }
+
+    let (value, comment) = parse_value(rest);
+    (value, comment, false)
+}
+
+/// Finds the first of `targets` that isn't inside a quoted string.
+fn find_outside_quotes(text: &str, targets: &[char]) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && q == '"' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if targets.contains(&c) => return Some(i),
+            None => {}
+        }
+    }
+    None
+}
+
+fn split_outside_quotes<'a>(text: &'a str, separators: &[char]) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some(pos) = find_outside_quotes(rest, separators) {
+        parts.push(&rest[..pos]);
+        rest = &rest[pos + 1..];
+    }
+    parts.push(rest);
+    parts
+}
+
+/// Quotes a value when it couldn't be read back as written otherwise.
+fn quote_value(value: &str) -> String {
+    let needs_quotes = value != value.trim()
+        || value.starts_with(['"', '\'', '['])
+        || value.ends_with('\\')
+        || value.contains(['#', '=', '\n', '\r', '\t']);
+    if needs_quotes {
+        quote(value)
+    } else {
+        value.to_string()
+    }
+}
+
+fn quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Expands a leading `~` to the home directory and `$VAR` / `${VAR}` to
+/// environment variables; `$$` is a literal `$`. Unknown variables are left
+/// as written so a typo shows up in the resulting path.
+pub fn expand_value(value: &str) -> String {
+    let home = || std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE")).ok();
+
+    let mut out = String::new();
+    let mut rest = value;
+    if rest == "~" || rest.starts_with("~/") || rest.starts_with("~\\") {
+        if let Some(home) = home() {
+            out.push_str(&home);
+            rest = &rest[1..];
+        }
+    }
+
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+
+        if let Some(after) = after.strip_prefix('$') {
+            out.push('$');
+            rest = after;
+            continue;
+        }
+
+        let (name, consumed) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            }
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], end)
+        };
+
+        match std::env::var(name) {
+            Ok(expanded) if !name.is_empty() => out.push_str(&expanded),
+            _ => out.push_str(&rest[pos..pos + 1 + consumed]),
+        }
+        rest = &after[consumed..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Splits "window.decorations" into ("window", "decorations"). Keys without
+/// a dot live at the top of the file, outside any section.
+fn split_section(key: &str) -> (&str, &str) {
+    key.rsplit_once('.').unwrap_or(("", key))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -198,4 +948,325 @@ mod tests {
         // Clean up
         let _ = fs::remove_file(&temp_file);
     }
+
+    #[test]
+    fn test_sections() {
+        let temp_file = env::temp_dir().join("test_config_sections.conf");
+        let temp_path = temp_file.to_str().unwrap();
+        let _ = fs::remove_file(&temp_file);
+
+        fs::write(&temp_file, "top=1\n\n[window]\ndecorations=false # Title bar\n\n[shortcuts]\ncommand_palette=Ctrl+P\n").unwrap();
+
+        let mut parser = ConfigParser::new(temp_path);
+        assert!(parser.load().is_ok());
+        assert_eq!(parser.get("top"), Some(&"1".to_string()));
+        assert_eq!(parser.get_bool("window.decorations"), Some(false));
+        assert_eq!(parser.get_str("shortcuts.command_palette"), Some(&"Ctrl+P".to_string()));
+        assert_eq!(parser.section_keys("window"), vec!["decorations".to_string()]);
+
+        parser.set_bool("window.maximized", true);
+        assert!(parser.save().is_ok());
+
+        let content = fs::read_to_string(&temp_file).unwrap();
+        assert!(content.contains("[window]\ndecorations=false # Title bar\nmaximized=true"));
+
+        let mut parser2 = ConfigParser::new(temp_path);
+        assert!(parser2.load().is_ok());
+        assert_eq!(parser2.get_bool("window.maximized"), Some(true));
+        assert_eq!(parser2.get("top"), Some(&"1".to_string()));
+
+        let _ = fs::remove_file(&temp_file);
+    }
+
+    #[test]
+    fn test_lists() {
+        let temp_file = env::temp_dir().join("test_config_lists.conf");
+        let temp_path = temp_file.to_str().unwrap();
+        let _ = fs::remove_file(&temp_file);
+
+        fs::write(
+            &temp_file,
+            "plugins=markdown, mermaid ,\nignore[]=*.tmp # Skipped by sync\nignore[]=node_modules\nrecent_files[]=/notes/a, b.canvas\n",
+        )
+        .unwrap();
+
+        let mut parser = ConfigParser::new(temp_path);
+        assert!(parser.load().is_ok());
+        assert_eq!(parser.get_list("plugins"), Some(vec!["markdown".to_string(), "mermaid".to_string()]));
+        assert_eq!(parser.get_list("ignore"), Some(vec!["*.tmp".to_string(), "node_modules".to_string()]));
+        assert_eq!(parser.get_list("recent_files"), Some(vec!["/notes/a, b.canvas".to_string()]));
+        assert_eq!(parser.get_list("missing"), None);
+
+        parser.set_list("recent_files", &["/notes/a, b.canvas", "/notes/c.canvas"]);
+        parser.set_list("plugins", &["mermaid", "katex"]);
+        parser.set_list("titles", &["say \"hi\"", "plain"]);
+        assert!(parser.save().is_ok());
+
+        let content = fs::read_to_string(&temp_file).unwrap();
+        assert!(content.contains("ignore[]=*.tmp # Skipped by sync\nignore[]=node_modules\n"));
+        assert!(content.contains("plugins=mermaid, katex\n"));
+        assert!(content.contains("recent_files[]=/notes/a, b.canvas\nrecent_files[]=/notes/c.canvas\n"));
+        assert!(content.contains("titles[]=say \"hi\"\ntitles[]=plain\n"));
+
+        let mut parser2 = ConfigParser::new(temp_path);
+        assert!(parser2.load().is_ok());
+        assert_eq!(
+            parser2.get_list("recent_files"),
+            Some(vec!["/notes/a, b.canvas".to_string(), "/notes/c.canvas".to_string()])
+        );
+        assert_eq!(parser2.get_list("ignore").map(|items| items.len()), Some(2));
+        assert_eq!(parser2.get_list("plugins"), Some(vec!["mermaid".to_string(), "katex".to_string()]));
+        assert_eq!(parser2.get_list("titles"), Some(vec!["say \"hi\"".to_string(), "plain".to_string()]));
+
+        let _ = fs::remove_file(&temp_file);
+    }
+
+    #[test]
+    fn test_preserves_layout() {
+        let temp_file = env::temp_dir().join("test_config_layout.conf");
+        let temp_path = temp_file.to_str().unwrap();
+        let _ = fs::remove_file(&temp_file);
+
+        let original = "# My settings\n\nzoom = 1.5\nwindow_maximized=true\n\n# Editor\n[editor]\n# Keep this\nfont = \"Fira Code\" # Mono\nobsolete=1\n\n[alpha]\nb=2\n";
+        fs::write(&temp_file, original).unwrap();
+
+        let mut parser = ConfigParser::new(temp_path);
+        assert!(parser.load().is_ok());
+        assert!(parser.save().is_ok());
+        assert_eq!(fs::read_to_string(&temp_file).unwrap(), original);
+
+        parser.set_bool("window_maximized", false);
+        parser.remove("editor.obsolete");
+        parser.set("editor.tab_size", "4");
+        parser.set("top", "yes");
+        parser.set("zeta.a", "1");
+        assert!(parser.save().is_ok());
+
+        assert_eq!(
+            fs::read_to_string(&temp_file).unwrap(),
+            "# My settings\n\nzoom = 1.5\nwindow_maximized=false\ntop=yes\n\n# Editor\n[editor]\n# Keep this\nfont = \"Fira Code\" # Mono\ntab_size=4\n\n[alpha]\nb=2\n\n[zeta]\na=1\n"
+        );
+
+        let _ = fs::remove_file(&temp_file);
+    }
+
+    #[test]
+    fn test_includes() {
+        let dir = env::temp_dir().join("test_config_includes");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let main = dir.join("settings.conf");
+
+        fs::write(&main, "zoom=1\ntheme=light\n@include local.conf\n@include missing.conf\ntheme=dark\n").unwrap();
+        fs::write(dir.join("local.conf"), "zoom=2\ntheme=sepia\nbackup_dir=/mnt/backup\n[editor]\nfont=Iosevka\n").unwrap();
+
+        let mut parser = ConfigParser::new(main.to_str().unwrap());
+        assert!(parser.load().is_ok());
+        assert_eq!(parser.get("zoom"), Some(&"2".to_string()));
+        assert_eq!(parser.get("theme"), Some(&"dark".to_string()));
+        assert_eq!(parser.get("editor.font"), Some(&"Iosevka".to_string()));
+
+        // Included values stay in their own file
+        assert!(parser.save().is_ok());
+        assert_eq!(
+            fs::read_to_string(&main).unwrap(),
+            "zoom=1\ntheme=light\n@include local.conf\n@include missing.conf\ntheme=dark\n"
+        );
+
+        // Changing one writes an override after the include
+        parser.set("zoom", "3");
+        parser.set("editor.font", "Menlo");
+        assert!(parser.save().is_ok());
+        assert_eq!(
+            fs::read_to_string(&main).unwrap(),
+            "zoom=1\ntheme=light\n@include local.conf\n@include missing.conf\ntheme=dark\nzoom=3\n\n[editor]\nfont=Menlo\n"
+        );
+
+        let mut parser2 = ConfigParser::new(main.to_str().unwrap());
+        assert!(parser2.load().is_ok());
+        assert_eq!(parser2.get("zoom"), Some(&"3".to_string()));
+        assert_eq!(parser2.get("editor.font"), Some(&"Menlo".to_string()));
+
+        fs::write(dir.join("local.conf"), "@include settings.conf\n").unwrap();
+        assert!(ConfigParser::new(main.to_str().unwrap()).load().is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_string_escaping() {
+        let temp_file = env::temp_dir().join("test_config_escaping.conf");
+        let temp_path = temp_file.to_str().unwrap();
+        fs::write(
+            &temp_file,
+            "plain=Ctrl+# # Comment\nquoted=\"a # b\" # Kept\nliteral='C:\\path\\n'\nunterminated=\"oops\nitems[]=\"x # y\"\nitems[]=p, q\n",
+        )
+        .unwrap();
+
+        let mut parser = ConfigParser::new(temp_path);
+        assert!(parser.load().is_ok());
+        assert_eq!(parser.get_str("plain"), Some(&"Ctrl+#".to_string()));
+        assert_eq!(parser.get_str("quoted"), Some(&"a # b".to_string()));
+        assert_eq!(parser.get_str("literal"), Some(&"C:\\path\\n".to_string()));
+        assert_eq!(parser.get_str("unterminated"), Some(&"\"oops".to_string()));
+        assert_eq!(parser.get_list("items"), Some(vec!["x # y".to_string(), "p, q".to_string()]));
+
+        let tricky = [" padded ", "a=b", "#hash", "line\nbreak \"q\" \\", "'single"];
+        for (i, value) in tricky.iter().enumerate() {
+            parser.set_str(&format!("tricky{}", i), value);
+        }
+        parser.set_list("items", &["x # y", "p, q", "r"]);
+        assert!(parser.save().is_ok());
+
+        let mut parser2 = ConfigParser::new(temp_path);
+        assert!(parser2.load().is_ok());
+        for (i, value) in tricky.iter().enumerate() {
+            assert_eq!(parser2.get_str(&format!("tricky{}", i)), Some(&value.to_string()));
+        }
+        assert_eq!(parser2.get_list("items"), Some(vec!["x # y".to_string(), "p, q".to_string(), "r".to_string()]));
+        assert_eq!(parser2.get_str("plain"), Some(&"Ctrl+#".to_string()));
+
+        let _ = fs::remove_file(&temp_file);
+    }
+
+    #[test]
+    fn test_multiline_values() {
+        let temp_file = env::temp_dir().join("test_config_multiline.conf");
+        let temp_path = temp_file.to_str().unwrap();
+        let original = "css=\"\"\"\nbody {\n  color: red; # not a comment\n}\n\"\"\" # Custom CSS\n\
+pinned = [\n  \"/notes/a, b.canvas\",\n  /notes/c.canvas # Work\n]\n\
+prompt=Summarize the \\\n    following text\n\
+title=[Draft] Notes\n\
+path=C:\\Temp\\\nnext=1\n";
+        fs::write(&temp_file, original).unwrap();
+
+        let mut parser = ConfigParser::new(temp_path);
+        assert!(parser.load().is_ok());
+        assert_eq!(parser.get_str("css"), Some(&"body {\n  color: red; # not a comment\n}".to_string()));
+        assert_eq!(parser.comments.get("css"), Some(&"Custom CSS".to_string()));
+        assert_eq!(parser.get_list("pinned"), Some(vec!["/notes/a, b.canvas".to_string(), "/notes/c.canvas".to_string()]));
+        assert_eq!(parser.get_str("prompt"), Some(&"Summarize the following text".to_string()));
+        assert_eq!(parser.get_str("title"), Some(&"[Draft] Notes".to_string()));
+        assert_eq!(parser.get_str("path"), Some(&"C:\\Temp\\".to_string()));
+        assert_eq!(parser.get_str("next"), Some(&"1".to_string()));
+
+        assert!(parser.save().is_ok());
+        assert_eq!(fs::read_to_string(&temp_file).unwrap(), original);
+
+        parser.set_list("pinned", &["/notes/d.canvas", "x, y"]);
+        parser.set_str("snippet", "line one\nline two");
+        assert!(parser.save().is_ok());
+        let content = fs::read_to_string(&temp_file).unwrap();
+        assert!(content.contains("pinned=[/notes/d.canvas, \"x, y\"]\n"));
+        assert!(content.contains("snippet=\"\"\"\nline one\nline two\n\"\"\""));
+
+        let mut parser2 = ConfigParser::new(temp_path);
+        assert!(parser2.load().is_ok());
+        assert_eq!(parser2.get_list("pinned"), Some(vec!["/notes/d.canvas".to_string(), "x, y".to_string()]));
+        assert_eq!(parser2.get_str("snippet"), Some(&"line one\nline two".to_string()));
+
+        let _ = fs::remove_file(&temp_file);
+    }
+
+    #[test]
+    fn test_transaction_rolls_back() {
+        let temp_file = env::temp_dir().join("test_config_transaction.conf");
+        let temp_path = temp_file.to_str().unwrap();
+        fs::write(&temp_file, "zoom=1\ntheme=dark\n").unwrap();
+
+        let mut parser = ConfigParser::new(temp_path);
+        assert!(parser.load().is_ok());
+        parser.set_range("zoom", 0.25, 4.0);
+        parser.set_validator("theme", |value| match value {
+            "light" | "dark" => Ok(()),
+            _ => Err(AppError::validation("expected light or dark")),
+        });
+
+        let result = parser.transaction(|cfg| {
+            cfg.set("zoom", "2");
+            cfg.set("theme", "neon");
+            Ok(())
+        });
+        assert!(result.unwrap_err().message().contains("theme"));
+        assert_eq!(parser.get("zoom"), Some(&"1".to_string()));
+        assert_eq!(fs::read_to_string(&temp_file).unwrap(), "zoom=1\ntheme=dark\n");
+
+        assert!(parser.transaction(|cfg| cfg.set_float("zoom", 9.0)).is_err());
+        assert_eq!(parser.get("zoom"), Some(&"1".to_string()));
+
+        assert!(parser.transaction(|cfg| { cfg.set("theme", "light"); Ok(()) }).is_ok());
+        assert_eq!(fs::read_to_string(&temp_file).unwrap(), "zoom=1\ntheme=light\n");
+        assert_eq!(fs::read_to_string(format!("{}.bak", temp_path)).unwrap(), "zoom=1\ntheme=dark\n");
+
+        let _ = fs::remove_file(&temp_file);
+        let _ = fs::remove_file(format!("{}.bak", temp_path));
+    }
+
+    #[test]
+    fn test_expansion() {
+        env::set_var("CONFIG_PARSER_TEST_HOST", "studio");
+        let home = env::var("HOME").unwrap();
+
+        assert_eq!(expand_value("~/Backups/${CONFIG_PARSER_TEST_HOST}"), format!("{}/Backups/studio", home));
+        assert_eq!(expand_value("$CONFIG_PARSER_TEST_HOST-x"), "studio-x");
+        assert_eq!(expand_value("cost $$5 ${CONFIG_PARSER_MISSING} ${open"), "cost $5 ${CONFIG_PARSER_MISSING} ${open");
+        assert_eq!(expand_value("a~b"), "a~b");
+
+        let temp_file = env::temp_dir().join("test_config_expansion.conf");
+        let temp_path = temp_file.to_str().unwrap();
+        fs::write(&temp_file, "backup_dir=~/Backups/${CONFIG_PARSER_TEST_HOST}\n").unwrap();
+
+        let mut parser = ConfigParser::new(temp_path);
+        assert!(parser.load().is_ok());
+        assert_eq!(parser.get_expanded("backup_dir"), Some(format!("{}/Backups/studio", home)));
+        assert!(parser.save().is_ok());
+        assert_eq!(fs::read_to_string(&temp_file).unwrap(), "backup_dir=~/Backups/${CONFIG_PARSER_TEST_HOST}\n");
+
+        let _ = fs::remove_file(&temp_file);
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Mode {
+        Light,
+        Dark,
+    }
+
+    impl FromStr for Mode {
+        type Err = ();
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "light" => Ok(Mode::Light),
+                "dark" => Ok(Mode::Dark),
+                _ => Err(()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_typed_values() {
+        let mut parser = ConfigParser::new("unused.conf");
+        parser.set_range("autosave_interval", 1.0, 3600.0);
+        parser.set_range("zoom", 0.25, 4.0);
+
+        assert!(parser.set_int("autosave_interval", 30).is_ok());
+        assert!(parser.set_int("autosave_interval", 0).is_err());
+        assert_eq!(parser.get_int("autosave_interval"), Some(30));
+
+        assert!(parser.set_float("zoom", 1.5).is_ok());
+        assert!(parser.set_float("zoom", 10.0).is_err());
+        assert!(parser.set_float("zoom", f64::NAN).is_err());
+        assert_eq!(parser.get_float("zoom"), Some(1.5));
+
+        // Hand-edited values outside the range read back as missing
+        parser.set("zoom", "9");
+        assert_eq!(parser.get_float("zoom"), None);
+        parser.set("autosave_interval", "soon");
+        assert_eq!(parser.get_int("autosave_interval"), None);
+
+        parser.set("theme", "dark");
+        assert_eq!(parser.get_enum::<Mode>("theme"), Some(Mode::Dark));
+        parser.set("theme", "sepia");
+        assert_eq!(parser.get_enum::<Mode>("theme"), None);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,178 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use crate::settings_manager;
+use crate::error::{AppError, AppResult};
+
+/// Where the shared repository is checked out, inside the app data directory.
+const CHECKOUT_DIR: &str = "config-repo";
+
+/// The folders of the shared repository that are layered into the app.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigLayer {
+    Templates,
+    Snippets,
+    Prompts,
+    ShortcutPresets,
+}
+
+impl ConfigLayer {
+    const ALL: [ConfigLayer; 4] = [ConfigLayer::Templates, ConfigLayer::Snippets, ConfigLayer::Prompts, ConfigLayer::ShortcutPresets];
+
+    fn dir_name(self) -> &'static str {
+        match self {
+            ConfigLayer::Templates => "templates",
+            ConfigLayer::Snippets => "snippets",
+            ConfigLayer::Prompts => "prompts",
+            ConfigLayer::ShortcutPresets => "shortcut-presets",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConfigRepoStatus {
+    /// Empty when no repository is configured
+    pub url: String,
+    pub last_synced: Option<DateTime<Utc>>,
+    /// Commit the checkout is at
+    pub commit: Option<String>,
+    pub error: Option<String>,
+    /// The layer folders the checkout has, like "templates"
+    pub layers: Vec<&'static str>,
+}
+
+#[derive(Default)]
+pub struct ConfigRepoState {
+    status: Mutex<ConfigRepoStatus>,
+    /// Held while git runs so scheduled and manual syncs don't overlap
+    sync_lock: Mutex<()>,
+}
+
+fn get_checkout_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir.join(CHECKOUT_DIR))
+}
+
+fn git(dir: Option<&Path>, args: &[&str]) -> AppResult<String> {
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.arg("-C").arg(dir);
+    }
+    // Never wait for a password prompt in the background
+    command.env("GIT_TERMINAL_PROMPT", "0");
+
+    let output = command
+        .args(args)
+        .output()
+        .map_err(|e| AppError::external(format!("Failed to run git: {}", e)))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(AppError::external(format!("git {} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim())))
+    }
+}
+
+/// Clones the configured repository, or fast-forwards the existing checkout.
+/// A checkout of a different repository is replaced. Emits
+/// "config-repo-synced" with the new status either way.
+pub fn sync(app_handle: &AppHandle) -> AppResult<ConfigRepoStatus> {
+    let state = app_handle.state::<ConfigRepoState>();
+    let _syncing = state.sync_lock.lock()?;
+
+    let settings = settings_manager::current_settings(app_handle, &app_handle.state::<settings_manager::SettingsState>())?;
+    let url = settings.sync_config_repo;
+    let checkout = get_checkout_path(app_handle)?;
+
+    let result = if url.is_empty() {
+        Ok(None)
+    } else {
+        update_checkout(&url, &checkout).map(Some)
+    };
+
+    let status = {
+        let mut status = state.status.lock()?;
+        status.url = url;
+        match &result {
+            Ok(commit) => {
+                status.commit = commit.clone();
+                status.last_synced = commit.as_ref().map(|_| Utc::now());
+                status.error = None;
+                status.layers = if commit.is_some() { present_layers(&checkout) } else { Vec::new() };
+            }
+            Err(e) => status.error = Some(e.to_string()),
+        }
+        status.clone()
+    };
+    let _ = app_handle.emit("config-repo-synced", &status);
+
+    result.map(|_| status)
+}
+
+fn present_layers(checkout: &Path) -> Vec<&'static str> {
+    ConfigLayer::ALL
+        .iter()
+        .map(|layer| layer.dir_name())
+        .filter(|name| checkout.join(name).is_dir())
+        .collect()
+}
+
+fn update_checkout(url: &str, checkout: &Path) -> AppResult<String> {
+    let current_url = checkout
+        .join(".git")
+        .exists()
+        .then(|| git(Some(checkout), &["remote", "get-url", "origin"]).ok())
+        .flatten();
+
+    if current_url.as_deref() == Some(url) {
+        git(Some(checkout), &["pull", "--ff-only", "--quiet"])?;
+    } else {
+        if checkout.exists() {
+            std::fs::remove_dir_all(checkout).map_err(|e| AppError::io(format!("Failed to remove old checkout: {}", e)))?;
+        }
+        let checkout_str = checkout.to_str().ok_or_else(|| AppError::validation("Invalid checkout path"))?;
+        git(None, &["clone", "--depth", "1", "--quiet", "--", url, checkout_str])?;
+    }
+
+    git(Some(checkout), &["rev-parse", "HEAD"])
+}
+
+pub fn status(state: &ConfigRepoState) -> AppResult<ConfigRepoStatus> {
+    Ok(state.status.lock()?.clone())
+}
+
+/// The folder `layer` comes from in the shared repository, if one is
+/// configured and has that folder.
+pub fn layer_dir(app_handle: &AppHandle, layer: ConfigLayer) -> Option<PathBuf> {
+    let settings = settings_manager::current_settings(app_handle, &app_handle.state::<settings_manager::SettingsState>()).ok()?;
+    if settings.sync_config_repo.is_empty() {
+        return None;
+    }
+    let dir = get_checkout_path(app_handle).ok()?.join(layer.dir_name());
+    dir.is_dir().then_some(dir)
+}
+
+/// Files directly in `layer`'s folder with the given extension, sorted.
+pub fn layer_files(app_handle: &AppHandle, layer: ConfigLayer, extension: &str) -> Vec<PathBuf> {
+    let Some(dir) = layer_dir(app_handle, layer) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|e| e == extension))
+        .collect();
+    files.sort();
+    files
+}
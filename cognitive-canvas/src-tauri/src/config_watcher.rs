@@ -0,0 +1,102 @@
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use std::collections::HashSet;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::time::Duration;
+use crate::{settings_manager, shortcuts_manager};
+use crate::error::{AppError, AppResult};
+
+/// Editors often write a file in several steps; changes within this window
+/// are handled as one reload.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(300);
+
+const WATCHED_FILES: &[&str] = &["settings.conf", "shortcuts.conf"];
+
+/// Keeps the watcher alive for the lifetime of the app.
+#[derive(Default)]
+pub struct ConfigWatcherState {
+    watcher: Mutex<Option<RecommendedWatcher>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SettingsReloaded {
+    settings: Option<settings_manager::Settings>,
+    shortcuts: Option<shortcuts_manager::Shortcuts>,
+}
+
+/// Watches the config files in the app data directory and re-applies them
+/// when they're edited by hand, emitting "settings-reloaded".
+pub fn start(app_handle: &AppHandle) -> AppResult<()> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    let (sender, receiver) = mpsc::channel::<String>();
+    let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+        let event = match result {
+            Ok(event) if event.kind.is_create() || event.kind.is_modify() => event,
+            _ => return,
+        };
+        for path in event.paths {
+            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                if WATCHED_FILES.contains(&name) {
+                    let _ = sender.send(name.to_string());
+                }
+            }
+        }
+    })
+    .map_err(|e| AppError::io(format!("Failed to watch config files: {}", e)))?;
+
+    // The directory is watched rather than the files, since saving with
+    // many editors replaces the file instead of writing to it
+    watcher
+        .watch(&app_data_dir, RecursiveMode::NonRecursive)
+        .map_err(|e| AppError::io(format!("Failed to watch config files: {}", e)))?;
+
+    let app = app_handle.clone();
+    std::thread::spawn(move || {
+        while let Ok(first) = receiver.recv() {
+            let mut changed = HashSet::from([first]);
+            while let Ok(name) = receiver.recv_timeout(RELOAD_DEBOUNCE) {
+                changed.insert(name);
+            }
+            if let Err(e) = reload(&app, &changed) {
+                tracing::warn!("Failed to reload config: {}", e);
+            }
+        }
+    });
+
+    let state = app_handle.state::<ConfigWatcherState>();
+    *state.watcher.lock()? = Some(watcher);
+    Ok(())
+}
+
+fn reload(app_handle: &AppHandle, changed: &HashSet<String>) -> AppResult<()> {
+    let mut payload = SettingsReloaded { settings: None, shortcuts: None };
+
+    if changed.contains("settings.conf") {
+        let state = app_handle.state::<settings_manager::SettingsState>();
+        // Our own debounced writes land here too; they change nothing
+        if let Some(settings) = settings_manager::reload_settings(app_handle, &state)? {
+            settings_manager::apply_window_settings(app_handle, &settings)?;
+            settings_manager::emit_theme_changed(app_handle, &settings)?;
+            payload.settings = Some(settings);
+        }
+    }
+    if changed.contains("shortcuts.conf") {
+        payload.shortcuts = Some(shortcuts_manager::load_shortcuts(app_handle)?);
+        #[cfg(desktop)]
+        crate::global_shortcuts::register_all(app_handle)?;
+    }
+
+    if payload.settings.is_some() || payload.shortcuts.is_some() {
+        let _ = app_handle.emit("settings-reloaded", &payload);
+    }
+    Ok(())
+}
@@ -0,0 +1,248 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// The newest canvas schema this build understands. Documents say which
+/// one they were written against in a top-level `schemaVersion`; Lexical
+/// state without one is version 1.
+pub const SCHEMA_VERSION: u64 = 1;
+
+/// A value in a canvas document that doesn't have the type the schema
+/// expects there.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SchemaError {
+    /// e.g. "root.children[2].format"
+    pub path: String,
+    pub expected: String,
+    /// The JSON type found, or "missing"
+    pub found: String,
+}
+
+/// A minor problem that was fixed in place, like a missing `version`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Repair {
+    pub path: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SchemaReport {
+    /// `None` for content that isn't a canvas, which isn't checked
+    pub schema_version: Option<u64>,
+    pub errors: Vec<SchemaError>,
+    pub repairs: Vec<Repair>,
+    /// The repaired document, when repairs were made
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
+impl SchemaReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+fn type_name(value: Option<&Value>) -> &'static str {
+    match value {
+        None => "missing",
+        Some(Value::Null) => "null",
+        Some(Value::Bool(_)) => "bool",
+        Some(Value::Number(_)) => "number",
+        Some(Value::String(_)) => "string",
+        Some(Value::Array(_)) => "array",
+        Some(Value::Object(_)) => "object",
+    }
+}
+
+struct Checker {
+    repair: bool,
+    errors: Vec<SchemaError>,
+    repairs: Vec<Repair>,
+}
+
+impl Checker {
+    fn error(&mut self, path: &str, expected: &str, found: Option<&Value>) {
+        self.errors.push(SchemaError {
+            path: path.to_string(),
+            expected: expected.to_string(),
+            found: type_name(found).to_string(),
+        });
+    }
+
+    /// Fills in `key` with `default` when it's missing or null; reported as
+    /// an error when not repairing.
+    fn default_field(&mut self, node: &mut Map<String, Value>, path: &str, key: &str, expected: &str, default: Value) -> bool {
+        if !node.get(key).is_none_or(Value::is_null) {
+            return false;
+        }
+        if self.repair {
+            self.repairs.push(Repair {
+                path: format!("{}.{}", path, key),
+                description: format!("Set missing {} to {}", key, default),
+            });
+            node.insert(key.to_string(), default);
+        } else {
+            self.error(&format!("{}.{}", path, key), expected, node.get(key));
+        }
+        true
+    }
+
+    fn number(&mut self, node: &mut Map<String, Value>, path: &str, key: &str, default: i64) {
+        if self.default_field(node, path, key, "number", Value::from(default)) {
+            return;
+        }
+        let field_path = format!("{}.{}", path, key);
+        match node.get(key) {
+            Some(Value::Number(_)) => {}
+            // Numbers written as strings, e.g. "format": "1"
+            Some(Value::String(s)) if self.repair && s.trim().parse::<i64>().is_ok() => {
+                let number = s.trim().parse::<i64>().unwrap_or(default);
+                self.repairs.push(Repair {
+                    path: field_path,
+                    description: format!("Converted \"{}\" to a number", s),
+                });
+                node.insert(key.to_string(), Value::from(number));
+            }
+            found => self.error(&field_path, "number", found),
+        }
+    }
+
+    fn string(&mut self, node: &mut Map<String, Value>, path: &str, key: &str, default: Option<&str>) {
+        match default {
+            Some(default) if self.default_field(node, path, key, "string", Value::from(default)) => {}
+            _ if node.get(key).is_some_and(Value::is_string) => {}
+            _ => self.error(&format!("{}.{}", path, key), "string", node.get(key)),
+        }
+    }
+
+    fn node(&mut self, node: &mut Value, path: &str, is_root: bool) {
+        let Some(object) = node.as_object_mut() else {
+            self.error(path, "object", Some(node));
+            return;
+        };
+
+        if is_root {
+            self.default_field(object, path, "type", "string", Value::from("root"));
+            self.default_field(object, path, "children", "array", Value::Array(Vec::new()));
+        }
+        if !object.get("type").is_some_and(Value::is_string) {
+            self.error(&format!("{}.type", path), "string", object.get("type"));
+        }
+        self.number(object, path, "version", 1);
+
+        if object.get("type").and_then(Value::as_str) == Some("text") {
+            self.string(object, path, "text", None);
+            self.number(object, path, "format", 0);
+            self.number(object, path, "detail", 0);
+            self.string(object, path, "mode", Some("normal"));
+            self.string(object, path, "style", Some(""));
+        } else if object.contains_key("children") {
+            self.number(object, path, "indent", 0);
+            // Elements keep their alignment as a string, e.g. "center"
+            if !object.get("format").is_none_or(|f| f.is_string() || f.is_number()) {
+                self.error(&format!("{}.format", path), "string", object.get("format"));
+            }
+            if !object.get("direction").is_none_or(|d| d.is_string() || d.is_null()) {
+                self.error(&format!("{}.direction", path), "string or null", object.get("direction"));
+            }
+        }
+
+        match object.get_mut("children") {
+            None => {}
+            Some(Value::Array(children)) => {
+                for (i, child) in children.iter_mut().enumerate() {
+                    self.node(child, &format!("{}.children[{}]", path, i), false);
+                }
+            }
+            Some(other) => self.error(&format!("{}.children", path), "array", Some(other)),
+        }
+    }
+}
+
+/// Checks a canvas document against the schema, optionally fixing what
+/// can be fixed without guessing: missing versions, indents, text formats
+/// and modes, and numbers stored as strings. Content that isn't a JSON
+/// object with a `root` isn't a canvas and passes as is.
+pub fn check(content: &str, repair: bool) -> SchemaReport {
+    let Ok(Value::Object(mut document)) = serde_json::from_str::<Value>(content) else {
+        return SchemaReport::default();
+    };
+    let Some(root) = document.get_mut("root") else {
+        return SchemaReport::default();
+    };
+
+    let mut checker = Checker { repair, errors: Vec::new(), repairs: Vec::new() };
+    checker.node(root, "root", true);
+
+    let schema_version = match document.get("schemaVersion") {
+        None => 1,
+        Some(version) => match version.as_u64() {
+            Some(version) if (1..=SCHEMA_VERSION).contains(&version) => version,
+            Some(version) => {
+                checker.errors.push(SchemaError {
+                    path: "schemaVersion".to_string(),
+                    expected: format!("a version up to {}", SCHEMA_VERSION),
+                    found: version.to_string(),
+                });
+                version
+            }
+            None => {
+                checker.error("schemaVersion", "number", Some(version));
+                1
+            }
+        },
+    };
+
+    let content = (!checker.repairs.is_empty()).then(|| Value::Object(document).to_string());
+    SchemaReport {
+        schema_version: Some(schema_version),
+        errors: checker.errors,
+        repairs: checker.repairs,
+        content,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOC: &str = r#"{"root":{"type":"root","version":1,"indent":0,"format":"","direction":null,"children":[
+        {"type":"paragraph","indent":0,"format":"center","direction":"ltr","children":[
+            {"type":"text","version":1,"text":"hi","format":"1","detail":0,"style":""}
+        ]},
+        {"type":"image","version":1,"src":"a.png"}
+    ]}}"#;
+
+    #[test]
+    fn test_check_reports_paths() {
+        let report = check(DOC, false);
+        let errors: Vec<(&str, &str, &str)> =
+            report.errors.iter().map(|e| (e.path.as_str(), e.expected.as_str(), e.found.as_str())).collect();
+        assert_eq!(
+            errors,
+            vec![
+                ("root.children[0].version", "number", "missing"),
+                ("root.children[0].children[0].format", "number", "string"),
+                ("root.children[0].children[0].mode", "string", "missing"),
+            ]
+        );
+        assert!(report.content.is_none());
+        assert!(check("# markdown", false).is_valid());
+        assert!(check(r#"{"url":"a.pdf"}"#, false).schema_version.is_none());
+    }
+
+    #[test]
+    fn test_check_repairs_minor_issues() {
+        let report = check(DOC, true);
+        assert!(report.is_valid(), "{:?}", report.errors);
+        assert_eq!(report.repairs.len(), 3);
+        let repaired: Value = serde_json::from_str(report.content.as_deref().unwrap()).unwrap();
+        assert_eq!(repaired["root"]["children"][0]["children"][0]["format"], 1);
+        assert_eq!(repaired["root"]["children"][0]["children"][0]["mode"], "normal");
+
+        // Missing text or the wrong shape of children can't be guessed
+        let broken = r#"{"schemaVersion":9,"root":{"children":[{"type":"text","version":1},{"type":"list","version":1,"children":{}}]}}"#;
+        let report = check(broken, true);
+        let paths: Vec<&str> = report.errors.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["root.children[0].text", "root.children[1].children", "schemaVersion"]);
+    }
+}
@@ -0,0 +1,123 @@
+use base64::Engine;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
+use yrs::{Doc, GetString, ReadTxn, StateVector, Text, Transact, Update};
+use crate::document_text;
+use crate::error::{AppError, AppResult};
+
+const CONTENT_FIELD: &str = "content";
+
+/// One Yjs document per open canvas, keyed by document id.
+#[derive(Default)]
+pub struct CrdtState {
+    docs: Mutex<HashMap<String, Doc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EncodedUpdate {
+    /// Base64 Yjs v1 update containing everything the remote side is missing
+    pub update: String,
+    /// Base64 state vector of the local document after the edit
+    pub state_vector: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MergeResult {
+    pub content: String,
+    pub state_vector: String,
+}
+
+fn encode(bytes: Vec<u8>) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+fn decode(value: &str) -> AppResult<Vec<u8>> {
+    base64::engine::general_purpose::STANDARD
+        .decode(value)
+        .map_err(|e| AppError::validation(format!("Invalid base64 payload: {}", e)))
+}
+
+/// Records `content` as the new local version of the document and returns
+/// the update a peer at `remote_state_vector` needs (everything, if none).
+pub fn encode_local_update(
+    state: &CrdtState,
+    doc_id: &str,
+    content: &str,
+    remote_state_vector: Option<&str>,
+) -> AppResult<EncodedUpdate> {
+    let mut docs = state.docs.lock()?;
+    let doc = docs.entry(doc_id.to_string()).or_insert_with(Doc::new);
+    let text = doc.get_or_insert_text(CONTENT_FIELD);
+
+    let mut txn = doc.transact_mut();
+    let current = text.get_string(&txn);
+    if let Some((start, removed, inserted)) = document_text::changed_range(&current, content) {
+        if removed > 0 {
+            text.remove_range(&mut txn, start as u32, removed as u32);
+        }
+        if !inserted.is_empty() {
+            text.insert(&mut txn, start as u32, inserted);
+        }
+    }
+
+    let remote = match remote_state_vector {
+        Some(sv) => StateVector::decode_v1(&decode(sv)?)
+            .map_err(|e| AppError::validation(format!("Invalid state vector: {}", e)))?,
+        None => StateVector::default(),
+    };
+
+    Ok(EncodedUpdate {
+        update: encode(txn.encode_state_as_update_v1(&remote)),
+        state_vector: encode(txn.state_vector().encode_v1()),
+    })
+}
+
+/// Merges an update from a peer or the sync backend and returns the
+/// resulting document content.
+pub fn apply_remote_update(state: &CrdtState, doc_id: &str, update: &str) -> AppResult<MergeResult> {
+    let update = Update::decode_v1(&decode(update)?).map_err(|e| AppError::validation(format!("Invalid update: {}", e)))?;
+
+    let mut docs = state.docs.lock()?;
+    let doc = docs.entry(doc_id.to_string()).or_insert_with(Doc::new);
+    let text = doc.get_or_insert_text(CONTENT_FIELD);
+
+    let mut txn = doc.transact_mut();
+    txn.apply_update(update).map_err(|e| AppError::validation(format!("Failed to apply update: {}", e)))?;
+
+    Ok(MergeResult {
+        content: text.get_string(&txn),
+        state_vector: encode(txn.state_vector().encode_v1()),
+    })
+}
+
+pub fn close_document(state: &CrdtState, doc_id: &str) -> AppResult<()> {
+    state.docs.lock()?.remove(doc_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concurrent_edits_converge() {
+        let alice = CrdtState::default();
+        let bob = CrdtState::default();
+
+        let initial = encode_local_update(&alice, "doc", "hello world", None).unwrap();
+        let synced = apply_remote_update(&bob, "doc", &initial.update).unwrap();
+        assert_eq!(synced.content, "hello world");
+
+        let from_alice = encode_local_update(&alice, "doc", "hello brave world", None).unwrap();
+        let from_bob = encode_local_update(&bob, "doc", "hello world!", None).unwrap();
+
+        let merged_alice = apply_remote_update(&alice, "doc", &from_bob.update).unwrap();
+        let merged_bob = apply_remote_update(&bob, "doc", &from_alice.update).unwrap();
+
+        assert_eq!(merged_alice.content, "hello brave world!");
+        assert_eq!(merged_alice.content, merged_bob.content);
+    }
+}
@@ -0,0 +1,226 @@
+use chrono::{Datelike, Duration, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use crate::{document_text, workspace};
+use crate::error::AppResult;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeadlineRange {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    /// "day" (default) or "week"
+    #[serde(default)]
+    pub group_by: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadlineSource {
+    Frontmatter,
+    Task,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Deadline {
+    pub due: NaiveDate,
+    pub title: String,
+    pub path: String,
+    pub source: DeadlineSource,
+    pub completed: bool,
+    /// Still open and due before today
+    pub overdue: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadlineGroup {
+    pub label: String,
+    pub start: NaiveDate,
+    pub deadlines: Vec<Deadline>,
+}
+
+/// Collects every due date found in the workspace within `range`, grouped
+/// by day or ISO week in chronological order.
+pub fn get_deadlines(workspace: &Path, range: &DeadlineRange) -> AppResult<Vec<DeadlineGroup>> {
+    let mut deadlines = Vec::new();
+    let today = Local::now().date_naive();
+
+    for path in workspace::document_files(workspace)? {
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+        deadlines.extend(
+            extract_deadlines(&path, &content, today)
+                .into_iter()
+                .filter(|d| d.due >= range.start && d.due <= range.end),
+        );
+    }
+
+    deadlines.sort_by(|a, b| a.due.cmp(&b.due).then_with(|| a.title.cmp(&b.title)));
+
+    let by_week = range.group_by.as_deref() == Some("week");
+    let mut groups: Vec<DeadlineGroup> = Vec::new();
+
+    for deadline in deadlines {
+        let (label, start) = if by_week {
+            let week = deadline.due.iso_week();
+            let start = deadline.due - Duration::days(deadline.due.weekday().num_days_from_monday() as i64);
+            (format!("{}-W{:02}", week.year(), week.week()), start)
+        } else {
+            (deadline.due.format("%Y-%m-%d").to_string(), deadline.due)
+        };
+
+        match groups.last_mut() {
+            Some(group) if group.label == label => group.deadlines.push(deadline),
+            _ => groups.push(DeadlineGroup { label, start, deadlines: vec![deadline] }),
+        }
+    }
+
+    Ok(groups)
+}
+
+/// Finds `due:` in frontmatter plus checklist lines carrying a due marker,
+/// e.g. `- [ ] Send invoice due:2025-03-01` or `[x] Ship @due(2025-03-01)`.
+/// Open ones due before `today` are marked overdue.
+pub(crate) fn extract_deadlines(path: &Path, content: &str, today: NaiveDate) -> Vec<Deadline> {
+    let text = document_text::plain_text(content);
+    let path_str = path.to_string_lossy().to_string();
+    let file_title = path
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or("Untitled")
+        .to_string();
+
+    let mut deadlines = Vec::new();
+
+    let frontmatter = document_text::frontmatter(&text);
+    if let Some(due) = frontmatter.iter().find(|(k, _)| k == "due").and_then(|(_, v)| parse_date(v)) {
+        let title = frontmatter
+            .iter()
+            .find(|(k, _)| k == "title")
+            .map(|(_, v)| v.clone())
+            .unwrap_or_else(|| file_title.clone());
+        deadlines.push(Deadline {
+            due,
+            title,
+            path: path_str.clone(),
+            source: DeadlineSource::Frontmatter,
+            completed: false,
+            overdue: due < today,
+        });
+    }
+
+    for line in text.lines() {
+        let line = line.trim().trim_start_matches(['-', '*']).trim_start();
+        let (completed, rest) = if let Some(rest) = line.strip_prefix("[ ]") {
+            (false, rest)
+        } else if let Some(rest) = line.strip_prefix("[x]").or_else(|| line.strip_prefix("[X]")) {
+            (true, rest)
+        } else {
+            continue;
+        };
+
+        let marker = ["@due(", "due:", "📅"].iter().find_map(|m| rest.find(m).map(|pos| (pos, m.len())));
+        let (pos, len) = match marker {
+            Some(marker) => marker,
+            None => continue,
+        };
+
+        if let Some(due) = parse_date(rest[pos + len..].trim_start()) {
+            deadlines.push(Deadline {
+                due,
+                title: rest[..pos].trim().to_string(),
+                path: path_str.clone(),
+                source: DeadlineSource::Task,
+                completed,
+                overdue: !completed && due < today,
+            });
+        }
+    }
+
+    deadlines
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    let candidate: String = value.chars().take(10).collect();
+    NaiveDate::parse_from_str(&candidate, "%Y-%m-%d").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("2025-03-01"), Some(date("2025-03-01")));
+        assert_eq!(parse_date("2025-03-01) and more"), Some(date("2025-03-01")));
+        assert_eq!(parse_date("2025-02-30"), None);
+        assert_eq!(parse_date("03/01/2025"), None);
+        assert_eq!(parse_date(""), None);
+    }
+
+    #[test]
+    fn test_due_and_overdue() {
+        let content = "---\ntitle: Launch\ndue: 2025-03-10\n---\n\
+            - [ ] Send invoice due:2025-03-01\n\
+            - [x] Book venue @due(2025-02-20)\n\
+            * [ ] Print flyers 📅 2025-03-05\n\
+            - [ ] Draft agenda due:soon\n\
+            Plain text due:2025-01-01\n";
+        let today = date("2025-03-05");
+        let deadlines = extract_deadlines(Path::new("/notes/launch.md"), content, today);
+
+        let summary: Vec<(&str, NaiveDate, bool, bool)> =
+            deadlines.iter().map(|d| (d.title.as_str(), d.due, d.completed, d.overdue)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("Launch", date("2025-03-10"), false, false),
+                ("Send invoice", date("2025-03-01"), false, true),
+                ("Book venue", date("2025-02-20"), true, false),
+                // Due today isn't overdue yet
+                ("Print flyers", date("2025-03-05"), false, false),
+            ]
+        );
+        assert!(matches!(deadlines[0].source, DeadlineSource::Frontmatter));
+        assert!(deadlines[1..].iter().all(|d| matches!(d.source, DeadlineSource::Task)));
+
+        let later = extract_deadlines(Path::new("/notes/launch.md"), content, date("2025-03-11"));
+        assert!(later[0].overdue);
+    }
+
+    #[test]
+    fn test_grouping() {
+        let dir = std::env::temp_dir().join("test_deadlines_grouping");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("plan.md"),
+            "- [ ] B task due:2025-03-04\n- [ ] A task due:2025-03-04\n- [ ] Next week due:2025-03-10\n- [ ] Too late due:2025-04-01\n",
+        )
+        .unwrap();
+
+        let range = |group_by: Option<&str>| DeadlineRange {
+            start: date("2025-03-01"),
+            end: date("2025-03-31"),
+            group_by: group_by.map(str::to_string),
+        };
+
+        let days = get_deadlines(&dir, &range(None)).unwrap();
+        let labels: Vec<&str> = days.iter().map(|g| g.label.as_str()).collect();
+        assert_eq!(labels, vec!["2025-03-04", "2025-03-10"]);
+        let titles: Vec<&str> = days[0].deadlines.iter().map(|d| d.title.as_str()).collect();
+        assert_eq!(titles, vec!["A task", "B task"]);
+
+        let weeks = get_deadlines(&dir, &range(Some("week"))).unwrap();
+        let labels: Vec<(&str, NaiveDate)> = weeks.iter().map(|g| (g.label.as_str(), g.start)).collect();
+        assert_eq!(labels, vec![("2025-W10", date("2025-03-03")), ("2025-W11", date("2025-03-10"))]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,92 @@
+use tauri::{AppHandle, Url};
+use std::path::Path;
+use crate::cli::{self, CliCommand};
+use crate::error::{AppError, AppResult};
+
+/// Registered with the OS so `cognitivecanvas://...` links open the app.
+pub const SCHEME: &str = "cognitivecanvas";
+
+pub fn is_deep_link(arg: &str) -> bool {
+    arg.strip_prefix(SCHEME).is_some_and(|rest| rest.starts_with(':'))
+}
+
+/// Turns a link into the command it stands for:
+///
+/// - `cognitivecanvas://open?path=/notes/plan.canvas` (`path` may repeat)
+/// - `cognitivecanvas://new?title=Meeting`
+/// - `cognitivecanvas://capture?text=Buy%20milk`
+/// - `cognitivecanvas://search?query=roadmap`
+///
+/// Links come from other apps and web pages, so paths have to be absolute;
+/// there's no sensible directory to resolve a relative one against.
+pub fn parse(link: &str) -> AppResult<CliCommand> {
+    let url = Url::parse(link).map_err(|e| AppError::validation(format!("Invalid link {}: {}", link, e)))?;
+    if url.scheme() != SCHEME {
+        return Err(AppError::validation(format!("Not a {} link: {}", SCHEME, link)));
+    }
+
+    let values = |key: &str| -> Vec<String> {
+        url.query_pairs()
+            .filter(|(k, v)| k == key && !v.trim().is_empty())
+            .map(|(_, v)| v.into_owned())
+            .collect()
+    };
+    let value = |key: &str| {
+        values(key)
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::validation(format!("Link is missing {}: {}", key, link)))
+    };
+
+    // `cognitivecanvas://open?...` has the action as host, `cognitivecanvas:open?...` as path
+    let action = url.host_str().unwrap_or_else(|| url.path()).trim_matches('/');
+    match action {
+        "open" => {
+            let paths = values("path");
+            if paths.is_empty() {
+                return Err(AppError::validation(format!("Link is missing path: {}", link)));
+            }
+            if let Some(relative) = paths.iter().find(|path| !Path::new(path).is_absolute()) {
+                return Err(AppError::validation(format!("Linked path must be absolute: {}", relative)));
+            }
+            Ok(CliCommand::Open { paths })
+        }
+        "new" => Ok(CliCommand::New { title: value("title")? }),
+        "capture" => Ok(CliCommand::Capture { text: value("text")? }),
+        "search" => Ok(CliCommand::Search { query: value("query")? }),
+        other => Err(AppError::validation(format!("Unknown link action {}", other))),
+    }
+}
+
+/// Hands a link to the frontend the same way a forwarded command line is.
+pub fn handle(app_handle: &AppHandle, link: &str) {
+    match parse(link) {
+        Ok(command) => cli::dispatch(app_handle, command),
+        Err(e) => tracing::warn!("{}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_links() {
+        let absolute = std::env::temp_dir().join("plan.canvas").to_string_lossy().to_string();
+        let link = format!("cognitivecanvas://open?path={}", absolute.replace(' ', "%20"));
+        assert!(is_deep_link(&link));
+        assert_eq!(parse(&link).unwrap(), CliCommand::Open { paths: vec![absolute] });
+
+        assert_eq!(
+            parse("cognitivecanvas://capture?text=Buy%20milk&source=web").unwrap(),
+            CliCommand::Capture { text: "Buy milk".to_string() }
+        );
+        assert_eq!(parse("cognitivecanvas:search?query=road+map").unwrap(), CliCommand::Search { query: "road map".to_string() });
+
+        assert!(parse("cognitivecanvas://open?path=notes.md").is_err());
+        assert!(parse("cognitivecanvas://new").is_err());
+        assert!(parse("cognitivecanvas://delete?path=/a").is_err());
+        assert!(parse("https://open?path=/a").is_err());
+        assert!(!is_deep_link("cognitivecanvasnotes.md"));
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use crate::{document_format, document_text, document_version};
+use crate::error::{AppError, AppResult};
+
+/// How many documents are kept before the least recently used is evicted.
+const CACHE_CAPACITY: usize = 64;
+
+/// A document as read from disk along with what's derived from it. Only
+/// valid while the file still has `modified` as its mtime.
+#[derive(Debug)]
+pub struct CachedDocument {
+    pub content: String,
+    pub modified: u64,
+    pub content_hash: String,
+    pub outline: Vec<document_text::OutlineEntry>,
+    pub links: Vec<String>,
+}
+
+impl CachedDocument {
+    fn new(content: String, modified: u64) -> Self {
+        Self {
+            content_hash: document_version::content_hash(&content),
+            outline: document_text::outline(&content),
+            links: document_text::links(&content),
+            content,
+            modified,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Lru {
+    entries: HashMap<String, Arc<CachedDocument>>,
+    /// Least recently used first
+    order: VecDeque<String>,
+}
+
+impl Lru {
+    fn touch(&mut self, path: &str) {
+        if let Some(pos) = self.order.iter().position(|p| p == path) {
+            self.order.remove(pos);
+        }
+        self.order.push_back(path.to_string());
+    }
+
+    fn insert(&mut self, path: &str, document: Arc<CachedDocument>) {
+        self.entries.insert(path.to_string(), document);
+        self.touch(path);
+        while self.order.len() > CACHE_CAPACITY {
+            if let Some(evicted) = self.order.pop_front() {
+                self.entries.remove(&evicted);
+            }
+        }
+    }
+
+    fn remove(&mut self, path: &str) {
+        self.entries.remove(path);
+        self.order.retain(|p| p != path);
+    }
+}
+
+/// Parsed documents keyed by path and checked against the file's mtime on
+/// every access, so a changed file is never served stale.
+#[derive(Default)]
+pub struct DocumentCache {
+    inner: Mutex<Lru>,
+}
+
+impl DocumentCache {
+    /// Returns the cached document, reading and parsing it again if the file
+    /// changed since or isn't cached yet.
+    pub fn load(&self, path: &str) -> AppResult<Arc<CachedDocument>> {
+        let modified = document_version::modified_millis(Path::new(path));
+
+        if let Some(modified) = modified {
+            let mut lru = self.inner.lock()?;
+            if let Some(document) = lru.entries.get(path).cloned() {
+                if document.modified == modified {
+                    lru.touch(path);
+                    return Ok(document);
+                }
+            }
+        }
+
+        let content = document_format::read_document(Path::new(path))
+            .map_err(|e| AppError::io(format!("Failed to load document: {}", e)))?;
+        let document = Arc::new(CachedDocument::new(content, modified.unwrap_or(0)));
+
+        // Without an mtime there's nothing to validate against later
+        if modified.is_some() {
+            self.inner.lock()?.insert(path, document.clone());
+        }
+        Ok(document)
+    }
+
+    /// Records content just written to `path`, saving the next read.
+    pub fn store(&self, path: &str, content: String, modified: u64) {
+        if let Ok(mut lru) = self.inner.lock() {
+            lru.insert(path, Arc::new(CachedDocument::new(content, modified)));
+        }
+    }
+
+    pub fn invalidate(&self, path: &str) {
+        if let Ok(mut lru) = self.inner.lock() {
+            lru.remove(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_cache_revalidates_and_evicts() {
+        let dir = std::env::temp_dir().join("test_doc_cache");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let first = dir.join("first.md");
+        let first_path = first.to_str().unwrap();
+        fs::write(&first, "# One").unwrap();
+
+        let cache = DocumentCache::default();
+        let loaded = cache.load(first_path).unwrap();
+        assert_eq!(loaded.outline[0].title, "One");
+        assert!(Arc::ptr_eq(&loaded, &cache.load(first_path).unwrap()));
+
+        // A different mtime forces a re-read
+        cache.store(first_path, "# Stale".to_string(), loaded.modified + 1);
+        assert_eq!(cache.load(first_path).unwrap().content, "# One");
+
+        for i in 0..CACHE_CAPACITY {
+            let path = dir.join(format!("{}.md", i));
+            fs::write(&path, "").unwrap();
+            cache.load(path.to_str().unwrap()).unwrap();
+        }
+        assert!(!cache.inner.lock().unwrap().entries.contains_key(first_path));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,152 @@
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+use std::path::{Path, PathBuf};
+use crate::settings_manager::{self, SettingsState};
+use crate::error::{AppError, AppResult};
+
+/// Canvases stored as MessagePack instead of JSON text. They hold the same
+/// Lexical state, just smaller and quicker to parse once they have
+/// thousands of nodes.
+pub const BINARY_EXTENSION: &str = "canvasb";
+
+/// Every zstd frame starts with these bytes.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Documents smaller than this stay uncompressed even with compression on;
+/// there's little to gain and they stay readable by other tools.
+const MIN_COMPRESSED_SIZE: usize = 4096;
+
+/// Whether the user turned on `compress_documents`.
+pub fn compression_enabled(app_handle: &AppHandle) -> bool {
+    let settings_state = app_handle.state::<SettingsState>();
+    settings_manager::current_settings(app_handle, &settings_state).is_ok_and(|settings| settings.compress_documents)
+}
+
+pub fn is_binary_path(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some(BINARY_EXTENSION)
+}
+
+/// Whether `bytes` start like a MessagePack map. A JSON document starts
+/// with `{` and text never starts with a continuation byte, so this can't
+/// be mistaken for either; a text file starting with a two-byte character
+/// in 0xde/0xdf simply fails to decode and is read as text.
+fn looks_binary(bytes: &[u8]) -> bool {
+    matches!(bytes.first(), Some(0x80..=0x8f | 0xde | 0xdf))
+}
+
+/// Whether a document starting with `head` has to go through `decode`
+/// before it's text, rather than being read as is.
+pub fn needs_decoding(head: &[u8]) -> bool {
+    head.starts_with(&ZSTD_MAGIC) || looks_binary(head)
+}
+
+/// Turns a document's bytes into its content, decompressing zstd and
+/// decoding MessagePack to JSON text whatever the file is called.
+pub fn decode(bytes: Vec<u8>) -> AppResult<String> {
+    let bytes = if bytes.starts_with(&ZSTD_MAGIC) {
+        zstd::decode_all(bytes.as_slice()).map_err(|e| AppError::io(format!("Failed to decompress document: {}", e)))?
+    } else {
+        bytes
+    };
+    if looks_binary(&bytes) {
+        if let Ok(value) = rmp_serde::from_slice::<Value>(&bytes) {
+            return Ok(value.to_string());
+        }
+    }
+    String::from_utf8(bytes).map_err(|e| AppError::io(format!("Failed to decode document: {}", e)))
+}
+
+/// The bytes to write for `content` at `path`: MessagePack for `.canvasb`
+/// files, the text as is for everything else, zstd-compressed on top if
+/// `compress` is set and the document is big enough.
+pub fn encode(path: &Path, content: &str, compress: bool) -> AppResult<Vec<u8>> {
+    let bytes = if is_binary_path(path) {
+        let value: Value = serde_json::from_str(content)
+            .map_err(|e| AppError::validation(format!("Failed to encode {}: binary canvases must be JSON: {}", path.display(), e)))?;
+        rmp_serde::to_vec(&value).map_err(|e| AppError::io(format!("Failed to encode {}: {}", path.display(), e)))?
+    } else {
+        content.as_bytes().to_vec()
+    };
+    if !compress || bytes.len() < MIN_COMPRESSED_SIZE {
+        return Ok(bytes);
+    }
+    zstd::encode_all(bytes.as_slice(), COMPRESSION_LEVEL).map_err(|e| AppError::io(format!("Failed to compress {}: {}", path.display(), e)))
+}
+
+pub fn read_document(path: &Path) -> AppResult<String> {
+    let bytes = std::fs::read(path).map_err(|e| AppError::io(format!("Failed to read {}: {}", path.display(), e)))?;
+    decode(bytes)
+}
+
+pub async fn read_document_async(path: &Path) -> AppResult<String> {
+    let bytes = tokio::fs::read(path)
+        .await
+        .map_err(|e| AppError::io(format!("Failed to read {}: {}", path.display(), e)))?;
+    decode(bytes)
+}
+
+/// Writes a copy of a canvas next to it in the other format, `.canvas`
+/// <-> `.canvasb`, and returns the copy's path. The original is kept.
+pub fn convert_document(path: &Path, compress: bool) -> AppResult<PathBuf> {
+    let content = read_document(path)?;
+    let dest = if is_binary_path(path) {
+        path.with_extension("canvas")
+    } else {
+        path.with_extension(BINARY_EXTENSION)
+    };
+    if dest.exists() {
+        return Err(AppError::conflict(format!("{} already exists", dest.display())));
+    }
+    let bytes = encode(&dest, &content, compress)?;
+    std::fs::write(&dest, bytes).map_err(|e| AppError::io(format!("Failed to write {}: {}", dest.display(), e)))?;
+    Ok(dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_round_trip() {
+        let dir = std::env::temp_dir().join("test_document_format");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let canvas = dir.join("big.canvas");
+        let content = r#"{"root":{"children":[{"text":"hi","type":"text","version":1}],"type":"root"}}"#;
+        fs::write(&canvas, content).unwrap();
+
+        let binary = convert_document(&canvas, false).unwrap();
+        assert_eq!(binary, dir.join("big.canvasb"));
+        let bytes = fs::read(&binary).unwrap();
+        assert!(looks_binary(&bytes));
+        assert!(bytes.len() < content.len());
+        assert_eq!(read_document(&binary).unwrap(), content);
+        assert!(convert_document(&canvas, false).is_err());
+
+        assert_eq!(decode("# notes".as_bytes().to_vec()).unwrap(), "# notes");
+        assert!(encode(&binary, "# not json", false).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_compression() {
+        let path = Path::new("notes.md");
+        let long = "All work and no play. ".repeat(500);
+        let compressed = encode(path, &long, true).unwrap();
+        assert!(compressed.starts_with(&ZSTD_MAGIC));
+        assert!(compressed.len() < long.len() / 10);
+        assert_eq!(decode(compressed).unwrap(), long);
+
+        // Small documents are left alone
+        assert_eq!(encode(path, "short", true).unwrap(), b"short");
+
+        let canvas = format!(r#"{{"root":{{"children":[],"text":"{}","type":"root"}}}}"#, long);
+        let bytes = encode(Path::new("big.canvasb"), &canvas, true).unwrap();
+        assert!(bytes.starts_with(&ZSTD_MAGIC));
+        assert_eq!(decode(bytes).unwrap(), canvas);
+    }
+}
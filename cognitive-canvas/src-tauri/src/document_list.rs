@@ -0,0 +1,115 @@
+use rayon::prelude::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use crate::ignore_rules::IgnoreRules;
+use crate::{document_format, document_text, document_version, tags, workspace};
+use crate::error::{AppError, AppResult};
+
+/// A row of the file browser: a document, or a folder when listing one
+/// level.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DocumentEntry {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    /// Milliseconds since the epoch, as `save_document` reports it
+    pub modified: u64,
+    /// The first heading, or the file name without extension; nothing for
+    /// folders
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+fn folder_entry(path: &Path) -> DocumentEntry {
+    DocumentEntry {
+        name: file_name(path),
+        path: path.to_string_lossy().to_string(),
+        is_dir: true,
+        size: 0,
+        modified: document_version::modified_millis(path).unwrap_or(0),
+        title: None,
+        tags: Vec::new(),
+    }
+}
+
+/// Documents that can't be read are still listed, with what the
+/// filesystem knows.
+fn document_entry(path: &Path) -> DocumentEntry {
+    let content = document_format::read_document(path).unwrap_or_default();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    DocumentEntry {
+        name: file_name(path),
+        path: path.to_string_lossy().to_string(),
+        is_dir: false,
+        size: std::fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+        modified: document_version::modified_millis(path).unwrap_or(0),
+        title: Some(document_text::title(&content).unwrap_or(stem)),
+        tags: tags::document_tags(path, &document_text::plain_text(&content)),
+    }
+}
+
+/// The documents in `dir` with their titles and tags, read in parallel.
+/// With `recursive`, documents in subfolders are included and folders
+/// aren't listed themselves. What the ignore rules of `dir` and the
+/// `workspace` folders above it exclude is skipped either way.
+pub fn list_documents(dir: &Path, recursive: bool, workspace: Option<&Path>) -> AppResult<Vec<DocumentEntry>> {
+    let mut rules = IgnoreRules::for_dir(workspace, dir);
+    let (folders, documents): (Vec<PathBuf>, Vec<PathBuf>) = if recursive {
+        (Vec::new(), workspace::scan_from(dir, rules, &mut workspace::ScanCache::default(), |_| {})?)
+    } else {
+        let entries = std::fs::read_dir(dir).map_err(|e| AppError::io(format!("Failed to read {}: {}", dir.display(), e)))?;
+        rules.read_dir_files(dir);
+        let (folders, files): (Vec<PathBuf>, Vec<PathBuf>) = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| !rules.is_ignored(path, path.is_dir()))
+            .partition(|path| path.is_dir());
+        (folders, files.into_iter().filter(|path| workspace::is_document(path)).collect())
+    };
+
+    let mut entries: Vec<DocumentEntry> = folders.iter().map(|path| folder_entry(path)).collect();
+    entries.extend(workspace::scan_pool().install(|| documents.par_iter().map(|path| document_entry(path)).collect::<Vec<_>>()));
+    // Folders first, then by name as the file browser shows them
+    entries.sort_by_cached_key(|entry| (!entry.is_dir, entry.path.to_lowercase()));
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_list_documents() {
+        let dir = std::env::temp_dir().join("test_document_list");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("Projects")).unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::write(dir.join("b.md"), "---\ntags: [work]\n---\n# Beta plan\nText").unwrap();
+        fs::write(dir.join("a.txt"), "no heading").unwrap();
+        fs::write(dir.join("image.png"), "").unwrap();
+        fs::write(dir.join(".canvasignore"), "old/\n").unwrap();
+        fs::create_dir_all(dir.join("old")).unwrap();
+        fs::write(dir.join("Projects/c.md"), "# C").unwrap();
+
+        let entries = list_documents(&dir, false, None).unwrap();
+        let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, vec!["Projects", "a.txt", "b.md"]);
+        assert!(entries[0].is_dir && entries[0].title.is_none());
+        assert_eq!(entries[1].title.as_deref(), Some("a"));
+        assert_eq!(entries[2].title.as_deref(), Some("Beta plan"));
+        assert_eq!(entries[2].tags, vec!["work"]);
+        assert!(entries[2].size > 0 && entries[2].modified > 0);
+
+        let recursive = list_documents(&dir, true, None).unwrap();
+        let names: Vec<&str> = recursive.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, vec!["a.txt", "b.md", "c.md"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
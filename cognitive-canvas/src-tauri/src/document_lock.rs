@@ -0,0 +1,176 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::sidecar;
+use crate::error::{AppError, AppResult};
+
+/// Locks are refreshed this often while their document is open...
+pub const REFRESH_INTERVAL_SECS: u64 = 5 * 60;
+
+/// ...so one that wasn't refreshed for this long belongs to an instance
+/// that crashed or a machine that went away.
+const STALE_AFTER_MINUTES: i64 = 15;
+
+/// Who has a document open, kept in a `.lock` sidecar next to it. Locks
+/// are advisory: they only make the next instance warn its user.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentLock {
+    pub pid: u32,
+    pub hostname: String,
+    pub acquired_at: DateTime<Utc>,
+    pub refreshed_at: DateTime<Utc>,
+}
+
+impl DocumentLock {
+    fn new() -> Self {
+        let now = Utc::now();
+        Self { pid: std::process::id(), hostname: hostname(), acquired_at: now, refreshed_at: now }
+    }
+
+    fn is_own(&self) -> bool {
+        self.pid == std::process::id() && self.hostname == hostname()
+    }
+
+    fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        if now - self.refreshed_at > Duration::minutes(STALE_AFTER_MINUTES) {
+            return true;
+        }
+        // A process on this machine that isn't running anymore
+        self.hostname == hostname() && !process_running(self.pid)
+    }
+}
+
+/// Documents this instance holds the lock of.
+#[derive(Default)]
+pub struct DocumentLockState {
+    held: Mutex<HashSet<PathBuf>>,
+}
+
+fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn process_running(pid: u32) -> bool {
+    Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+/// Without a cheap way to ask, another process is assumed alive until its
+/// lock goes stale.
+#[cfg(not(target_os = "linux"))]
+fn process_running(_pid: u32) -> bool {
+    true
+}
+
+fn lock_path(document_path: &Path) -> PathBuf {
+    sidecar::sidecar_path(document_path, "lock")
+}
+
+fn read_lock(document_path: &Path) -> Option<DocumentLock> {
+    let content = std::fs::read_to_string(lock_path(document_path)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Takes the lock on a document being opened. If another live instance
+/// holds it, the lock is left alone and returned so the user can be
+/// warned; stale locks are taken over.
+pub fn acquire(state: &DocumentLockState, document_path: &Path) -> AppResult<Option<DocumentLock>> {
+    if let Some(existing) = read_lock(document_path) {
+        if !existing.is_own() && !existing.is_stale(Utc::now()) {
+            return Ok(Some(existing));
+        }
+    }
+    sidecar::write_json(&lock_path(document_path), &DocumentLock::new())?;
+    state.held.lock()?.insert(document_path.to_path_buf());
+    Ok(None)
+}
+
+/// Removes the lock on a document that was closed, if it's ours.
+pub fn release(state: &DocumentLockState, document_path: &Path) -> AppResult<()> {
+    state.held.lock()?.remove(document_path);
+    if read_lock(document_path).is_some_and(|lock| lock.is_own()) {
+        let path = lock_path(document_path);
+        std::fs::remove_file(&path).map_err(|e| AppError::io(format!("Failed to remove {}: {}", path.display(), e)))?;
+    }
+    Ok(())
+}
+
+pub fn release_all(state: &DocumentLockState) -> AppResult<()> {
+    let held: Vec<PathBuf> = state.held.lock()?.iter().cloned().collect();
+    for path in held {
+        release(state, &path)?;
+    }
+    Ok(())
+}
+
+/// Keeps the locks of open documents fresh. A lock another instance took
+/// over in the meantime is given up rather than fought over.
+pub fn refresh_all(state: &DocumentLockState) -> AppResult<()> {
+    let mut held = state.held.lock()?;
+    held.retain(|path| match read_lock(path) {
+        Some(lock) if !lock.is_own() => false,
+        lock => {
+            let refreshed = DocumentLock {
+                refreshed_at: Utc::now(),
+                ..lock.unwrap_or_else(DocumentLock::new)
+            };
+            if let Err(e) = sidecar::write_json(&lock_path(path), &refreshed) {
+                tracing::warn!("Failed to refresh lock: {}", e);
+            }
+            true
+        }
+    });
+    Ok(())
+}
+
+/// The live lock another instance holds on a document, if any.
+pub fn foreign_lock(document_path: &Path) -> Option<DocumentLock> {
+    read_lock(document_path).filter(|lock| !lock.is_own() && !lock.is_stale(Utc::now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_acquire_and_release() {
+        let dir = std::env::temp_dir().join("test_document_lock");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let doc = dir.join("plan.canvas");
+        let state = DocumentLockState::default();
+
+        assert!(acquire(&state, &doc).unwrap().is_none());
+        assert!(lock_path(&doc).exists());
+        // Opening it again in the same instance isn't a conflict
+        assert!(acquire(&state, &doc).unwrap().is_none());
+
+        let other = DocumentLock { hostname: "elsewhere".to_string(), ..DocumentLock::new() };
+        sidecar::write_json(&lock_path(&doc), &other).unwrap();
+        assert_eq!(acquire(&state, &doc).unwrap(), Some(other.clone()));
+        assert_eq!(foreign_lock(&doc), Some(other.clone()));
+
+        // Someone else's lock is given up on refresh and left in place on release
+        refresh_all(&state).unwrap();
+        assert!(state.held.lock().unwrap().is_empty());
+        release(&state, &doc).unwrap();
+        assert!(lock_path(&doc).exists());
+
+        let abandoned = DocumentLock { refreshed_at: Utc::now() - Duration::hours(1), ..other };
+        sidecar::write_json(&lock_path(&doc), &abandoned).unwrap();
+        assert!(acquire(&state, &doc).unwrap().is_none());
+        release_all(&state).unwrap();
+        assert!(!lock_path(&doc).exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,150 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::SystemTime;
+use crate::sidecar;
+use crate::error::{AppError, AppResult};
+
+/// What's kept in the `.meta.json` sidecar. Timestamps normally come from
+/// the filesystem; `created` is only stored for filesystems without a
+/// creation time.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct StoredMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created: Option<DateTime<Utc>>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    fields: BTreeMap<String, Value>,
+}
+
+/// User-facing metadata of a document.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentMetadata {
+    #[serde(default)]
+    pub created: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub modified: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Custom fields, e.g. "status" or "project"
+    #[serde(default)]
+    pub fields: BTreeMap<String, Value>,
+}
+
+/// Changes to apply with `update_document_metadata`. Tags are replaced as
+/// a whole; fields are merged, and a field set to null is removed.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MetadataPatch {
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub fields: BTreeMap<String, Value>,
+}
+
+fn sidecar_path(document_path: &Path) -> std::path::PathBuf {
+    sidecar::sidecar_path(document_path, "meta")
+}
+
+fn timestamp(time: std::io::Result<SystemTime>) -> Option<DateTime<Utc>> {
+    time.ok().map(DateTime::<Utc>::from)
+}
+
+/// Trims tags and drops empty and repeated ones, keeping the first
+/// spelling of each.
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().trim_start_matches('#');
+        if !tag.is_empty() && !normalized.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            normalized.push(tag.to_string());
+        }
+    }
+    normalized
+}
+
+pub fn load(document_path: &Path) -> AppResult<DocumentMetadata> {
+    let stored: StoredMetadata = sidecar::read_json(&sidecar_path(document_path))?;
+    let file = std::fs::metadata(document_path).ok();
+    let modified = file.as_ref().and_then(|m| timestamp(m.modified()));
+    let created = file
+        .as_ref()
+        .and_then(|m| timestamp(m.created()))
+        .or(stored.created)
+        .or(modified);
+
+    Ok(DocumentMetadata {
+        created,
+        modified,
+        tags: stored.tags,
+        fields: stored.fields,
+    })
+}
+
+/// Applies `patch` and returns the updated metadata. The document itself
+/// isn't touched, so its mtime stays as it was.
+pub fn update(document_path: &Path, patch: MetadataPatch) -> AppResult<DocumentMetadata> {
+    if !document_path.exists() {
+        return Err(AppError::not_found(format!("Document not found: {}", document_path.display())));
+    }
+    let path = sidecar_path(document_path);
+    let mut stored: StoredMetadata = sidecar::read_json(&path)?;
+
+    if let Some(tags) = patch.tags {
+        stored.tags = normalize_tags(&tags);
+    }
+    for (key, value) in patch.fields {
+        let key = key.trim().to_string();
+        if key.is_empty() {
+            return Err(AppError::validation("Field names can't be empty"));
+        }
+        if value.is_null() {
+            stored.fields.remove(&key);
+        } else {
+            stored.fields.insert(key, value);
+        }
+    }
+    if stored.created.is_none() {
+        let file = std::fs::metadata(document_path).map_err(|e| AppError::io(format!("Failed to read {}: {}", document_path.display(), e)))?;
+        if file.created().is_err() {
+            stored.created = timestamp(file.modified());
+        }
+    }
+
+    sidecar::write_json(&path, &stored)?;
+    load(document_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_update_metadata() {
+        let dir = std::env::temp_dir().join("test_document_metadata");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let doc = dir.join("plan.canvas");
+        fs::write(&doc, "{}").unwrap();
+
+        let metadata = load(&doc).unwrap();
+        assert!(metadata.modified.is_some() && metadata.created.is_some());
+        assert!(metadata.tags.is_empty());
+
+        let patch: MetadataPatch = serde_json::from_str(r##"{"tags":[" work","#Work","q3",""],"fields":{"status":"draft","priority":2}}"##).unwrap();
+        let metadata = update(&doc, patch).unwrap();
+        assert_eq!(metadata.tags, vec!["work", "q3"]);
+        assert_eq!(metadata.fields["priority"], 2);
+
+        let patch: MetadataPatch = serde_json::from_str(r#"{"fields":{"status":null}}"#).unwrap();
+        let metadata = update(&doc, patch).unwrap();
+        assert_eq!(metadata.tags, vec!["work", "q3"]);
+        assert!(!metadata.fields.contains_key("status"));
+        assert!(update(&dir.join("missing.canvas"), MetadataPatch::default()).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
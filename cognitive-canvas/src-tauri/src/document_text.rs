@@ -0,0 +1,314 @@
+use serde::Serialize;
+use serde_json::Value;
+
+/// Returns the readable text of a document. Canvas files hold serialized
+/// Lexical editor state, so block-level nodes become lines; anything that
+/// isn't Lexical JSON (Markdown, plain text) is returned unchanged.
+pub fn plain_text(content: &str) -> String {
+    let value: Value = match serde_json::from_str(content) {
+        Ok(value) => value,
+        Err(_) => return content.to_string(),
+    };
+
+    let root = match value.get("root") {
+        Some(root) => root,
+        None => return content.to_string(),
+    };
+
+    let mut lines = Vec::new();
+    collect_blocks(root, &mut lines);
+    lines.join("\n")
+}
+
+/// Counts the words in a document's readable text.
+pub fn word_count(content: &str) -> usize {
+    plain_text(content).split_whitespace().count()
+}
+
+fn collect_blocks(node: &Value, lines: &mut Vec<String>) {
+    let children = match node.get("children").and_then(|c| c.as_array()) {
+        Some(children) => children,
+        None => return,
+    };
+
+    for child in children {
+        let has_block_children = child
+            .get("children")
+            .and_then(|c| c.as_array())
+            .is_some_and(|c| c.iter().any(is_block));
+
+        if has_block_children {
+            collect_blocks(child, lines);
+        } else {
+            let mut line = String::new();
+            collect_inline(child, &mut line);
+            lines.push(line);
+        }
+    }
+}
+
+fn collect_inline(node: &Value, out: &mut String) {
+    if let Some(text) = node.get("text").and_then(|t| t.as_str()) {
+        out.push_str(text);
+    }
+    if node.get("type").and_then(|t| t.as_str()) == Some("linebreak") {
+        out.push('\n');
+    }
+    if let Some(children) = node.get("children").and_then(|c| c.as_array()) {
+        for child in children {
+            collect_inline(child, out);
+        }
+    }
+}
+
+fn is_block(node: &Value) -> bool {
+    matches!(
+        node.get("type").and_then(|t| t.as_str()),
+        Some("paragraph" | "heading" | "quote" | "list" | "listitem" | "code" | "table" | "tablerow" | "tablecell")
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OutlineEntry {
+    pub level: u8,
+    pub title: String,
+}
+
+/// Lists the headings of a document: Lexical heading nodes for canvas
+/// files, `#` lines for Markdown.
+pub fn outline(content: &str) -> Vec<OutlineEntry> {
+    let root = serde_json::from_str::<Value>(content)
+        .ok()
+        .and_then(|value| value.get("root").cloned());
+
+    let mut entries = Vec::new();
+    match root {
+        Some(root) => collect_headings(&root, &mut entries),
+        None => {
+            for line in content.lines() {
+                let hashes = line.chars().take_while(|c| *c == '#').count();
+                if (1..=6).contains(&hashes) && line[hashes..].starts_with(' ') {
+                    entries.push(OutlineEntry {
+                        level: hashes as u8,
+                        title: line[hashes..].trim().to_string(),
+                    });
+                }
+            }
+        }
+    }
+    entries
+}
+
+fn collect_headings(node: &Value, entries: &mut Vec<OutlineEntry>) {
+    for child in node.get("children").and_then(|c| c.as_array()).into_iter().flatten() {
+        if child.get("type").and_then(|t| t.as_str()) == Some("heading") {
+            let level = child
+                .get("tag")
+                .and_then(|t| t.as_str())
+                .and_then(|tag| tag.strip_prefix('h'))
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(1);
+            let mut title = String::new();
+            collect_inline(child, &mut title);
+            entries.push(OutlineEntry { level, title: title.trim().to_string() });
+        } else {
+            collect_headings(child, entries);
+        }
+    }
+}
+
+/// The document's first top-level heading, if it has one.
+pub fn title(content: &str) -> Option<String> {
+    outline(content).into_iter().find(|entry| entry.level == 1).map(|entry| entry.title)
+}
+
+/// Lists link targets in a document: Lexical link nodes, Markdown
+/// `[text](target)` links and `[[wiki links]]`, in order of appearance and
+/// without duplicates.
+pub fn links(content: &str) -> Vec<String> {
+    let mut found = Vec::new();
+
+    if let Some(root) = serde_json::from_str::<Value>(content).ok().and_then(|v| v.get("root").cloned()) {
+        collect_link_nodes(&root, &mut found);
+    }
+
+    let text = plain_text(content);
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find('[') {
+        rest = &rest[start..];
+        if let Some(inner) = rest.strip_prefix("[[") {
+            if let Some(end) = inner.find("]]") {
+                // [[Target|Alias]] links to Target
+                let target = inner[..end].split('|').next().unwrap_or("").trim();
+                found.push(target.to_string());
+                rest = &inner[end + 2..];
+                continue;
+            }
+        } else if let Some(close) = rest.find("](") {
+            let after = &rest[close + 2..];
+            if !rest[1..close].contains('[') {
+                if let Some(end) = after.find(')') {
+                    // [text](<target with spaces>)
+                    let target = after[..end].trim();
+                    let target = target.strip_prefix('<').and_then(|t| t.strip_suffix('>')).unwrap_or(target);
+                    found.push(target.to_string());
+                    rest = &after[end + 1..];
+                    continue;
+                }
+            }
+        }
+        rest = &rest[1..];
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    found.retain(|link| !link.is_empty() && seen.insert(link.clone()));
+    found
+}
+
+fn collect_link_nodes(node: &Value, found: &mut Vec<String>) {
+    for child in node.get("children").and_then(|c| c.as_array()).into_iter().flatten() {
+        if matches!(child.get("type").and_then(|t| t.as_str()), Some("link" | "autolink")) {
+            if let Some(url) = child.get("url").and_then(|u| u.as_str()) {
+                found.push(url.to_string());
+            }
+        }
+        collect_link_nodes(child, found);
+    }
+}
+
+/// Splits a leading `---` YAML-style frontmatter block into `key: value`
+/// pairs. Only flat scalar entries are understood.
+pub fn frontmatter(text: &str) -> Vec<(String, String)> {
+    let body = match text.strip_prefix("---\n").or_else(|| text.strip_prefix("---\r\n")) {
+        Some(body) => body,
+        None => return Vec::new(),
+    };
+
+    body.lines()
+        .take_while(|line| line.trim() != "---")
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            let value = value.trim().trim_matches('"').trim_matches('\'');
+            Some((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Tags from the frontmatter `tags` field, written as `a, b` or `[a, b]`.
+pub fn tags(text: &str) -> Vec<String> {
+    frontmatter(text)
+        .into_iter()
+        .find(|(key, _)| key == "tags")
+        .map(|(_, value)| {
+            value
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .split(',')
+                .map(|tag| tag.trim().trim_matches('"').trim_matches('\'').to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Splits text into pieces of at most `max_chars` characters, breaking
+/// between lines. Blank lines are dropped and a line too long for one
+/// piece is cut.
+pub fn chunk_text(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let line_chars = line.chars().count();
+        if current_chars > 0 && current_chars + 1 + line_chars > max_chars {
+            chunks.push(std::mem::take(&mut current));
+            current_chars = 0;
+        }
+        if current_chars > 0 {
+            current.push('\n');
+            current_chars += 1;
+        }
+        current.push_str(line);
+        current_chars += line_chars;
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+        .into_iter()
+        .flat_map(|chunk| {
+            let chars: Vec<char> = chunk.chars().collect();
+            chars.chunks(max_chars).map(|part| part.iter().collect::<String>()).collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Smallest single replacement turning `old` into `new`, as
+/// (byte offset, bytes removed, inserted text). Offsets always land on
+/// char boundaries.
+pub fn changed_range<'a>(old: &str, new: &'a str) -> Option<(usize, usize, &'a str)> {
+    if old == new {
+        return None;
+    }
+
+    let prefix = old
+        .char_indices()
+        .zip(new.chars())
+        .find(|((_, a), b)| a != b)
+        .map(|((i, _), _)| i)
+        .unwrap_or_else(|| old.len().min(new.len()));
+
+    let old_rest = &old[prefix..];
+    let new_rest = &new[prefix..];
+    let suffix = old_rest
+        .chars()
+        .rev()
+        .zip(new_rest.chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum::<usize>();
+
+    Some((
+        prefix,
+        old_rest.len() - suffix,
+        &new_rest[..new_rest.len() - suffix],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_range_respects_char_boundaries() {
+        assert_eq!(changed_range("café", "cafés"), Some((5, 0, "s")));
+        assert_eq!(changed_range("naïve", "naive"), Some((2, 2, "i")));
+        assert_eq!(changed_range("same", "same"), None);
+    }
+
+    #[test]
+    fn test_chunk_text() {
+        assert!(chunk_text("\n  \n", 100).is_empty());
+        assert_eq!(chunk_text("Intro\n\nBody", 100), vec!["Intro\nBody"]);
+
+        // Counted in chars, so multi-byte text splits like ASCII
+        let line = "é".repeat(40);
+        let text = vec![line.as_str(); 7].join("\n");
+        let chunks = chunk_text(&text, 120);
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks.join("\n"), text);
+
+        let chunks = chunk_text(&format!("short\n{}", "x".repeat(250)), 100);
+        assert_eq!(chunks.len(), 4);
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 100));
+    }
+
+    #[test]
+    fn test_links() {
+        let markdown = "See [[Project Plan|the plan]], [docs](https://example.com/a) and [[Project Plan]].\n[not a link] [x](<my notes.md>) [y](z";
+        assert_eq!(links(markdown), vec!["Project Plan", "https://example.com/a", "my notes.md"]);
+
+        let lexical = r#"{"root":{"children":[{"type":"paragraph","children":[{"type":"link","url":"notes/b.canvas","children":[{"type":"text","text":"B"}]}]}]}}"#;
+        assert_eq!(links(lexical), vec!["notes/b.canvas"]);
+    }
+}
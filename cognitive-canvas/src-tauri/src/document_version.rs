@@ -0,0 +1,113 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SavedDocument {
+    pub file_path: String,
+    pub modified: u64,
+    pub content_hash: String,
+}
+
+pub fn content_hash(content: &str) -> String {
+    hex::encode(Sha256::digest(content.as_bytes()))
+}
+
+pub fn modified_millis(path: &Path) -> Option<u64> {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+}
+
+/// Compares the file on disk against the version the caller last saw.
+/// A matching mtime short-circuits; otherwise the content hash decides, so
+/// a touch without changes isn't reported as a conflict. Conflicts carry
+/// both versions so the frontend can offer a merge instead of a bare
+/// failure message.
+pub async fn check_for_conflict(
+    path: &Path,
+    expected_modified: Option<u64>,
+    expected_hash: Option<&str>,
+    local_content: &str,
+) -> AppResult<()> {
+    if expected_modified.is_none() && expected_hash.is_none() {
+        return Ok(());
+    }
+
+    let disk_modified = match modified_millis(path) {
+        Some(modified) => modified,
+        // Deleted since load; writing it back can't clobber anything
+        None => return Ok(()),
+    };
+
+    if expected_modified == Some(disk_modified) {
+        return Ok(());
+    }
+
+    let disk_content = crate::document_format::read_document_async(path)
+        .await
+        .map_err(|e| AppError::io(format!("Failed to read document: {}", e)))?;
+    let disk_hash = content_hash(&disk_content);
+
+    if expected_hash == Some(disk_hash.as_str()) {
+        return Ok(());
+    }
+
+    Err(AppError::conflict(format!("{} changed on disk since it was opened", path.display()))
+        .at(path)
+        .with_details(serde_json::json!({
+            "disk_content": disk_content,
+            "disk_modified": disk_modified,
+            "disk_hash": disk_hash,
+            "local_content": local_content,
+        })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_check_for_conflict() {
+        let dir = std::env::temp_dir().join("test_document_version_conflict");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("plan.md");
+        fs::write(&path, "# Plan").unwrap();
+        let loaded_hash = content_hash("# Plan");
+        let check = |modified: Option<u64>, hash: Option<&str>| {
+            tauri::async_runtime::block_on(check_for_conflict(&path, modified, hash, "# Plan, edited"))
+        };
+
+        // Nothing to compare against
+        assert!(check(None, None).is_ok());
+
+        // A matching mtime is trusted without reading the file
+        let disk_modified = modified_millis(&path).unwrap();
+        assert!(check(Some(disk_modified), Some("not the hash")).is_ok());
+
+        // Touched since, but the content is the same
+        assert!(check(Some(0), Some(&loaded_hash)).is_ok());
+
+        // Edited by something else
+        fs::write(&path, "# Plan, changed elsewhere").unwrap();
+        let Err(AppError::Conflict(context)) = check(Some(0), Some(&loaded_hash)) else {
+            panic!("expected a conflict");
+        };
+        assert_eq!(context.path.as_deref(), Some(path.to_string_lossy().as_ref()));
+        let details = context.details.unwrap();
+        assert_eq!(details["disk_content"], "# Plan, changed elsewhere");
+        assert_eq!(details["local_content"], "# Plan, edited");
+        assert_eq!(details["disk_hash"], content_hash("# Plan, changed elsewhere"));
+
+        // Deleted since it was loaded: saving recreates it
+        fs::remove_file(&path).unwrap();
+        assert!(check(Some(disk_modified), Some(&loaded_hash)).is_ok());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
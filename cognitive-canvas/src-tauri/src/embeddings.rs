@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::{ai, document_text, document_version, sidecar};
+use crate::error::{AppError, AppResult};
+
+/// Roughly a few paragraphs; long documents are embedded in pieces so a
+/// passage deep inside one can still be found.
+const CHUNK_CHARS: usize = 1500;
+/// Past this, the rest of a document isn't embedded.
+const MAX_CHUNKS: usize = 64;
+const SNIPPET_CHARS: usize = 160;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EmbeddedChunk {
+    snippet: String,
+    /// Normalized, so the dot product of two is their cosine similarity
+    vector: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EmbeddedDocument {
+    /// Of the content that was embedded, to skip saves that changed nothing
+    content_hash: String,
+    chunks: Vec<EmbeddedChunk>,
+}
+
+/// Vectors are only comparable when made by the same model, so the index
+/// starts over when the model changes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct EmbeddingIndex {
+    model: String,
+    documents: BTreeMap<String, EmbeddedDocument>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SemanticMatch {
+    pub path: String,
+    /// Cosine similarity of the best matching part, up to 1.0
+    pub score: f32,
+    /// The start of that part
+    pub snippet: String,
+}
+
+/// Taken only once a document's vectors are back from the model, so slow
+/// embedding requests don't block each other; updates to embeddings.json
+/// happen one at a time under it.
+#[derive(Default)]
+pub struct EmbeddingState {
+    lock: Mutex<()>,
+}
+
+fn get_index_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir.join("embeddings.json"))
+}
+
+fn normalize(vector: &mut [f32]) {
+    let length = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if length > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= length);
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+impl EmbeddingIndex {
+    /// The `k` documents with the part most similar to `query`, which must
+    /// be normalized.
+    fn nearest(&self, query: &[f32], k: usize) -> Vec<SemanticMatch> {
+        let mut matches: Vec<SemanticMatch> = self
+            .documents
+            .iter()
+            .filter_map(|(path, document)| {
+                let best = document
+                    .chunks
+                    .iter()
+                    .map(|chunk| (dot(&chunk.vector, query), chunk))
+                    .max_by(|a, b| a.0.total_cmp(&b.0))?;
+                Some(SemanticMatch { path: path.clone(), score: best.0, snippet: best.1.snippet.clone() })
+            })
+            .collect();
+        matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+        matches.truncate(k);
+        matches
+    }
+}
+
+/// Embeds a document that was just saved, when semantic indexing is on.
+/// Unchanged documents aren't sent again.
+pub async fn index_document(app_handle: &AppHandle, path: &Path, content: &str) -> AppResult<()> {
+    let config = ai::load_ai_config(app_handle)?;
+    if !config.semantic_index {
+        return Ok(());
+    }
+    let model = ai::embedding_model(&config)?;
+    let key = path.to_string_lossy().to_string();
+    let content_hash = document_version::content_hash(content);
+
+    let index_path = get_index_path(app_handle)?;
+    let index: EmbeddingIndex = sidecar::read_json(&index_path)?;
+    if index.model == model && index.documents.get(&key).is_some_and(|d| d.content_hash == content_hash) {
+        return Ok(());
+    }
+
+    let texts: Vec<String> = document_text::chunk_text(&document_text::plain_text(content), CHUNK_CHARS)
+        .into_iter()
+        .take(MAX_CHUNKS)
+        .collect();
+    let vectors = if texts.is_empty() { Vec::new() } else { ai::embed(&config, &texts).await? };
+    let chunks: Vec<EmbeddedChunk> = texts
+        .iter()
+        .zip(vectors)
+        .map(|(text, mut vector)| {
+            normalize(&mut vector);
+            EmbeddedChunk { snippet: text.chars().take(SNIPPET_CHARS).collect(), vector }
+        })
+        .collect();
+
+    // Read again, as other saves may have finished while this one was embedded
+    let state = app_handle.state::<EmbeddingState>();
+    let _guard = state.lock.lock()?;
+    let mut index: EmbeddingIndex = sidecar::read_json(&index_path)?;
+    if index.model != model {
+        index = EmbeddingIndex { model, documents: BTreeMap::new() };
+    }
+    if chunks.is_empty() {
+        index.documents.remove(&key);
+    } else {
+        index.documents.insert(key, EmbeddedDocument { content_hash, chunks });
+    }
+    sidecar::write_json(&index_path, &index)
+}
+
+/// Keeps a renamed document's vectors, as its content didn't change.
+pub fn rename_document(app_handle: &AppHandle, old_path: &Path, new_path: &Path) -> AppResult<()> {
+    let state = app_handle.state::<EmbeddingState>();
+    let _guard = state.lock.lock()?;
+    let index_path = get_index_path(app_handle)?;
+    let mut index: EmbeddingIndex = sidecar::read_json(&index_path)?;
+    if let Some(document) = index.documents.remove(old_path.to_string_lossy().as_ref()) {
+        index.documents.insert(new_path.to_string_lossy().to_string(), document);
+        sidecar::write_json(&index_path, &index)?;
+    }
+    Ok(())
+}
+
+/// The `k` saved documents closest in meaning to `query`. Only documents
+/// saved since semantic indexing was turned on are found.
+pub async fn semantic_search(app_handle: &AppHandle, query: &str, k: usize) -> AppResult<Vec<SemanticMatch>> {
+    let config = ai::load_ai_config(app_handle)?;
+    let model = ai::embedding_model(&config)?;
+    let mut index: EmbeddingIndex = sidecar::read_json(&get_index_path(app_handle)?)?;
+    if index.model != model || index.documents.is_empty() || query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    index.documents.retain(|path, _| Path::new(path).exists());
+
+    let mut query = ai::embed(&config, &[query.to_string()])
+        .await?
+        .pop()
+        .ok_or_else(|| AppError::not_found("No embedding for the query"))?;
+    normalize(&mut query);
+    Ok(index.nearest(&query, k))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nearest() {
+        let chunk = |snippet: &str, mut vector: Vec<f32>| {
+            normalize(&mut vector);
+            EmbeddedChunk { snippet: snippet.to_string(), vector }
+        };
+        let mut index = EmbeddingIndex { model: "test".to_string(), documents: BTreeMap::new() };
+        index.documents.insert(
+            "cooking.md".to_string(),
+            EmbeddedDocument { content_hash: String::new(), chunks: vec![chunk("Pasta", vec![1.0, 0.0]), chunk("Knives", vec![0.6, 0.8])] },
+        );
+        index.documents.insert(
+            "travel.md".to_string(),
+            EmbeddedDocument { content_hash: String::new(), chunks: vec![chunk("Flights", vec![0.0, 1.0])] },
+        );
+
+        let mut query = vec![0.1, 1.0];
+        normalize(&mut query);
+        let matches = index.nearest(&query, 5);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].path, "travel.md");
+        // A document is as close as its best part
+        assert_eq!(matches[1].snippet, "Knives");
+        assert_eq!(index.nearest(&query, 1).len(), 1);
+    }
+}
@@ -0,0 +1,192 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::path::Path;
+
+/// What every error carries besides its code. `message` is English for
+/// logs and as a fallback; the frontend localizes by `code` and the
+/// context fields.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ErrorContext {
+    pub message: String,
+    /// The file or folder the error is about
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Structured extras, like both versions of a conflicting save
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+/// The error every command returns. Serializes as
+/// `{ "code": "not_found", "message": "...", "path": "..." }`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum AppError {
+    /// Reading or writing a file failed
+    Io(ErrorContext),
+    /// A document, record or name that doesn't exist
+    NotFound(ErrorContext),
+    /// Something else got there first: a newer version on disk, a name
+    /// that's taken, a lock held elsewhere
+    Conflict(ErrorContext),
+    /// Input that's malformed, empty or out of range
+    Validation(ErrorContext),
+    /// A settings or .conf file that's missing something or wrong
+    Config(ErrorContext),
+    /// A server didn't answer, or answered with an error
+    Network(ErrorContext),
+    /// A helper program like git or tesseract is missing or failed
+    External(ErrorContext),
+    /// Not available on this platform or for this kind of file
+    Unsupported(ErrorContext),
+    /// Stopped by `cancel_operation` before it finished
+    Cancelled(ErrorContext),
+    /// A bug or an unexpected state, like a poisoned lock
+    Internal(ErrorContext),
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+fn context(message: impl Into<String>) -> ErrorContext {
+    ErrorContext { message: message.into(), ..ErrorContext::default() }
+}
+
+impl AppError {
+    pub fn io(message: impl Into<String>) -> Self {
+        AppError::Io(context(message))
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        AppError::NotFound(context(message))
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        AppError::Conflict(context(message))
+    }
+
+    pub fn validation(message: impl Into<String>) -> Self {
+        AppError::Validation(context(message))
+    }
+
+    pub fn config(message: impl Into<String>) -> Self {
+        AppError::Config(context(message))
+    }
+
+    pub fn network(message: impl Into<String>) -> Self {
+        AppError::Network(context(message))
+    }
+
+    pub fn external(message: impl Into<String>) -> Self {
+        AppError::External(context(message))
+    }
+
+    pub fn unsupported(message: impl Into<String>) -> Self {
+        AppError::Unsupported(context(message))
+    }
+
+    pub fn cancelled(message: impl Into<String>) -> Self {
+        AppError::Cancelled(context(message))
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        AppError::Internal(context(message))
+    }
+
+    fn context(&self) -> &ErrorContext {
+        match self {
+            AppError::Io(context)
+            | AppError::NotFound(context)
+            | AppError::Conflict(context)
+            | AppError::Validation(context)
+            | AppError::Config(context)
+            | AppError::Network(context)
+            | AppError::External(context)
+            | AppError::Unsupported(context)
+            | AppError::Cancelled(context)
+            | AppError::Internal(context) => context,
+        }
+    }
+
+    fn context_mut(&mut self) -> &mut ErrorContext {
+        match self {
+            AppError::Io(context)
+            | AppError::NotFound(context)
+            | AppError::Conflict(context)
+            | AppError::Validation(context)
+            | AppError::Config(context)
+            | AppError::Network(context)
+            | AppError::External(context)
+            | AppError::Unsupported(context)
+            | AppError::Cancelled(context)
+            | AppError::Internal(context) => context,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.context().message
+    }
+
+    /// Names the file or folder the error is about.
+    pub fn at(mut self, path: impl AsRef<Path>) -> Self {
+        self.context_mut().path = Some(path.as_ref().to_string_lossy().to_string());
+        self
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.context_mut().details = Some(details);
+        self
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<std::io::Error> for AppError {
+    fn from(error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::NotFound => AppError::not_found(error.to_string()),
+            _ => AppError::io(error.to_string()),
+        }
+    }
+}
+
+/// A lock is only poisoned after a panic while it was held.
+impl<T> From<std::sync::PoisonError<T>> for AppError {
+    fn from(error: std::sync::PoisonError<T>) -> Self {
+        AppError::internal(error.to_string())
+    }
+}
+
+/// Window and event calls, which fail only when the window is gone or the
+/// platform refuses.
+impl From<tauri::Error> for AppError {
+    fn from(error: tauri::Error) -> Self {
+        AppError::internal(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize() {
+        let error = AppError::not_found("No template named Weekly").at("/templates/Weekly.md");
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({ "code": "not_found", "message": "No template named Weekly", "path": "/templates/Weekly.md" })
+        );
+
+        let error = AppError::validation("Bad nodes").with_details(serde_json::json!({ "errors": [] }));
+        assert_eq!(serde_json::to_value(&error).unwrap()["details"]["errors"], serde_json::json!([]));
+        assert_eq!(error.to_string(), "Bad nodes");
+
+        let missing = std::io::Error::new(std::io::ErrorKind::NotFound, "gone");
+        assert!(matches!(AppError::from(missing), AppError::NotFound(_)));
+    }
+}
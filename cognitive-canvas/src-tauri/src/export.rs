@@ -0,0 +1,663 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use crate::comments::{self, CommentThread};
+use crate::export_targets::ExportTransformer;
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Pdf,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum BlockKind {
+    Paragraph,
+    Heading(u8),
+    Quote,
+    ListItem { ordered: bool, number: usize },
+    Code,
+}
+
+#[derive(Debug, Clone)]
+struct Block {
+    kind: BlockKind,
+    text: String,
+    /// Identifiers a comment thread's `node_id` may refer to
+    anchors: Vec<String>,
+}
+
+/// Flattens Lexical editor state into top-level blocks. Plain-text
+/// documents become one paragraph per non-empty line.
+fn parse_blocks(content: &str) -> Vec<Block> {
+    let root = serde_json::from_str::<Value>(content)
+        .ok()
+        .and_then(|value| value.get("root").cloned());
+
+    let root = match root {
+        Some(root) => root,
+        None => {
+            return content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .enumerate()
+                .map(|(i, line)| Block {
+                    kind: BlockKind::Paragraph,
+                    text: line.to_string(),
+                    anchors: vec![i.to_string()],
+                })
+                .collect()
+        }
+    };
+
+    let mut blocks = Vec::new();
+    for node in root.get("children").and_then(|c| c.as_array()).into_iter().flatten() {
+        let node_type = node.get("type").and_then(|t| t.as_str()).unwrap_or("paragraph");
+
+        if node_type == "list" {
+            let ordered = node.get("listType").and_then(|t| t.as_str()) == Some("number");
+            let items = node.get("children").and_then(|c| c.as_array()).into_iter().flatten();
+            for (i, item) in items.enumerate() {
+                blocks.push(Block {
+                    kind: BlockKind::ListItem { ordered, number: i + 1 },
+                    text: inline_text(item),
+                    anchors: node_anchors(item, blocks.len()),
+                });
+            }
+            continue;
+        }
+
+        let kind = match node_type {
+            "heading" => {
+                let level = node
+                    .get("tag")
+                    .and_then(|t| t.as_str())
+                    .and_then(|t| t.trim_start_matches('h').parse().ok())
+                    .unwrap_or(1);
+                BlockKind::Heading(level)
+            }
+            "quote" => BlockKind::Quote,
+            "code" => BlockKind::Code,
+            _ => BlockKind::Paragraph,
+        };
+
+        blocks.push(Block {
+            kind,
+            text: inline_text(node),
+            anchors: node_anchors(node, blocks.len()),
+        });
+    }
+
+    blocks
+}
+
+fn node_anchors(node: &Value, index: usize) -> Vec<String> {
+    let mut anchors = vec![index.to_string()];
+    for key in ["id", "key", "__key"] {
+        if let Some(id) = node.get(key).and_then(|v| v.as_str()) {
+            anchors.push(id.to_string());
+        }
+    }
+    anchors
+}
+
+fn inline_text(node: &Value) -> String {
+    let mut text = String::new();
+    if let Some(value) = node.get("text").and_then(|t| t.as_str()) {
+        text.push_str(value);
+    }
+    if node.get("type").and_then(|t| t.as_str()) == Some("linebreak") {
+        text.push('\n');
+    }
+    for child in node.get("children").and_then(|c| c.as_array()).into_iter().flatten() {
+        text.push_str(&inline_text(child));
+    }
+    text
+}
+
+/// Pairs each block with the comment threads anchored to it. Threads whose
+/// node can't be found are returned separately.
+fn attach_threads(blocks: &[Block], threads: Vec<CommentThread>) -> (Vec<Vec<CommentThread>>, Vec<CommentThread>) {
+    let mut attached = vec![Vec::new(); blocks.len()];
+    let mut unanchored = Vec::new();
+
+    for thread in threads {
+        match blocks.iter().position(|b| b.anchors.contains(&thread.node_id)) {
+            Some(index) => attached[index].push(thread),
+            None => unanchored.push(thread),
+        }
+    }
+
+    (attached, unanchored)
+}
+
+fn thread_summary(thread: &CommentThread) -> Vec<String> {
+    let mut lines: Vec<String> = thread
+        .comments
+        .iter()
+        .map(|c| format!("{} ({}): {}", c.author, c.created_at.format("%Y-%m-%d %H:%M"), c.body))
+        .collect();
+    if thread.resolved {
+        if let Some(first) = lines.first_mut() {
+            first.push_str(" [resolved]");
+        }
+    }
+    lines
+}
+
+/// Renders the document with its comment threads and writes it to `dest`.
+/// A custom target's transformer, if given, runs last on the rendered output.
+/// `on_stage` is called with `(done, total, stage)` before each stage and
+/// can stop the export by returning an error; nothing is left at `dest` then.
+pub fn export_with_comments<F>(
+    document_path: &Path,
+    content: &str,
+    dest: &Path,
+    format: ExportFormat,
+    transformer: Option<&dyn ExportTransformer>,
+    mut on_stage: F,
+) -> AppResult<()>
+where
+    F: FnMut(usize, usize, &str) -> AppResult<()>,
+{
+    const STAGES: usize = 3;
+    let title = document_path
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or("Untitled")
+        .to_string();
+
+    on_stage(0, STAGES, "Rendering")?;
+    let blocks = parse_blocks(content);
+    let (attached, unanchored) = attach_threads(&blocks, comments::list_comments(document_path)?);
+
+    let output = match format {
+        ExportFormat::Markdown => render_markdown(&blocks, &attached, &unanchored).into_bytes(),
+        ExportFormat::Html => render_html(&title, &blocks, &attached, &unanchored).into_bytes(),
+        ExportFormat::Pdf => render_pdf(&title, &blocks, &attached, &unanchored),
+    };
+    let output = match transformer {
+        Some(transformer) => {
+            on_stage(1, STAGES, transformer.label())?;
+            transformer
+                .transform(&title, output)
+                .map_err(|e| AppError::external(format!("Export target {} failed: {}", transformer.id(), e)))?
+        }
+        None => output,
+    };
+
+    // Written next to `dest` and renamed when done, so a cancelled or
+    // failed export doesn't leave a broken file behind
+    on_stage(2, STAGES, "Writing")?;
+    let file_name = dest.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| "export".to_string());
+    let partial = dest.with_file_name(format!("{}.part", file_name));
+    let result = std::fs::write(&partial, output)
+        .map_err(|e| AppError::io(format!("Failed to write export: {}", e)))
+        .and_then(|_| on_stage(STAGES, STAGES, ""));
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e);
+    }
+    std::fs::rename(&partial, dest).map_err(|e| AppError::io(format!("Failed to save {}: {}", dest.display(), e)))
+}
+
+fn render_markdown(blocks: &[Block], attached: &[Vec<CommentThread>], unanchored: &[CommentThread]) -> String {
+    let mut out = String::new();
+    let mut footnotes = Vec::new();
+
+    let mut previous_was_list = false;
+
+    for (block, threads) in blocks.iter().zip(attached) {
+        let is_list = matches!(block.kind, BlockKind::ListItem { .. });
+        if previous_was_list && !is_list {
+            out.push('\n');
+        }
+        previous_was_list = is_list;
+
+        let mut line = match &block.kind {
+            BlockKind::Heading(level) => format!("{} {}", "#".repeat(*level as usize), block.text),
+            BlockKind::Quote => format!("> {}", block.text.replace('\n', "\n> ")),
+            BlockKind::ListItem { ordered: true, number } => format!("{}. {}", number, block.text),
+            BlockKind::ListItem { ordered: false, .. } => format!("- {}", block.text),
+            BlockKind::Code => format!("```\n{}\n```", block.text),
+            BlockKind::Paragraph => block.text.clone(),
+        };
+
+        for thread in threads {
+            footnotes.push(thread);
+            line.push_str(&format!("[^{}]", footnotes.len()));
+        }
+
+        out.push_str(&line);
+        out.push_str(if is_list { "\n" } else { "\n\n" });
+    }
+
+    footnotes.extend(unanchored);
+    if !footnotes.is_empty() {
+        out.push_str("\n---\n\n");
+        for (i, thread) in footnotes.iter().enumerate() {
+            let mut lines = thread_summary(thread).into_iter();
+            if let Some(first) = lines.next() {
+                out.push_str(&format!("[^{}]: {}\n", i + 1, first));
+            }
+            for reply in lines {
+                out.push_str(&format!("    {}\n", reply));
+            }
+        }
+    }
+
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render_html(title: &str, blocks: &[Block], attached: &[Vec<CommentThread>], unanchored: &[CommentThread]) -> String {
+    let mut body = String::new();
+    let mut footnotes: Vec<&CommentThread> = Vec::new();
+    let mut open_list: Option<bool> = None;
+
+    for (block, threads) in blocks.iter().zip(attached) {
+        let list = match block.kind {
+            BlockKind::ListItem { ordered, .. } => Some(ordered),
+            _ => None,
+        };
+        if open_list != list {
+            if let Some(ordered) = open_list {
+                body.push_str(if ordered { "</ol>\n" } else { "</ul>\n" });
+            }
+            if let Some(ordered) = list {
+                body.push_str(if ordered { "<ol>\n" } else { "<ul>\n" });
+            }
+            open_list = list;
+        }
+
+        let mut refs = String::new();
+        for thread in threads {
+            footnotes.push(thread);
+            let n = footnotes.len();
+            refs.push_str(&format!("<sup class=\"comment-ref\"><a href=\"#comment-{0}\" id=\"ref-{0}\">{0}</a></sup>", n));
+        }
+
+        let text = escape_html(&block.text).replace('\n', "<br>");
+        let element = match &block.kind {
+            BlockKind::Heading(level) => format!("<h{0}>{1}{2}</h{0}>", level, text, refs),
+            BlockKind::Quote => format!("<blockquote>{}{}</blockquote>", text, refs),
+            BlockKind::ListItem { .. } => format!("<li>{}{}</li>", text, refs),
+            BlockKind::Code => format!("<pre><code>{}</code></pre>{}", text, refs),
+            BlockKind::Paragraph => format!("<p>{}{}</p>", text, refs),
+        };
+        body.push_str(&element);
+        body.push('\n');
+    }
+    if let Some(ordered) = open_list {
+        body.push_str(if ordered { "</ol>\n" } else { "</ul>\n" });
+    }
+
+    footnotes.extend(unanchored);
+    if !footnotes.is_empty() {
+        body.push_str("<hr>\n<section class=\"comments\">\n<h2>Comments</h2>\n<ol>\n");
+        for (i, thread) in footnotes.iter().enumerate() {
+            let class = if thread.resolved { "comment resolved" } else { "comment" };
+            body.push_str(&format!("<li id=\"comment-{}\" class=\"{}\">", i + 1, class));
+            for line in thread_summary(thread) {
+                body.push_str(&format!("<p>{}</p>", escape_html(&line)));
+            }
+            body.push_str("</li>\n");
+        }
+        body.push_str("</ol>\n</section>\n");
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\
+         body{{font-family:sans-serif;max-width:48rem;margin:2rem auto;line-height:1.5}}\
+         .comment-ref a{{color:#b45309;text-decoration:none}}\
+         .comments{{font-size:.9rem;color:#444}}\
+         .resolved{{opacity:.6}}</style>\n</head>\n<body>\n<h1>{}</h1>\n{}</body>\n</html>\n",
+        escape_html(title),
+        escape_html(title),
+        body
+    )
+}
+
+const PDF_PAGE_WIDTH: f32 = 612.0;
+const PDF_PAGE_HEIGHT: f32 = 792.0;
+const PDF_MARGIN: f32 = 72.0;
+const PDF_LINE_HEIGHT: f32 = 15.0;
+const PDF_WRAP_COLUMNS: usize = 85;
+
+fn escape_pdf(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\\' => "\\\\".to_string(),
+            '(' => "\\(".to_string(),
+            ')' => "\\)".to_string(),
+            c if c.is_ascii() && !c.is_ascii_control() => c.to_string(),
+            _ => "?".to_string(),
+        })
+        .collect()
+}
+
+fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.len() + word.len() + 1 > columns {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+struct PdfPage {
+    lines: Vec<(f32, String, f32)>,
+    /// (y position, note text) for sticky-note annotations
+    notes: Vec<(f32, String)>,
+}
+
+/// Writes a plain Helvetica PDF where each comment thread becomes a
+/// sticky-note annotation beside the block it belongs to.
+fn render_pdf(title: &str, blocks: &[Block], attached: &[Vec<CommentThread>], unanchored: &[CommentThread]) -> Vec<u8> {
+    let mut pages = vec![PdfPage { lines: Vec::new(), notes: Vec::new() }];
+    let mut y = PDF_PAGE_HEIGHT - PDF_MARGIN;
+
+    // Returns the page index and baseline the line landed on
+    let place = |pages: &mut Vec<PdfPage>, y: &mut f32, text: String, size: f32| -> (usize, f32) {
+        if *y < PDF_MARGIN {
+            pages.push(PdfPage { lines: Vec::new(), notes: Vec::new() });
+            *y = PDF_PAGE_HEIGHT - PDF_MARGIN;
+        }
+        let line_y = *y;
+        let page = pages.len() - 1;
+        pages[page].lines.push((line_y, text, size));
+        *y -= PDF_LINE_HEIGHT * size / 11.0;
+        (page, line_y)
+    };
+
+    place(&mut pages, &mut y, title.to_string(), 18.0);
+    y -= PDF_LINE_HEIGHT;
+
+    for (block, threads) in blocks.iter().zip(attached) {
+        let (prefix, size) = match &block.kind {
+            BlockKind::Heading(level) => ("", 18.0 - (*level as f32).min(4.0) * 1.5),
+            BlockKind::ListItem { ordered: true, .. } => ("", 11.0),
+            BlockKind::ListItem { ordered: false, .. } => ("- ", 11.0),
+            BlockKind::Quote => ("| ", 11.0),
+            _ => ("", 11.0),
+        };
+        let text = match &block.kind {
+            BlockKind::ListItem { ordered: true, number } => format!("{}. {}", number, block.text),
+            _ => format!("{}{}", prefix, block.text),
+        };
+
+        let mut first_line = None;
+        for line in wrap(&text, PDF_WRAP_COLUMNS) {
+            let placed = place(&mut pages, &mut y, line, size);
+            first_line.get_or_insert(placed);
+        }
+        y -= PDF_LINE_HEIGHT / 2.0;
+
+        if let Some((page, line_y)) = first_line {
+            for thread in threads {
+                pages[page].notes.push((line_y, thread_summary(thread).join("\n")));
+            }
+        }
+    }
+
+    if !unanchored.is_empty() {
+        y -= PDF_LINE_HEIGHT;
+        place(&mut pages, &mut y, "Comments".to_string(), 14.0);
+        for thread in unanchored {
+            for line in thread_summary(thread) {
+                for wrapped in wrap(&line, PDF_WRAP_COLUMNS) {
+                    place(&mut pages, &mut y, wrapped, 10.0);
+                }
+            }
+        }
+    }
+
+    let mut objects: Vec<String> = Vec::new();
+    // 1: catalog, 2: page tree, 3: font; pages follow
+    objects.push("<< /Type /Catalog /Pages 2 0 R >>".to_string());
+    objects.push(String::new());
+    objects.push("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string());
+
+    let mut page_ids = Vec::new();
+    for page in &pages {
+        let mut stream = String::from("BT\n");
+        for (line_y, text, size) in &page.lines {
+            stream.push_str(&format!(
+                "/F1 {} Tf 1 0 0 1 {} {} Tm ({}) Tj\n",
+                size,
+                PDF_MARGIN,
+                line_y,
+                escape_pdf(text)
+            ));
+        }
+        stream.push_str("ET");
+
+        objects.push(format!("<< /Length {} >>\nstream\n{}\nendstream", stream.len(), stream));
+        let content_id = objects.len();
+
+        let mut annot_ids = Vec::new();
+        for (note_y, note) in &page.notes {
+            objects.push(format!(
+                "<< /Type /Annot /Subtype /Text /Rect [{} {} {} {}] /Contents ({}) /Name /Comment >>",
+                PDF_PAGE_WIDTH - PDF_MARGIN + 10.0,
+                note_y - 4.0,
+                PDF_PAGE_WIDTH - PDF_MARGIN + 28.0,
+                note_y + 14.0,
+                note.split('\n').map(escape_pdf).collect::<Vec<_>>().join("\\n")
+            ));
+            annot_ids.push(objects.len());
+        }
+
+        let annots = annot_ids.iter().map(|id| format!("{} 0 R", id)).collect::<Vec<_>>().join(" ");
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R /Annots [{}] >>",
+            PDF_PAGE_WIDTH, PDF_PAGE_HEIGHT, content_id, annots
+        ));
+        page_ids.push(objects.len());
+    }
+
+    objects[1] = format!(
+        "<< /Type /Pages /Kids [{}] /Count {} >>",
+        page_ids.iter().map(|id| format!("{} 0 R", id)).collect::<Vec<_>>().join(" "),
+        page_ids.len()
+    );
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", i + 1, object));
+    }
+
+    let xref_offset = pdf.len();
+    pdf.push_str(&format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1));
+    for offset in offsets {
+        pdf.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    pdf.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref_offset
+    ));
+
+    pdf.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::comments::Comment;
+    use crate::sidecar;
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+    use std::fs;
+
+    const CONTENT: &str = r#"{"root":{"children":[
+        {"type":"heading","tag":"h2","key":"h","children":[{"type":"text","text":"Plan"}]},
+        {"type":"paragraph","key":"p","children":[{"type":"text","text":"Use <b> & (parens)"}]},
+        {"type":"list","listType":"number","children":[
+            {"type":"listitem","key":"a","children":[{"type":"text","text":"First"}]},
+            {"type":"listitem","key":"b","children":[{"type":"text","text":"Second"}]}
+        ]},
+        {"type":"quote","key":"q","children":[{"type":"text","text":"Done"}]}
+    ]}}"#;
+
+    fn thread(id: &str, node_id: &str, body: &str, resolved: bool) -> CommentThread {
+        let created_at = Utc.with_ymd_and_hms(2024, 5, 1, 9, 30, 0).unwrap();
+        CommentThread {
+            id: id.to_string(),
+            node_id: node_id.to_string(),
+            comments: vec![Comment {
+                id: format!("{}-1", id),
+                author: "Ana".to_string(),
+                body: body.to_string(),
+                created_at,
+            }],
+            resolved,
+            resolved_at: None,
+            created_at,
+        }
+    }
+
+    /// Writes a document with a comments sidecar and exports it in `format`.
+    fn export(name: &str, format: ExportFormat) -> Vec<u8> {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let document = dir.join("Plan.md");
+        fs::write(&document, CONTENT).unwrap();
+        let threads = vec![
+            thread("t1", "p", "Check this", false),
+            thread("t2", "b", "Reorder?", true),
+            thread("t3", "gone", "Orphaned", false),
+        ];
+        sidecar::write_json(&sidecar::sidecar_path(&document, "comments"), &json!({ "threads": threads })).unwrap();
+
+        let dest = dir.join("out");
+        export_with_comments(&document, CONTENT, &dest, format, None, |_, _, _| Ok(())).unwrap();
+        let output = fs::read(&dest).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+        output
+    }
+
+    #[test]
+    fn test_export_markdown() {
+        let output = String::from_utf8(export("test_export_markdown", ExportFormat::Markdown)).unwrap();
+
+        assert_eq!(
+            output,
+            "## Plan\n\n\
+             Use <b> & (parens)[^1]\n\n\
+             1. First\n\
+             2. Second[^2]\n\
+             \n> Done\n\n\
+             \n---\n\n\
+             [^1]: Ana (2024-05-01 09:30): Check this\n\
+             [^2]: Ana (2024-05-01 09:30): Reorder? [resolved]\n\
+             [^3]: Ana (2024-05-01 09:30): Orphaned\n"
+        );
+
+        // Plain-text documents fall back to a paragraph per non-empty line
+        let blocks = parse_blocks("one\n\ntwo\n");
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[1].kind, BlockKind::Paragraph);
+        assert_eq!(blocks[1].anchors, vec!["1"]);
+    }
+
+    #[test]
+    fn test_export_html() {
+        let output = String::from_utf8(export("test_export_html", ExportFormat::Html)).unwrap();
+
+        assert!(output.contains("<title>Plan</title>"));
+        assert!(output.contains("<h2>Plan</h2>"));
+        assert!(output.contains(
+            "<p>Use &lt;b&gt; &amp; (parens)<sup class=\"comment-ref\"><a href=\"#comment-1\" id=\"ref-1\">1</a></sup></p>"
+        ));
+        assert!(output.contains("<ol>\n<li>First</li>\n<li>Second<sup"));
+        assert!(output.contains("</li>\n</ol>\n<blockquote>Done</blockquote>"));
+        assert!(output.contains("<li id=\"comment-2\" class=\"comment resolved\"><p>Ana (2024-05-01 09:30): Reorder? [resolved]</p></li>"));
+        assert!(output.contains("<li id=\"comment-3\" class=\"comment\"><p>Ana (2024-05-01 09:30): Orphaned</p></li>"));
+    }
+
+    #[test]
+    fn test_export_pdf() {
+        let output = String::from_utf8(export("test_export_pdf", ExportFormat::Pdf)).unwrap();
+
+        assert!(output.starts_with("%PDF-1.4\n"));
+        assert!(output.ends_with("%%EOF\n"));
+        assert!(output.contains("(Use <b> & \\(parens\\)) Tj"));
+        assert!(output.contains("/Contents (Ana \\(2024-05-01 09:30\\): Check this) /Name /Comment"));
+        assert!(output.contains("(Ana \\(2024-05-01 09:30\\): Orphaned) Tj"));
+
+        // The xref table must point at each object header
+        let startxref: usize = output.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+        let xref = &output[startxref..];
+        assert!(xref.starts_with("xref\n"));
+        let entries: Vec<&str> = xref.lines().skip(3).take_while(|line| !line.starts_with("trailer")).collect();
+        assert!(!entries.is_empty());
+        for (i, entry) in entries.iter().enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(output[offset..].starts_with(&format!("{} 0 obj\n", i + 1)));
+        }
+
+        // Page annotations reference the two anchored threads
+        let page = output.split("0 obj\n").find(|object| object.starts_with("<< /Type /Page ")).unwrap();
+        let annots = &page[page.find("/Annots [").unwrap()..];
+        assert_eq!(annots[..annots.find(']').unwrap()].matches(" 0 R").count(), 2);
+    }
+
+    #[test]
+    fn test_export_stopped() {
+        let dir = std::env::temp_dir().join("test_export_stopped");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let document = dir.join("Plan.md");
+        fs::write(&document, CONTENT).unwrap();
+        let dest = dir.join("Plan.html");
+
+        let mut stages = Vec::new();
+        export_with_comments(&document, CONTENT, &dest, ExportFormat::Html, None, |done, total, stage| {
+            stages.push((done, total, stage.to_string()));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(stages.iter().map(|(done, ..)| *done).collect::<Vec<_>>(), vec![0, 2, 3]);
+        assert!(dest.exists());
+        fs::remove_file(&dest).unwrap();
+
+        // Stopping before or after writing leaves neither the file nor the partial one
+        for stop_at in [0, 2, 3] {
+            let result = export_with_comments(&document, CONTENT, &dest, ExportFormat::Html, None, |done, _, _| {
+                if done == stop_at {
+                    return Err(AppError::cancelled("Export was cancelled"));
+                }
+                Ok(())
+            });
+            assert!(result.is_err());
+            assert!(!dest.exists());
+            assert!(!dir.join("Plan.html.part").exists());
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
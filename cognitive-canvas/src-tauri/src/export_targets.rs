@@ -0,0 +1,198 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use crate::export::ExportFormat;
+use crate::error::{AppError, AppResult};
+
+const BUILTIN_TARGETS: &[(&str, &str, ExportFormat, &str)] = &[
+    ("markdown", "Markdown", ExportFormat::Markdown, "md"),
+    ("html", "HTML", ExportFormat::Html, "html"),
+    ("pdf", "PDF", ExportFormat::Pdf, "pdf"),
+];
+
+/// An entry in the export menu.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportTarget {
+    pub id: String,
+    pub label: String,
+    /// What's rendered before the target's transformer runs
+    pub format: ExportFormat,
+    pub extension: String,
+    pub builtin: bool,
+}
+
+/// A custom export target. The document is rendered as `format()` first and
+/// the result is handed to `transform` as the final stage before writing.
+pub trait ExportTransformer: Send + Sync {
+    fn id(&self) -> &str;
+
+    fn label(&self) -> &str;
+
+    fn format(&self) -> ExportFormat;
+
+    fn extension(&self) -> &str;
+
+    fn transform(&self, title: &str, output: Vec<u8>) -> AppResult<Vec<u8>>;
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Replacement {
+    pub from: String,
+    pub to: String,
+}
+
+/// A transformer described as data, so the frontend and scripts can add
+/// targets without code: a list of replacements, then a template where
+/// `{{title}}` and `{{content}}` are filled in. Only for Markdown and HTML.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplateTransformer {
+    pub id: String,
+    pub label: String,
+    pub format: ExportFormat,
+    #[serde(default)]
+    pub extension: Option<String>,
+    #[serde(default)]
+    pub replacements: Vec<Replacement>,
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+impl ExportTransformer for TemplateTransformer {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn label(&self) -> &str {
+        &self.label
+    }
+
+    fn format(&self) -> ExportFormat {
+        self.format
+    }
+
+    fn extension(&self) -> &str {
+        match &self.extension {
+            Some(extension) => extension,
+            None => builtin_extension(self.format),
+        }
+    }
+
+    fn transform(&self, title: &str, output: Vec<u8>) -> AppResult<Vec<u8>> {
+        let mut content = String::from_utf8(output).map_err(|e| AppError::validation(format!("Export isn't text: {}", e)))?;
+        for replacement in &self.replacements {
+            content = content.replace(&replacement.from, &replacement.to);
+        }
+        if let Some(template) = &self.template {
+            content = template.replace("{{title}}", title).replace("{{content}}", &content);
+        }
+        Ok(content.into_bytes())
+    }
+}
+
+fn builtin_extension(format: ExportFormat) -> &'static str {
+    BUILTIN_TARGETS
+        .iter()
+        .find(|(_, _, f, _)| *f == format)
+        .map(|(_, _, _, extension)| *extension)
+        .unwrap_or("txt")
+}
+
+/// Custom export targets by id, next to the built-in formats.
+#[derive(Default)]
+pub struct ExportTargetRegistry {
+    transformers: RwLock<BTreeMap<String, Arc<dyn ExportTransformer>>>,
+}
+
+impl ExportTargetRegistry {
+    /// Adds a custom target, replacing an earlier one with the same id.
+    pub fn register(&self, transformer: Arc<dyn ExportTransformer>) -> AppResult<()> {
+        let id = transformer.id().trim();
+        if id.is_empty() {
+            return Err(AppError::validation("Export target id can't be empty"));
+        }
+        if BUILTIN_TARGETS.iter().any(|(builtin, ..)| *builtin == id) {
+            return Err(AppError::conflict(format!("{} is a built-in export format", id)));
+        }
+
+        let mut transformers = self.transformers.write()?;
+        transformers.insert(id.to_string(), transformer);
+        Ok(())
+    }
+
+    pub fn register_template(&self, template: TemplateTransformer) -> AppResult<()> {
+        if template.format == ExportFormat::Pdf {
+            return Err(AppError::unsupported("Templates only work with Markdown and HTML exports"));
+        }
+        self.register(Arc::new(template))
+    }
+
+    pub fn unregister(&self, id: &str) -> bool {
+        self.transformers.write().is_ok_and(|mut transformers| transformers.remove(id).is_some())
+    }
+
+    /// The built-in formats followed by custom targets in id order.
+    pub fn list(&self) -> Vec<ExportTarget> {
+        let mut targets: Vec<ExportTarget> = BUILTIN_TARGETS
+            .iter()
+            .map(|(id, label, format, extension)| ExportTarget {
+                id: id.to_string(),
+                label: label.to_string(),
+                format: *format,
+                extension: extension.to_string(),
+                builtin: true,
+            })
+            .collect();
+
+        if let Ok(transformers) = self.transformers.read() {
+            targets.extend(transformers.values().map(|t| ExportTarget {
+                id: t.id().to_string(),
+                label: t.label().to_string(),
+                format: t.format(),
+                extension: t.extension().to_string(),
+                builtin: false,
+            }));
+        }
+        targets
+    }
+
+    /// The format to render for `id`, and the transformer to finish with if
+    /// it's a custom target.
+    pub fn resolve(&self, id: &str) -> AppResult<(ExportFormat, Option<Arc<dyn ExportTransformer>>)> {
+        if let Some((_, _, format, _)) = BUILTIN_TARGETS.iter().find(|(builtin, ..)| *builtin == id) {
+            return Ok((*format, None));
+        }
+        let transformers = self.transformers.read()?;
+        let transformer = transformers.get(id).ok_or_else(|| AppError::not_found(format!("Unknown export target {}", id)))?;
+        Ok((transformer.format(), Some(transformer.clone())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_targets() {
+        let registry = ExportTargetRegistry::default();
+        let template: TemplateTransformer = serde_json::from_str(
+            r#"{"id":"wiki","label":"Company wiki","format":"html",
+                "replacements":[{"from":"<h1>","to":"<h1 class=\"wiki\">"}],
+                "template":"<main data-title=\"{{title}}\">{{content}}</main>"}"#,
+        )
+        .unwrap();
+        registry.register_template(template).unwrap();
+
+        let ids: Vec<String> = registry.list().into_iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec!["markdown", "html", "pdf", "wiki"]);
+
+        let (format, transformer) = registry.resolve("wiki").unwrap();
+        assert_eq!(format, ExportFormat::Html);
+        let output = transformer.unwrap().transform("Notes", b"<h1>Hi</h1>".to_vec()).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "<main data-title=\"Notes\"><h1 class=\"wiki\">Hi</h1></main>");
+
+        assert!(registry.resolve("pdf").unwrap().1.is_none());
+        assert!(registry.resolve("docx").is_err());
+        let builtin: TemplateTransformer = serde_json::from_str(r#"{"id":"markdown","label":"M","format":"markdown"}"#).unwrap();
+        assert!(registry.register_template(builtin).is_err());
+    }
+}
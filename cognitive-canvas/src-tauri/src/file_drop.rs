@@ -0,0 +1,134 @@
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use std::path::{Path, PathBuf};
+use crate::{assets, cli};
+
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "svg", "bmp"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DroppedKind {
+    Canvas,
+    Markdown,
+    Image,
+    Folder,
+    Other,
+}
+
+/// A dropped file as the frontend should import it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DroppedFile {
+    pub kind: DroppedKind,
+    pub name: String,
+    /// What was dropped
+    pub source: String,
+    /// What to use: the copy in the workspace's assets for images that were
+    /// copied, relative to the workspace, otherwise the source
+    pub path: String,
+    pub copied: bool,
+    pub size: u64,
+}
+
+/// Payload of the "files-dropped" event.
+#[derive(Debug, Clone, Serialize)]
+pub struct FilesDropped {
+    pub files: Vec<DroppedFile>,
+    /// Where in the window they were dropped, in physical pixels
+    pub x: f64,
+    pub y: f64,
+}
+
+pub fn classify(path: &Path) -> DroppedKind {
+    if path.is_dir() {
+        return DroppedKind::Folder;
+    }
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+    match extension.as_str() {
+        "canvas" | "canvasb" => DroppedKind::Canvas,
+        "md" | "markdown" | "txt" => DroppedKind::Markdown,
+        e if IMAGE_EXTENSIONS.contains(&e) => DroppedKind::Image,
+        _ => DroppedKind::Other,
+    }
+}
+
+/// Classifies dropped paths and copies images that live outside the
+/// workspace into its assets folder, so documents don't point at a
+/// Downloads folder that gets cleaned up. Documents are opened where they
+/// are.
+pub fn import(paths: &[PathBuf], workspace: Option<&Path>) -> Vec<DroppedFile> {
+    paths
+        .iter()
+        .map(|source| {
+            let kind = classify(source);
+            let mut file = DroppedFile {
+                kind,
+                name: source.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                source: source.to_string_lossy().to_string(),
+                path: source.to_string_lossy().to_string(),
+                copied: false,
+                size: std::fs::metadata(source).map(|m| m.len()).unwrap_or(0),
+            };
+            let Some(workspace) = workspace.filter(|root| kind == DroppedKind::Image && !source.starts_with(root)) else {
+                return file;
+            };
+
+            match assets::import_asset(workspace, source) {
+                Ok(path) => {
+                    file.path = path;
+                    file.copied = true;
+                }
+                Err(e) => tracing::warn!("Failed to copy {} into the workspace: {}", source.display(), e),
+            }
+            file
+        })
+        .collect()
+}
+
+/// Imports what was dropped on the window and tells the frontend with a
+/// "files-dropped" event.
+pub fn handle_drop(app_handle: &AppHandle, paths: Vec<PathBuf>, x: f64, y: f64) {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let workspace = cli::workspace_root(&app_handle).ok();
+        let files = import(&paths, workspace.as_deref());
+        if let Err(e) = app_handle.emit("files-dropped", FilesDropped { files, x, y }) {
+            tracing::warn!("Failed to emit files-dropped: {}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_import() {
+        let dir = std::env::temp_dir().join("test_file_drop");
+        let _ = fs::remove_dir_all(&dir);
+        let workspace = dir.join("workspace");
+        let downloads = dir.join("downloads");
+        fs::create_dir_all(&workspace).unwrap();
+        fs::create_dir_all(&downloads).unwrap();
+        fs::write(downloads.join("photo.PNG"), "new").unwrap();
+        fs::write(downloads.join("notes.md"), "# Notes").unwrap();
+        fs::write(workspace.join("inside.png"), "mine").unwrap();
+
+        let files = import(
+            &[downloads.join("photo.PNG"), downloads.join("notes.md"), workspace.join("inside.png"), downloads.clone()],
+            Some(&workspace),
+        );
+        let kinds: Vec<DroppedKind> = files.iter().map(|f| f.kind).collect();
+        assert_eq!(kinds, vec![DroppedKind::Image, DroppedKind::Markdown, DroppedKind::Image, DroppedKind::Folder]);
+
+        assert!(files[0].copied);
+        assert_eq!(fs::read_to_string(workspace.join(&files[0].path)).unwrap(), "new");
+        assert!(!files[1].copied && !files[2].copied);
+
+        // Dropping the same image again reuses the copy
+        let again = import(&[downloads.join("photo.PNG")], Some(&workspace));
+        assert_eq!(again[0].path, files[0].path);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,114 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::fuzzy::fuzzy_match;
+use crate::workspace::{self, ScanCache};
+use crate::error::AppResult;
+
+/// A file name match outranks one spread over the directories.
+const FILE_NAME_BONUS: i64 = 15;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileMatch {
+    pub path: String,
+    /// Path relative to the workspace, with `/` separators
+    pub relative: String,
+    pub score: i64,
+    /// Character positions in `relative` that matched the query
+    pub matched: Vec<usize>,
+}
+
+/// Directory listings per workspace, kept between calls so typing in the
+/// quick open palette only re-reads directories that changed.
+#[derive(Default)]
+pub struct FileFinderState {
+    caches: Mutex<HashMap<PathBuf, ScanCache>>,
+}
+
+fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Scores `query` against the file name and the whole relative path and
+/// keeps the better of the two.
+fn match_file(query: &str, relative: &str) -> Option<(i64, Vec<usize>)> {
+    let name_start = relative.rfind('/').map(|i| relative[..=i].chars().count()).unwrap_or(0);
+    let name: String = relative.chars().skip(name_start).collect();
+
+    let by_name = fuzzy_match(query, &name)
+        .map(|(score, matched)| (score + FILE_NAME_BONUS, matched.into_iter().map(|i| i + name_start).collect::<Vec<_>>()));
+    let by_path = fuzzy_match(query, relative);
+
+    match (by_name, by_path) {
+        (Some(name), Some(path)) if path.0 > name.0 => Some(path),
+        (Some(name), _) => Some(name),
+        (None, path) => path,
+    }
+}
+
+/// Ranks `paths` against `query`, best first. An empty query keeps them
+/// in path order.
+pub fn rank(root: &Path, paths: &[PathBuf], query: &str, limit: usize) -> Vec<FileMatch> {
+    let query = query.trim();
+    let mut matches: Vec<FileMatch> = paths
+        .iter()
+        .filter_map(|path| {
+            let relative = relative_path(root, path);
+            let (score, matched) = if query.is_empty() { (0, Vec::new()) } else { match_file(query, &relative)? };
+            Some(FileMatch {
+                path: path.to_string_lossy().to_string(),
+                relative,
+                score,
+                matched,
+            })
+        })
+        .collect();
+
+    // Stable, so equal scores stay in path order
+    matches.sort_by_key(|m| std::cmp::Reverse(m.score));
+    matches.truncate(limit);
+    matches
+}
+
+/// Lists the documents in `root` using the cached listings and ranks them.
+pub fn fuzzy_find_files(state: &FileFinderState, root: &Path, query: &str, limit: usize) -> AppResult<Vec<FileMatch>> {
+    let paths = {
+        let mut caches = state.caches.lock()?;
+        let cache = caches.entry(root.to_path_buf()).or_default();
+        workspace::scan(root, cache, |_| {})?
+    };
+    Ok(rank(root, &paths, query, limit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank_files() {
+        let root = Path::new("/ws");
+        let paths: Vec<PathBuf> = ["/ws/archive/meeting-notes.md", "/ws/notes/meeting.md", "/ws/notes/plan.canvas", "/ws/readme.md"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+
+        let results = rank(root, &paths, "meet", 10);
+        assert_eq!(results[0].relative, "notes/meeting.md");
+        assert_eq!(results[0].matched, vec![6, 7, 8, 9]);
+        assert_eq!(results.len(), 2);
+
+        // Matches across directories when the file name alone doesn't
+        let results = rank(root, &paths, "np", 10);
+        assert_eq!(results[0].relative, "notes/plan.canvas");
+        assert_eq!(results[0].matched, vec![0, 6]);
+
+        assert!(rank(root, &paths, "xyz", 10).is_empty());
+        assert_eq!(rank(root, &paths, "", 3).len(), 3);
+    }
+}
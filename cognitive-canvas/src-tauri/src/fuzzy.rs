@@ -0,0 +1,49 @@
+/// Matches the characters of `query` in order, ignoring case. Matches at the
+/// start of words and runs of consecutive characters score higher; gaps
+/// cost a little. Returns the score and the matched character positions.
+pub fn fuzzy_match(query: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let text: Vec<char> = text.chars().collect();
+    let mut positions = Vec::new();
+    let mut score = 0i64;
+    let mut next = 0;
+
+    for wanted in query.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase) {
+        let offset = text[next..].iter().position(|c| c.to_lowercase().eq(std::iter::once(wanted)))?;
+        let index = next + offset;
+
+        score += 1;
+        let word_start = index == 0 || !text[index - 1].is_alphanumeric();
+        if word_start {
+            score += 8;
+        }
+        match positions.last() {
+            Some(&last) if last + 1 == index => score += 5,
+            Some(_) => score -= (offset as i64).min(5),
+            None if index == 0 => score += 10,
+            None => {}
+        }
+
+        positions.push(index);
+        next = index + 1;
+    }
+
+    // Among equal matches prefer the shorter text
+    score -= (text.len() as i64 - positions.len() as i64) / 8;
+    Some((score, positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(fuzzy_match("tsb", "Show or hide the sidebar").map(|m| m.1), Some(vec![13, 17, 21]));
+        assert!(fuzzy_match("zz", "Zoom in").is_none());
+        assert!(fuzzy_match("", "Anything").is_some());
+
+        let word_starts = fuzzy_match("nd", "New document").unwrap().0;
+        let scattered = fuzzy_match("nd", "Unfold").unwrap().0;
+        assert!(word_starts > scattered);
+    }
+}
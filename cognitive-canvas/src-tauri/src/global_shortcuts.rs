@@ -0,0 +1,124 @@
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use crate::accelerator::KeySequence;
+use crate::shortcuts_manager::{self, InvalidShortcut};
+use crate::error::{AppError, AppResult};
+
+/// What's currently bound system-wide and what couldn't be, kept so the
+/// frontend can ask after missing the "global-shortcuts-failed" event.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GlobalShortcutStatus {
+    pub registered: BTreeMap<String, String>,
+    pub errors: Vec<InvalidShortcut>,
+}
+
+#[derive(Default)]
+pub struct GlobalShortcutState {
+    status: Mutex<GlobalShortcutStatus>,
+}
+
+/// Replaces every global shortcut with the `[global]` bindings from
+/// shortcuts.conf. Bindings that fail are skipped and reported with a
+/// "global-shortcuts-failed" event; the rest still work.
+pub fn register_all(app_handle: &AppHandle) -> AppResult<()> {
+    let bindings = shortcuts_manager::load_global_shortcuts(app_handle)?;
+    let keymap = shortcuts_manager::load_shortcuts(app_handle)?;
+    let platform = std::env::consts::OS;
+    let manager = app_handle.global_shortcut();
+
+    manager
+        .unregister_all()
+        .map_err(|e| AppError::internal(format!("Failed to clear global shortcuts: {}", e)))?;
+
+    let mut status = GlobalShortcutStatus::default();
+    for (action, binding) in bindings {
+        let invalid = |message: String| InvalidShortcut {
+            action: action.clone(),
+            accelerator: binding.clone(),
+            message,
+        };
+
+        let accelerator = match KeySequence::parse_portable(&binding, platform) {
+            // The OS only reports single key combinations
+            Ok(sequence) if sequence.is_chord() => {
+                status.errors.push(invalid("Global shortcuts can't be chords".to_string()));
+                continue;
+            }
+            Ok(sequence) => sequence.steps[0].clone(),
+            Err(error) => {
+                status.errors.push(invalid(error.to_string()));
+                continue;
+            }
+        };
+        if accelerator.is_reserved(platform) {
+            status.errors.push(invalid("The system already uses this shortcut".to_string()));
+            continue;
+        }
+        // A global shortcut would swallow the key before the window sees
+        // it, including the first step of an in-app chord
+        let resolved = accelerator.resolve(platform);
+        let taken_by = keymap
+            .bindings()
+            .find(|(_, local)| {
+                KeySequence::parse(local, platform).is_ok_and(|local| local.resolve(platform).steps.first() == Some(&resolved))
+            })
+            .map(|(local_action, _)| local_action);
+        if let Some(local_action) = taken_by {
+            status.errors.push(invalid(format!("Already used by {}", local_action)));
+            continue;
+        }
+
+        let shortcut = resolved.to_string();
+        let handler_action = action.clone();
+        let registered = manager.on_shortcut(shortcut.as_str(), move |app, _shortcut, event| {
+            if event.state == ShortcutState::Pressed {
+                trigger(app, &handler_action);
+            }
+        });
+        match registered {
+            Ok(()) => {
+                status.registered.insert(action.clone(), shortcut);
+            }
+            Err(e) => status.errors.push(invalid(format!("Failed to register shortcut: {}", e))),
+        }
+    }
+
+    if !status.errors.is_empty() {
+        let _ = app_handle.emit("global-shortcuts-failed", &status.errors);
+    }
+    let state = app_handle.state::<GlobalShortcutState>();
+    *state.status.lock()? = status;
+    Ok(())
+}
+
+pub fn status(state: &GlobalShortcutState) -> AppResult<GlobalShortcutStatus> {
+    Ok(state.status.lock()?.clone())
+}
+
+fn trigger(app_handle: &AppHandle, action: &str) {
+    let Some(window) = app_handle.get_webview_window("main") else {
+        return;
+    };
+
+    match action {
+        "toggle_window" => {
+            let showing = window.is_visible().unwrap_or(false) && window.is_focused().unwrap_or(false);
+            if showing {
+                let _ = window.hide();
+            } else {
+                let _ = window.show();
+                let _ = window.unminimize();
+                let _ = window.set_focus();
+            }
+        }
+        _ => {
+            let _ = window.show();
+            let _ = window.unminimize();
+            let _ = window.set_focus();
+            let _ = app_handle.emit("global-shortcut", action);
+        }
+    }
+}
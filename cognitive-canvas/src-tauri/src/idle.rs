@@ -0,0 +1,134 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use crate::settings_manager::{self, SettingsState};
+
+/// How often `wait_until_idle` looks again while the user is busy.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Deferred work runs after this even if the user never stops typing.
+const MAX_DEFER: Duration = Duration::from_secs(120);
+
+/// When the frontend last reported typing, scrolling or clicking.
+pub struct IdleState {
+    last_activity: Mutex<Instant>,
+}
+
+impl Default for IdleState {
+    fn default() -> Self {
+        Self { last_activity: Mutex::new(Instant::now()) }
+    }
+}
+
+impl IdleState {
+    pub fn notify(&self) {
+        if let Ok(mut last_activity) = self.last_activity.lock() {
+            *last_activity = Instant::now();
+        }
+    }
+
+    fn since_activity(&self) -> Duration {
+        self.last_activity.lock().map(|last| last.elapsed()).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IdleStatus {
+    /// Seconds since the last input, in the app or, where the OS says,
+    /// anywhere
+    pub idle_seconds: f64,
+    pub threshold_seconds: u32,
+    pub idle: bool,
+}
+
+/// Time since the last keyboard or mouse input anywhere on the system, if
+/// the platform can tell: GetLastInputInfo on Windows, the HID system's
+/// idle time on macOS and xprintidle on X11.
+#[cfg(windows)]
+fn system_idle() -> Option<Duration> {
+    use windows::Win32::System::SystemInformation::GetTickCount;
+    use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
+
+    let mut info = LASTINPUTINFO { cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32, dwTime: 0 };
+    if !unsafe { GetLastInputInfo(&mut info) }.as_bool() {
+        return None;
+    }
+    let now = unsafe { GetTickCount() };
+    Some(Duration::from_millis(u64::from(now.wrapping_sub(info.dwTime))))
+}
+
+#[cfg(target_os = "macos")]
+fn system_idle() -> Option<Duration> {
+    let output = std::process::Command::new("ioreg").args(["-c", "IOHIDSystem", "-d", "4"]).output().ok()?;
+    parse_hid_idle_time(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn system_idle() -> Option<Duration> {
+    let output = std::process::Command::new("xprintidle").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let millis = String::from_utf8_lossy(&output.stdout).trim().parse().ok()?;
+    Some(Duration::from_millis(millis))
+}
+
+/// Reads `"HIDIdleTime" = <nanoseconds>` from ioreg's output.
+#[cfg_attr(not(any(target_os = "macos", test)), allow(dead_code))]
+fn parse_hid_idle_time(ioreg: &str) -> Option<Duration> {
+    let line = ioreg.lines().find(|line| line.contains("\"HIDIdleTime\""))?;
+    let nanos = line.split('=').nth(1)?.trim().parse().ok()?;
+    Some(Duration::from_nanos(nanos))
+}
+
+/// The shorter of the app's and the system's idle time, so typing in
+/// another app counts as busy too.
+fn idle_for(app_handle: &AppHandle) -> Duration {
+    let app_idle = app_handle.state::<IdleState>().since_activity();
+    system_idle().map_or(app_idle, |system| system.min(app_idle))
+}
+
+fn threshold(app_handle: &AppHandle) -> u32 {
+    let settings_state = app_handle.state::<SettingsState>();
+    settings_manager::current_settings(app_handle, &settings_state)
+        .map(|settings| settings.idle_save_delay)
+        .unwrap_or_default()
+}
+
+pub fn idle_status(app_handle: &AppHandle) -> IdleStatus {
+    let threshold_seconds = threshold(app_handle);
+    let idle_for = idle_for(app_handle);
+    IdleStatus {
+        idle_seconds: idle_for.as_secs_f64(),
+        threshold_seconds,
+        idle: idle_for >= Duration::from_secs(u64::from(threshold_seconds)),
+    }
+}
+
+/// Returns once the user has been idle for `idle_save_delay` seconds, or
+/// after `MAX_DEFER` so work isn't put off forever.
+pub async fn wait_until_idle(app_handle: &AppHandle) {
+    let started = Instant::now();
+    loop {
+        let wanted = Duration::from_secs(u64::from(threshold(app_handle)));
+        let idle_for = idle_for(app_handle);
+        if idle_for >= wanted || started.elapsed() >= MAX_DEFER {
+            return;
+        }
+        tokio::time::sleep((wanted - idle_for).max(POLL_INTERVAL)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hid_idle_time() {
+        let ioreg = "    | |   \"HIDIdleTime\" = 2500000000\n    | |   \"HIDKeyboardModifierMappingPairs\" = ()";
+        assert_eq!(parse_hid_idle_time(ioreg), Some(Duration::from_millis(2500)));
+        assert_eq!(parse_hid_idle_time("\"HIDIdleTime\" = soon"), None);
+        assert_eq!(parse_hid_idle_time(""), None);
+    }
+}
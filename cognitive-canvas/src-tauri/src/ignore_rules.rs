@@ -0,0 +1,221 @@
+use std::path::{Path, PathBuf};
+
+/// Read in every folder of a workspace; rules apply to the folder they're
+/// in and everything below it.
+pub const IGNORE_FILES: &[&str] = &[".canvasignore", ".gitignore"];
+
+/// Always ignored unless a `!pattern` brings them back: hidden files and
+/// folders, dependencies, and what editors and sync clients leave behind.
+const DEFAULT_PATTERNS: &[&str] = &[
+    ".*",
+    "node_modules/",
+    "*.tmp",
+    "*.swp",
+    "*~",
+    "~$*",
+    "\\#*#",
+    "*.part",
+    "*.crdownload",
+    "@eaDir/",
+    "\\#recycle/",
+    "$RECYCLE.BIN/",
+    "Thumbs.db",
+    "desktop.ini",
+];
+
+#[derive(Debug, Clone)]
+struct Rule {
+    /// The folder of the ignore file
+    base: PathBuf,
+    pattern: Vec<char>,
+    negated: bool,
+    dir_only: bool,
+    /// Matched against the path below `base` rather than just the name
+    anchored: bool,
+}
+
+/// gitignore-style patterns: `*`, `?`, `[a-z]`, `**` across folders, `!`
+/// to re-include, a trailing `/` for folders only, and a leading or inner
+/// `/` to match from the ignore file's folder. Later rules win.
+#[derive(Debug, Clone)]
+pub struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+impl Default for IgnoreRules {
+    fn default() -> Self {
+        let mut rules = IgnoreRules { rules: Vec::new() };
+        rules.add(Path::new(""), &DEFAULT_PATTERNS.join("\n"));
+        rules
+    }
+}
+
+impl IgnoreRules {
+    /// The defaults plus the ignore files from `workspace` down to above
+    /// `dir`, for starting a scan part way into a workspace. `dir`'s own
+    /// ignore files are read by the scan.
+    pub fn for_dir(workspace: Option<&Path>, dir: &Path) -> Self {
+        let mut rules = IgnoreRules::default();
+        let Some(workspace) = workspace.filter(|workspace| dir.starts_with(workspace) && dir != *workspace) else {
+            return rules;
+        };
+        let mut ancestors: Vec<&Path> = dir.ancestors().skip(1).take_while(|a| a.starts_with(workspace)).collect();
+        ancestors.reverse();
+        for ancestor in ancestors {
+            rules.read_dir_files(ancestor);
+        }
+        rules
+    }
+
+    /// Adds the patterns of an ignore file in `base`, one per line.
+    pub fn add(&mut self, base: &Path, text: &str) {
+        for line in text.lines() {
+            let line = line.trim_end_matches(' ');
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let anchored = line.contains('/');
+            let pattern = line.strip_prefix('/').unwrap_or(line);
+            if pattern.is_empty() {
+                continue;
+            }
+            self.rules.push(Rule { base: base.to_path_buf(), pattern: pattern.chars().collect(), negated, dir_only, anchored });
+        }
+    }
+
+    /// Adds the ignore files in `dir` that exist.
+    pub fn read_dir_files(&mut self, dir: &Path) {
+        for name in IGNORE_FILES {
+            if let Ok(text) = std::fs::read_to_string(dir.join(name)) {
+                self.add(dir, &text);
+            }
+        }
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let name: Vec<char> = path.file_name().unwrap_or_default().to_string_lossy().chars().collect();
+        let mut ignored = false;
+        for rule in self.rules.iter().filter(|rule| is_dir || !rule.dir_only) {
+            let Ok(relative) = path.strip_prefix(&rule.base) else {
+                continue;
+            };
+            let matched = if rule.anchored {
+                let relative: Vec<char> = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/")
+                    .chars()
+                    .collect();
+                glob_match(&rule.pattern, &relative)
+            } else {
+                glob_match(&rule.pattern, &name)
+            };
+            if matched {
+                ignored = !rule.negated;
+            }
+        }
+        ignored
+    }
+}
+
+/// Whether `c` is in a `[...]` class, given what's between the brackets.
+fn class_contains(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('!' | '^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            found |= (class[i]..=class[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            found |= class[i] == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            let rest = &pattern[2..];
+            // `a/**/b` also matches `a/b`
+            if let Some(after) = rest.strip_prefix(&['/']) {
+                if glob_match(after, text) {
+                    return true;
+                }
+            }
+            (0..=text.len()).any(|i| glob_match(rest, &text[i..]))
+        }
+        Some('*') => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != '/')
+            .any(|i| glob_match(&pattern[1..], &text[i..])),
+        Some('?') => text.first().is_some_and(|c| *c != '/') && glob_match(&pattern[1..], &text[1..]),
+        Some('[') => match pattern.iter().skip(2).position(|c| *c == ']').map(|end| end + 2) {
+            Some(end) => {
+                text.first().is_some_and(|c| *c != '/' && class_contains(&pattern[1..end], *c))
+                    && glob_match(&pattern[end + 1..], &text[1..])
+            }
+            None => text.first() == Some(&'[') && glob_match(&pattern[1..], &text[1..]),
+        },
+        Some('\\') if pattern.len() > 1 => text.first() == Some(&pattern[1]) && glob_match(&pattern[2..], &text[1..]),
+        Some(c) => text.first() == Some(c) && glob_match(&pattern[1..], &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        glob_match(&pattern.chars().collect::<Vec<_>>(), &text.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(matches("*.md", "plan.md"));
+        assert!(!matches("*.md", "notes/plan.md"));
+        assert!(matches("notes/**/draft-?.md", "notes/draft-1.md"));
+        assert!(matches("notes/**/draft-?.md", "notes/2024/q1/draft-2.md"));
+        assert!(matches("**/build", "build"));
+        assert!(matches("[Tt]emp[0-9]", "temp7"));
+        assert!(!matches("[!Tt]emp", "temp"));
+        assert!(matches("\\#notes", "#notes"));
+    }
+
+    #[test]
+    fn test_is_ignored() {
+        let root = Path::new("/w");
+        let mut rules = IgnoreRules::default();
+        rules.add(root, "# Drafts stay local\n/drafts/\n*.log\nexports/*.md\n!.github/\n");
+        rules.add(&root.join("notes"), "scratch.md\n!keep.log\n");
+
+        assert!(rules.is_ignored(&root.join(".obsidian"), true));
+        assert!(!rules.is_ignored(&root.join(".github"), true));
+        assert!(rules.is_ignored(&root.join("app/node_modules"), true));
+        assert!(!rules.is_ignored(&root.join("node_modules"), false));
+        assert!(rules.is_ignored(&root.join("notes/~$plan.md"), false));
+        assert!(rules.is_ignored(&root.join("#plan.md#"), false));
+        assert!(rules.is_ignored(&root.join("drafts"), true));
+        assert!(!rules.is_ignored(&root.join("notes/drafts"), true));
+        assert!(rules.is_ignored(&root.join("exports/plan.md"), false));
+        assert!(!rules.is_ignored(&root.join("exports/2024/plan.md"), false));
+        assert!(rules.is_ignored(&root.join("notes/deep/scratch.md"), false));
+        assert!(!rules.is_ignored(&root.join("scratch.md"), false));
+        assert!(rules.is_ignored(&root.join("debug.log"), false));
+        assert!(!rules.is_ignored(&root.join("notes/keep.log"), false));
+    }
+}
@@ -0,0 +1,382 @@
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Component, Path, PathBuf};
+use crate::{document_format, document_text, sidecar, tags, workspace};
+use crate::error::{AppError, AppResult};
+use crate::operations::Operation;
+
+/// Longest summary shown next to a link, in characters.
+const SUMMARY_LENGTH: usize = 160;
+
+/// Which documents an index lists.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IndexSource {
+    /// Every document below `path`
+    Folder { path: String },
+    /// Documents below `root` matching `query`. Every word has to appear in
+    /// the title or path; `tag:name` requires the tag.
+    Query { root: String, query: String },
+}
+
+impl IndexSource {
+    fn root(&self) -> &Path {
+        match self {
+            IndexSource::Folder { path } => Path::new(path),
+            IndexSource::Query { root, .. } => Path::new(root),
+        }
+    }
+
+    fn heading(&self) -> String {
+        match self {
+            IndexSource::Folder { path } => {
+                let name = Path::new(path).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| path.clone());
+                format!("Index of {}", name)
+            }
+            IndexSource::Query { query, .. } => format!("Index: {}", query),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+    #[default]
+    Folder,
+    Tag,
+}
+
+/// An index document the scheduler keeps up to date.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexDocument {
+    pub source: IndexSource,
+    pub dest: String,
+    #[serde(default)]
+    pub group_by: GroupBy,
+    #[serde(default)]
+    pub last_generated: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexSummary {
+    pub dest: String,
+    pub entries: usize,
+    pub groups: usize,
+}
+
+struct Entry {
+    /// Path relative to the source's root
+    relative: PathBuf,
+    path: PathBuf,
+    title: String,
+    tags: Vec<String>,
+    summary: Option<String>,
+}
+
+fn get_registry_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir.join("index_documents.json"))
+}
+
+/// The frontmatter `summary` or `description`, or else the first line of
+/// text that isn't a heading, shortened to `SUMMARY_LENGTH`.
+fn summarize(text: &str, title: &str) -> Option<String> {
+    let frontmatter = document_text::frontmatter(text);
+    if let Some((_, value)) = frontmatter.iter().find(|(key, value)| (key == "summary" || key == "description") && !value.is_empty()) {
+        return Some(value.clone());
+    }
+
+    let body = match text.strip_prefix("---") {
+        Some(rest) if !frontmatter.is_empty() => rest.split_once("\n---").map(|(_, body)| body).unwrap_or(""),
+        _ => text,
+    };
+    let line = body
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#') && *line != title && *line != "---")?;
+
+    if line.chars().count() <= SUMMARY_LENGTH {
+        return Some(line.to_string());
+    }
+    let short: String = line.chars().take(SUMMARY_LENGTH).collect();
+    Some(format!("{}…", short.trim_end()))
+}
+
+fn matches_query(entry: &Entry, query: &str) -> bool {
+    let title = entry.title.to_lowercase();
+    let path = entry.relative.to_string_lossy().to_lowercase();
+    query.split_whitespace().all(|word| match word.strip_prefix("tag:") {
+        Some(tag) => entry.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)),
+        None => {
+            let word = word.to_lowercase();
+            title.contains(&word) || path.contains(&word)
+        }
+    })
+}
+
+fn collect_entries<F>(source: &IndexSource, dest: &Path, mut on_progress: F) -> AppResult<Vec<Entry>>
+where
+    F: FnMut(usize, usize, &Path) -> AppResult<()>,
+{
+    let root = source.root();
+    let mut entries = Vec::new();
+    let documents = workspace::document_files(root)?;
+    for (done, path) in documents.iter().enumerate() {
+        on_progress(done, documents.len(), path)?;
+        let path = path.clone();
+        if path == dest {
+            continue;
+        }
+        let Ok(content) = document_format::read_document(&path) else {
+            continue;
+        };
+        let text = document_text::plain_text(&content);
+        let title = document_text::title(&content)
+            .unwrap_or_else(|| path.file_stem().unwrap_or_default().to_string_lossy().to_string());
+
+        let entry = Entry {
+            relative: path.strip_prefix(root).unwrap_or(&path).to_path_buf(),
+            tags: tags::document_tags(&path, &text),
+            summary: summarize(&text, &title),
+            title,
+            path,
+        };
+        if let IndexSource::Query { query, .. } = source {
+            if !matches_query(&entry, query) {
+                continue;
+            }
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// `to` relative to the directory `from`, e.g. `../notes/a.md`.
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    for component in &to[common..] {
+        relative.push(component);
+    }
+    relative
+}
+
+fn link_target(dest: &Path, path: &Path) -> String {
+    let target = relative_path(dest.parent().unwrap_or(Path::new("")), path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    if target.contains(' ') {
+        format!("<{}>", target)
+    } else {
+        target
+    }
+}
+
+/// Groups entries under headings in order; the unnamed group comes first.
+fn group(entries: &[Entry], group_by: GroupBy) -> BTreeMap<String, Vec<&Entry>> {
+    let mut groups: BTreeMap<String, Vec<&Entry>> = BTreeMap::new();
+    for entry in entries {
+        match group_by {
+            GroupBy::Folder => {
+                let folder = entry
+                    .relative
+                    .parent()
+                    .map(|p| p.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/"))
+                    .unwrap_or_default();
+                groups.entry(folder).or_default().push(entry);
+            }
+            GroupBy::Tag if entry.tags.is_empty() => groups.entry(String::new()).or_default().push(entry),
+            GroupBy::Tag => {
+                for tag in &entry.tags {
+                    groups.entry(tag.clone()).or_default().push(entry);
+                }
+            }
+        }
+    }
+    for group in groups.values_mut() {
+        group.sort_by_key(|entry| entry.title.to_lowercase());
+    }
+    groups
+}
+
+fn render(source: &IndexSource, dest: &Path, group_by: GroupBy, entries: &[Entry]) -> (String, usize) {
+    let groups = group(entries, group_by);
+    let mut out = String::new();
+    let _ = writeln!(out, "# {}\n", source.heading());
+    let _ = writeln!(
+        out,
+        "_Generated {}. Edits to this document are overwritten when it's refreshed._\n",
+        Local::now().format("%Y-%m-%d %H:%M")
+    );
+
+    for (name, entries) in &groups {
+        let heading = match (name.as_str(), group_by) {
+            ("", GroupBy::Folder) => "Top level",
+            ("", GroupBy::Tag) => "Untagged",
+            (name, _) => name,
+        };
+        let _ = writeln!(out, "## {}\n", heading);
+        for entry in entries {
+            let _ = write!(out, "- [{}]({})", entry.title.replace(['[', ']'], ""), link_target(dest, &entry.path));
+            if let Some(summary) = &entry.summary {
+                let _ = write!(out, " — {}", summary);
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    (out, groups.len())
+}
+
+/// Writes a Markdown map of content to `dest`: a link to every document in
+/// `source`, grouped by folder or tag, with a one-line summary each.
+/// `on_progress` is called before each document is read; an error from it
+/// stops before anything is written.
+pub fn generate<F>(source: &IndexSource, dest: &Path, group_by: GroupBy, on_progress: F) -> AppResult<IndexSummary>
+where
+    F: FnMut(usize, usize, &Path) -> AppResult<()>,
+{
+    if !dest.extension().and_then(|e| e.to_str()).is_some_and(|e| e == "md" || e == "markdown") {
+        return Err(AppError::unsupported("Index documents are written as Markdown (.md)"));
+    }
+    let entries = collect_entries(source, dest, on_progress)?;
+    let (content, groups) = render(source, dest, group_by, &entries);
+    std::fs::write(dest, content).map_err(|e| AppError::io(format!("Failed to write index document: {}", e)))?;
+
+    Ok(IndexSummary {
+        dest: dest.to_string_lossy().to_string(),
+        entries: entries.len(),
+        groups,
+    })
+}
+
+pub fn list_index_documents(app_handle: &AppHandle) -> AppResult<Vec<IndexDocument>> {
+    sidecar::read_json(&get_registry_path(app_handle)?)
+}
+
+/// Generates an index document. With `keep_refreshed` set it's added to
+/// the ones the scheduler regenerates, or removed from them when false.
+pub fn generate_index_document(
+    app_handle: &AppHandle,
+    source: IndexSource,
+    dest: &str,
+    group_by: GroupBy,
+    keep_refreshed: Option<bool>,
+    operation: &Operation,
+) -> AppResult<IndexSummary> {
+    let summary = generate(&source, Path::new(dest), group_by, |done, total, path| {
+        operation.step(done, total, path.to_string_lossy());
+        operation.check_cancelled()
+    })?;
+
+    if let Some(keep) = keep_refreshed {
+        let registry_path = get_registry_path(app_handle)?;
+        let mut documents: Vec<IndexDocument> = sidecar::read_json(&registry_path)?;
+        documents.retain(|document| document.dest != dest);
+        if keep {
+            documents.push(IndexDocument {
+                source,
+                dest: dest.to_string(),
+                group_by,
+                last_generated: Some(Utc::now()),
+            });
+        }
+        sidecar::write_json(&registry_path, &documents)?;
+    }
+    Ok(summary)
+}
+
+/// Stops refreshing the index document at `dest`. The file is left alone.
+pub fn remove_index_document(app_handle: &AppHandle, dest: &str) -> AppResult<bool> {
+    let registry_path = get_registry_path(app_handle)?;
+    let mut documents: Vec<IndexDocument> = sidecar::read_json(&registry_path)?;
+    let count = documents.len();
+    documents.retain(|document| document.dest != dest);
+    if documents.len() == count {
+        return Ok(false);
+    }
+    sidecar::write_json(&registry_path, &documents)?;
+    Ok(true)
+}
+
+/// Regenerates every registered index document. One failing doesn't stop
+/// the others; the errors are returned together.
+pub fn refresh_all(app_handle: &AppHandle) -> AppResult<()> {
+    let registry_path = get_registry_path(app_handle)?;
+    let mut documents: Vec<IndexDocument> = sidecar::read_json(&registry_path)?;
+    if documents.is_empty() {
+        return Ok(());
+    }
+
+    let mut errors = Vec::new();
+    for document in &mut documents {
+        match generate(&document.source, Path::new(&document.dest), document.group_by, |_, _, _| Ok(())) {
+            Ok(_) => document.last_generated = Some(Utc::now()),
+            Err(e) => errors.push(format!("{}: {}", document.dest, e)),
+        }
+    }
+    sidecar::write_json(&registry_path, &documents)?;
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::io(errors.join("; ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_generate_index() {
+        let root = std::env::temp_dir().join("test_index_documents");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("projects/big plans")).unwrap();
+        fs::write(root.join("inbox.md"), "---\ntags: todo\n---\n# Inbox\nThings to sort out later.").unwrap();
+        fs::write(root.join("projects/launch.md"), "---\ntags: [todo, work]\nsummary: Launch checklist\n---\nBody").unwrap();
+        fs::write(root.join("projects/big plans/roadmap.md"), "# Roadmap\n\n## Q1\nShip it.").unwrap();
+
+        let source = IndexSource::Folder { path: root.to_string_lossy().to_string() };
+        let dest = root.join("projects/index.md");
+        let summary = generate(&source, &dest, GroupBy::Folder, |_, _, _| Ok(())).unwrap();
+        assert_eq!((summary.entries, summary.groups), (3, 3));
+
+        let content = fs::read_to_string(&dest).unwrap();
+        assert!(content.starts_with("# Index of test_index_documents\n"));
+        assert!(content.contains("## Top level\n\n- [Inbox](../inbox.md) — Things to sort out later.\n"));
+        assert!(content.contains("- [Roadmap](<big plans/roadmap.md>) — Ship it.\n"));
+        assert!(content.contains("- [launch](launch.md) — Launch checklist\n"));
+
+        // The index doesn't list itself when regenerated
+        assert_eq!(generate(&source, &dest, GroupBy::Tag, |_, _, _| Ok(())).unwrap().entries, 3);
+        let content = fs::read_to_string(&dest).unwrap();
+        assert!(content.contains("## todo\n\n- [Inbox](../inbox.md)"));
+        assert!(content.contains("## Untagged\n\n- [Roadmap]"));
+
+        let query = IndexSource::Query { root: root.to_string_lossy().to_string(), query: "tag:work projects".to_string() };
+        assert_eq!(generate(&query, &dest, GroupBy::Folder, |_, _, _| Ok(())).unwrap().entries, 1);
+        assert!(generate(&query, &root.join("index.canvas"), GroupBy::Folder, |_, _, _| Ok(())).is_err());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
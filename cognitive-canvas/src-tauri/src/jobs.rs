@@ -0,0 +1,370 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use tauri::{AppHandle, Manager};
+use tokio::sync::Notify;
+use crate::archive::{self, ArchiveOptions};
+use crate::index_documents::{self, GroupBy, IndexSource};
+use crate::operations::{Operation, OperationKind};
+use crate::sync_manager::{self, SyncDirection};
+use crate::{sidecar, thumbnails};
+use crate::error::{AppError, AppResult};
+
+/// Jobs waiting to run; enqueueing more than this fails.
+const MAX_QUEUED_JOBS: usize = 256;
+/// Finished jobs whose status can still be looked up.
+const FINISHED_HISTORY: usize = 100;
+const WORKERS: usize = 2;
+
+/// Work that can be queued to run in the background.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobKind {
+    IndexDocument {
+        source: IndexSource,
+        dest: String,
+        #[serde(default)]
+        group_by: GroupBy,
+    },
+    /// Makes a thumbnail ahead of it being shown
+    Thumbnail { source: String, max_size: Option<u32> },
+    /// Exports the workspace `dir` to the archive `dest`
+    Backup {
+        dir: String,
+        dest: String,
+        #[serde(default)]
+        options: ArchiveOptions,
+    },
+    Sync { direction: SyncDirection },
+}
+
+impl JobKind {
+    fn operation_kind(&self) -> OperationKind {
+        match self {
+            JobKind::IndexDocument { .. } => OperationKind::IndexDocument,
+            JobKind::Thumbnail { .. } => OperationKind::Thumbnail,
+            JobKind::Backup { .. } => OperationKind::ArchiveExport,
+            JobKind::Sync { .. } => OperationKind::Sync,
+        }
+    }
+}
+
+/// Higher priorities run first; jobs of equal priority in the order they
+/// were queued.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub kind: JobKind,
+    pub priority: JobPriority,
+    pub queued_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JobStatus {
+    #[serde(flatten)]
+    pub job: Job,
+    pub state: JobState,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+#[derive(Default)]
+struct JobQueue {
+    next_id: u64,
+    queued: Vec<Job>,
+    running: Vec<Job>,
+    finished: VecDeque<JobStatus>,
+}
+
+impl JobQueue {
+    /// Queues `kind`, or returns the id of the same work already waiting,
+    /// raising its priority if this one's is higher.
+    fn push(&mut self, kind: JobKind, priority: JobPriority) -> AppResult<String> {
+        if let Some(job) = self.queued.iter_mut().find(|job| job.kind == kind) {
+            job.priority = job.priority.max(priority);
+            return Ok(job.id.clone());
+        }
+        if self.queued.len() >= MAX_QUEUED_JOBS {
+            return Err(AppError::conflict(format!("The job queue is full ({} jobs waiting)", MAX_QUEUED_JOBS)));
+        }
+
+        self.next_id += 1;
+        let job = Job {
+            id: format!("job-{}", self.next_id),
+            kind,
+            priority,
+            queued_at: Utc::now(),
+        };
+        let id = job.id.clone();
+        self.queued.push(job);
+        Ok(id)
+    }
+
+    /// Takes the next job to run and marks it running.
+    fn pop(&mut self) -> Option<Job> {
+        let priority = self.queued.iter().map(|job| job.priority).max()?;
+        let index = self.queued.iter().position(|job| job.priority == priority)?;
+        let job = self.queued.remove(index);
+        self.running.push(job.clone());
+        Some(job)
+    }
+
+    fn finish(&mut self, id: &str, result: &AppResult<()>) {
+        let Some(index) = self.running.iter().position(|job| job.id == id) else {
+            return;
+        };
+        let (state, error) = match result {
+            Ok(()) => (JobState::Succeeded, None),
+            Err(AppError::Cancelled(_)) => (JobState::Cancelled, None),
+            Err(e) => (JobState::Failed, Some(e.to_string())),
+        };
+        self.finished.push_back(JobStatus {
+            job: self.running.remove(index),
+            state,
+            finished_at: Some(Utc::now()),
+            error,
+        });
+        if self.finished.len() > FINISHED_HISTORY {
+            self.finished.pop_front();
+        }
+    }
+
+    fn status(&self, id: &str) -> Option<JobStatus> {
+        let unfinished = |job: &Job, state| JobStatus { job: job.clone(), state, finished_at: None, error: None };
+        if let Some(job) = self.running.iter().find(|job| job.id == id) {
+            return Some(unfinished(job, JobState::Running));
+        }
+        if let Some(job) = self.queued.iter().find(|job| job.id == id) {
+            return Some(unfinished(job, JobState::Queued));
+        }
+        self.finished.iter().rev().find(|status| status.job.id == id).cloned()
+    }
+
+    /// What has to be run after a restart: jobs that were running, since
+    /// they didn't get to finish, then the queued ones.
+    fn pending(&self) -> Vec<Job> {
+        self.running.iter().chain(&self.queued).cloned().collect()
+    }
+
+    fn restore(&mut self, jobs: Vec<Job>) {
+        let restored_ids = jobs.iter().filter_map(|job| job.id.strip_prefix("job-")?.parse::<u64>().ok());
+        self.next_id = self.next_id.max(restored_ids.max().unwrap_or(0));
+        self.queued.extend(jobs);
+    }
+}
+
+#[derive(Default)]
+pub struct JobsState {
+    queue: Mutex<JobQueue>,
+    wake: Notify,
+}
+
+impl JobsState {
+    pub fn status(&self, id: &str) -> AppResult<JobStatus> {
+        self.queue.lock()?.status(id).ok_or_else(|| AppError::not_found(format!("No job with id {}", id)))
+    }
+}
+
+fn get_jobs_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir.join("jobs.json"))
+}
+
+fn save(path: &Path, queue: &JobQueue) -> AppResult<()> {
+    sidecar::write_json(path, &queue.pending())
+}
+
+/// Queues `kind` and returns its job id. Waiting jobs are saved, so they
+/// still run if the app quits first.
+pub fn enqueue(app_handle: &AppHandle, kind: JobKind, priority: JobPriority) -> AppResult<String> {
+    let path = get_jobs_path(app_handle)?;
+    let state = app_handle.state::<JobsState>();
+    let id = {
+        let mut queue = state.queue.lock()?;
+        let id = queue.push(kind, priority)?;
+        save(&path, &queue)?;
+        id
+    };
+    state.wake.notify_one();
+    Ok(id)
+}
+
+/// Loads the jobs left pending by the last run. They wait until
+/// `spawn_workers` is called.
+pub fn restore(app_handle: &AppHandle) -> AppResult<()> {
+    let jobs: Vec<Job> = sidecar::read_json(&get_jobs_path(app_handle)?)?;
+    app_handle.state::<JobsState>().queue.lock()?.restore(jobs);
+    Ok(())
+}
+
+/// Starts the tasks that run queued jobs, a few at a time.
+pub fn spawn_workers(app_handle: &AppHandle) {
+    for _ in 0..WORKERS {
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = work(&app_handle).await {
+                tracing::error!("Job worker stopped: {}", e);
+            }
+        });
+    }
+}
+
+/// Takes jobs off the queue until the app quits. Nothing in the loop can
+/// stop it: failing to save the queue is logged, and a lock poisoned by a
+/// panicking job is taken over, so every job that starts is finished.
+async fn work(app_handle: &AppHandle) -> AppResult<()> {
+    let path = get_jobs_path(app_handle)?;
+    let state = app_handle.state::<JobsState>();
+    let lock_queue = || state.queue.lock().unwrap_or_else(PoisonError::into_inner);
+    loop {
+        let next = {
+            let mut queue = lock_queue();
+            let job = queue.pop();
+            if job.is_some() {
+                log_save_error(save(&path, &queue));
+            }
+            job
+        };
+        let Some(job) = next else {
+            state.wake.notified().await;
+            continue;
+        };
+
+        let result = run(app_handle, &job).await;
+        if let Err(e) = &result {
+            tracing::warn!("Job {} failed: {}", job.id, e);
+        }
+        let mut queue = lock_queue();
+        queue.finish(&job.id, &result);
+        log_save_error(save(&path, &queue));
+    }
+}
+
+fn log_save_error(result: AppResult<()>) {
+    if let Err(e) = result {
+        tracing::error!("Failed to save pending jobs: {}", e);
+    }
+}
+
+/// Runs `job` as an operation under the job's id, so it reports progress
+/// and `cancel_operation` stops it.
+async fn run(app_handle: &AppHandle, job: &Job) -> AppResult<()> {
+    let operation = Operation::start(app_handle, job.kind.operation_kind(), Some(job.id.clone()))?;
+    match job.kind.clone() {
+        JobKind::IndexDocument { source, dest, group_by } => {
+            run_blocking(app_handle, move |app| {
+                index_documents::generate_index_document(app, source, &dest, group_by, None, &operation).map(|_| ())
+            })
+            .await
+        }
+        JobKind::Thumbnail { source, max_size } => {
+            run_blocking(app_handle, move |app| thumbnails::get_thumbnail(app, Path::new(&source), max_size).map(|_| ())).await
+        }
+        JobKind::Backup { dir, dest, options } => {
+            run_blocking(app_handle, move |app| {
+                archive::export_workspace_archive(app, Path::new(&dir), Path::new(&dest), &options, &operation).map(|_| ())
+            })
+            .await
+        }
+        JobKind::Sync { direction } => sync_manager::sync(app_handle, direction, &operation).await.map(|_| ()),
+    }
+}
+
+async fn run_blocking<F>(app_handle: &AppHandle, work: F) -> AppResult<()>
+where
+    F: FnOnce(&AppHandle) -> AppResult<()> + Send + 'static,
+{
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn_blocking(move || work(&app_handle))
+        .await
+        .map_err(|e| AppError::internal(format!("Job failed to run: {}", e)))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thumbnail(source: &str) -> JobKind {
+        JobKind::Thumbnail { source: source.to_string(), max_size: None }
+    }
+
+    #[test]
+    fn test_job_queue() {
+        let mut queue = JobQueue::default();
+        let first = queue.push(thumbnail("a.png"), JobPriority::Low).unwrap();
+        let second = queue.push(JobKind::Sync { direction: SyncDirection::Push }, JobPriority::Normal).unwrap();
+        let third = queue.push(thumbnail("b.png"), JobPriority::Normal).unwrap();
+        assert_eq!((first.as_str(), second.as_str(), third.as_str()), ("job-1", "job-2", "job-3"));
+
+        // Queuing the same work again bumps it instead of adding a job
+        assert_eq!(queue.push(thumbnail("a.png"), JobPriority::High).unwrap(), first);
+        assert_eq!(queue.queued.len(), 3);
+
+        assert_eq!(queue.pop().unwrap().id, first);
+        assert_eq!(queue.pop().unwrap().id, second);
+        assert_eq!(queue.status(&second).unwrap().state, JobState::Running);
+        assert_eq!(queue.status(&third).unwrap().state, JobState::Queued);
+
+        queue.finish(&first, &Ok(()));
+        queue.finish(&second, &Err(AppError::network("Server unreachable")));
+        assert_eq!(queue.status(&first).unwrap().state, JobState::Succeeded);
+        let failed = queue.status(&second).unwrap();
+        assert_eq!(failed.state, JobState::Failed);
+        assert_eq!(failed.error.as_deref(), Some("Server unreachable"));
+        assert!(queue.status("job-9").is_none());
+    }
+
+    #[test]
+    fn test_job_queue_restore() {
+        let mut queue = JobQueue::default();
+        queue.push(thumbnail("a.png"), JobPriority::Normal).unwrap();
+        queue.push(thumbnail("b.png"), JobPriority::Normal).unwrap();
+        let running = queue.pop().unwrap();
+
+        let pending = queue.pending();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].id, running.id);
+
+        let mut restored = JobQueue::default();
+        restored.restore(pending);
+        assert_eq!(restored.pop().unwrap().id, "job-1");
+        assert_eq!(restored.push(thumbnail("c.png"), JobPriority::Low).unwrap(), "job-3");
+    }
+
+    #[test]
+    fn test_job_queue_is_bounded() {
+        let mut queue = JobQueue::default();
+        for i in 0..MAX_QUEUED_JOBS {
+            queue.push(thumbnail(&format!("{}.png", i)), JobPriority::Low).unwrap();
+        }
+        assert!(matches!(queue.push(thumbnail("full.png"), JobPriority::High), Err(AppError::Conflict(_))));
+    }
+}
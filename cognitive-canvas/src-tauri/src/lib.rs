@@ -1,9 +1,87 @@
 use serde::{Deserialize, Serialize};
 use std::path::Path;
+use std::time::Duration;
+use error::{AppError, AppResult};
 
 mod settings_manager;
 mod shortcuts_manager;
 mod config_parser;
+mod accelerator;
+mod activity;
+mod ai;
+mod archive;
+mod asset_refs;
+mod assets;
+mod audio;
+mod canvas_graph;
+mod cli;
+mod clipboard;
+mod collab;
+mod command_registry;
+mod comments;
+mod config_repo;
+mod config_watcher;
+mod content_schema;
+mod crdt;
+mod deadlines;
+mod deep_link;
+mod doc_cache;
+mod document_format;
+mod document_list;
+mod document_lock;
+mod document_metadata;
+mod document_text;
+mod document_version;
+mod embeddings;
+mod error;
+mod export;
+mod export_targets;
+mod file_drop;
+mod file_finder;
+mod file_stream;
+mod fuzzy;
+mod ignore_rules;
+mod index_documents;
+mod jobs;
+mod link_graph;
+mod link_index;
+mod local_llm;
+mod logging;
+mod node_types;
+mod ocr;
+mod onboarding;
+mod operations;
+mod rename;
+mod snippets;
+mod speech;
+mod spellcheck;
+mod summarize;
+mod tags;
+mod templates;
+mod thumbnails;
+mod trash;
+mod undo_history;
+#[cfg(desktop)]
+mod global_shortcuts;
+#[cfg(desktop)]
+mod tray;
+mod idle;
+mod monitors;
+mod prefetch;
+mod processors;
+mod retention;
+mod s3;
+mod safe_mode;
+mod scheduler;
+mod sidecar;
+mod startup;
+mod suggestions;
+mod sync_manager;
+mod sync_provider;
+mod titlebar;
+mod webdav;
+mod window_effects;
+mod workspace;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DocumentData {
@@ -11,6 +89,26 @@ pub struct DocumentData {
     pub title: String,
     pub content: String,
     pub file_path: Option<String>,
+    /// File mtime (ms since epoch) when the document was loaded or last saved
+    #[serde(default)]
+    pub modified: Option<u64>,
+    /// SHA-256 of the content as it was on disk at load/save time
+    #[serde(default)]
+    pub content_hash: Option<String>,
+    /// What the document processors derived on load (frontmatter, links, tasks)
+    #[serde(default)]
+    pub metadata: serde_json::Map<String, serde_json::Value>,
+    /// Timestamps, tags and custom fields. Read on load; changed through
+    /// `update_document_metadata`, not by saving
+    #[serde(default)]
+    pub properties: document_metadata::DocumentMetadata,
+    /// Schema problems found on load; minor ones are already repaired in
+    /// `content`
+    #[serde(default, skip_deserializing)]
+    pub schema: content_schema::SchemaReport,
+    /// Set when another instance or machine has the document open
+    #[serde(default, skip_deserializing)]
+    pub lock: Option<document_lock::DocumentLock>,
 }
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
@@ -19,29 +117,113 @@ fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
+/// Sends `prompt` to the AI provider configured in ai.conf and returns its
+/// answer with token usage.
 #[tauri::command]
-async fn ping_backend(name: String) -> Result<String, String> {
-    Ok(format!("GOD MODE: Online. Acknowledged input: {}. Stand by.", name))
+async fn ai_complete(
+    app_handle: tauri::AppHandle,
+    prompt: String,
+    options: Option<ai::CompletionOptions>,
+) -> AppResult<ai::Completion> {
+    let config = ai::load_ai_config(&app_handle)?;
+    ai::complete(&config, &prompt, &options.unwrap_or_default()).await
 }
 
+/// Like `ai_complete`, but emits the answer as `ai-token` events while it's
+/// generated. `request_id` is chosen by the caller and doubles as the
+/// operation id to cancel it with.
 #[tauri::command]
-async fn save_file(path: String, contents: String) -> Result<(), String> {
+async fn ai_complete_stream(
+    app_handle: tauri::AppHandle,
+    request_id: String,
+    prompt: String,
+    options: Option<ai::CompletionOptions>,
+) -> AppResult<ai::Completion> {
+    let config = ai::load_ai_config(&app_handle)?;
+    let operation = operations::Operation::start(&app_handle, operations::OperationKind::AiGeneration, Some(request_id.clone()))?;
+    ai::complete_stream(&app_handle, &operation, &config, &request_id, &prompt, &options.unwrap_or_default()).await
+}
+
+#[tauri::command]
+fn ai_cancel(state: tauri::State<'_, operations::OperationsState>, request_id: String) -> AppResult<()> {
+    state.cancel(&request_id)?;
+    Ok(())
+}
+
+/// Models on the local Ollama or llama.cpp server, the configured one by
+/// default.
+#[tauri::command]
+async fn list_local_models(app_handle: tauri::AppHandle, base_url: Option<String>) -> AppResult<Vec<local_llm::LocalModel>> {
+    let base_url = match base_url {
+        Some(base_url) => base_url,
+        None => local_llm::base_url_for(&ai::load_ai_config(&app_handle)?),
+    };
+    local_llm::list_models(&base_url).await
+}
+
+#[tauri::command]
+async fn check_local_ai(app_handle: tauri::AppHandle, base_url: Option<String>) -> AppResult<local_llm::LocalServerStatus> {
+    let base_url = match base_url {
+        Some(base_url) => base_url,
+        None => local_llm::base_url_for(&ai::load_ai_config(&app_handle)?),
+    };
+    Ok(local_llm::check_health(&base_url).await)
+}
+
+/// The `k` documents closest in meaning to `query`, from the embeddings
+/// made as documents are saved.
+#[tauri::command]
+async fn semantic_search(app_handle: tauri::AppHandle, query: String, k: Option<usize>) -> AppResult<Vec<embeddings::SemanticMatch>> {
+    embeddings::semantic_search(&app_handle, &query, k.unwrap_or(10)).await
+}
+
+/// A summary and key points of a document by the configured AI provider,
+/// reused until the document changes.
+#[tauri::command]
+async fn summarize_document(
+    app_handle: tauri::AppHandle,
+    path: String,
+    length: Option<summarize::SummaryLength>,
+) -> AppResult<summarize::Summary> {
+    let config = ai::load_ai_config(&app_handle)?;
+    summarize::summarize_document(&config, Path::new(&path), length.unwrap_or_default()).await
+}
+
+#[tauri::command]
+fn get_ai_config(app_handle: tauri::AppHandle) -> AppResult<ai::AiConfig> {
+    ai::load_ai_config(&app_handle)
+}
+
+#[tauri::command]
+fn configure_ai(app_handle: tauri::AppHandle, config: ai::AiConfig) -> AppResult<()> {
+    ai::save_ai_config(&app_handle, &config)
+}
+
+#[tauri::command]
+async fn save_file(path: String, contents: String) -> AppResult<()> {
     match tokio::fs::write(&path, contents).await {
         Ok(_) => Ok(()),
-        Err(e) => Err(format!("Failed to save file: {}", e)),
+        Err(e) => Err(AppError::io(format!("Failed to save file: {}", e))),
     }
 }
 
 #[tauri::command]
-async fn load_file(path: String) -> Result<String, String> {
+async fn load_file(path: String) -> AppResult<String> {
     match tokio::fs::read_to_string(&path).await {
         Ok(content) => Ok(content),
-        Err(e) => Err(format!("Failed to load file: {}", e)),
+        Err(e) => Err(AppError::io(format!("Failed to load file: {}", e))),
     }
 }
 
 #[tauri::command]
-async fn save_document(document: DocumentData) -> Result<String, String> {
+async fn save_document(
+    app_handle: tauri::AppHandle,
+    cache: tauri::State<'_, doc_cache::DocumentCache>,
+    processors: tauri::State<'_, processors::ProcessorRegistry>,
+    node_types: tauri::State<'_, node_types::NodeTypeRegistry>,
+    mut document: DocumentData,
+    force: Option<bool>,
+) -> AppResult<document_version::SavedDocument> {
     let file_path = match &document.file_path {
         Some(path) => path.clone(),
         None => {
@@ -55,46 +237,419 @@ async fn save_document(document: DocumentData) -> Result<String, String> {
         }
     };
 
-    match tokio::fs::write(&file_path, &document.content).await {
-        Ok(_) => Ok(file_path),
-        Err(e) => Err(format!("Failed to save document: {}", e)),
+    if !force.unwrap_or(false) {
+        document_version::check_for_conflict(
+            Path::new(&file_path),
+            document.modified,
+            document.content_hash.as_deref(),
+            &document.content,
+        ).await?;
+    }
+
+    let mut context = processors::DocumentContext::new(Path::new(&file_path), document.content);
+    for error in processors.on_save(&mut context) {
+        tracing::warn!("Processor '{}' failed on save: {}", error.processor, error.message);
+    }
+    document.content = context.content;
+
+    let report = content_schema::check(&document.content, true);
+    if !report.is_valid() {
+        return Err(AppError::validation("The document doesn't match the canvas schema")
+            .with_details(serde_json::json!({ "errors": report.errors })));
+    }
+    if let Some(repaired) = report.content {
+        document.content = repaired;
+    }
+
+    let mut errors = canvas_graph::validate(&document.content);
+    errors.extend(node_types.validate(&document.content));
+    if !errors.is_empty() {
+        return Err(AppError::validation("Some nodes don't match their node type")
+            .with_details(serde_json::json!({ "errors": errors })));
+    }
+
+    // What's on disk now, to tell how much this save added
+    let journal = activity::enabled(&app_handle);
+    let created = !Path::new(&file_path).exists();
+    let previous_words = if journal && !created {
+        cache.load(&file_path).map(|d| document_text::word_count(&d.content)).unwrap_or(0)
+    } else {
+        0
+    };
+
+    let compress = document_format::compression_enabled(&app_handle);
+    let bytes = document_format::encode(Path::new(&file_path), &document.content, compress)?;
+    match tokio::fs::write(&file_path, bytes).await {
+        Ok(_) => {
+            let modified = document_version::modified_millis(Path::new(&file_path)).unwrap_or(0);
+            let content_hash = document_version::content_hash(&document.content);
+            if journal {
+                let words = document_text::word_count(&document.content);
+                if let Err(e) = activity::record_save(&app_handle, &file_path, created, previous_words, words) {
+                    tracing::warn!("Failed to record activity: {}", e);
+                }
+            }
+            if let Err(e) = link_index::index_document(&app_handle, Path::new(&file_path), &document.content) {
+                tracing::warn!("Failed to index links: {}", e);
+            }
+            // Embedding can take a while, so it waits until the user stops
+            // typing and doesn't hold up the save
+            let (embed_handle, embed_path, embed_content) = (app_handle.clone(), file_path.clone(), document.content.clone());
+            tauri::async_runtime::spawn(async move {
+                idle::wait_until_idle(&embed_handle).await;
+                if let Err(e) = embeddings::index_document(&embed_handle, Path::new(&embed_path), &embed_content).await {
+                    tracing::warn!("Failed to embed document: {}", e);
+                }
+            });
+            cache.store(&file_path, document.content, modified);
+            Ok(document_version::SavedDocument {
+                modified,
+                content_hash,
+                file_path,
+            })
+        }
+        Err(e) => {
+            cache.invalidate(&file_path);
+            Err(AppError::io(format!("Failed to save document: {}", e)).at(&file_path))
+        }
     }
 }
 
 #[tauri::command]
-async fn load_document(path: String) -> Result<DocumentData, String> {
-    let content = match tokio::fs::read_to_string(&path).await {
-        Ok(content) => content,
-        Err(e) => return Err(format!("Failed to load document: {}", e)),
+async fn load_document(
+    app_handle: tauri::AppHandle,
+    prefetch_state: tauri::State<'_, prefetch::PrefetchState>,
+    path: String,
+) -> AppResult<DocumentData> {
+    use tauri::Manager;
+
+    let cached = {
+        let app_handle = app_handle.clone();
+        let path = path.clone();
+        tauri::async_runtime::spawn_blocking(move || app_handle.state::<doc_cache::DocumentCache>().load(&path))
+            .await
+            .map_err(|e| AppError::internal(format!("Failed to load document: {}", e)))??
     };
 
+    let lock = document_lock::acquire(&app_handle.state::<document_lock::DocumentLockState>(), Path::new(&path))
+        .unwrap_or_else(|e| {
+            tracing::warn!("Failed to lock document: {}", e);
+            None
+        });
+    if let Some(lock) = &lock {
+        tracing::info!("{} is open in process {} on {}", path, lock.pid, lock.hostname);
+    }
+
+    if let Err(e) = prefetch::record_open(&app_handle, &prefetch_state, &path) {
+        tracing::warn!("Failed to record document open: {}", e);
+    }
+    if let Err(e) = activity::record(&app_handle, activity::ActivityKind::DocumentOpened, &path, None) {
+        tracing::warn!("Failed to record activity: {}", e);
+    }
+
     let file_name = Path::new(&path)
         .file_stem()
         .and_then(|name| name.to_str())
         .unwrap_or("Untitled")
         .to_string();
 
+    let mut schema = content_schema::check(&cached.content, true);
+    for error in &schema.errors {
+        tracing::warn!("{}: {} should be {}, found {}", path, error.path, error.expected, error.found);
+    }
+    let content = schema.content.take().unwrap_or_else(|| cached.content.clone());
+
+    let mut context = processors::DocumentContext::new(Path::new(&path), content);
+    for error in app_handle.state::<processors::ProcessorRegistry>().on_load(&mut context) {
+        tracing::warn!("Processor '{}' failed on load: {}", error.processor, error.message);
+    }
+
     Ok(DocumentData {
         id: format!("doc-{}", chrono::Utc::now().timestamp_millis()),
         title: file_name,
-        content,
+        content: context.content,
+        modified: Some(cached.modified),
+        content_hash: Some(cached.content_hash.clone()),
+        metadata: context.metadata,
+        properties: document_metadata::load(Path::new(&path)).unwrap_or_else(|e| {
+            tracing::warn!("Failed to load document metadata: {}", e);
+            document_metadata::DocumentMetadata::default()
+        }),
+        schema,
+        lock,
         file_path: Some(path),
     })
 }
 
+/// Writes a `.canvasb` copy of a `.canvas` document or the other way
+/// round, returning the new file's path.
 #[tauri::command]
-fn get_settings(app_handle: tauri::AppHandle) -> Result<settings_manager::Settings, String> {
-    settings_manager::load_settings(&app_handle)
+async fn convert_document(app_handle: tauri::AppHandle, path: String) -> AppResult<String> {
+    let compress = document_format::compression_enabled(&app_handle);
+    tauri::async_runtime::spawn_blocking(move || document_format::convert_document(Path::new(&path), compress))
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to convert document: {}", e)))?
+        .map(|dest| dest.to_string_lossy().to_string())
 }
 
+/// The latest log entries at `level` ("info" unless given) or worse,
+/// newest first, for attaching to bug reports.
 #[tauri::command]
-fn set_window_decorations(app_handle: tauri::AppHandle, decorations: bool) -> Result<(), String> {
-    let mut settings = settings_manager::load_settings(&app_handle)?;
-    
-    settings.window_decorations = decorations;
-    
-    // Save the new settings
-    settings_manager::save_settings(&app_handle, &settings)?;
+async fn get_recent_logs(app_handle: tauri::AppHandle, level: Option<logging::LogLevel>, limit: Option<usize>) -> AppResult<Vec<logging::LogEntry>> {
+    let dir = logging::get_logs_dir(&app_handle)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        logging::recent_logs(&dir, level.unwrap_or_default(), limit.unwrap_or(logging::DEFAULT_LIMIT))
+    })
+    .await
+    .map_err(|e| AppError::internal(format!("Failed to read logs: {}", e)))?
+}
+
+#[tauri::command]
+fn open_log_folder(app_handle: tauri::AppHandle) -> AppResult<()> {
+    use tauri_plugin_opener::OpenerExt;
+    let dir = logging::get_logs_dir(&app_handle)?;
+    app_handle
+        .opener()
+        .open_path(dir.to_string_lossy(), None::<&str>)
+        .map_err(|e| AppError::io(format!("Failed to open {}: {}", dir.display(), e)))
+}
+
+/// Called by the editor on input, throttled, so background work can wait
+/// for a pause in typing.
+#[tauri::command]
+fn notify_activity(idle_state: tauri::State<'_, idle::IdleState>) {
+    idle_state.notify();
+}
+
+/// Whether the user has paused long enough for an autosave.
+#[tauri::command]
+fn get_idle_status(app_handle: tauri::AppHandle) -> idle::IdleStatus {
+    idle::idle_status(&app_handle)
+}
+
+/// Called when a document is closed so other instances stop warning about it.
+#[tauri::command]
+fn release_document_lock(locks: tauri::State<'_, document_lock::DocumentLockState>, path: String) -> AppResult<()> {
+    document_lock::release(&locks, Path::new(&path))
+}
+
+/// Moves a document into the app's trash instead of deleting it.
+#[tauri::command]
+fn trash_document(
+    app_handle: tauri::AppHandle,
+    cache: tauri::State<'_, doc_cache::DocumentCache>,
+    locks: tauri::State<'_, document_lock::DocumentLockState>,
+    trash_state: tauri::State<'_, trash::TrashState>,
+    path: String,
+) -> AppResult<trash::TrashEntry> {
+    document_lock::release(&locks, Path::new(&path))?;
+    let entry = trash::trash_document(&trash_state, &trash::get_trash_dir(&app_handle)?, Path::new(&path))?;
+    cache.invalidate(&path);
+    Ok(entry)
+}
+
+/// Renames or moves a document, updating the links to it in other
+/// documents.
+#[tauri::command]
+async fn rename_document(
+    app_handle: tauri::AppHandle,
+    cache: tauri::State<'_, doc_cache::DocumentCache>,
+    locks: tauri::State<'_, document_lock::DocumentLockState>,
+    old: String,
+    new: String,
+) -> AppResult<rename::RenameReport> {
+    document_lock::release(&locks, Path::new(&old))?;
+    let handle = app_handle.clone();
+    let report = tauri::async_runtime::spawn_blocking(move || rename::rename_document(&handle, Path::new(&old), Path::new(&new)))
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to rename document: {}", e)))??;
+    cache.invalidate(&report.old_path);
+    for reference in &report.updated {
+        cache.invalidate(&reference.path);
+    }
+    Ok(report)
+}
+
+/// Everything the file browser shows for a folder in one call, instead of
+/// loading each document for its title.
+#[tauri::command]
+async fn list_documents(app_handle: tauri::AppHandle, dir: String, recursive: Option<bool>) -> AppResult<Vec<document_list::DocumentEntry>> {
+    let workspace = cli::workspace_root(&app_handle).ok();
+    tauri::async_runtime::spawn_blocking(move || {
+        document_list::list_documents(Path::new(&dir), recursive.unwrap_or(false), workspace.as_deref())
+    })
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to list documents: {}", e)))?
+}
+
+/// Zips a workspace for backup or sharing; "archive-progress" and
+/// "operation-progress" events report how far along it is.
+#[tauri::command]
+async fn export_workspace_archive(
+    app_handle: tauri::AppHandle,
+    dir: String,
+    dest: String,
+    options: Option<archive::ArchiveOptions>,
+    operation_id: Option<String>,
+) -> AppResult<archive::ArchiveSummary> {
+    let operation = operations::Operation::start(&app_handle, operations::OperationKind::ArchiveExport, operation_id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        archive::export_workspace_archive(&app_handle, Path::new(&dir), Path::new(&dest), &options.unwrap_or_default(), &operation)
+    })
+    .await
+    .map_err(|e| AppError::internal(format!("Failed to export workspace: {}", e)))?
+}
+
+/// Extracts an exported workspace archive into `dest_dir`, reporting
+/// progress like exporting does.
+#[tauri::command]
+async fn import_workspace_archive(
+    app_handle: tauri::AppHandle,
+    zip_path: String,
+    dest_dir: String,
+    operation_id: Option<String>,
+) -> AppResult<archive::ImportedArchive> {
+    let operation = operations::Operation::start(&app_handle, operations::OperationKind::ArchiveImport, operation_id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        archive::import_workspace_archive(&app_handle, Path::new(&zip_path), Path::new(&dest_dir), &operation)
+    })
+    .await
+    .map_err(|e| AppError::internal(format!("Failed to import workspace: {}", e)))?
+}
+
+/// Exports, imports, syncs and scans that are still running, for a
+/// progress panel opened after they started.
+#[tauri::command]
+fn list_active_operations(state: tauri::State<'_, operations::OperationsState>) -> AppResult<Vec<operations::OperationProgress>> {
+    state.list()
+}
+
+/// Stops the operation `id` at its next step; partial output is removed.
+/// Returns whether it was still running.
+#[tauri::command]
+fn cancel_operation(state: tauri::State<'_, operations::OperationsState>, id: String) -> AppResult<bool> {
+    state.cancel(&id)
+}
+
+/// Queues background work, run by priority once earlier jobs are done.
+/// Returns the job id; the job reports progress as an operation with
+/// that id.
+#[tauri::command]
+fn enqueue_job(app_handle: tauri::AppHandle, kind: jobs::JobKind, priority: Option<jobs::JobPriority>) -> AppResult<String> {
+    jobs::enqueue(&app_handle, kind, priority.unwrap_or_default())
+}
+
+/// Whether a job is queued, running or finished, and why it failed.
+#[tauri::command]
+fn get_job_status(state: tauri::State<'_, jobs::JobsState>, id: String) -> AppResult<jobs::JobStatus> {
+    state.status(&id)
+}
+
+#[tauri::command]
+fn list_templates(app_handle: tauri::AppHandle) -> AppResult<Vec<templates::TemplateInfo>> {
+    templates::available_templates(&app_handle)
+}
+
+#[tauri::command]
+fn save_as_template(app_handle: tauri::AppHandle, document: String, name: String) -> AppResult<templates::TemplateInfo> {
+    templates::save_as_template(&templates::get_templates_dir(&app_handle)?, Path::new(&document), &name)
+}
+
+/// Creates a document from the template `name` in `dir`, filling in its
+/// title and dates. Returns the new document's path.
+#[tauri::command]
+fn create_from_template(app_handle: tauri::AppHandle, name: String, dir: String, title: String) -> AppResult<String> {
+    let template = templates::find_template(&app_handle, &name)?;
+    let compress = document_format::compression_enabled(&app_handle);
+    let path = templates::create_from_template(&template, Path::new(&dir), &title, compress)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+fn list_trash(app_handle: tauri::AppHandle) -> AppResult<Vec<trash::TrashEntry>> {
+    trash::list_trash(&trash::get_trash_dir(&app_handle)?)
+}
+
+/// Returns where the document was restored to, which differs from where it
+/// was when something else has taken that name since.
+#[tauri::command]
+fn restore_from_trash(app_handle: tauri::AppHandle, state: tauri::State<'_, trash::TrashState>, id: String) -> AppResult<String> {
+    let path = trash::restore_from_trash(&state, &trash::get_trash_dir(&app_handle)?, &id)?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Permanently deletes trashed documents, all of them or those trashed more
+/// than `older_than_days` ago.
+#[tauri::command]
+fn empty_trash(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, trash::TrashState>,
+    older_than_days: Option<i64>,
+) -> AppResult<usize> {
+    trash::empty_trash(&state, &trash::get_trash_dir(&app_handle)?, older_than_days)
+}
+
+#[tauri::command]
+fn get_document_lock(path: String) -> Option<document_lock::DocumentLock> {
+    document_lock::foreign_lock(Path::new(&path))
+}
+
+#[tauri::command]
+fn get_document_metadata(path: String) -> AppResult<document_metadata::DocumentMetadata> {
+    document_metadata::load(Path::new(&path))
+}
+
+#[tauri::command]
+fn update_document_metadata(
+    app_handle: tauri::AppHandle,
+    path: String,
+    patch: document_metadata::MetadataPatch,
+) -> AppResult<document_metadata::DocumentMetadata> {
+    let changes_tags = patch.tags.is_some();
+    let metadata = document_metadata::update(Path::new(&path), patch)?;
+    if changes_tags {
+        tags::index_document(&app_handle, Path::new(&path), &metadata.tags)?;
+    }
+    Ok(metadata)
+}
+
+#[tauri::command]
+fn add_tag(app_handle: tauri::AppHandle, path: String, tag: String) -> AppResult<Vec<String>> {
+    tags::add_tag(&app_handle, Path::new(&path), &tag)
+}
+
+#[tauri::command]
+fn remove_tag(app_handle: tauri::AppHandle, path: String, tag: String) -> AppResult<Vec<String>> {
+    tags::remove_tag(&app_handle, Path::new(&path), &tag)
+}
+
+#[tauri::command]
+fn list_tags(app_handle: tauri::AppHandle) -> AppResult<Vec<tags::TagCount>> {
+    tags::list_tags(&app_handle)
+}
+
+#[tauri::command]
+fn find_documents_by_tag(app_handle: tauri::AppHandle, query: String) -> AppResult<Vec<String>> {
+    tags::find_documents_by_tag(&app_handle, &query)
+}
+
+#[tauri::command]
+fn get_settings(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, settings_manager::SettingsState>,
+) -> AppResult<settings_manager::Settings> {
+    settings_manager::current_settings(&app_handle, &state)
+}
+
+#[tauri::command]
+fn set_window_decorations(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, settings_manager::SettingsState>,
+    decorations: bool,
+) -> AppResult<()> {
+    let settings = settings_manager::update_settings(&app_handle, &state, |settings| {
+        settings.window_decorations = decorations;
+    })?;
     
     // Apply the window decorations immediately
     settings_manager::apply_window_settings(&app_handle, &settings)?;
@@ -103,13 +658,14 @@ fn set_window_decorations(app_handle: tauri::AppHandle, decorations: bool) -> Re
 }
 
 #[tauri::command]
-fn set_window_maximized(app_handle: tauri::AppHandle, maximized: bool) -> Result<(), String> {
-    let mut settings = settings_manager::load_settings(&app_handle)?;
-    
-    settings.window_maximized = maximized;
-    
-    // Save the new settings
-    settings_manager::save_settings(&app_handle, &settings)?;
+fn set_window_maximized(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, settings_manager::SettingsState>,
+    maximized: bool,
+) -> AppResult<()> {
+    let settings = settings_manager::update_settings(&app_handle, &state, |settings| {
+        settings.window_maximized = maximized;
+    })?;
     
     // Apply the window settings immediately
     settings_manager::apply_window_settings(&app_handle, &settings)?;
@@ -118,13 +674,14 @@ fn set_window_maximized(app_handle: tauri::AppHandle, maximized: bool) -> Result
 }
 
 #[tauri::command]
-fn set_window_fullscreen(app_handle: tauri::AppHandle, fullscreen: bool) -> Result<(), String> {
-    let mut settings = settings_manager::load_settings(&app_handle)?;
-    
-    settings.window_fullscreen = fullscreen;
-    
-    // Save the new settings
-    settings_manager::save_settings(&app_handle, &settings)?;
+fn set_window_fullscreen(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, settings_manager::SettingsState>,
+    fullscreen: bool,
+) -> AppResult<()> {
+    let settings = settings_manager::update_settings(&app_handle, &state, |settings| {
+        settings.window_fullscreen = fullscreen;
+    })?;
     
     // Apply the window settings immediately
     settings_manager::apply_window_settings(&app_handle, &settings)?;
@@ -133,67 +690,1370 @@ fn set_window_fullscreen(app_handle: tauri::AppHandle, fullscreen: bool) -> Resu
 }
 
 #[tauri::command]
-fn get_shortcuts(app_handle: tauri::AppHandle) -> Result<shortcuts_manager::Shortcuts, String> {
-    shortcuts_manager::load_shortcuts(&app_handle)
+fn get_settings_schema() -> Vec<settings_manager::SettingSchema> {
+    settings_manager::settings_schema()
 }
 
 #[tauri::command]
-fn get_config_file_path(app_handle: tauri::AppHandle) -> Result<String, String> {
-    use tauri::Manager;
-    
-    let app_data_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-    
-    let config_path = app_data_dir.join("settings.conf");
-    config_path.to_str()
-        .ok_or("Invalid config path".to_string())
-        .map(|s| s.to_string())
+fn set_theme(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, settings_manager::SettingsState>,
+    theme: settings_manager::Theme,
+    accent_color: Option<String>,
+) -> AppResult<()> {
+    if let Some(color) = &accent_color {
+        settings_manager::validate_color(color)?;
+    }
+
+    let settings = settings_manager::update_settings(&app_handle, &state, |settings| {
+        settings.theme = theme;
+        if let Some(color) = accent_color {
+            settings.accent_color = color;
+        }
+    })?;
+
+    settings_manager::emit_theme_changed(&app_handle, &settings)
 }
 
 #[tauri::command]
-fn show_window_when_ready(app_handle: tauri::AppHandle) -> Result<(), String> {
-    use tauri::Manager;
-    
-    if let Some(window) = app_handle.get_webview_window("main") {
-        window.show().map_err(|e| e.to_string())?;
-        window.set_focus().map_err(|e| e.to_string())?;
-        println!("🚀 Window shown from Rust backend");
-    }
+fn set_always_on_top(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, settings_manager::SettingsState>,
+    always_on_top: bool,
+) -> AppResult<()> {
+    let settings = settings_manager::update_settings(&app_handle, &state, |settings| {
+        settings.window_always_on_top = always_on_top;
+    })?;
+
+    // Apply the window settings immediately
+    settings_manager::apply_window_settings(&app_handle, &settings)?;
+
     Ok(())
 }
 
+#[tauri::command]
+fn set_window_opacity(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, settings_manager::SettingsState>,
+    opacity: f64,
+) -> AppResult<()> {
+    settings_manager::validate_opacity(opacity)?;
+    let settings = settings_manager::update_settings(&app_handle, &state, |settings| {
+        settings.window_opacity = opacity;
+    })?;
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .plugin(tauri_plugin_opener::init())
-        .plugin(tauri_plugin_dialog::init())
-        .plugin(tauri_plugin_store::Builder::default().build())
-        .plugin(tauri_plugin_shell::init())
-        .setup(|app| {
-            let app_handle = app.handle().clone();
-            if let Ok(settings) = settings_manager::load_settings(&app_handle) {
-                let _ = settings_manager::apply_window_settings(&app_handle, &settings);
+    // Apply the window settings immediately
+    settings_manager::apply_window_settings(&app_handle, &settings)?;
+
+    Ok(())
+}
+
+#[tauri::command]
+fn set_zoom_level(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, settings_manager::SettingsState>,
+    zoom_level: f64,
+) -> AppResult<()> {
+    settings_manager::validate_zoom_level(zoom_level)?;
+    let settings = settings_manager::update_settings(&app_handle, &state, |settings| {
+        settings.zoom_level = zoom_level;
+    })?;
+
+    // Apply the window settings immediately
+    settings_manager::apply_window_settings(&app_handle, &settings)?;
+
+    Ok(())
+}
+
+#[tauri::command]
+fn get_system_locale() -> String {
+    settings_manager::system_locale()
+}
+
+#[tauri::command]
+fn set_language(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, settings_manager::SettingsState>,
+    language: String,
+) -> AppResult<()> {
+    settings_manager::validate_language(&language)?;
+    settings_manager::update_settings(&app_handle, &state, |settings| {
+        settings.language = language;
+    })?;
+    Ok(())
+}
+
+#[tauri::command]
+fn flush_settings(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, settings_manager::SettingsState>,
+) -> AppResult<()> {
+    settings_manager::flush_settings(&app_handle, &state)
+}
+
+#[tauri::command]
+fn update_settings(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, settings_manager::SettingsState>,
+    patch: settings_manager::SettingsPatch,
+) -> AppResult<settings_manager::Settings> {
+    if patch.is_empty() {
+        return settings_manager::current_settings(&app_handle, &state);
+    }
+    patch.validate()?;
+
+    let settings = settings_manager::update_settings(&app_handle, &state, |settings| patch.apply(settings))?;
+    // Reapplying resets the window to its saved geometry, so only do it when needed
+    if patch.changes_window() {
+        settings_manager::apply_window_settings(&app_handle, &settings)?;
+    }
+    if patch.changes_theme() {
+        settings_manager::emit_theme_changed(&app_handle, &settings)?;
+    }
+    if patch.sync_config_repo.is_some() {
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            if let Err(e) = config_repo::sync(&app_handle) {
+                tracing::warn!("Failed to sync config repository: {}", e);
             }
-            Ok(())
-        })
-        .invoke_handler(tauri::generate_handler![
-            greet, 
-            ping_backend, 
-            save_file, 
-            load_file, 
-            save_document, 
-            load_document,
-            get_settings,
-            get_shortcuts,
-            set_window_decorations,
-            set_window_maximized,
-            set_window_fullscreen,
-            get_config_file_path,
-            show_window_when_ready
-        ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        });
+    }
+
+    Ok(settings)
+}
+
+#[tauri::command]
+fn list_monitors(app_handle: tauri::AppHandle) -> AppResult<Vec<monitors::MonitorInfo>> {
+    use tauri::Manager;
+    let window = app_handle.get_webview_window("main").ok_or_else(|| AppError::not_found("Main window not found"))?;
+    monitors::list_monitors(&window)
+}
+
+#[tauri::command]
+fn move_window_to_monitor(app_handle: tauri::AppHandle, index: usize) -> AppResult<()> {
+    use tauri::Manager;
+    let window = app_handle.get_webview_window("main").ok_or_else(|| AppError::not_found("Main window not found"))?;
+    monitors::move_to_monitor(&window, index)
+}
+
+#[tauri::command]
+fn get_titlebar_config(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, settings_manager::SettingsState>,
+) -> AppResult<titlebar::TitlebarConfig> {
+    let settings = settings_manager::current_settings(&app_handle, &state)?;
+    Ok(titlebar::titlebar_config(settings.window_decorations))
+}
+
+#[tauri::command]
+fn start_window_drag(app_handle: tauri::AppHandle) -> AppResult<()> {
+    use tauri::Manager;
+    let window = app_handle.get_webview_window("main").ok_or_else(|| AppError::not_found("Main window not found"))?;
+    titlebar::start_drag(&window)
+}
+
+#[tauri::command]
+fn toggle_maximize_on_double_click(app_handle: tauri::AppHandle) -> AppResult<bool> {
+    use tauri::Manager;
+    let window = app_handle.get_webview_window("main").ok_or_else(|| AppError::not_found("Main window not found"))?;
+    // The resize that follows records the new state in the settings
+    titlebar::toggle_maximize(&window)
+}
+
+#[tauri::command]
+fn get_shortcuts(app_handle: tauri::AppHandle) -> AppResult<shortcuts_manager::Shortcuts> {
+    shortcuts_manager::load_shortcuts(&app_handle)
+}
+
+#[tauri::command]
+fn set_shortcuts(
+    app_handle: tauri::AppHandle,
+    shortcuts: std::collections::BTreeMap<String, String>,
+) -> AppResult<shortcuts_manager::Shortcuts> {
+    shortcuts_manager::set_shortcuts(&app_handle, &shortcuts)
+}
+
+#[tauri::command]
+fn get_shortcut(app_handle: tauri::AppHandle, action: String) -> AppResult<Option<String>> {
+    let shortcuts = shortcuts_manager::load_shortcuts(&app_handle)?;
+    Ok(shortcuts.get(&action).map(|s| s.to_string()))
+}
+
+#[tauri::command]
+fn get_config_repo_status(
+    state: tauri::State<'_, config_repo::ConfigRepoState>,
+) -> AppResult<config_repo::ConfigRepoStatus> {
+    config_repo::status(&state)
+}
+
+#[tauri::command]
+async fn sync_config_repo_now(app_handle: tauri::AppHandle) -> AppResult<config_repo::ConfigRepoStatus> {
+    tauri::async_runtime::spawn_blocking(move || config_repo::sync(&app_handle))
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to sync config repository: {}", e)))?
+}
+
+#[tauri::command]
+fn list_shortcut_presets(app_handle: tauri::AppHandle) -> AppResult<Vec<shortcuts_manager::PresetInfo>> {
+    shortcuts_manager::list_presets(&app_handle)
+}
+
+#[tauri::command]
+fn apply_shortcut_preset(
+    app_handle: tauri::AppHandle,
+    name: String,
+) -> AppResult<shortcuts_manager::Shortcuts> {
+    shortcuts_manager::apply_preset(&app_handle, &name)
+}
+
+#[tauri::command]
+fn match_shortcut_step(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, shortcuts_manager::ChordState>,
+    step: String,
+) -> AppResult<shortcuts_manager::ChordMatch> {
+    shortcuts_manager::match_step(&app_handle, &state, &step)
+}
+
+#[tauri::command]
+fn cancel_shortcut_chord(state: tauri::State<'_, shortcuts_manager::ChordState>) -> AppResult<()> {
+    shortcuts_manager::cancel_chord(&state)
+}
+
+#[tauri::command]
+fn list_commands(
+    app_handle: tauri::AppHandle,
+    registry: tauri::State<'_, command_registry::CommandRegistry>,
+    query: Option<String>,
+    limit: Option<usize>,
+) -> AppResult<Vec<command_registry::CommandMatch>> {
+    let shortcuts = shortcuts_manager::load_shortcuts(&app_handle)?;
+    Ok(registry.search(&shortcuts, query.as_deref().unwrap_or(""), limit.unwrap_or(command_registry::DEFAULT_LIMIT)))
+}
+
+#[tauri::command]
+fn register_command(
+    registry: tauri::State<'_, command_registry::CommandRegistry>,
+    command: command_registry::CommandInfo,
+) -> AppResult<()> {
+    registry.register(command)
+}
+
+#[tauri::command]
+fn unregister_command(registry: tauri::State<'_, command_registry::CommandRegistry>, id: String) -> bool {
+    registry.unregister(&id)
+}
+
+#[cfg(desktop)]
+#[tauri::command]
+fn get_global_shortcuts(
+    state: tauri::State<'_, global_shortcuts::GlobalShortcutState>,
+) -> AppResult<global_shortcuts::GlobalShortcutStatus> {
+    global_shortcuts::status(&state)
+}
+
+#[tauri::command]
+fn get_config_file_path(app_handle: tauri::AppHandle) -> AppResult<String> {
+    use tauri::Manager;
+    
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+    
+    let config_path = app_data_dir.join("settings.conf");
+    config_path.to_str()
+        .ok_or_else(|| AppError::config("Invalid config path"))
+        .map(|s| s.to_string())
+}
+
+#[tauri::command]
+fn take_pending_cli_commands(state: tauri::State<'_, cli::CliState>) -> AppResult<Vec<cli::CliCommand>> {
+    cli::take_pending(&state)
+}
+
+#[tauri::command]
+fn get_safe_mode_status(state: tauri::State<'_, safe_mode::SafeModeState>) -> AppResult<safe_mode::SafeModeStatus> {
+    safe_mode::status(&state)
+}
+
+#[tauri::command]
+fn frontend_ready(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, startup::StartupState>,
+) -> AppResult<startup::StartupStatus> {
+    startup::frontend_ready(&app_handle, &state)
+}
+
+#[tauri::command]
+fn get_onboarding_state(app_handle: tauri::AppHandle) -> AppResult<onboarding::OnboardingState> {
+    onboarding::get_onboarding_state(&app_handle)
+}
+
+#[tauri::command]
+fn complete_onboarding_step(
+    app_handle: tauri::AppHandle,
+    id: String,
+    actions: Option<Vec<onboarding::SetupAction>>,
+) -> AppResult<onboarding::OnboardingState> {
+    onboarding::complete_onboarding_step(&app_handle, &id, &actions.unwrap_or_default())
+}
+
+#[tauri::command]
+fn reset_onboarding(app_handle: tauri::AppHandle) -> AppResult<onboarding::OnboardingState> {
+    onboarding::reset_onboarding(&app_handle)
+}
+
+#[tauri::command]
+fn get_sync_config(app_handle: tauri::AppHandle) -> AppResult<sync_manager::SyncConfig> {
+    sync_manager::load_sync_config(&app_handle)
+}
+
+#[tauri::command]
+fn configure_sync(app_handle: tauri::AppHandle, config: sync_manager::SyncConfig) -> AppResult<()> {
+    if !std::path::Path::new(&config_parser::expand_value(&config.local_dir)).is_dir() {
+        return Err(AppError::not_found(format!("Workspace folder does not exist: {}", config.local_dir)));
+    }
+    sync_manager::save_sync_config(&app_handle, &config)
+}
+
+#[tauri::command]
+fn set_sync_credentials(app_handle: tauri::AppHandle, credentials: sync_manager::SyncCredentials) -> AppResult<()> {
+    sync_manager::set_credentials(&app_handle, credentials)
+}
+
+#[tauri::command]
+async fn sync_now(app_handle: tauri::AppHandle, operation_id: Option<String>) -> AppResult<sync_manager::SyncReport> {
+    let operation = operations::Operation::start(&app_handle, operations::OperationKind::Sync, operation_id)?;
+    sync_manager::sync(&app_handle, sync_manager::SyncDirection::Both, &operation).await
+}
+
+#[tauri::command]
+async fn sync_push(app_handle: tauri::AppHandle, operation_id: Option<String>) -> AppResult<sync_manager::SyncReport> {
+    let operation = operations::Operation::start(&app_handle, operations::OperationKind::Sync, operation_id)?;
+    sync_manager::sync(&app_handle, sync_manager::SyncDirection::Push, &operation).await
+}
+
+#[tauri::command]
+async fn sync_pull(app_handle: tauri::AppHandle, operation_id: Option<String>) -> AppResult<sync_manager::SyncReport> {
+    let operation = operations::Operation::start(&app_handle, operations::OperationKind::Sync, operation_id)?;
+    sync_manager::sync(&app_handle, sync_manager::SyncDirection::Pull, &operation).await
+}
+
+#[tauri::command]
+async fn get_sync_status(app_handle: tauri::AppHandle) -> AppResult<Vec<sync_manager::FileSyncEntry>> {
+    sync_manager::get_sync_status(&app_handle).await
+}
+
+#[tauri::command]
+async fn classify_clipboard() -> AppResult<clipboard::ClipboardContent> {
+    clipboard::classify_clipboard().await
+}
+
+/// Copies a file into the workspace's assets under a content-hash name and
+/// returns its path relative to the workspace.
+#[tauri::command]
+async fn import_asset(workspace: String, path: String) -> AppResult<String> {
+    tauri::async_runtime::spawn_blocking(move || assets::import_asset(Path::new(&workspace), Path::new(&path)))
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to import asset: {}", e)))?
+}
+
+/// A cached PNG preview of an image, at most `max_size` pixels wide and
+/// high (256 by default).
+#[tauri::command]
+async fn get_thumbnail(
+    app_handle: tauri::AppHandle,
+    asset_path: String,
+    max_size: Option<u32>,
+) -> AppResult<tauri::ipc::Response> {
+    let bytes = tauri::async_runtime::spawn_blocking(move || {
+        thumbnails::get_thumbnail(&app_handle, Path::new(&asset_path), max_size)
+    })
+    .await
+    .map_err(|e| AppError::internal(format!("Failed to get thumbnail: {}", e)))??;
+    Ok(tauri::ipc::Response::new(bytes))
+}
+
+/// Starts recording a voice memo from the default microphone.
+#[tauri::command]
+fn start_audio_recording(recording: tauri::State<'_, audio::RecordingState>, workspace: String) -> AppResult<()> {
+    audio::start_recording(&recording, Path::new(&workspace))
+}
+
+/// Stops the recording and returns the memo saved in the workspace's assets.
+#[tauri::command]
+async fn stop_audio_recording(app_handle: tauri::AppHandle) -> AppResult<audio::AudioNote> {
+    tauri::async_runtime::spawn_blocking(move || {
+        use tauri::Manager;
+        audio::stop_recording(&app_handle.state::<audio::RecordingState>())
+    })
+    .await
+    .map_err(|e| AppError::internal(format!("Failed to stop recording: {}", e)))?
+}
+
+#[tauri::command]
+fn is_audio_recording(recording: tauri::State<'_, audio::RecordingState>) -> bool {
+    audio::is_recording(&recording)
+}
+
+#[tauri::command]
+fn get_audio_info(workspace: String, path: String) -> AppResult<audio::AudioNote> {
+    audio::audio_info(Path::new(&workspace), &path)
+}
+
+/// Reads text aloud with the platform's speech synthesizer. `rate` is
+/// relative to normal speed.
+#[tauri::command]
+fn speak_text(
+    speech_state: tauri::State<'_, speech::SpeechState>,
+    text: String,
+    voice: Option<String>,
+    rate: Option<f64>,
+) -> AppResult<()> {
+    speech::speak_text(&speech_state, &text, voice.as_deref(), rate)
+}
+
+#[tauri::command]
+fn stop_speaking(speech_state: tauri::State<'_, speech::SpeechState>) -> AppResult<()> {
+    speech::stop_speaking(&speech_state)
+}
+
+#[tauri::command]
+fn is_speaking(speech_state: tauri::State<'_, speech::SpeechState>) -> bool {
+    speech::is_speaking(&speech_state)
+}
+
+#[tauri::command]
+async fn list_voices() -> AppResult<Vec<speech::Voice>> {
+    tauri::async_runtime::spawn_blocking(speech::list_voices)
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to list voices: {}", e)))?
+}
+
+/// Misspelled words in `text` with suggestions. `lang` names a hunspell
+/// dictionary like "en_US"; loading one the first time takes a moment.
+#[tauri::command]
+async fn check_text(app_handle: tauri::AppHandle, text: String, lang: String) -> AppResult<Vec<spellcheck::Misspelling>> {
+    tauri::async_runtime::spawn_blocking(move || {
+        use tauri::Manager;
+        spellcheck::check_text(&app_handle, &app_handle.state::<spellcheck::SpellcheckState>(), &text, &lang)
+    })
+    .await
+    .map_err(|e| AppError::internal(format!("Failed to check spelling: {}", e)))?
+}
+
+#[tauri::command]
+fn add_to_dictionary(app_handle: tauri::AppHandle, word: String) -> AppResult<()> {
+    spellcheck::add_to_dictionary(&app_handle, &word)
+}
+
+#[tauri::command]
+fn remove_from_dictionary(app_handle: tauri::AppHandle, word: String) -> AppResult<()> {
+    spellcheck::remove_from_dictionary(&app_handle, &word)
+}
+
+#[tauri::command]
+fn list_snippets(app_handle: tauri::AppHandle) -> AppResult<Vec<snippets::Snippet>> {
+    snippets::list_snippets(&snippets::get_snippets_path(&app_handle)?)
+}
+
+/// Adds a snippet or replaces the one with the same name.
+#[tauri::command]
+fn save_snippet(app_handle: tauri::AppHandle, snippet: snippets::NewSnippet) -> AppResult<snippets::Snippet> {
+    snippets::save_snippet(&snippets::get_snippets_path(&app_handle)?, snippet)
+}
+
+#[tauri::command]
+fn delete_snippet(app_handle: tauri::AppHandle, name: String) -> AppResult<bool> {
+    snippets::delete_snippet(&snippets::get_snippets_path(&app_handle)?, &name)
+}
+
+/// Snippets for the command palette, best match first.
+#[tauri::command]
+fn search_snippets(app_handle: tauri::AppHandle, query: Option<String>, limit: Option<usize>) -> AppResult<Vec<snippets::SnippetMatch>> {
+    let all = snippets::list_snippets(&snippets::get_snippets_path(&app_handle)?)?;
+    Ok(snippets::search_snippets(all, query.as_deref().unwrap_or(""), limit.unwrap_or(snippets::DEFAULT_LIMIT)))
+}
+
+/// Text in an image with word bounding boxes, via a local tesseract.
+#[tauri::command]
+async fn ocr_image(path: String, language: Option<String>) -> AppResult<ocr::OcrResult> {
+    tauri::async_runtime::spawn_blocking(move || ocr::ocr_image(Path::new(&path), language.as_deref()))
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to read text from image: {}", e)))?
+}
+
+#[tauri::command]
+fn clear_thumbnails(app_handle: tauri::AppHandle) -> AppResult<()> {
+    thumbnails::clear_thumbnails(&app_handle)
+}
+
+#[tauri::command]
+async fn list_assets(workspace: String) -> AppResult<Vec<assets::Asset>> {
+    tauri::async_runtime::spawn_blocking(move || assets::list_assets(Path::new(&workspace)))
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to list assets: {}", e)))?
+}
+
+#[tauri::command]
+async fn delete_unused_assets(workspace: String) -> AppResult<Vec<String>> {
+    tauri::async_runtime::spawn_blocking(move || assets::delete_unused_assets(Path::new(&workspace)))
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to delete unused assets: {}", e)))?
+}
+
+/// Saves the clipboard's image into the workspace and returns its path
+/// relative to the workspace, for embedding.
+#[tauri::command]
+async fn paste_clipboard_image(workspace: String) -> AppResult<String> {
+    tauri::async_runtime::spawn_blocking(move || clipboard::paste_image(Path::new(&workspace)))
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to paste image: {}", e)))?
+}
+
+#[tauri::command]
+fn get_deadlines(workspace: String, range: deadlines::DeadlineRange) -> AppResult<Vec<deadlines::DeadlineGroup>> {
+    deadlines::get_deadlines(Path::new(&workspace), &range)
+}
+
+// Async so it runs on the runtime the server's listener registers with
+#[tauri::command]
+async fn start_collab_session(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, collab::CollabState>,
+    document: String,
+) -> AppResult<collab::CollabSessionInfo> {
+    collab::start_session(&app_handle, &state, document)
+}
+
+#[tauri::command]
+async fn join_collab_session(
+    app_handle: tauri::AppHandle,
+    state: tauri::State<'_, collab::CollabState>,
+    host: String,
+    port: u16,
+    code: String,
+    name: String,
+) -> AppResult<collab::CollabSessionInfo> {
+    collab::join_session(&app_handle, &state, host, port, code, name).await
+}
+
+#[tauri::command]
+fn send_collab_edit(state: tauri::State<'_, collab::CollabState>, payload: serde_json::Value) -> AppResult<()> {
+    collab::send_edit(&state, payload)
+}
+
+#[tauri::command]
+fn get_collab_session(state: tauri::State<'_, collab::CollabState>) -> AppResult<Option<collab::CollabSessionInfo>> {
+    collab::session_info(&state)
+}
+
+#[tauri::command]
+fn stop_collab_session(state: tauri::State<'_, collab::CollabState>) -> AppResult<()> {
+    collab::stop_session(&state)
+}
+
+#[tauri::command]
+fn encode_local_update(
+    state: tauri::State<'_, crdt::CrdtState>,
+    doc_id: String,
+    content: String,
+    state_vector: Option<String>,
+) -> AppResult<crdt::EncodedUpdate> {
+    crdt::encode_local_update(&state, &doc_id, &content, state_vector.as_deref())
+}
+
+#[tauri::command]
+fn apply_remote_update(state: tauri::State<'_, crdt::CrdtState>, doc_id: String, update: String) -> AppResult<crdt::MergeResult> {
+    crdt::apply_remote_update(&state, &doc_id, &update)
+}
+
+#[tauri::command]
+fn close_crdt_document(state: tauri::State<'_, crdt::CrdtState>, doc_id: String) -> AppResult<()> {
+    crdt::close_document(&state, &doc_id)
+}
+
+/// Undo and redo steps saved with the document, so they survive a restart.
+#[tauri::command]
+fn get_undo_history(path: String) -> AppResult<undo_history::UndoHistory> {
+    undo_history::get_undo_history(Path::new(&path))
+}
+
+#[tauri::command]
+fn push_undo_batch(path: String, batch: undo_history::UndoBatch) -> AppResult<()> {
+    undo_history::push_undo_batch(Path::new(&path), batch)
+}
+
+/// Returns the step to reverse, or null when there's nothing to undo.
+#[tauri::command]
+fn undo_batch(path: String) -> AppResult<Option<undo_history::UndoBatch>> {
+    undo_history::undo(Path::new(&path))
+}
+
+#[tauri::command]
+fn redo_batch(path: String) -> AppResult<Option<undo_history::UndoBatch>> {
+    undo_history::redo(Path::new(&path))
+}
+
+#[tauri::command]
+fn clear_undo_history(path: String) -> AppResult<()> {
+    undo_history::clear_undo_history(Path::new(&path))
+}
+
+#[tauri::command]
+fn add_comment(app_handle: tauri::AppHandle, path: String, comment: comments::NewComment) -> AppResult<comments::CommentThread> {
+    comments::add_comment(&app_handle, Path::new(&path), comment)
+}
+
+#[tauri::command]
+fn list_comments(path: String) -> AppResult<Vec<comments::CommentThread>> {
+    comments::list_comments(Path::new(&path))
+}
+
+#[tauri::command]
+fn resolve_thread(app_handle: tauri::AppHandle, id: String, resolved: Option<bool>) -> AppResult<comments::CommentThread> {
+    comments::resolve_thread(&app_handle, &id, resolved.unwrap_or(true))
+}
+
+#[tauri::command]
+fn get_suggestion_mode(path: String) -> AppResult<bool> {
+    suggestions::is_enabled(Path::new(&path))
+}
+
+#[tauri::command]
+fn set_suggestion_mode(path: String, enabled: bool) -> AppResult<()> {
+    suggestions::set_enabled(Path::new(&path), enabled)
+}
+
+#[tauri::command]
+fn propose_edit(app_handle: tauri::AppHandle, path: String, author: String, content: String) -> AppResult<Option<suggestions::Suggestion>> {
+    suggestions::propose_edit(&app_handle, Path::new(&path), &author, &content)
+}
+
+#[tauri::command]
+fn list_suggestions(path: String) -> AppResult<Vec<suggestions::Suggestion>> {
+    suggestions::list_suggestions(Path::new(&path))
+}
+
+#[tauri::command]
+fn accept_suggestion(app_handle: tauri::AppHandle, id: String) -> AppResult<String> {
+    suggestions::accept_suggestion(&app_handle, &id)
+}
+
+#[tauri::command]
+fn reject_suggestion(app_handle: tauri::AppHandle, id: String) -> AppResult<()> {
+    suggestions::reject_suggestion(&app_handle, &id)
+}
+
+#[tauri::command]
+async fn export_with_comments(
+    app_handle: tauri::AppHandle,
+    cache: tauri::State<'_, doc_cache::DocumentCache>,
+    processors: tauri::State<'_, processors::ProcessorRegistry>,
+    node_types: tauri::State<'_, node_types::NodeTypeRegistry>,
+    export_targets: tauri::State<'_, export_targets::ExportTargetRegistry>,
+    path: String,
+    dest: String,
+    format: String,
+    operation_id: Option<String>,
+) -> AppResult<()> {
+    let operation = operations::Operation::start(&app_handle, operations::OperationKind::DocumentExport, operation_id)?;
+    let (format, transformer) = export_targets.resolve(&format)?;
+    let document = cache.load(&path)?;
+    let mut context = processors::DocumentContext::new(Path::new(&path), document.content.clone());
+    for error in processors.on_export(&mut context, format) {
+        tracing::warn!("Processor '{}' failed on export: {}", error.processor, error.message);
+    }
+    let content = node_types.prepare_export(&context.content);
+    operation.check_cancelled()?;
+    tauri::async_runtime::spawn_blocking(move || {
+        export::export_with_comments(Path::new(&path), &content, Path::new(&dest), format, transformer.as_deref(), |done, total, stage| {
+            operation.step(done, total, stage);
+            operation.check_cancelled()
+        })
+    })
+    .await
+    .map_err(|e| AppError::internal(format!("Failed to export document: {}", e)))?
+}
+
+#[tauri::command]
+fn list_export_targets(
+    export_targets: tauri::State<'_, export_targets::ExportTargetRegistry>,
+) -> Vec<export_targets::ExportTarget> {
+    export_targets.list()
+}
+
+#[tauri::command]
+fn register_export_target(
+    export_targets: tauri::State<'_, export_targets::ExportTargetRegistry>,
+    target: export_targets::TemplateTransformer,
+) -> AppResult<()> {
+    export_targets.register_template(target)
+}
+
+#[tauri::command]
+fn unregister_export_target(
+    export_targets: tauri::State<'_, export_targets::ExportTargetRegistry>,
+    id: String,
+) -> bool {
+    export_targets.unregister(&id)
+}
+
+#[tauri::command]
+fn get_node_type_schemas(node_types: tauri::State<'_, node_types::NodeTypeRegistry>) -> Vec<node_types::NodeTypeSchema> {
+    node_types.schemas()
+}
+
+#[tauri::command]
+fn register_node_type(
+    node_types: tauri::State<'_, node_types::NodeTypeRegistry>,
+    schema: node_types::NodeTypeSchema,
+) -> AppResult<()> {
+    node_types.register(schema)
+}
+
+/// Turns off a processor, built-in ones included, until the app restarts.
+#[tauri::command]
+fn unregister_processor(processors: tauri::State<'_, processors::ProcessorRegistry>, name: String) -> bool {
+    processors.unregister(&name)
+}
+
+#[tauri::command]
+fn get_processor_metrics(processors: tauri::State<'_, processors::ProcessorRegistry>) -> Vec<processors::ProcessorMetrics> {
+    processors.metrics()
+}
+
+#[tauri::command]
+fn get_retention_config(app_handle: tauri::AppHandle) -> AppResult<retention::RetentionConfig> {
+    retention::load_retention_config(&app_handle)
+}
+
+#[tauri::command]
+fn set_retention_config(app_handle: tauri::AppHandle, config: retention::RetentionConfig) -> AppResult<()> {
+    retention::save_retention_config(&app_handle, &config)
+}
+
+#[tauri::command]
+fn run_retention_now(app_handle: tauri::AppHandle) -> AppResult<retention::RetentionReport> {
+    retention::run_retention(&app_handle)
+}
+
+#[tauri::command]
+fn export_activity_journal(
+    app_handle: tauri::AppHandle,
+    range: Option<activity::ActivityRange>,
+    dest: String,
+) -> AppResult<usize> {
+    activity::export_journal(&app_handle, &range.unwrap_or_default(), Path::new(&dest))
+}
+
+#[tauri::command]
+async fn add_asset_reference(app_handle: tauri::AppHandle, path: String) -> AppResult<asset_refs::AssetReference> {
+    tauri::async_runtime::spawn_blocking(move || asset_refs::add_reference(&app_handle, Path::new(&path)))
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to add asset: {}", e)))?
+}
+
+#[tauri::command]
+fn list_asset_references(app_handle: tauri::AppHandle) -> AppResult<Vec<asset_refs::AssetReference>> {
+    asset_refs::list_assets(&app_handle)
+}
+
+#[tauri::command]
+async fn check_asset(app_handle: tauri::AppHandle, asset_id: String) -> AppResult<asset_refs::AssetCheck> {
+    tauri::async_runtime::spawn_blocking(move || asset_refs::check_asset(&app_handle, &asset_id))
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to check asset: {}", e)))?
+}
+
+#[tauri::command]
+async fn relink_asset(
+    app_handle: tauri::AppHandle,
+    asset_id: String,
+    new_path: String,
+) -> AppResult<asset_refs::AssetCheck> {
+    tauri::async_runtime::spawn_blocking(move || asset_refs::relink_asset(&app_handle, &asset_id, Path::new(&new_path)))
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to relink asset: {}", e)))?
+}
+
+#[tauri::command]
+async fn locate_asset(app_handle: tauri::AppHandle, asset_id: String, search_root: String) -> AppResult<Vec<String>> {
+    tauri::async_runtime::spawn_blocking(move || asset_refs::locate_asset(&app_handle, &asset_id, Path::new(&search_root)))
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to locate asset: {}", e)))?
+}
+
+#[tauri::command]
+fn get_document_outline(
+    cache: tauri::State<'_, doc_cache::DocumentCache>,
+    path: String,
+) -> AppResult<Vec<document_text::OutlineEntry>> {
+    Ok(cache.load(&path)?.outline.clone())
+}
+
+#[tauri::command]
+fn check_document_schema(content: String, repair: Option<bool>) -> content_schema::SchemaReport {
+    content_schema::check(&content, repair.unwrap_or(false))
+}
+
+#[tauri::command]
+fn get_canvas_graph(
+    cache: tauri::State<'_, doc_cache::DocumentCache>,
+    path: String,
+) -> AppResult<Option<canvas_graph::CanvasGraph>> {
+    Ok(canvas_graph::CanvasGraph::parse(&cache.load(&path)?.content))
+}
+
+#[tauri::command]
+fn get_node_count(
+    cache: tauri::State<'_, doc_cache::DocumentCache>,
+    path: String,
+    node_type: Option<String>,
+) -> AppResult<usize> {
+    let graph = canvas_graph::CanvasGraph::parse(&cache.load(&path)?.content).unwrap_or_default();
+    Ok(graph.node_count(node_type.as_deref()))
+}
+
+#[tauri::command]
+fn find_node(
+    cache: tauri::State<'_, doc_cache::DocumentCache>,
+    path: String,
+    query: canvas_graph::NodeQuery,
+) -> AppResult<Option<canvas_graph::CanvasNode>> {
+    let graph = canvas_graph::CanvasGraph::parse(&cache.load(&path)?.content).unwrap_or_default();
+    Ok(graph.find(&query).cloned())
+}
+
+#[tauri::command]
+fn get_backlinks(app_handle: tauri::AppHandle, path: String) -> AppResult<Vec<String>> {
+    link_index::get_backlinks(&app_handle, Path::new(&path))
+}
+
+#[tauri::command]
+fn get_outgoing_links(app_handle: tauri::AppHandle, path: String) -> AppResult<Vec<link_index::OutgoingLink>> {
+    link_index::get_outgoing_links(&app_handle, Path::new(&path))
+}
+
+#[tauri::command]
+fn get_document_links(cache: tauri::State<'_, doc_cache::DocumentCache>, path: String) -> AppResult<Vec<String>> {
+    Ok(cache.load(&path)?.links.clone())
+}
+
+#[tauri::command]
+async fn scan_workspace(app_handle: tauri::AppHandle, root: String, operation_id: Option<String>) -> AppResult<Vec<String>> {
+    let operation = operations::Operation::start(&app_handle, operations::OperationKind::WorkspaceScan, operation_id)?;
+    let documents = tauri::async_runtime::spawn_blocking(move || {
+        workspace::scan_workspace(&app_handle, Path::new(&root), &operation)
+    })
+    .await
+    .map_err(|e| AppError::internal(format!("Failed to scan workspace: {}", e)))??;
+
+    Ok(documents.iter().map(|path| path.to_string_lossy().to_string()).collect())
+}
+
+#[tauri::command]
+async fn fuzzy_find_files(
+    app_handle: tauri::AppHandle,
+    workspace: String,
+    query: String,
+    limit: Option<usize>,
+) -> AppResult<Vec<file_finder::FileMatch>> {
+    use tauri::Manager;
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app_handle.state::<file_finder::FileFinderState>();
+        file_finder::fuzzy_find_files(&state, Path::new(&workspace), &query, limit.unwrap_or(50))
+    })
+    .await
+    .map_err(|e| AppError::internal(format!("Failed to search files: {}", e)))?
+}
+
+#[tauri::command]
+async fn export_link_graph(
+    app_handle: tauri::AppHandle,
+    root: String,
+    dest: String,
+    format: link_graph::GraphFormat,
+    operation_id: Option<String>,
+) -> AppResult<link_graph::LinkGraphSummary> {
+    let operation = operations::Operation::start(&app_handle, operations::OperationKind::LinkGraphExport, operation_id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        link_graph::export_link_graph(Path::new(&root), Path::new(&dest), format, |done, total, path| {
+            operation.step(done, total, path.to_string_lossy());
+            operation.check_cancelled()
+        })
+    })
+    .await
+    .map_err(|e| AppError::internal(format!("Failed to export link graph: {}", e)))?
+}
+
+#[tauri::command]
+async fn generate_index_document(
+    app_handle: tauri::AppHandle,
+    source: index_documents::IndexSource,
+    dest: String,
+    group_by: Option<index_documents::GroupBy>,
+    keep_refreshed: Option<bool>,
+    operation_id: Option<String>,
+) -> AppResult<index_documents::IndexSummary> {
+    let operation = operations::Operation::start(&app_handle, operations::OperationKind::IndexDocument, operation_id)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        index_documents::generate_index_document(&app_handle, source, &dest, group_by.unwrap_or_default(), keep_refreshed, &operation)
+    })
+    .await
+    .map_err(|e| AppError::internal(format!("Failed to generate index document: {}", e)))?
+}
+
+#[tauri::command]
+fn list_index_documents(app_handle: tauri::AppHandle) -> AppResult<Vec<index_documents::IndexDocument>> {
+    index_documents::list_index_documents(&app_handle)
+}
+
+#[tauri::command]
+fn remove_index_document(app_handle: tauri::AppHandle, dest: String) -> AppResult<bool> {
+    index_documents::remove_index_document(&app_handle, &dest)
+}
+
+#[tauri::command]
+async fn read_asset(path: String) -> AppResult<tauri::ipc::Response> {
+    file_stream::read_bytes(Path::new(&path)).await
+}
+
+#[tauri::command]
+async fn stream_file(
+    path: String,
+    offset: Option<u64>,
+    length: Option<u64>,
+    chunk_size: Option<usize>,
+    on_chunk: tauri::ipc::Channel,
+) -> AppResult<u64> {
+    file_stream::stream_file(Path::new(&path), offset.unwrap_or(0), length, chunk_size, &on_chunk).await
+}
+
+#[tauri::command]
+async fn load_document_chunked(
+    path: String,
+    chunk_size: Option<usize>,
+    on_chunk: tauri::ipc::Channel,
+) -> AppResult<file_stream::DocumentHeader> {
+    file_stream::load_document_chunked(Path::new(&path), chunk_size, &on_chunk).await
+}
+
+#[tauri::command]
+async fn open_read_stream(
+    streams: tauri::State<'_, file_stream::StreamState>,
+    path: String,
+) -> AppResult<file_stream::ReadStreamInfo> {
+    file_stream::open_read_stream(&streams, Path::new(&path)).await
+}
+
+#[tauri::command]
+async fn read_chunk(
+    streams: tauri::State<'_, file_stream::StreamState>,
+    id: u64,
+    chunk_size: Option<usize>,
+) -> AppResult<tauri::ipc::Response> {
+    file_stream::read_chunk(&streams, id, chunk_size).await
+}
+
+#[tauri::command]
+async fn open_write_stream(
+    streams: tauri::State<'_, file_stream::StreamState>,
+    path: String,
+    overwrite: Option<bool>,
+) -> AppResult<u64> {
+    file_stream::open_write_stream(&streams, Path::new(&path), overwrite.unwrap_or(false)).await
+}
+
+/// Takes the chunk as the raw request body, with the stream's id in a
+/// `stream-id` header.
+#[tauri::command]
+async fn write_chunk(
+    streams: tauri::State<'_, file_stream::StreamState>,
+    request: tauri::ipc::Request<'_>,
+) -> AppResult<u64> {
+    file_stream::write_chunk(&streams, &request).await
+}
+
+#[tauri::command]
+async fn close_stream(streams: tauri::State<'_, file_stream::StreamState>, id: u64) -> AppResult<u64> {
+    file_stream::close_stream(&streams, id).await
+}
+
+#[tauri::command]
+async fn cancel_stream(streams: tauri::State<'_, file_stream::StreamState>, id: u64) -> AppResult<()> {
+    file_stream::cancel_stream(&streams, id).await
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    let mut builder = tauri::Builder::default();
+
+    // Registered first so a second launch hands its arguments to the running
+    // instance and exits before doing any work
+    #[cfg(desktop)]
+    {
+        builder = builder.plugin(tauri_plugin_single_instance::init(|app, args, cwd| {
+            match cli::parse(&args, Path::new(&cwd)) {
+                Ok(Some(command)) => cli::dispatch(app, command),
+                Ok(None) => cli::focus_main_window(app),
+                Err(e) => tracing::warn!("{}", e),
+            }
+        }));
+        builder = builder
+            .plugin(tauri_plugin_deep_link::init())
+            .plugin(tauri_plugin_global_shortcut::Builder::new().build())
+            .manage(global_shortcuts::GlobalShortcutState::default());
+    }
+
+    builder
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_store::Builder::default().build())
+        .plugin(tauri_plugin_shell::init())
+        .manage(logging::LogState::default())
+        .manage(settings_manager::SettingsState::default())
+        .manage(config_watcher::ConfigWatcherState::default())
+        .manage(collab::CollabState::default())
+        .manage(crdt::CrdtState::default())
+        .manage(prefetch::PrefetchState::default())
+        .manage(doc_cache::DocumentCache::default())
+        .manage(startup::StartupState::default())
+        .manage(safe_mode::SafeModeState::default())
+        .manage(cli::CliState::default())
+        .manage(activity::ActivityState::default())
+        .manage(shortcuts_manager::ChordState::default())
+        .manage(config_repo::ConfigRepoState::default())
+        .manage(processors::ProcessorRegistry::with_builtins())
+        .manage(node_types::NodeTypeRegistry::default())
+        .manage(export_targets::ExportTargetRegistry::default())
+        .manage(command_registry::CommandRegistry::default())
+        .manage(file_finder::FileFinderState::default())
+        .manage(link_index::LinkIndexState::default())
+        .manage(file_stream::StreamState::default())
+        .manage(document_lock::DocumentLockState::default())
+        .manage(audio::RecordingState::default())
+        .manage(speech::SpeechState::default())
+        .manage(spellcheck::SpellcheckState::default())
+        .manage(embeddings::EmbeddingState::default())
+        .manage(idle::IdleState::default())
+        .manage(operations::OperationsState::default())
+        .manage(jobs::JobsState::default())
+        .manage(trash::TrashState::default())
+        .setup(|app| {
+            use tauri::Manager;
+            let app_handle = app.handle().clone();
+            if let Err(e) = logging::init(&app_handle) {
+                eprintln!("{}", e);
+            }
+
+            // Launched to run a single command with no instance to forward it to
+            let args: Vec<String> = std::env::args().collect();
+            let cwd = std::env::current_dir().unwrap_or_default();
+            match cli::parse(&args, &cwd) {
+                // Links are for the UI even when they start the app
+                Ok(Some(command)) if args.get(1).is_some_and(|arg| deep_link::is_deep_link(arg)) => {
+                    cli::dispatch(&app_handle, command);
+                }
+                Ok(Some(cli::CliCommand::Open { paths })) => {
+                    cli::dispatch(&app_handle, cli::CliCommand::Open { paths });
+                }
+                Ok(Some(command)) => {
+                    match cli::run_headless(&app_handle, &command) {
+                        Ok(lines) => lines.iter().for_each(|line| println!("{}", line)),
+                        Err(e) => eprintln!("{}", e),
+                    }
+                    app_handle.exit(0);
+                    return Ok(());
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    eprintln!("{}", e);
+                    app_handle.exit(2);
+                    return Ok(());
+                }
+            }
+
+            #[cfg(desktop)]
+            {
+                use tauri_plugin_deep_link::DeepLinkExt;
+                // Installed builds register the scheme at install time
+                #[cfg(any(target_os = "linux", all(debug_assertions, windows)))]
+                if let Err(e) = app.deep_link().register_all() {
+                    tracing::warn!("Failed to register {} links: {}", deep_link::SCHEME, e);
+                }
+                // macOS delivers links as events instead of arguments
+                let links_handle = app_handle.clone();
+                app.deep_link().on_open_url(move |event| {
+                    for url in event.urls() {
+                        deep_link::handle(&links_handle, url.as_str());
+                    }
+                });
+            }
+
+            #[cfg(desktop)]
+            if let Err(e) = tray::build(&app_handle) {
+                tracing::warn!("Failed to add the tray icon: {}", e);
+            }
+
+            let safe_mode = safe_mode::begin_launch(&app_handle, &args);
+            if safe_mode.active {
+                tracing::warn!("Starting in safe mode ({:?})", safe_mode.reason);
+                settings_manager::enter_safe_mode(&app_handle.state::<settings_manager::SettingsState>())?;
+            }
+
+            startup::run(&app_handle, safe_mode.active);
+            // Jobs queued in safe mode keep until a normal launch runs them
+            if let Err(e) = jobs::restore(&app_handle) {
+                tracing::warn!("Failed to restore pending jobs: {}", e);
+            }
+            // Not optional: a lock that isn't refreshed looks abandoned
+            scheduler::spawn_periodic(
+                &app_handle,
+                "document-locks",
+                Duration::from_secs(document_lock::REFRESH_INTERVAL_SECS),
+                Duration::from_secs(document_lock::REFRESH_INTERVAL_SECS),
+                |app| document_lock::refresh_all(&app.state::<document_lock::DocumentLockState>()),
+            );
+            // Background work is non-essential and skipped in safe mode
+            if !safe_mode.active {
+                prefetch::spawn_prefetch(&app_handle, Duration::from_secs(2));
+                jobs::spawn_workers(&app_handle);
+                scheduler::spawn_periodic_when_idle(
+                    &app_handle,
+                    "retention",
+                    Duration::from_secs(60),
+                    Duration::from_secs(60 * 60),
+                    |app| retention::run_retention(app).map(|_| ()),
+                );
+                scheduler::spawn_periodic(
+                    &app_handle,
+                    "config-repo",
+                    Duration::from_secs(30),
+                    Duration::from_secs(30 * 60),
+                    |app| config_repo::sync(app).map(|_| ()),
+                );
+                scheduler::spawn_periodic_when_idle(
+                    &app_handle,
+                    "index-documents",
+                    Duration::from_secs(90),
+                    Duration::from_secs(15 * 60),
+                    index_documents::refresh_all,
+                );
+            }
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, position }) = event {
+                use tauri::Manager;
+                file_drop::handle_drop(window.app_handle(), paths.clone(), position.x, position.y);
+            }
+            if let tauri::WindowEvent::Moved(_) | tauri::WindowEvent::Resized(_) = event {
+                if let Err(e) = settings_manager::record_window_state(window) {
+                    tracing::warn!("Failed to record window state: {}", e);
+                }
+            }
+            if cfg!(target_os = "macos") {
+                if let tauri::WindowEvent::Resized(_) = event {
+                    let _ = settings_manager::refresh_traffic_lights(window);
+                }
+            }
+        })
+        .invoke_handler(tauri::generate_handler![
+            greet, 
+            ai_complete,
+            ai_complete_stream,
+            ai_cancel,
+            list_local_models,
+            check_local_ai,
+            semantic_search,
+            summarize_document,
+            get_ai_config,
+            configure_ai,
+            save_file, 
+            load_file, 
+            save_document, 
+            load_document,
+            convert_document,
+            get_recent_logs,
+            open_log_folder,
+            notify_activity,
+            get_idle_status,
+            release_document_lock,
+            trash_document,
+            rename_document,
+            list_documents,
+            export_workspace_archive,
+            import_workspace_archive,
+            list_active_operations,
+            cancel_operation,
+            enqueue_job,
+            get_job_status,
+            list_templates,
+            save_as_template,
+            create_from_template,
+            list_trash,
+            restore_from_trash,
+            empty_trash,
+            get_document_lock,
+            get_document_metadata,
+            update_document_metadata,
+            add_tag,
+            remove_tag,
+            list_tags,
+            find_documents_by_tag,
+            get_settings,
+            get_settings_schema,
+            set_theme,
+            flush_settings,
+            update_settings,
+            get_shortcuts,
+            get_shortcut,
+            set_shortcuts,
+            get_config_repo_status,
+            sync_config_repo_now,
+            list_shortcut_presets,
+            apply_shortcut_preset,
+            match_shortcut_step,
+            cancel_shortcut_chord,
+            list_commands,
+            register_command,
+            unregister_command,
+            #[cfg(desktop)]
+            get_global_shortcuts,
+            get_titlebar_config,
+            start_window_drag,
+            toggle_maximize_on_double_click,
+            list_monitors,
+            move_window_to_monitor,
+            set_window_decorations,
+            set_window_maximized,
+            set_window_fullscreen,
+            set_always_on_top,
+            set_window_opacity,
+            set_zoom_level,
+            get_system_locale,
+            set_language,
+            get_config_file_path,
+            frontend_ready,
+            get_onboarding_state,
+            complete_onboarding_step,
+            reset_onboarding,
+            get_safe_mode_status,
+            take_pending_cli_commands,
+            get_sync_config,
+            configure_sync,
+            set_sync_credentials,
+            sync_now,
+            sync_push,
+            sync_pull,
+            get_sync_status,
+            classify_clipboard,
+            paste_clipboard_image,
+            import_asset,
+            list_assets,
+            get_thumbnail,
+            clear_thumbnails,
+            ocr_image,
+            start_audio_recording,
+            stop_audio_recording,
+            is_audio_recording,
+            get_audio_info,
+            speak_text,
+            stop_speaking,
+            is_speaking,
+            list_voices,
+            check_text,
+            add_to_dictionary,
+            remove_from_dictionary,
+            list_snippets,
+            save_snippet,
+            delete_snippet,
+            search_snippets,
+            delete_unused_assets,
+            get_deadlines,
+            start_collab_session,
+            join_collab_session,
+            send_collab_edit,
+            get_collab_session,
+            stop_collab_session,
+            encode_local_update,
+            apply_remote_update,
+            close_crdt_document,
+            get_undo_history,
+            push_undo_batch,
+            undo_batch,
+            redo_batch,
+            clear_undo_history,
+            add_comment,
+            list_comments,
+            resolve_thread,
+            get_suggestion_mode,
+            set_suggestion_mode,
+            propose_edit,
+            list_suggestions,
+            accept_suggestion,
+            reject_suggestion,
+            export_with_comments,
+            list_export_targets,
+            register_export_target,
+            unregister_export_target,
+            unregister_processor,
+            get_processor_metrics,
+            get_node_type_schemas,
+            register_node_type,
+            get_retention_config,
+            set_retention_config,
+            run_retention_now,
+            export_activity_journal,
+            add_asset_reference,
+            list_asset_references,
+            check_asset,
+            relink_asset,
+            locate_asset,
+            get_document_outline,
+            get_document_links,
+            get_backlinks,
+            check_document_schema,
+            get_canvas_graph,
+            get_node_count,
+            find_node,
+            get_outgoing_links,
+            scan_workspace,
+            fuzzy_find_files,
+            export_link_graph,
+            generate_index_document,
+            list_index_documents,
+            remove_index_document,
+            read_asset,
+            stream_file,
+            load_document_chunked,
+            open_read_stream,
+            read_chunk,
+            open_write_stream,
+            write_chunk,
+            close_stream,
+            cancel_stream
+        ])
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| {
+            if let tauri::RunEvent::Exit = event {
+                use tauri::Manager;
+                let state = app_handle.state::<settings_manager::SettingsState>();
+                if let Err(e) = settings_manager::flush_settings(app_handle, &state) {
+                    tracing::error!("Failed to save settings on exit: {}", e);
+                }
+                let _ = speech::stop_speaking(&app_handle.state::<speech::SpeechState>());
+                if let Err(e) = document_lock::release_all(&app_handle.state::<document_lock::DocumentLockState>()) {
+                    tracing::error!("Failed to release document locks: {}", e);
+                }
+                if let Err(e) = app_handle.state::<file_stream::StreamState>().discard_all() {
+                    tracing::error!("Failed to remove unfinished stream files: {}", e);
+                }
+                safe_mode::record_clean_exit(app_handle);
+            }
+        });
 }
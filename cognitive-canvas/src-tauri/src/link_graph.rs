@@ -0,0 +1,324 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+use std::path::{Component, Path, PathBuf};
+use crate::{document_format, document_text, document_version, tags, workspace};
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphFormat {
+    Graphml,
+    Gexf,
+    Json,
+}
+
+/// A document in the graph. `id` is its path relative to the workspace
+/// root, with `/` separators.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GraphNode {
+    pub id: String,
+    pub title: String,
+    pub tags: Vec<String>,
+    pub word_count: usize,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+/// A link from one document to another. Links to files outside the
+/// workspace or to web pages aren't edges.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct GraphEdge {
+    pub source: String,
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LinkGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkGraphSummary {
+    pub nodes: usize,
+    pub edges: usize,
+}
+
+fn node_id(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Collapses `.` and `..` without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Lowercased file name without extension, which `[[wiki links]]` match on.
+pub(crate) fn file_stem(path: &Path) -> String {
+    path.file_stem().map(|s| s.to_string_lossy().to_lowercase()).unwrap_or_default()
+}
+
+/// Where a link could point if it's a path relative to the linking
+/// document: as written, then with each document extension. Empty for web
+/// links and links within the same document.
+pub(crate) fn link_candidates(from: &Path, link: &str) -> Vec<PathBuf> {
+    if link.contains("://") || link.starts_with("mailto:") || link.starts_with('#') {
+        return Vec::new();
+    }
+    let link = link.split('#').next().unwrap_or(link).trim();
+    if link.is_empty() {
+        return Vec::new();
+    }
+
+    let relative = normalize(&from.parent().unwrap_or(Path::new("")).join(link));
+    std::iter::once(relative.clone())
+        .chain(workspace::DOCUMENT_EXTENSIONS.iter().map(|ext| relative.with_extension(ext)))
+        .collect()
+}
+
+/// Finds the document a link points at: a path relative to the linking
+/// document (with or without extension), or failing that a document with
+/// that name anywhere in the workspace, as `[[wiki links]]` expect.
+fn resolve_link(from: &Path, link: &str, documents: &HashSet<PathBuf>, by_stem: &HashMap<String, PathBuf>) -> Option<PathBuf> {
+    let candidates = link_candidates(from, link);
+    if candidates.is_empty() {
+        return None;
+    }
+    candidates
+        .into_iter()
+        .find(|candidate| documents.contains(candidate))
+        .or_else(|| by_stem.get(&file_stem(Path::new(link.split('#').next().unwrap_or(link)))).cloned())
+}
+
+/// Reads every document below `root` and the links between them.
+/// Documents that can't be read are left out. `on_document` is called with
+/// `(done, total, path)` before each one and can stop the build by
+/// returning an error.
+pub fn build<F>(root: &Path, mut on_document: F) -> AppResult<LinkGraph>
+where
+    F: FnMut(usize, usize, &Path) -> AppResult<()>,
+{
+    let paths = workspace::document_files(root)?;
+    let documents: HashSet<PathBuf> = paths.iter().cloned().collect();
+    let mut by_stem = HashMap::new();
+    for path in &paths {
+        // Paths are sorted, so the shallowest match wins for duplicate names
+        by_stem.entry(file_stem(path)).or_insert_with(|| path.clone());
+    }
+
+    let mut graph = LinkGraph::default();
+    let mut edges = BTreeSet::new();
+    for (done, path) in paths.iter().enumerate() {
+        on_document(done, paths.len(), path)?;
+        let Ok(content) = document_format::read_document(path) else {
+            continue;
+        };
+        let id = node_id(root, path);
+        let text = document_text::plain_text(&content);
+
+        for link in document_text::links(&content) {
+            if let Some(target) = resolve_link(path, &link, &documents, &by_stem).filter(|target| target != path) {
+                edges.insert(GraphEdge { source: id.clone(), target: node_id(root, &target) });
+            }
+        }
+
+        graph.nodes.push(GraphNode {
+            title: document_text::title(&content).unwrap_or_else(|| path.file_stem().unwrap_or_default().to_string_lossy().to_string()),
+            tags: tags::document_tags(path, &text),
+            word_count: document_text::word_count(&content),
+            last_modified: document_version::modified_millis(path)
+                .and_then(|millis| DateTime::from_timestamp_millis(millis as i64)),
+            id,
+        });
+    }
+
+    // Unreadable documents have no node, so drop the edges pointing at them
+    let ids: HashSet<&str> = graph.nodes.iter().map(|node| node.id.as_str()).collect();
+    graph.edges = edges.into_iter().filter(|edge| ids.contains(edge.target.as_str())).collect();
+    Ok(graph)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn modified_string(node: &GraphNode) -> String {
+    node.last_modified.map(|t| t.to_rfc3339()).unwrap_or_default()
+}
+
+fn to_graphml(graph: &LinkGraph) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+    out.push_str("  <key id=\"title\" for=\"node\" attr.name=\"title\" attr.type=\"string\"/>\n");
+    out.push_str("  <key id=\"tags\" for=\"node\" attr.name=\"tags\" attr.type=\"string\"/>\n");
+    out.push_str("  <key id=\"word_count\" for=\"node\" attr.name=\"word_count\" attr.type=\"int\"/>\n");
+    out.push_str("  <key id=\"last_modified\" for=\"node\" attr.name=\"last_modified\" attr.type=\"string\"/>\n");
+    out.push_str("  <graph id=\"links\" edgedefault=\"directed\">\n");
+
+    for node in &graph.nodes {
+        let _ = writeln!(out, "    <node id=\"{}\">", escape_xml(&node.id));
+        let _ = writeln!(out, "      <data key=\"title\">{}</data>", escape_xml(&node.title));
+        let _ = writeln!(out, "      <data key=\"tags\">{}</data>", escape_xml(&node.tags.join(", ")));
+        let _ = writeln!(out, "      <data key=\"word_count\">{}</data>", node.word_count);
+        let _ = writeln!(out, "      <data key=\"last_modified\">{}</data>", modified_string(node));
+        out.push_str("    </node>\n");
+    }
+    for edge in &graph.edges {
+        let _ = writeln!(out, "    <edge source=\"{}\" target=\"{}\"/>", escape_xml(&edge.source), escape_xml(&edge.target));
+    }
+
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+fn to_gexf(graph: &LinkGraph) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">\n");
+    out.push_str("  <graph defaultedgetype=\"directed\" mode=\"static\">\n");
+    out.push_str("    <attributes class=\"node\">\n");
+    out.push_str("      <attribute id=\"tags\" title=\"tags\" type=\"string\"/>\n");
+    out.push_str("      <attribute id=\"word_count\" title=\"word_count\" type=\"integer\"/>\n");
+    out.push_str("      <attribute id=\"last_modified\" title=\"last_modified\" type=\"string\"/>\n");
+    out.push_str("    </attributes>\n");
+
+    out.push_str("    <nodes>\n");
+    for node in &graph.nodes {
+        let _ = writeln!(out, "      <node id=\"{}\" label=\"{}\">", escape_xml(&node.id), escape_xml(&node.title));
+        out.push_str("        <attvalues>\n");
+        let _ = writeln!(out, "          <attvalue for=\"tags\" value=\"{}\"/>", escape_xml(&node.tags.join(", ")));
+        let _ = writeln!(out, "          <attvalue for=\"word_count\" value=\"{}\"/>", node.word_count);
+        let _ = writeln!(out, "          <attvalue for=\"last_modified\" value=\"{}\"/>", modified_string(node));
+        out.push_str("        </attvalues>\n");
+        out.push_str("      </node>\n");
+    }
+    out.push_str("    </nodes>\n");
+
+    out.push_str("    <edges>\n");
+    for (i, edge) in graph.edges.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            "      <edge id=\"{}\" source=\"{}\" target=\"{}\"/>",
+            i,
+            escape_xml(&edge.source),
+            escape_xml(&edge.target)
+        );
+    }
+    out.push_str("    </edges>\n");
+
+    out.push_str("  </graph>\n</gexf>\n");
+    out
+}
+
+pub fn render(graph: &LinkGraph, format: GraphFormat) -> AppResult<String> {
+    match format {
+        GraphFormat::Graphml => Ok(to_graphml(graph)),
+        GraphFormat::Gexf => Ok(to_gexf(graph)),
+        GraphFormat::Json => serde_json::to_string_pretty(graph).map_err(|e| AppError::io(format!("Failed to serialize link graph: {}", e))),
+    }
+}
+
+/// Writes the link graph of the workspace at `root` to `dest`, reporting
+/// each document to `on_document` as `build` does. A stopped or failed
+/// export leaves nothing at `dest`.
+pub fn export_link_graph<F>(root: &Path, dest: &Path, format: GraphFormat, on_document: F) -> AppResult<LinkGraphSummary>
+where
+    F: FnMut(usize, usize, &Path) -> AppResult<()>,
+{
+    let graph = build(root, on_document)?;
+    let output = render(&graph, format)?;
+
+    // Written next to `dest` and renamed when done, so a failed export
+    // doesn't leave a broken file behind
+    let file_name = dest.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| "links".to_string());
+    let partial = dest.with_file_name(format!("{}.part", file_name));
+    if let Err(e) = std::fs::write(&partial, output) {
+        let _ = std::fs::remove_file(&partial);
+        return Err(AppError::io(format!("Failed to write link graph: {}", e)));
+    }
+    std::fs::rename(&partial, dest).map_err(|e| AppError::io(format!("Failed to save {}: {}", dest.display(), e)))?;
+    Ok(LinkGraphSummary { nodes: graph.nodes.len(), edges: graph.edges.len() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_build_link_graph() {
+        let root = std::env::temp_dir().join("test_link_graph");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("notes")).unwrap();
+        fs::write(root.join("index.md"), "---\ntags: [hub, start]\n---\n# Home\nSee [[Plan]], [b](notes/b.md), [b again](notes/b) and [web](https://example.com).").unwrap();
+        fs::write(root.join("notes/plan.md"), "Back to [home](../index.md) and [[Missing]] and [[plan]].").unwrap();
+        fs::write(root.join("notes/b.md"), "just words & more").unwrap();
+
+        let graph = build(&root, |_, _, _| Ok(())).unwrap();
+        let ids: Vec<&str> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, vec!["index.md", "notes/b.md", "notes/plan.md"]);
+        assert_eq!(graph.nodes[0].title, "Home");
+        assert_eq!(graph.nodes[0].tags, vec!["hub", "start"]);
+        assert_eq!(graph.nodes[1].word_count, 4);
+        assert!(graph.nodes[1].last_modified.is_some());
+
+        let edges: Vec<(&str, &str)> = graph.edges.iter().map(|e| (e.source.as_str(), e.target.as_str())).collect();
+        assert_eq!(edges, vec![("index.md", "notes/b.md"), ("index.md", "notes/plan.md"), ("notes/plan.md", "index.md")]);
+
+        let graphml = render(&graph, GraphFormat::Graphml).unwrap();
+        assert!(graphml.contains("<edge source=\"notes/plan.md\" target=\"index.md\"/>"));
+        let gexf = render(&graph, GraphFormat::Gexf).unwrap();
+        assert!(gexf.contains("<attvalue for=\"tags\" value=\"hub, start\"/>"));
+        assert!(gexf.contains("<edge id=\"2\""));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_export_link_graph_stopped() {
+        let root = std::env::temp_dir().join("test_export_link_graph_stopped");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.md"), "See [[b]].").unwrap();
+        fs::write(root.join("b.md"), "Back to [[a]].").unwrap();
+        let dest = root.join("links.json");
+
+        let summary = export_link_graph(&root, &dest, GraphFormat::Json, |_, _, _| Ok(())).unwrap();
+        assert_eq!((summary.nodes, summary.edges), (2, 2));
+        assert!(dest.exists());
+        fs::remove_file(&dest).unwrap();
+
+        let result = export_link_graph(&root, &dest, GraphFormat::Json, |done, _, _| {
+            if done == 1 {
+                return Err(AppError::cancelled("Export was cancelled"));
+            }
+            Ok(())
+        });
+        assert!(result.is_err());
+        assert!(!dest.exists());
+        assert!(!root.join("links.json.part").exists());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,202 @@
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::link_graph::{file_stem, link_candidates};
+use crate::{document_text, sidecar};
+use crate::error::{AppError, AppResult};
+
+/// What a link was resolved to when its document was saved:
+/// `path:<file>`, or `name:<name>` for links that don't name an existing
+/// file, so a `[[wiki link]]` connects once a document with that name is
+/// saved.
+fn path_key(path: &str) -> String {
+    format!("path:{}", path)
+}
+
+fn name_key(name: &str) -> String {
+    format!("name:{}", name)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexedLink {
+    /// The link as written
+    target: String,
+    key: String,
+}
+
+/// Links in both directions: each saved document's outgoing links, and
+/// for every link target the documents pointing at it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct LinkIndex {
+    outgoing: BTreeMap<String, Vec<IndexedLink>>,
+    incoming: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl LinkIndex {
+    fn set_document(&mut self, path: &str, links: Vec<IndexedLink>) {
+        for link in self.outgoing.remove(path).unwrap_or_default() {
+            if let Some(sources) = self.incoming.get_mut(&link.key) {
+                sources.remove(path);
+                if sources.is_empty() {
+                    self.incoming.remove(&link.key);
+                }
+            }
+        }
+        if links.is_empty() {
+            return;
+        }
+        for link in &links {
+            self.incoming.entry(link.key.clone()).or_default().insert(path.to_string());
+        }
+        self.outgoing.insert(path.to_string(), links);
+    }
+
+    fn backlinks(&self, path: &str) -> BTreeSet<String> {
+        [path_key(path), name_key(&file_stem(Path::new(path)))]
+            .iter()
+            .filter_map(|key| self.incoming.get(key))
+            .flatten()
+            .filter(|source| source.as_str() != path)
+            .cloned()
+            .collect()
+    }
+
+    /// Where a link leads: its file, or a known document with its name.
+    fn resolve(&self, key: &str) -> Option<String> {
+        if let Some(path) = key.strip_prefix("path:") {
+            return Some(path.to_string());
+        }
+        let name = key.strip_prefix("name:")?;
+        self.outgoing
+            .keys()
+            .map(String::as_str)
+            .chain(self.incoming.keys().filter_map(|key| key.strip_prefix("path:")))
+            .find(|path| file_stem(Path::new(path)) == name)
+            .map(str::to_string)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutgoingLink {
+    pub target: String,
+    /// The document it leads to, or `None` if there's no such document yet
+    pub path: Option<String>,
+}
+
+/// Held while link_index.json is read and written back, so two documents
+/// saved at once don't drop each other's links.
+#[derive(Default)]
+pub struct LinkIndexState {
+    lock: Mutex<()>,
+}
+
+fn get_index_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir.join("link_index.json"))
+}
+
+/// The internal links of a document; web links are left out.
+fn parse_links(path: &Path, content: &str) -> Vec<IndexedLink> {
+    document_text::links(content)
+        .into_iter()
+        .filter_map(|target| {
+            let candidates = link_candidates(path, &target);
+            if candidates.is_empty() {
+                return None;
+            }
+            let key = match candidates.into_iter().find(|candidate| candidate.is_file()) {
+                Some(found) => path_key(&found.to_string_lossy()),
+                None => name_key(&file_stem(Path::new(target.split('#').next().unwrap_or(&target)))),
+            };
+            Some(IndexedLink { target, key })
+        })
+        .collect()
+}
+
+/// Re-reads the links of a document that was just saved.
+pub fn index_document(app_handle: &AppHandle, path: &Path, content: &str) -> AppResult<()> {
+    let state = app_handle.state::<LinkIndexState>();
+    let _guard = state.lock.lock()?;
+
+    let index_path = get_index_path(app_handle)?;
+    let mut index: LinkIndex = sidecar::read_json(&index_path)?;
+    index.set_document(&path.to_string_lossy(), parse_links(path, content));
+    sidecar::write_json(&index_path, &index)
+}
+
+/// Forgets the links of a document that was renamed or deleted.
+pub fn remove_document(app_handle: &AppHandle, path: &Path) -> AppResult<()> {
+    let state = app_handle.state::<LinkIndexState>();
+    let _guard = state.lock.lock()?;
+
+    let index_path = get_index_path(app_handle)?;
+    let mut index: LinkIndex = sidecar::read_json(&index_path)?;
+    index.set_document(&path.to_string_lossy(), Vec::new());
+    sidecar::write_json(&index_path, &index)
+}
+
+/// Documents linking to `path`, by path or by name.
+pub fn get_backlinks(app_handle: &AppHandle, path: &Path) -> AppResult<Vec<String>> {
+    let index: LinkIndex = sidecar::read_json(&get_index_path(app_handle)?)?;
+    Ok(index.backlinks(&path.to_string_lossy()).into_iter().collect())
+}
+
+/// Links in `path` as of its last save, with where they lead.
+pub fn get_outgoing_links(app_handle: &AppHandle, path: &Path) -> AppResult<Vec<OutgoingLink>> {
+    let index: LinkIndex = sidecar::read_json(&get_index_path(app_handle)?)?;
+    let links = index.outgoing.get(path.to_string_lossy().as_ref()).cloned().unwrap_or_default();
+    Ok(links
+        .into_iter()
+        .map(|link| OutgoingLink {
+            path: index.resolve(&link.key),
+            target: link.target,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_backlinks() {
+        let dir = std::env::temp_dir().join("test_link_index");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("notes")).unwrap();
+        let plan = dir.join("notes/plan.md");
+        fs::write(&plan, "").unwrap();
+        let home = dir.join("home.md");
+        let journal = dir.join("journal.md");
+        let key = |p: &Path| p.to_string_lossy().to_string();
+
+        let mut index = LinkIndex::default();
+        index.set_document(&key(&home), parse_links(&home, "See [plan](notes/plan) and [[Journal]] and [web](https://example.com)"));
+        index.set_document(&key(&plan), parse_links(&plan, "Up to [home](../home.md)"));
+
+        assert_eq!(index.outgoing[&key(&home)].len(), 2);
+        assert_eq!(index.backlinks(&key(&plan)), BTreeSet::from([key(&home)]));
+        assert_eq!(index.backlinks(&key(&home)), BTreeSet::from([key(&plan)]));
+        // A wiki link finds the document by name, even one saved later
+        assert_eq!(index.backlinks(&key(&journal)), BTreeSet::from([key(&home)]));
+        assert_eq!(index.resolve("name:plan"), Some(key(&plan)));
+        assert!(index.resolve("name:missing").is_none());
+
+        // Re-saving without links removes them in both directions
+        index.set_document(&key(&home), Vec::new());
+        assert!(index.backlinks(&key(&plan)).is_empty());
+        assert!(index.backlinks(&key(&journal)).is_empty());
+        assert!(!index.outgoing.contains_key(&key(&home)));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,213 @@
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use serde_json::Value;
+use std::time::Duration;
+use crate::ai::{self, AiConfig, Provider};
+use crate::error::{AppError, AppResult};
+
+/// Ollama's OpenAI-compatible API. A llama.cpp server is usually at
+/// http://localhost:8080/v1 instead.
+pub const DEFAULT_BASE_URL: &str = "http://localhost:11434/v1";
+/// Local servers answer at once or not at all.
+const HEALTH_TIMEOUT_SECS: u64 = 3;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LocalModel {
+    /// What to use as the model in the AI config
+    pub name: String,
+    /// Bytes on disk
+    pub size: Option<u64>,
+    /// e.g. "8.0B"
+    pub parameter_size: Option<String>,
+    /// e.g. "Q4_K_M"
+    pub quantization: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LocalServerStatus {
+    pub base_url: String,
+    pub reachable: bool,
+    /// "ollama", "llama.cpp" or "openai-compatible" when reachable
+    pub server: Option<String>,
+    pub version: Option<String>,
+    pub error: Option<String>,
+}
+
+/// The local server to talk to: the configured one when the provider is
+/// "ollama", otherwise Ollama's default address.
+pub fn base_url_for(config: &AiConfig) -> String {
+    match Provider::parse(&config.provider) {
+        Ok(Provider::Ollama) => ai::base_url(Provider::Ollama, config).to_string(),
+        _ => DEFAULT_BASE_URL.to_string(),
+    }
+}
+
+/// The server itself, as Ollama's own API and llama.cpp's health check
+/// aren't under /v1.
+fn server_root(base_url: &str) -> &str {
+    let base_url = base_url.trim_end_matches('/');
+    base_url.strip_suffix("/v1").unwrap_or(base_url)
+}
+
+fn client() -> AppResult<Client> {
+    Client::builder()
+        .timeout(Duration::from_secs(HEALTH_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| AppError::network(format!("Failed to create HTTP client: {}", e)))
+}
+
+/// GETs `url` as JSON, or None when the server doesn't have that endpoint.
+async fn get_json(client: &Client, url: &str) -> AppResult<Option<(StatusCode, Value)>> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| AppError::network(format!("Failed to reach {}: {}", url, e)))?;
+    let status = response.status();
+    if status == StatusCode::NOT_FOUND || status == StatusCode::METHOD_NOT_ALLOWED {
+        return Ok(None);
+    }
+    let text = response.text().await.map_err(|e| AppError::network(format!("Failed to read {}: {}", url, e)))?;
+    Ok(serde_json::from_str(&text).ok().map(|value| (status, value)))
+}
+
+/// Ollama's `/api/tags`.
+fn parse_ollama_models(value: &Value) -> Vec<LocalModel> {
+    value["models"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|model| {
+            let details = &model["details"];
+            Some(LocalModel {
+                name: model["name"].as_str()?.to_string(),
+                size: model["size"].as_u64(),
+                parameter_size: details["parameter_size"].as_str().map(String::from),
+                quantization: details["quantization_level"].as_str().map(String::from),
+            })
+        })
+        .collect()
+}
+
+/// The OpenAI API's `/models`, which llama.cpp's server also has.
+fn parse_openai_models(value: &Value) -> Vec<LocalModel> {
+    value["data"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|model| {
+            Some(LocalModel {
+                name: model["id"].as_str()?.to_string(),
+                size: model["meta"]["size"].as_u64(),
+                parameter_size: None,
+                quantization: None,
+            })
+        })
+        .collect()
+}
+
+/// The models the local server has downloaded or loaded.
+pub async fn list_models(base_url: &str) -> AppResult<Vec<LocalModel>> {
+    let client = client()?;
+    let root = server_root(base_url);
+    if let Some((status, value)) = get_json(&client, &format!("{}/api/tags", root)).await? {
+        if status.is_success() && value.get("models").is_some() {
+            return Ok(parse_ollama_models(&value));
+        }
+    }
+
+    match get_json(&client, &format!("{}/v1/models", root)).await? {
+        Some((status, value)) if status.is_success() => Ok(parse_openai_models(&value)),
+        Some((status, _)) => Err(AppError::io(format!("Failed to list local models: {}", status))),
+        None => Err(AppError::network(format!("{} doesn't list its models", root))),
+    }
+}
+
+/// Whether a local server is running at `base_url` and which kind it is.
+/// Never fails; problems are reported in `error`.
+pub async fn check_health(base_url: &str) -> LocalServerStatus {
+    let mut status = LocalServerStatus { base_url: base_url.to_string(), ..Default::default() };
+    let client = match client() {
+        Ok(client) => client,
+        Err(e) => {
+            status.error = Some(e.to_string());
+            return status;
+        }
+    };
+    let root = server_root(base_url);
+
+    let result = async {
+        if let Some((code, value)) = get_json(&client, &format!("{}/api/version", root)).await? {
+            if code.is_success() {
+                status.server = Some("ollama".to_string());
+                status.version = value["version"].as_str().map(String::from);
+                return Ok(());
+            }
+        }
+        // llama.cpp answers 503 while the model is still loading
+        if let Some((code, value)) = get_json(&client, &format!("{}/health", root)).await? {
+            status.server = Some("llama.cpp".to_string());
+            if !code.is_success() {
+                let message = value["error"]["message"].as_str().unwrap_or("Server is not ready");
+                return Err(AppError::network(message));
+            }
+            return Ok(());
+        }
+        match get_json(&client, &format!("{}/v1/models", root)).await? {
+            Some((code, _)) if code.is_success() => {
+                status.server = Some("openai-compatible".to_string());
+                Ok(())
+            }
+            _ => Err(AppError::network(format!("No AI server found at {}", base_url))),
+        }
+    }
+    .await;
+
+    // Answering at all means something is listening
+    status.reachable = status.server.is_some();
+    status.error = result.err().map(|e| e.to_string());
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_server_root() {
+        assert_eq!(server_root("http://localhost:11434/v1"), "http://localhost:11434");
+        assert_eq!(server_root("http://localhost:8080/v1/"), "http://localhost:8080");
+        assert_eq!(server_root("http://gpu-box:11434"), "http://gpu-box:11434");
+    }
+
+    #[test]
+    fn test_parse_models() {
+        let tags = json!({ "models": [{
+            "name": "llama3.2:latest",
+            "size": 2019393189u64,
+            "details": { "family": "llama", "parameter_size": "3.2B", "quantization_level": "Q4_K_M" }
+        }] });
+        assert_eq!(
+            parse_ollama_models(&tags),
+            vec![LocalModel {
+                name: "llama3.2:latest".to_string(),
+                size: Some(2019393189),
+                parameter_size: Some("3.2B".to_string()),
+                quantization: Some("Q4_K_M".to_string()),
+            }]
+        );
+
+        let models = json!({ "object": "list", "data": [{ "id": "qwen2.5-7b-instruct-q4_k_m.gguf", "object": "model", "meta": { "size": 4683073536u64 } }] });
+        let parsed = parse_openai_models(&models);
+        assert_eq!(parsed[0].name, "qwen2.5-7b-instruct-q4_k_m.gguf");
+        assert_eq!(parsed[0].size, Some(4683073536));
+    }
+
+    #[test]
+    fn test_base_url_for() {
+        let ollama = AiConfig { provider: "ollama".to_string(), base_url: "http://gpu-box:11434/v1/".to_string(), ..AiConfig::default() };
+        assert_eq!(base_url_for(&ollama), "http://gpu-box:11434/v1");
+        assert_eq!(base_url_for(&AiConfig::default()), DEFAULT_BASE_URL);
+    }
+}
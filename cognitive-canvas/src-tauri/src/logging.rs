@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+use crate::error::{AppError, AppResult};
+
+const LOGS_DIR: &str = "logs";
+const LOG_PREFIX: &str = "cognitive-canvas";
+const LOG_SUFFIX: &str = "log";
+/// One file per day; older ones are deleted as new days start.
+const MAX_LOG_FILES: usize = 7;
+
+/// How many entries `get_recent_logs` returns unless asked for more.
+pub const DEFAULT_LIMIT: usize = 200;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    fn parse(level: &str) -> Option<Self> {
+        match level.to_ascii_lowercase().as_str() {
+            "trace" => Some(LogLevel::Trace),
+            "debug" => Some(LogLevel::Debug),
+            "info" => Some(LogLevel::Info),
+            "warn" => Some(LogLevel::Warn),
+            "error" => Some(LogLevel::Error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogEntry {
+    pub timestamp: String,
+    pub level: LogLevel,
+    /// The module that logged it
+    pub target: String,
+    pub message: String,
+    /// Structured fields other than the message, like `path` or `error`
+    pub fields: Map<String, Value>,
+}
+
+/// A line of the JSON log files, as tracing-subscriber writes it.
+#[derive(Deserialize)]
+struct LogLine {
+    timestamp: String,
+    level: String,
+    #[serde(default)]
+    target: String,
+    #[serde(default)]
+    fields: Map<String, Value>,
+}
+
+/// Keeps the background writer flushing until the app exits.
+#[derive(Default)]
+pub struct LogState {
+    guard: Mutex<Option<WorkerGuard>>,
+}
+
+pub fn get_logs_dir(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    let dir = app_data_dir.join(LOGS_DIR);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| AppError::io(format!("Failed to create logs directory: {}", e)))?;
+
+    Ok(dir)
+}
+
+/// Sends log events to stderr and, as JSON lines, to a daily log file in
+/// app data. Debug events are only kept in debug builds.
+pub fn init(app_handle: &AppHandle) -> AppResult<()> {
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_PREFIX)
+        .filename_suffix(LOG_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(get_logs_dir(app_handle)?)
+        .map_err(|e| AppError::io(format!("Failed to open log file: {}", e)))?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+
+    let level = if cfg!(debug_assertions) { LevelFilter::DEBUG } else { LevelFilter::INFO };
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().json().with_writer(writer).with_filter(level))
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr).with_filter(level))
+        .try_init()
+        .map_err(|e| AppError::internal(format!("Failed to set up logging: {}", e)))?;
+
+    if let Ok(mut state_guard) = app_handle.state::<LogState>().guard.lock() {
+        *state_guard = Some(guard);
+    }
+    Ok(())
+}
+
+fn parse_line(line: &str) -> Option<LogEntry> {
+    let mut line: LogLine = serde_json::from_str(line).ok()?;
+    let message = match line.fields.remove("message") {
+        Some(Value::String(message)) => message,
+        Some(other) => other.to_string(),
+        None => String::new(),
+    };
+    Some(LogEntry {
+        timestamp: line.timestamp,
+        level: LogLevel::parse(&line.level)?,
+        target: line.target,
+        message,
+        fields: line.fields,
+    })
+}
+
+/// The latest `limit` entries at `level` or more severe, newest first.
+/// Lines that aren't log entries, e.g. from a crash mid-write, are skipped.
+pub fn recent_logs(dir: &Path, level: LogLevel, limit: usize) -> AppResult<Vec<LogEntry>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| AppError::io(format!("Failed to read {}: {}", dir.display(), e)))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(LOG_PREFIX) && name.ends_with(LOG_SUFFIX))
+        })
+        .collect();
+    // Names end in the date, so the newest file sorts last
+    files.sort();
+
+    let mut entries = Vec::new();
+    for file in files.iter().rev() {
+        let content = std::fs::read_to_string(file).map_err(|e| AppError::io(format!("Failed to read {}: {}", file.display(), e)))?;
+        entries.extend(content.lines().rev().filter_map(parse_line).filter(|entry| entry.level >= level));
+        if entries.len() >= limit {
+            break;
+        }
+    }
+    entries.truncate(limit);
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn line(timestamp: &str, level: &str, message: &str) -> String {
+        format!(
+            r#"{{"timestamp":"{}","level":"{}","fields":{{"message":"{}","error":"disk full"}},"target":"cognitive_canvas_lib::sync"}}"#,
+            timestamp, level, message
+        )
+    }
+
+    #[test]
+    fn test_recent_logs() {
+        let dir = std::env::temp_dir().join("test_recent_logs");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let older = [line("2024-03-08T10:00:00Z", "ERROR", "Old failure"), line("2024-03-08T11:00:00Z", "INFO", "Started")];
+        let newer = [
+            line("2024-03-09T09:00:00Z", "WARN", "Slow save"),
+            "{\"timestamp\":\"2024-03-09T09:30:0".to_string(),
+            line("2024-03-09T10:00:00Z", "DEBUG", "Cache hit"),
+            line("2024-03-09T11:00:00Z", "ERROR", "Sync failed"),
+        ];
+        fs::write(dir.join("cognitive-canvas.2024-03-08.log"), older.join("\n")).unwrap();
+        fs::write(dir.join("cognitive-canvas.2024-03-09.log"), newer.join("\n")).unwrap();
+        fs::write(dir.join("notes.txt"), line("2024-03-10T00:00:00Z", "ERROR", "Not a log")).unwrap();
+
+        let entries = recent_logs(&dir, LogLevel::Warn, DEFAULT_LIMIT).unwrap();
+        let messages: Vec<&str> = entries.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, vec!["Sync failed", "Slow save", "Old failure"]);
+        assert_eq!(entries[0].level, LogLevel::Error);
+        assert_eq!(entries[0].target, "cognitive_canvas_lib::sync");
+        assert_eq!(entries[0].fields.get("error"), Some(&Value::from("disk full")));
+
+        assert_eq!(recent_logs(&dir, LogLevel::Trace, DEFAULT_LIMIT).unwrap().len(), 5);
+        assert_eq!(recent_logs(&dir, LogLevel::Info, 2).unwrap().len(), 2);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
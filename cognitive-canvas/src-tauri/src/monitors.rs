@@ -0,0 +1,94 @@
+use serde::Serialize;
+use tauri::{Monitor, PhysicalPosition, WebviewWindow};
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MonitorInfo {
+    pub index: usize,
+    pub name: Option<String>,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f64,
+    pub is_primary: bool,
+    pub is_current: bool,
+}
+
+fn same_monitor(a: &Monitor, b: &Monitor) -> bool {
+    a.name() == b.name() && a.position() == b.position()
+}
+
+/// Whether the point `(x, y)` in physical pixels lies on `monitor`.
+pub fn contains(monitor: &Monitor, x: i32, y: i32) -> bool {
+    let position = monitor.position();
+    let size = monitor.size();
+    x >= position.x
+        && y >= position.y
+        && i64::from(x) < i64::from(position.x) + i64::from(size.width)
+        && i64::from(y) < i64::from(position.y) + i64::from(size.height)
+}
+
+/// Lists the connected displays in the order the system reports them.
+pub fn list_monitors(window: &WebviewWindow) -> AppResult<Vec<MonitorInfo>> {
+    let primary = window.primary_monitor()?;
+    let current = window.current_monitor()?;
+
+    let monitors = window
+        .available_monitors()
+        .map_err(|e| AppError::io(format!("Failed to list monitors: {}", e)))?;
+
+    Ok(monitors
+        .iter()
+        .enumerate()
+        .map(|(index, monitor)| MonitorInfo {
+            index,
+            name: monitor.name().cloned(),
+            x: monitor.position().x,
+            y: monitor.position().y,
+            width: monitor.size().width,
+            height: monitor.size().height,
+            scale_factor: monitor.scale_factor(),
+            is_primary: primary.as_ref().is_some_and(|p| same_monitor(p, monitor)),
+            is_current: current.as_ref().is_some_and(|c| same_monitor(c, monitor)),
+        })
+        .collect())
+}
+
+/// Moves the window to the center of the monitor at `index` in
+/// `list_monitors`' order, keeping it maximized or fullscreen if it was.
+pub fn move_to_monitor(window: &WebviewWindow, index: usize) -> AppResult<()> {
+    let monitors = window
+        .available_monitors()
+        .map_err(|e| AppError::io(format!("Failed to list monitors: {}", e)))?;
+    let monitor = monitors
+        .get(index)
+        .ok_or_else(|| AppError::not_found(format!("No monitor at index {}", index)))?;
+
+    let fullscreen = window.is_fullscreen()?;
+    let maximized = window.is_maximized()?;
+    // Maximized and fullscreen windows are pinned to their display
+    if fullscreen {
+        window.set_fullscreen(false)?;
+    }
+    if maximized {
+        window.unmaximize()?;
+    }
+
+    let size = window.outer_size()?;
+    let width = size.width.min(monitor.size().width);
+    let height = size.height.min(monitor.size().height);
+    let x = monitor.position().x + ((monitor.size().width - width) / 2) as i32;
+    let y = monitor.position().y + ((monitor.size().height - height) / 2) as i32;
+    window
+        .set_position(PhysicalPosition::new(x, y))
+        ?;
+
+    if maximized {
+        window.maximize()?;
+    }
+    if fullscreen {
+        window.set_fullscreen(true)?;
+    }
+    Ok(())
+}
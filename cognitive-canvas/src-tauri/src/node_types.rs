@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldType {
+    String,
+    Number,
+    Bool,
+    Array,
+    Object,
+}
+
+impl FieldType {
+    fn matches(self, value: &Value) -> bool {
+        match self {
+            FieldType::String => value.is_string(),
+            FieldType::Number => value.is_number(),
+            FieldType::Bool => value.is_boolean(),
+            FieldType::Array => value.is_array(),
+            FieldType::Object => value.is_object(),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            FieldType::String => "string",
+            FieldType::Number => "number",
+            FieldType::Bool => "bool",
+            FieldType::Array => "array",
+            FieldType::Object => "object",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldSchema {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: FieldType,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// The payload a node of type `name` carries in the Lexical editor state.
+/// Fields not listed are allowed and left alone.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeTypeSchema {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub fields: Vec<FieldSchema>,
+    /// Field whose value stands in for the node in exports, for nodes
+    /// without text of their own
+    #[serde(default)]
+    pub export_text: Option<String>,
+    /// Built-in types can't be replaced by plugins
+    #[serde(default, skip_deserializing)]
+    pub builtin: bool,
+}
+
+/// A node in a saved document that doesn't match its type's schema.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NodeError {
+    /// Where the node is, e.g. "root.children[2].children[0]"
+    pub path: String,
+    pub node_type: String,
+    pub message: String,
+}
+
+fn field(name: &str, field_type: FieldType, required: bool) -> FieldSchema {
+    FieldSchema { name: name.to_string(), field_type, required }
+}
+
+fn builtins() -> Vec<NodeTypeSchema> {
+    use FieldType::*;
+    let schema = |name: &str, description: &str, fields: Vec<FieldSchema>, export_text: Option<&str>| NodeTypeSchema {
+        name: name.to_string(),
+        description: description.to_string(),
+        fields,
+        export_text: export_text.map(|f| f.to_string()),
+        builtin: true,
+    };
+    vec![
+        schema("text", "A run of formatted text", vec![field("text", String, true), field("format", Number, false)], None),
+        schema(
+            "image",
+            "An image from the workspace or a URL",
+            vec![field("src", String, true), field("altText", String, false), field("width", Number, false), field("height", Number, false)],
+            Some("altText"),
+        ),
+        schema("table", "A table of rows and cells", vec![field("children", Array, true)], None),
+        schema("code", "A code block", vec![field("children", Array, true), field("language", String, false)], None),
+        schema(
+            "ink",
+            "A freehand drawing as a list of strokes",
+            vec![field("strokes", Array, true), field("width", Number, false), field("height", Number, false)],
+            None,
+        ),
+        schema(
+            "embed",
+            "Content embedded from another site",
+            vec![field("url", String, true), field("provider", String, false)],
+            Some("url"),
+        ),
+    ]
+}
+
+/// Every node type the backend knows how to validate and export. Nodes of
+/// types that aren't registered, like Lexical's own paragraphs, aren't
+/// checked.
+pub struct NodeTypeRegistry {
+    types: RwLock<BTreeMap<String, NodeTypeSchema>>,
+}
+
+impl Default for NodeTypeRegistry {
+    fn default() -> Self {
+        Self {
+            types: RwLock::new(builtins().into_iter().map(|s| (s.name.clone(), s)).collect()),
+        }
+    }
+}
+
+impl NodeTypeRegistry {
+    /// Adds or replaces a custom node type.
+    pub fn register(&self, mut schema: NodeTypeSchema) -> AppResult<()> {
+        let name = schema.name.trim().to_string();
+        if name.is_empty() {
+            return Err(AppError::validation("Node type name can't be empty"));
+        }
+        if let Some(field) = &schema.export_text {
+            if !schema.fields.iter().any(|f| &f.name == field) {
+                return Err(AppError::validation(format!("export_text refers to unknown field {}", field)));
+            }
+        }
+
+        let mut types = self.types.write()?;
+        if types.get(&name).is_some_and(|existing| existing.builtin) {
+            return Err(AppError::conflict(format!("{} is a built-in node type", name)));
+        }
+        schema.name = name.clone();
+        schema.builtin = false;
+        types.insert(name, schema);
+        Ok(())
+    }
+
+    pub fn schemas(&self) -> Vec<NodeTypeSchema> {
+        self.types.read().map(|types| types.values().cloned().collect()).unwrap_or_default()
+    }
+
+    /// Checks every registered node in a Lexical document. Content that
+    /// isn't Lexical JSON has no nodes and always passes.
+    pub fn validate(&self, content: &str) -> Vec<NodeError> {
+        let Some(root) = serde_json::from_str::<Value>(content).ok().and_then(|v| v.get("root").cloned()) else {
+            return Vec::new();
+        };
+        let Ok(types) = self.types.read() else {
+            return Vec::new();
+        };
+
+        let mut errors = Vec::new();
+        validate_node(&types, &root, "root", &mut errors);
+        errors
+    }
+
+    /// Gives nodes without text of their own the value of their type's
+    /// `export_text` field as text, so exports show something for them.
+    pub fn prepare_export(&self, content: &str) -> String {
+        let Ok(mut value) = serde_json::from_str::<Value>(content) else {
+            return content.to_string();
+        };
+        let Ok(types) = self.types.read() else {
+            return content.to_string();
+        };
+        if let Some(root) = value.get_mut("root") {
+            fill_export_text(&types, root);
+        }
+        serde_json::to_string(&value).unwrap_or_else(|_| content.to_string())
+    }
+}
+
+fn children(node: &Value) -> impl Iterator<Item = &Value> {
+    node.get("children").and_then(|c| c.as_array()).into_iter().flatten()
+}
+
+fn validate_node(types: &BTreeMap<String, NodeTypeSchema>, node: &Value, path: &str, errors: &mut Vec<NodeError>) {
+    if let Some(schema) = node.get("type").and_then(|t| t.as_str()).and_then(|t| types.get(t)) {
+        for field in &schema.fields {
+            let message = match node.get(&field.name) {
+                None | Some(Value::Null) if field.required => format!("Missing required field {}", field.name),
+                Some(value) if !value.is_null() && !field.field_type.matches(value) => {
+                    format!("Field {} should be a {}", field.name, field.field_type.as_str())
+                }
+                _ => continue,
+            };
+            errors.push(NodeError {
+                path: path.to_string(),
+                node_type: schema.name.clone(),
+                message,
+            });
+        }
+    }
+
+    for (i, child) in children(node).enumerate() {
+        validate_node(types, child, &format!("{}.children[{}]", path, i), errors);
+    }
+}
+
+fn fill_export_text(types: &BTreeMap<String, NodeTypeSchema>, node: &mut Value) {
+    let fallback = node
+        .get("type")
+        .and_then(|t| t.as_str())
+        .and_then(|t| types.get(t))
+        .and_then(|schema| schema.export_text.as_ref())
+        .and_then(|field| node.get(field))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+
+    if let (Some(text), Some(object)) = (fallback, node.as_object_mut()) {
+        if !object.contains_key("text") && !object.contains_key("children") {
+            object.insert("text".to_string(), Value::String(text));
+        }
+    }
+
+    if let Some(children) = node.get_mut("children").and_then(|c| c.as_array_mut()) {
+        for child in children {
+            fill_export_text(types, child);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_and_register() {
+        let registry = NodeTypeRegistry::default();
+        let doc = r#"{"root":{"children":[
+            {"type":"paragraph","children":[{"type":"text","text":"hi"},{"type":"text","format":"bold"}]},
+            {"type":"image","src":"a.png","width":"wide"},
+            {"type":"chart","series":3}
+        ]}}"#;
+
+        let errors = registry.validate(doc);
+        let paths: Vec<&str> = errors.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["root.children[0].children[1]", "root.children[0].children[1]", "root.children[1]"]);
+        assert!(registry.validate("# plain markdown").is_empty());
+
+        let chart = NodeTypeSchema {
+            name: "chart".to_string(),
+            description: String::new(),
+            fields: vec![field("series", FieldType::Array, true), field("title", FieldType::String, false)],
+            export_text: Some("title".to_string()),
+            builtin: false,
+        };
+        registry.register(chart.clone()).unwrap();
+        assert_eq!(registry.validate(doc).len(), 4);
+        assert!(registry.register(NodeTypeSchema { name: "image".to_string(), ..chart.clone() }).is_err());
+        assert!(registry.register(NodeTypeSchema { export_text: Some("nope".to_string()), ..chart }).is_err());
+    }
+
+    #[test]
+    fn test_prepare_export() {
+        let registry = NodeTypeRegistry::default();
+        let doc = r#"{"root":{"children":[{"type":"embed","url":"https://example.com/v"},{"type":"image","src":"a.png"}]}}"#;
+        let prepared: Value = serde_json::from_str(&registry.prepare_export(doc)).unwrap();
+        assert_eq!(prepared["root"]["children"][0]["text"], "https://example.com/v");
+        assert!(prepared["root"]["children"][1].get("text").is_none());
+    }
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+use crate::{document_version, sidecar};
+use crate::error::{AppError, AppResult};
+
+/// Words tesseract is less sure of than this (0-100) are dropped; they're
+/// mostly noise from icons and UI chrome in screenshots.
+const MIN_CONFIDENCE: f64 = 30.0;
+
+/// A recognized word and where it is in the image, in pixels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OcrWord {
+    pub text: String,
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+    pub confidence: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OcrResult {
+    /// The words line by line, for search
+    pub text: String,
+    pub words: Vec<OcrWord>,
+    /// The image's mtime when it was read, so the `.ocr` sidecar is only
+    /// reused while the image is unchanged
+    #[serde(default)]
+    pub modified: Option<u64>,
+}
+
+/// Parses tesseract's TSV output, whose columns are level, page_num,
+/// block_num, par_num, line_num, word_num, left, top, width, height, conf
+/// and text. Words are level 5.
+fn parse_tsv(tsv: &str) -> OcrResult {
+    let mut result = OcrResult::default();
+    let mut lines: Vec<String> = Vec::new();
+    let mut current_line = None;
+
+    for row in tsv.lines().skip(1) {
+        let columns: Vec<&str> = row.split('\t').collect();
+        if columns.len() < 12 || columns[0] != "5" {
+            continue;
+        }
+        let text = columns[11..].join("\t").trim().to_string();
+        let number = |i: usize| columns[i].parse::<u32>().unwrap_or(0);
+        let confidence = columns[10].parse::<f64>().unwrap_or(-1.0);
+        if text.is_empty() || confidence < MIN_CONFIDENCE {
+            continue;
+        }
+
+        let line = (columns[2], columns[3], columns[4]);
+        if current_line == Some(line) {
+            if let Some(last) = lines.last_mut() {
+                last.push(' ');
+                last.push_str(&text);
+            }
+        } else {
+            lines.push(text.clone());
+            current_line = Some(line);
+        }
+        result.words.push(OcrWord {
+            text,
+            left: number(6),
+            top: number(7),
+            width: number(8),
+            height: number(9),
+            confidence,
+        });
+    }
+
+    result.text = lines.join("\n");
+    result
+}
+
+fn run_tesseract(path: &Path, language: &str) -> AppResult<String> {
+    let output = Command::new("tesseract")
+        .arg(path)
+        .args(["stdout", "-l", language, "tsv"])
+        .output()
+        .map_err(|e| AppError::external(format!("Failed to run tesseract, is it installed? {}", e)))?;
+    if !output.status.success() {
+        return Err(AppError::external(format!("tesseract failed: {}", String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Reads the text in an image with tesseract. The result is kept in an
+/// `.ocr` sidecar next to the image, so searching doesn't run OCR again.
+/// `language` is a tesseract language code like "eng" (the default) or
+/// "deu+eng".
+pub fn ocr_image(path: &Path, language: Option<&str>) -> AppResult<OcrResult> {
+    let modified = document_version::modified_millis(path).ok_or_else(|| AppError::not_found(format!("{} doesn't exist", path.display())))?;
+    let sidecar_path = sidecar::sidecar_path(path, "ocr");
+    let cached: OcrResult = sidecar::read_json(&sidecar_path)?;
+    if cached.modified == Some(modified) {
+        return Ok(cached);
+    }
+
+    let mut result = parse_tsv(&run_tesseract(path, language.unwrap_or("eng"))?);
+    result.modified = Some(modified);
+    sidecar::write_json(&sidecar_path, &result)?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tsv() {
+        let tsv = "level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext
+1\t1\t0\t0\t0\t0\t0\t0\t800\t600\t-1\t
+4\t1\t1\t1\t1\t0\t10\t10\t200\t20\t-1\t
+5\t1\t1\t1\t1\t1\t10\t10\t90\t20\t96.5\tQuarterly
+5\t1\t1\t1\t1\t2\t110\t10\t80\t20\t91\treport
+5\t1\t1\t1\t1\t3\t200\t10\t10\t20\t12\t|
+5\t1\t1\t1\t2\t1\t10\t40\t60\t20\t88\tRevenue
+5\t1\t1\t1\t2\t2\t80\t40\t40\t20\t90\t
+";
+        let result = parse_tsv(tsv);
+        assert_eq!(result.text, "Quarterly report\nRevenue");
+        assert_eq!(result.words.len(), 3);
+        assert_eq!(
+            result.words[1],
+            OcrWord { text: "report".to_string(), left: 110, top: 10, width: 80, height: 20, confidence: 91.0 }
+        );
+    }
+}
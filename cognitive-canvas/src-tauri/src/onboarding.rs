@@ -0,0 +1,224 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use std::path::{Path, PathBuf};
+use crate::settings_manager::{self, SettingsState, Theme};
+use crate::{shortcuts_manager, sidecar};
+use crate::error::{AppError, AppResult};
+
+/// The first-run steps in the order the frontend shows them, as (id, title).
+const STEPS: &[(&str, &str)] = &[
+    ("welcome", "Welcome"),
+    ("workspace", "Choose where your documents live"),
+    ("appearance", "Pick a theme and keyboard shortcuts"),
+    ("privacy", "Usage statistics"),
+];
+
+/// Documents written by `CreateExampleWorkspace`, as (relative path, content).
+const EXAMPLE_DOCUMENTS: &[(&str, &str)] = &[
+    (
+        "Welcome.md",
+        "---\ntags: [start]\n---\n# Welcome\n\nThis workspace shows a few things you can do. Start with [[Getting started]], then jot something down in [Ideas](Ideas.md).\n",
+    ),
+    (
+        "Getting started.md",
+        "---\ntags: [start, help]\n---\n# Getting started\n\n- Open the command palette to find any action.\n- Link documents with [[double brackets]] to build up your notes.\n- Tag documents to find them later without folders.\n",
+    ),
+    ("Ideas.md", "# Ideas\n\nA place for loose thoughts. Back to [[Welcome]].\n"),
+];
+
+/// A setup action run as part of completing a step.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum SetupAction {
+    /// Writes a few linked example documents into `path` and makes it the
+    /// default directory
+    CreateExampleWorkspace { path: String },
+    PickDefaultDirectory { path: String },
+    ChooseTheme { theme: Theme },
+    ChooseKeymap { preset: String },
+    SetTelemetry { enabled: bool },
+}
+
+impl SetupAction {
+    fn step(&self) -> &'static str {
+        match self {
+            SetupAction::CreateExampleWorkspace { .. } | SetupAction::PickDefaultDirectory { .. } => "workspace",
+            SetupAction::ChooseTheme { .. } | SetupAction::ChooseKeymap { .. } => "appearance",
+            SetupAction::SetTelemetry { .. } => "privacy",
+        }
+    }
+}
+
+/// onboarding.json: which steps are done, so an interrupted first run
+/// picks up where it stopped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct OnboardingProgress {
+    #[serde(default)]
+    completed: Vec<String>,
+    #[serde(default)]
+    finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OnboardingStep {
+    pub id: String,
+    pub title: String,
+    pub done: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OnboardingState {
+    pub steps: Vec<OnboardingStep>,
+    /// The first step not done yet; `None` once onboarding is finished
+    pub current: Option<String>,
+    pub finished: bool,
+}
+
+fn get_progress_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir.join("onboarding.json"))
+}
+
+fn state_of(progress: &OnboardingProgress) -> OnboardingState {
+    let steps: Vec<OnboardingStep> = STEPS
+        .iter()
+        .map(|(id, title)| OnboardingStep {
+            id: id.to_string(),
+            title: title.to_string(),
+            done: progress.completed.iter().any(|done| done == id),
+        })
+        .collect();
+    OnboardingState {
+        current: steps.iter().find(|step| !step.done).map(|step| step.id.clone()),
+        finished: progress.finished_at.is_some(),
+        steps,
+    }
+}
+
+pub fn get_onboarding_state(app_handle: &AppHandle) -> AppResult<OnboardingState> {
+    let progress: OnboardingProgress = sidecar::read_json(&get_progress_path(app_handle)?)?;
+    Ok(state_of(&progress))
+}
+
+/// Writes the example documents, leaving files that already exist alone.
+pub fn create_example_workspace(dir: &Path) -> AppResult<()> {
+    for (name, content) in EXAMPLE_DOCUMENTS {
+        let path = dir.join(name);
+        if path.exists() {
+            continue;
+        }
+        std::fs::create_dir_all(dir).map_err(|e| AppError::io(format!("Failed to create {}: {}", dir.display(), e)))?;
+        std::fs::write(&path, content).map_err(|e| AppError::io(format!("Failed to write {}: {}", path.display(), e)))?;
+    }
+    Ok(())
+}
+
+fn set_default_directory(app_handle: &AppHandle, path: &str) -> AppResult<()> {
+    settings_manager::validate_directory(path)?;
+    if path.trim().is_empty() {
+        return Err(AppError::validation("Choose a directory"));
+    }
+    let state = app_handle.state::<SettingsState>();
+    settings_manager::update_settings(app_handle, &state, |settings| settings.default_directory = path.trim().to_string())?;
+    Ok(())
+}
+
+fn run_action(app_handle: &AppHandle, action: &SetupAction) -> AppResult<()> {
+    let state = app_handle.state::<SettingsState>();
+    match action {
+        SetupAction::CreateExampleWorkspace { path } => {
+            settings_manager::validate_directory(path)?;
+            create_example_workspace(Path::new(path.trim()))?;
+            set_default_directory(app_handle, path)
+        }
+        SetupAction::PickDefaultDirectory { path } => set_default_directory(app_handle, path),
+        SetupAction::ChooseTheme { theme } => {
+            let settings = settings_manager::update_settings(app_handle, &state, |settings| settings.theme = *theme)?;
+            settings_manager::emit_theme_changed(app_handle, &settings)
+        }
+        SetupAction::ChooseKeymap { preset } => shortcuts_manager::apply_preset(app_handle, preset).map(|_| ()),
+        SetupAction::SetTelemetry { enabled } => {
+            settings_manager::update_settings(app_handle, &state, |settings| settings.telemetry = *enabled)?;
+            Ok(())
+        }
+    }
+}
+
+/// Runs `actions` and marks step `id` done. A step can be completed
+/// without actions to skip it, and completing it again re-runs them. The
+/// step is only marked once every action succeeded.
+pub fn complete_onboarding_step(app_handle: &AppHandle, id: &str, actions: &[SetupAction]) -> AppResult<OnboardingState> {
+    if !STEPS.iter().any(|(step, _)| *step == id) {
+        return Err(AppError::validation(format!("Unknown onboarding step {}", id)));
+    }
+    if let Some(action) = actions.iter().find(|action| action.step() != id) {
+        return Err(AppError::validation(format!("{:?} belongs to the {} step", action, action.step())));
+    }
+    for action in actions {
+        run_action(app_handle, action)?;
+    }
+
+    let path = get_progress_path(app_handle)?;
+    let mut progress: OnboardingProgress = sidecar::read_json(&path)?;
+    if !progress.completed.iter().any(|done| done == id) {
+        progress.completed.push(id.to_string());
+    }
+    if progress.finished_at.is_none() && STEPS.iter().all(|(step, _)| progress.completed.iter().any(|done| done == step)) {
+        progress.finished_at = Some(Utc::now());
+    }
+    sidecar::write_json(&path, &progress)?;
+    Ok(state_of(&progress))
+}
+
+/// Starts onboarding over on the next launch. Settings chosen during it stay.
+pub fn reset_onboarding(app_handle: &AppHandle) -> AppResult<OnboardingState> {
+    let path = get_progress_path(app_handle)?;
+    let progress = OnboardingProgress::default();
+    sidecar::write_json(&path, &progress)?;
+    Ok(state_of(&progress))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_progress_state() {
+        let mut progress = OnboardingProgress::default();
+        assert_eq!(state_of(&progress).current.as_deref(), Some("welcome"));
+
+        progress.completed = vec!["welcome".to_string(), "appearance".to_string()];
+        let state = state_of(&progress);
+        assert_eq!(state.current.as_deref(), Some("workspace"));
+        assert!(!state.finished);
+        assert_eq!(state.steps.iter().filter(|step| step.done).count(), 2);
+
+        let action: SetupAction = serde_json::from_str(r#"{"action":"choose_theme","theme":"dark"}"#).unwrap();
+        assert_eq!(action, SetupAction::ChooseTheme { theme: Theme::Dark });
+        assert_eq!(action.step(), "appearance");
+    }
+
+    #[test]
+    fn test_example_workspace() {
+        let dir = std::env::temp_dir().join("test_onboarding_workspace");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Ideas.md"), "mine").unwrap();
+
+        create_example_workspace(&dir).unwrap();
+        assert!(dir.join("Welcome.md").exists());
+        assert!(dir.join("Getting started.md").exists());
+        assert_eq!(fs::read_to_string(dir.join("Ideas.md")).unwrap(), "mine");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,200 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::sync::watch;
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationKind {
+    ArchiveExport,
+    ArchiveImport,
+    DocumentExport,
+    LinkGraphExport,
+    Sync,
+    WorkspaceScan,
+    IndexDocument,
+    Thumbnail,
+    AiGeneration,
+}
+
+/// Sent as "operation-progress" whenever an operation moves on, and once
+/// more with `done` set when it ends, whether it succeeded or not.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OperationProgress {
+    pub id: String,
+    pub kind: OperationKind,
+    /// 0 to 100, or None while the amount of work isn't known yet
+    pub percent: Option<f64>,
+    pub message: String,
+    pub started_at: DateTime<Utc>,
+    pub done: bool,
+}
+
+struct ActiveOperation {
+    progress: OperationProgress,
+    cancel: watch::Sender<bool>,
+}
+
+/// Operations that are running, oldest first.
+#[derive(Default)]
+pub struct OperationsState {
+    next_id: AtomicU64,
+    active: Mutex<Vec<ActiveOperation>>,
+}
+
+impl OperationsState {
+    /// Registers an operation under `id`, or a generated one when the
+    /// caller doesn't need to know it in advance. The receiver turns true
+    /// when it's cancelled.
+    fn begin(&self, kind: OperationKind, id: Option<String>) -> AppResult<(OperationProgress, watch::Receiver<bool>)> {
+        let id = id.unwrap_or_else(|| format!("op-{}", self.next_id.fetch_add(1, Ordering::Relaxed) + 1));
+        let mut active = self.active.lock()?;
+        if active.iter().any(|operation| operation.progress.id == id) {
+            return Err(AppError::conflict(format!("Operation {} is already running", id)));
+        }
+        let progress = OperationProgress {
+            id,
+            kind,
+            percent: None,
+            message: String::new(),
+            started_at: Utc::now(),
+            done: false,
+        };
+        let (cancel, cancelled) = watch::channel(false);
+        active.push(ActiveOperation { progress: progress.clone(), cancel });
+        Ok((progress, cancelled))
+    }
+
+    fn update(&self, id: &str, percent: Option<f64>, message: String) -> Option<OperationProgress> {
+        let mut active = self.active.lock().ok()?;
+        let progress = &mut active.iter_mut().find(|operation| operation.progress.id == id)?.progress;
+        progress.percent = percent.map(|percent| percent.clamp(0.0, 100.0));
+        progress.message = message;
+        Some(progress.clone())
+    }
+
+    fn finish(&self, id: &str) -> Option<OperationProgress> {
+        let mut active = self.active.lock().ok()?;
+        let index = active.iter().position(|operation| operation.progress.id == id)?;
+        let mut progress = active.remove(index).progress;
+        progress.done = true;
+        Some(progress)
+    }
+
+    pub fn list(&self) -> AppResult<Vec<OperationProgress>> {
+        Ok(self.active.lock()?.iter().map(|operation| operation.progress.clone()).collect())
+    }
+
+    /// Asks the operation `id` to stop. It does at its next step, removing
+    /// what it wrote so far. Returns whether it was running.
+    pub fn cancel(&self, id: &str) -> AppResult<bool> {
+        let active = self.active.lock()?;
+        let Some(operation) = active.iter().find(|operation| operation.progress.id == id) else {
+            return Ok(false);
+        };
+        let _ = operation.cancel.send(true);
+        Ok(true)
+    }
+}
+
+/// A running operation. It's listed by `list_active_operations` until
+/// dropped, so it should live as long as the work does.
+pub struct Operation {
+    app_handle: AppHandle,
+    id: String,
+    cancelled: watch::Receiver<bool>,
+}
+
+impl Operation {
+    pub fn start(app_handle: &AppHandle, kind: OperationKind, id: Option<String>) -> AppResult<Self> {
+        let (progress, cancelled) = app_handle.state::<OperationsState>().begin(kind, id)?;
+        let _ = app_handle.emit("operation-progress", &progress);
+        Ok(Self { app_handle: app_handle.clone(), id: progress.id, cancelled })
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancelled.borrow()
+    }
+
+    /// For `?` between steps of the work.
+    pub fn check_cancelled(&self) -> AppResult<()> {
+        if self.is_cancelled() {
+            return Err(AppError::cancelled(format!("Operation {} was cancelled", self.id)));
+        }
+        Ok(())
+    }
+
+    /// Resolves once the operation is cancelled, for `tokio::select!`
+    /// against async work.
+    pub async fn cancelled(&self) {
+        let mut cancelled = self.cancelled.clone();
+        let _ = cancelled.wait_for(|cancelled| *cancelled).await;
+    }
+
+    pub fn report(&self, percent: Option<f64>, message: impl Into<String>) {
+        let state = self.app_handle.state::<OperationsState>();
+        if let Some(progress) = state.update(&self.id, percent, message.into()) {
+            let _ = self.app_handle.emit("operation-progress", &progress);
+        }
+    }
+
+    /// Reports `done` of `total` steps.
+    pub fn step(&self, done: usize, total: usize, message: impl Into<String>) {
+        self.report(Some(percent(done, total)), message);
+    }
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        if let Some(progress) = self.app_handle.state::<OperationsState>().finish(&self.id) {
+            let _ = self.app_handle.emit("operation-progress", &progress);
+        }
+    }
+}
+
+fn percent(done: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 100.0;
+    }
+    done.min(total) as f64 * 100.0 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operations_state() {
+        let state = OperationsState::default();
+        let (first, cancelled) = state.begin(OperationKind::Sync, None).unwrap();
+        let (second, _) = state.begin(OperationKind::ArchiveExport, Some("export-1".to_string())).unwrap();
+        assert_eq!(first.id, "op-1");
+        assert_eq!(second.id, "export-1");
+        assert!(state.begin(OperationKind::ArchiveExport, Some("export-1".to_string())).is_err());
+
+        let updated = state.update("op-1", Some(150.0), "Uploading notes.md".to_string()).unwrap();
+        assert_eq!(updated.percent, Some(100.0));
+        assert_eq!(updated.message, "Uploading notes.md");
+        assert!(state.update("op-9", None, String::new()).is_none());
+
+        let ids: Vec<String> = state.list().unwrap().into_iter().map(|operation| operation.id).collect();
+        assert_eq!(ids, vec!["op-1", "export-1"]);
+        assert!(!*cancelled.borrow());
+        assert!(state.cancel("op-1").unwrap());
+        assert!(*cancelled.borrow());
+        assert!(!state.cancel("op-9").unwrap());
+        assert!(state.finish("op-1").unwrap().done);
+        assert!(state.finish("op-1").is_none());
+        assert_eq!(state.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_percent() {
+        assert_eq!(percent(1, 4), 25.0);
+        assert_eq!(percent(5, 4), 100.0);
+        assert_eq!(percent(0, 0), 100.0);
+    }
+}
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::doc_cache::DocumentCache;
+use crate::sidecar;
+use crate::error::{AppError, AppResult};
+
+/// Documents opened this soon after launch count as "opened at startup".
+const LAUNCH_WINDOW: Duration = Duration::from_secs(120);
+/// How many past launches are remembered when ranking documents.
+const MAX_LAUNCHES: usize = 20;
+/// How many documents get pre-warmed.
+const PREFETCH_COUNT: usize = 5;
+
+pub struct PrefetchState {
+    launched_at: Instant,
+    opened_this_launch: Mutex<Vec<String>>,
+}
+
+impl Default for PrefetchState {
+    fn default() -> Self {
+        Self {
+            launched_at: Instant::now(),
+            opened_this_launch: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+/// The documents opened shortly after each of the last launches, newest last.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct LaunchHistory {
+    launches: Vec<Vec<String>>,
+}
+
+fn get_history_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir.join("launch_history.json"))
+}
+
+/// Remembers that `path` was opened, if it happened within the launch window.
+pub fn record_open(app_handle: &AppHandle, state: &PrefetchState, path: &str) -> AppResult<()> {
+    if state.launched_at.elapsed() > LAUNCH_WINDOW {
+        return Ok(());
+    }
+
+    let opened = {
+        let mut opened = state.opened_this_launch.lock()?;
+        if opened.iter().any(|p| p == path) {
+            return Ok(());
+        }
+        opened.push(path.to_string());
+        opened.clone()
+    };
+
+    let history_path = get_history_path(app_handle)?;
+    let mut history: LaunchHistory = sidecar::read_json(&history_path)?;
+
+    // The first open of this launch starts a new entry, later ones extend it
+    if opened.len() == 1 || history.launches.is_empty() {
+        history.launches.push(opened);
+    } else if let Some(last) = history.launches.last_mut() {
+        *last = opened;
+    }
+    let excess = history.launches.len().saturating_sub(MAX_LAUNCHES);
+    history.launches.drain(..excess);
+
+    sidecar::write_json(&history_path, &history)
+}
+
+/// Follows a renamed document in the launch history, so it's still
+/// prefetched.
+pub fn rename_document(app_handle: &AppHandle, state: &PrefetchState, old_path: &str, new_path: &str) -> AppResult<()> {
+    for path in state.opened_this_launch.lock()?.iter_mut() {
+        if *path == old_path {
+            *path = new_path.to_string();
+        }
+    }
+
+    let history_path = get_history_path(app_handle)?;
+    let mut history: LaunchHistory = sidecar::read_json(&history_path)?;
+    for path in history.launches.iter_mut().flatten().filter(|path| path.as_str() == old_path) {
+        *path = new_path.to_string();
+    }
+    sidecar::write_json(&history_path, &history)
+}
+
+/// Ranks documents by how many recent launches opened them, breaking ties
+/// in favour of the most recent launch.
+fn candidates(history: &LaunchHistory) -> Vec<String> {
+    let mut scores: HashMap<&str, (usize, usize)> = HashMap::new();
+    for (launch_index, launch) in history.launches.iter().enumerate() {
+        let unique: HashSet<&str> = launch.iter().map(|p| p.as_str()).collect();
+        for path in unique {
+            let score = scores.entry(path).or_default();
+            score.0 += 1;
+            score.1 = launch_index;
+        }
+    }
+
+    let mut ranked: Vec<(&str, (usize, usize))> = scores.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    ranked
+        .into_iter()
+        .take(PREFETCH_COUNT)
+        .map(|(path, _)| path.to_string())
+        .collect()
+}
+
+/// Loads the documents usually opened at startup into the document cache
+/// in the background, so the first `load_document` is served from memory.
+pub fn spawn_prefetch(app_handle: &AppHandle, idle_delay: Duration) {
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(idle_delay).await;
+
+        let _ = tauri::async_runtime::spawn_blocking(move || {
+            let history: LaunchHistory = match get_history_path(&app_handle).and_then(|p| sidecar::read_json(&p)) {
+                Ok(history) => history,
+                Err(e) => {
+                    tracing::warn!("Failed to read launch history: {}", e);
+                    return;
+                }
+            };
+
+            let cache = app_handle.state::<DocumentCache>();
+            for path in candidates(&history) {
+                // Missing or unreadable files just aren't warmed
+                let _ = cache.load(&path);
+            }
+        })
+        .await;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates_prefer_frequent_then_recent() {
+        let history = LaunchHistory {
+            launches: vec![
+                vec!["a".to_string(), "b".to_string()],
+                vec!["b".to_string()],
+                vec!["c".to_string(), "a".to_string()],
+            ],
+        };
+        assert_eq!(candidates(&history), vec!["a", "b", "c"]);
+    }
+}
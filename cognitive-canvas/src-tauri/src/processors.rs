@@ -0,0 +1,310 @@
+use chrono::Local;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+use crate::{deadlines, document_text, export::ExportFormat};
+use crate::error::{AppError, AppResult};
+
+/// A document passing through the processor pipeline. Processors may rewrite
+/// `content` and record what they derive under their own key in `metadata`.
+#[derive(Debug, Clone)]
+pub struct DocumentContext {
+    pub path: PathBuf,
+    pub content: String,
+    pub metadata: Map<String, Value>,
+}
+
+impl DocumentContext {
+    pub fn new(path: &Path, content: String) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            content,
+            metadata: Map::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Hook {
+    Load,
+    Save,
+    Export,
+}
+
+/// One stage of the document pipeline. Built-in features and plugins
+/// implement this the same way; every hook is optional.
+pub trait DocumentProcessor: Send + Sync {
+    /// Unique name, also used to break ties between equal priorities.
+    fn name(&self) -> &str;
+
+    /// Lower runs first.
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// Runs after a document is read from disk, before it reaches the editor.
+    fn on_load(&self, _doc: &mut DocumentContext) -> AppResult<()> {
+        Ok(())
+    }
+
+    /// Runs before a document is written; changes to `content` are saved.
+    fn on_save(&self, _doc: &mut DocumentContext) -> AppResult<()> {
+        Ok(())
+    }
+
+    /// Runs on a copy of the document before it's exported as `format`.
+    fn on_export(&self, _doc: &mut DocumentContext, _format: ExportFormat) -> AppResult<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HookTiming {
+    pub calls: u64,
+    pub errors: u64,
+    pub total_micros: u64,
+    pub max_micros: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessorMetrics {
+    pub name: String,
+    pub priority: i32,
+    pub hooks: BTreeMap<Hook, HookTiming>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessorError {
+    pub processor: String,
+    pub message: String,
+}
+
+struct Entry {
+    processor: Arc<dyn DocumentProcessor>,
+    timings: Mutex<BTreeMap<Hook, HookTiming>>,
+}
+
+/// Every registered processor, kept sorted by (priority, name) so the
+/// pipeline order doesn't depend on registration order.
+#[derive(Default)]
+pub struct ProcessorRegistry {
+    entries: RwLock<Vec<Arc<Entry>>>,
+}
+
+impl ProcessorRegistry {
+    /// A registry with the built-in processors already in place.
+    pub fn with_builtins() -> Self {
+        let registry = Self::default();
+        for processor in builtins() {
+            registry.register(processor).expect("built-in processor names are unique");
+        }
+        registry
+    }
+
+    pub fn register(&self, processor: Arc<dyn DocumentProcessor>) -> AppResult<()> {
+        let mut entries = self.entries.write()?;
+        if entries.iter().any(|e| e.processor.name() == processor.name()) {
+            return Err(AppError::conflict(format!("A processor named '{}' is already registered", processor.name())));
+        }
+
+        let key = (processor.priority(), processor.name().to_string());
+        let index = entries
+            .iter()
+            .position(|e| (e.processor.priority(), e.processor.name().to_string()) > key)
+            .unwrap_or(entries.len());
+        entries.insert(index, Arc::new(Entry {
+            processor,
+            timings: Mutex::new(BTreeMap::new()),
+        }));
+        Ok(())
+    }
+
+    pub fn unregister(&self, name: &str) -> bool {
+        let Ok(mut entries) = self.entries.write() else {
+            return false;
+        };
+        let before = entries.len();
+        entries.retain(|e| e.processor.name() != name);
+        entries.len() != before
+    }
+
+    pub fn on_load(&self, doc: &mut DocumentContext) -> Vec<ProcessorError> {
+        self.run(Hook::Load, doc, |p, doc| p.on_load(doc))
+    }
+
+    pub fn on_save(&self, doc: &mut DocumentContext) -> Vec<ProcessorError> {
+        self.run(Hook::Save, doc, |p, doc| p.on_save(doc))
+    }
+
+    pub fn on_export(&self, doc: &mut DocumentContext, format: ExportFormat) -> Vec<ProcessorError> {
+        self.run(Hook::Export, doc, |p, doc| p.on_export(doc, format))
+    }
+
+    /// Runs `hook` on every processor in order. A failing processor is
+    /// reported but doesn't stop the ones after it.
+    fn run<F>(&self, hook: Hook, doc: &mut DocumentContext, call: F) -> Vec<ProcessorError>
+    where
+        F: Fn(&dyn DocumentProcessor, &mut DocumentContext) -> AppResult<()>,
+    {
+        // Snapshot so a slow processor doesn't hold the registry lock
+        let entries = match self.entries.read() {
+            Ok(entries) => entries.clone(),
+            Err(_) => return Vec::new(),
+        };
+
+        let mut errors = Vec::new();
+        for entry in entries {
+            let started = Instant::now();
+            let result = call(entry.processor.as_ref(), doc);
+            let elapsed = started.elapsed().as_micros() as u64;
+
+            if let Ok(mut timings) = entry.timings.lock() {
+                let timing = timings.entry(hook).or_default();
+                timing.calls += 1;
+                timing.total_micros += elapsed;
+                timing.max_micros = timing.max_micros.max(elapsed);
+                if result.is_err() {
+                    timing.errors += 1;
+                }
+            }
+
+            if let Err(error) = result {
+                errors.push(ProcessorError {
+                    processor: entry.processor.name().to_string(),
+                    message: error.to_string(),
+                });
+            }
+        }
+        errors
+    }
+
+    /// Processors in pipeline order with their accumulated timings.
+    pub fn metrics(&self) -> Vec<ProcessorMetrics> {
+        let Ok(entries) = self.entries.read() else {
+            return Vec::new();
+        };
+        entries
+            .iter()
+            .map(|e| ProcessorMetrics {
+                name: e.processor.name().to_string(),
+                priority: e.processor.priority(),
+                hooks: e.timings.lock().map(|t| t.clone()).unwrap_or_default(),
+            })
+            .collect()
+    }
+}
+
+fn builtins() -> Vec<Arc<dyn DocumentProcessor>> {
+    vec![
+        Arc::new(FrontmatterProcessor),
+        Arc::new(LinkProcessor),
+        Arc::new(TaskProcessor),
+    ]
+}
+
+/// Exposes `---` frontmatter fields as `metadata.frontmatter`.
+struct FrontmatterProcessor;
+
+impl DocumentProcessor for FrontmatterProcessor {
+    fn name(&self) -> &str {
+        "frontmatter"
+    }
+
+    fn priority(&self) -> i32 {
+        -100
+    }
+
+    fn on_load(&self, doc: &mut DocumentContext) -> AppResult<()> {
+        let fields: Map<String, Value> = document_text::frontmatter(&document_text::plain_text(&doc.content))
+            .into_iter()
+            .map(|(key, value)| (key, Value::String(value)))
+            .collect();
+        if !fields.is_empty() {
+            doc.metadata.insert("frontmatter".to_string(), Value::Object(fields));
+        }
+        Ok(())
+    }
+}
+
+/// Lists outgoing links as `metadata.links`.
+struct LinkProcessor;
+
+impl DocumentProcessor for LinkProcessor {
+    fn name(&self) -> &str {
+        "links"
+    }
+
+    fn on_load(&self, doc: &mut DocumentContext) -> AppResult<()> {
+        let links = document_text::links(&doc.content);
+        doc.metadata.insert("links".to_string(), serde_json::to_value(links).map_err(|e| AppError::internal(e.to_string()))?);
+        Ok(())
+    }
+}
+
+/// Lists dated tasks and the frontmatter due date as `metadata.tasks`.
+struct TaskProcessor;
+
+impl DocumentProcessor for TaskProcessor {
+    fn name(&self) -> &str {
+        "tasks"
+    }
+
+    fn on_load(&self, doc: &mut DocumentContext) -> AppResult<()> {
+        let tasks = deadlines::extract_deadlines(&doc.path, &doc.content, Local::now().date_naive());
+        doc.metadata.insert("tasks".to_string(), serde_json::to_value(tasks).map_err(|e| AppError::internal(e.to_string()))?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Tag(&'static str, i32, bool);
+
+    impl DocumentProcessor for Tag {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn priority(&self) -> i32 {
+            self.1
+        }
+
+        fn on_save(&self, doc: &mut DocumentContext) -> AppResult<()> {
+            if self.2 {
+                return Err(AppError::internal("boom"));
+            }
+            doc.content.push_str(self.0);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn runs_in_priority_then_name_order() {
+        let registry = ProcessorRegistry::default();
+        registry.register(Arc::new(Tag("b", 0, false))).unwrap();
+        registry.register(Arc::new(Tag("late", 10, false))).unwrap();
+        registry.register(Arc::new(Tag("a", 0, false))).unwrap();
+        registry.register(Arc::new(Tag("failing", 5, true))).unwrap();
+        registry.register(Arc::new(Tag("early", -1, false))).unwrap();
+        assert!(registry.register(Arc::new(Tag("a", 3, false))).is_err());
+
+        let mut doc = DocumentContext::new(Path::new("note.md"), String::new());
+        let errors = registry.on_save(&mut doc);
+
+        assert_eq!(doc.content, "earlyablate");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].processor, "failing");
+
+        let metrics = registry.metrics();
+        let names: Vec<_> = metrics.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["early", "a", "b", "failing", "late"]);
+        assert_eq!(metrics[3].hooks[&Hook::Save].errors, 1);
+        assert!(metrics.iter().all(|m| m.hooks[&Hook::Save].calls == 1));
+    }
+}
@@ -0,0 +1,380 @@
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use crate::link_graph::{file_stem, link_candidates};
+use crate::{cli, comments, document_format, document_lock, embeddings, link_index, prefetch, sidecar, suggestions, tags, workspace};
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UpdatedReference {
+    pub path: String,
+    /// How many links in it now point at the new path
+    pub links: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RenameReport {
+    pub old_path: String,
+    pub new_path: String,
+    pub updated: Vec<UpdatedReference>,
+    /// Documents linking to the old path that couldn't be updated, with why
+    pub failed: Vec<String>,
+}
+
+/// A link from one document to another as it's written in the first:
+/// `../notes/plan.md`, always with `/` separators.
+fn relative_link(from: &Path, to: &Path) -> String {
+    let base: Vec<_> = from.parent().unwrap_or(Path::new("")).components().collect();
+    let target: Vec<_> = to.components().collect();
+    let common = base.iter().zip(&target).take_while(|(a, b)| a == b).count();
+    std::iter::repeat_n("..".to_string(), base.len() - common)
+        .chain(target[common..].iter().map(|c| c.as_os_str().to_string_lossy().to_string()))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn split_anchor(target: &str) -> (&str, &str) {
+    target.find('#').map_or((target, ""), |i| target.split_at(i))
+}
+
+fn has_document_extension(link: &str) -> bool {
+    Path::new(link)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| workspace::DOCUMENT_EXTENSIONS.contains(&e))
+}
+
+/// A link from `from` to `to` written the way `original` was: absolute or
+/// relative, and with an extension only if it had one.
+fn link_like(original: &str, from: &Path, to: &Path) -> String {
+    if Path::new(original).is_absolute() {
+        return to.to_string_lossy().to_string();
+    }
+    let link = relative_link(from, to);
+    if has_document_extension(original) || !has_document_extension(&link) {
+        return link;
+    }
+    link.rsplit_once('.').map_or(link.clone(), |(stem, _)| stem.to_string())
+}
+
+/// The new target of a link in `document`, if it pointed at `old`, which
+/// has already been moved to `new`.
+fn retarget(document: &Path, old: &Path, new: &Path, target: &str, wiki: bool) -> Option<String> {
+    let (link, anchor) = split_anchor(target);
+    let candidates = link_candidates(document, link);
+    let points_at_old = candidates.iter().any(|candidate| candidate == old);
+
+    // [[Name]] matches by name anywhere, unless a document next to this one has it
+    if wiki && !link.contains('/') {
+        let shadowed = candidates.iter().any(|candidate| candidate != old && candidate.is_file());
+        if !points_at_old && (shadowed || file_stem(Path::new(link)) != file_stem(old)) {
+            return None;
+        }
+        let name = if has_document_extension(link) { new.file_name()? } else { new.file_stem()? };
+        return Some(format!("{}{}", name.to_string_lossy(), anchor));
+    }
+    points_at_old.then(|| format!("{}{}", link_like(link, document, new), anchor))
+}
+
+/// The new target of a relative link in the document at `old`, as it's
+/// about to move to `new`. Links by name still work from anywhere.
+fn rebase(old: &Path, new: &Path, target: &str, wiki: bool) -> Option<String> {
+    let (link, anchor) = split_anchor(target);
+    if wiki && !link.contains('/') {
+        return None;
+    }
+    let found = link_candidates(old, link).into_iter().find(|candidate| candidate.is_file())?;
+    let to = if found == old {
+        new
+    } else if old.parent() == new.parent() {
+        return None;
+    } else {
+        &found
+    };
+    Some(format!("{}{}", link_like(link, new, to), anchor))
+}
+
+/// Rewrites the `[[wiki links]]` and `[text](target)` links in `text`, the
+/// same ones `document_text::links` finds. `rewrite` gets each target and
+/// whether it's a wiki link; `count` goes up by the links it changed.
+fn rewrite_text(text: &str, rewrite: &dyn Fn(&str, bool) -> Option<String>, count: &mut usize) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('[') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(inner) = rest.strip_prefix("[[") {
+            if let Some(end) = inner.find("]]") {
+                let link = &inner[..end];
+                let (target, alias) = link.find('|').map_or((link, ""), |i| link.split_at(i));
+                out.push_str("[[");
+                match rewrite(target.trim(), true).filter(|new| new != target.trim()) {
+                    Some(new) => {
+                        out.push_str(&new);
+                        out.push_str(alias);
+                        *count += 1;
+                    }
+                    None => out.push_str(link),
+                }
+                out.push_str("]]");
+                rest = &inner[end + 2..];
+                continue;
+            }
+        } else if let Some(close) = rest.find("](") {
+            let after = &rest[close + 2..];
+            if !rest[1..close].contains('[') {
+                if let Some(end) = after.find(')') {
+                    let raw = &after[..end];
+                    let trimmed = raw.trim();
+                    let bracketed = trimmed.strip_prefix('<').and_then(|t| t.strip_suffix('>'));
+                    let target = bracketed.unwrap_or(trimmed);
+                    out.push_str(&rest[..close + 2]);
+                    match rewrite(target, false).filter(|new| new != target) {
+                        // Targets with spaces need the <...> form
+                        Some(new) if bracketed.is_some() || new.contains(' ') => {
+                            out.push_str(&format!("<{}>", new));
+                            *count += 1;
+                        }
+                        Some(new) => {
+                            out.push_str(&new);
+                            *count += 1;
+                        }
+                        None => out.push_str(raw),
+                    }
+                    out.push(')');
+                    rest = &after[end + 1..];
+                    continue;
+                }
+            }
+        }
+        out.push('[');
+        rest = &rest[1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn rewrite_nodes(node: &mut Value, rewrite: &dyn Fn(&str, bool) -> Option<String>, count: &mut usize) {
+    let is_link = matches!(node.get("type").and_then(|t| t.as_str()), Some("link" | "autolink"));
+    if is_link {
+        if let Some(url) = node.get("url").and_then(|u| u.as_str()) {
+            if let Some(new) = rewrite(url, false).filter(|new| new != url) {
+                node["url"] = Value::String(new);
+                *count += 1;
+            }
+        }
+    }
+    if let Some(text) = node.get("text").and_then(|t| t.as_str()) {
+        let before = *count;
+        let new = rewrite_text(text, rewrite, count);
+        if *count > before {
+            node["text"] = Value::String(new);
+        }
+    }
+    for child in node.get_mut("children").and_then(|c| c.as_array_mut()).into_iter().flatten() {
+        rewrite_nodes(child, rewrite, count);
+    }
+}
+
+/// Rewrites the links in a document, Lexical JSON or text. Returns the new
+/// content and how many links changed, or None if none did.
+fn rewrite_content(content: &str, rewrite: &dyn Fn(&str, bool) -> Option<String>) -> Option<(String, usize)> {
+    let mut count = 0;
+    let content = match serde_json::from_str::<Value>(content) {
+        Ok(mut value) if value.get("root").is_some() => {
+            rewrite_nodes(&mut value["root"], rewrite, &mut count);
+            if count == 0 {
+                return None;
+            }
+            serde_json::to_string(&value).ok()?
+        }
+        _ => rewrite_text(content, rewrite, &mut count),
+    };
+    (count > 0).then_some((content, count))
+}
+
+fn write_document(path: &Path, content: &str, compress: bool) -> AppResult<()> {
+    let bytes = document_format::encode(path, content, compress)?;
+    std::fs::write(path, bytes).map_err(|e| AppError::io(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// Moves a document and its sidecars from `old` to `new`, rebasing its own
+/// relative links when it changes folder. Both paths must be absolute.
+fn move_document(old: &Path, new: &Path, compress: bool) -> AppResult<()> {
+    if !old.is_file() {
+        return Err(AppError::validation(format!("{} is not a document", old.display())));
+    }
+    if new.exists() {
+        return Err(AppError::conflict(format!("{} already exists", new.display())));
+    }
+    if let Some(lock) = document_lock::foreign_lock(old) {
+        return Err(AppError::conflict(format!("{} is open on {}", old.display(), lock.hostname)));
+    }
+
+    // Where its links lead has to be worked out while it's still in place
+    let rebased = document_format::read_document(old)
+        .ok()
+        .and_then(|content| rewrite_content(&content, &|target, wiki| rebase(old, new, target, wiki)));
+
+    if let Some(parent) = new.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| AppError::io(format!("Failed to create {}: {}", parent.display(), e)))?;
+    }
+    std::fs::rename(old, new).map_err(|e| AppError::io(format!("Failed to rename {}: {}", old.display(), e)))?;
+    for (kind, sidecar_path) in sidecar::sidecars(old) {
+        if kind == "lock" {
+            let _ = std::fs::remove_file(&sidecar_path);
+        } else if let Err(e) = std::fs::rename(&sidecar_path, sidecar::sidecar_path(new, &kind)) {
+            tracing::warn!("Failed to move {}: {}", sidecar_path.display(), e);
+        }
+    }
+    if let Some((content, _)) = rebased {
+        write_document(new, &content, compress)?;
+    }
+    Ok(())
+}
+
+/// Points the links in `documents` that led to `old` at `new`.
+fn update_references(documents: &BTreeSet<PathBuf>, old: &Path, new: &Path, compress: bool, report: &mut RenameReport) {
+    let stem = file_stem(old);
+    for document in documents.iter().filter(|document| *document != new) {
+        let content = match document_format::read_document(document) {
+            Ok(content) => content,
+            Err(e) => {
+                report.failed.push(e.to_string());
+                continue;
+            }
+        };
+        // Any link to it mentions its name, which saves parsing most documents
+        if !content.to_lowercase().contains(&stem) {
+            continue;
+        }
+        let rewrite = |target: &str, wiki: bool| retarget(document, old, new, target, wiki);
+        let Some((content, links)) = rewrite_content(&content, &rewrite) else {
+            continue;
+        };
+        match write_document(document, &content, compress) {
+            Ok(()) => report.updated.push(UpdatedReference { path: document.to_string_lossy().to_string(), links }),
+            Err(e) => report.failed.push(e.to_string()),
+        }
+    }
+}
+
+/// Renames or moves a document, then updates the links to it across the
+/// workspace and the indexes that know it by path.
+pub fn rename_document(app_handle: &AppHandle, old: &Path, new: &Path) -> AppResult<RenameReport> {
+    let old = std::path::absolute(old).map_err(|e| AppError::io(format!("Failed to resolve {}: {}", old.display(), e)))?;
+    let new = std::path::absolute(new).map_err(|e| AppError::io(format!("Failed to resolve {}: {}", new.display(), e)))?;
+    let compress = document_format::compression_enabled(app_handle);
+    move_document(&old, &new, compress)?;
+
+    // Documents saved since the index was built know their links; the rest
+    // of the workspace is searched too
+    let mut documents: BTreeSet<PathBuf> = link_index::get_backlinks(app_handle, &old)
+        .unwrap_or_default()
+        .into_iter()
+        .map(PathBuf::from)
+        .collect();
+    if let Ok(root) = cli::workspace_root(app_handle) {
+        documents.extend(workspace::document_files(&root).unwrap_or_default().into_iter().filter_map(|p| std::path::absolute(p).ok()));
+    }
+
+    let mut report = RenameReport {
+        old_path: old.to_string_lossy().to_string(),
+        new_path: new.to_string_lossy().to_string(),
+        ..Default::default()
+    };
+    update_references(&documents, &old, &new, compress, &mut report);
+
+    if let Err(e) = link_index::remove_document(app_handle, &old) {
+        tracing::warn!("Failed to index links: {}", e);
+    }
+    let reindexed = std::iter::once(new.clone()).chain(report.updated.iter().map(|u| PathBuf::from(&u.path)));
+    for path in reindexed {
+        let result = document_format::read_document(&path).and_then(|content| link_index::index_document(app_handle, &path, &content));
+        if let Err(e) = result {
+            tracing::warn!("Failed to index links: {}", e);
+        }
+    }
+    let renames = [
+        tags::rename_document(app_handle, &old, &new),
+        embeddings::rename_document(app_handle, &old, &new),
+        comments::rename_document(app_handle, &old, &new),
+        suggestions::rename_document(app_handle, &old, &new),
+        prefetch::rename_document(app_handle, &app_handle.state::<prefetch::PrefetchState>(), &report.old_path, &report.new_path),
+    ];
+    for e in renames.into_iter().filter_map(Result::err) {
+        tracing::warn!("Failed to update index after rename: {}", e);
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_relative_link() {
+        let from = Path::new("/w/notes/index.md");
+        assert_eq!(relative_link(from, Path::new("/w/notes/plan.md")), "plan.md");
+        assert_eq!(relative_link(from, Path::new("/w/archive/2024/plan.md")), "../archive/2024/plan.md");
+        assert_eq!(relative_link(Path::new("/w/index.md"), Path::new("/w/notes/plan.md")), "notes/plan.md");
+    }
+
+    #[test]
+    fn test_rewrite_text() {
+        let rewrite = |target: &str, wiki: bool| match (target, wiki) {
+            ("Plan", true) | ("Plan#Goals", true) => Some(target.replace("Plan", "Roadmap")),
+            ("plan.md", false) => Some("2024/road map.md".to_string()),
+            _ => None,
+        };
+        let mut count = 0;
+        let text = "See [[Plan|the plan]], [[Plan#Goals]], [[Other]] and [the plan](plan.md) or [x](other.md). [[unclosed";
+        assert_eq!(
+            rewrite_text(text, &rewrite, &mut count),
+            "See [[Roadmap|the plan]], [[Roadmap#Goals]], [[Other]] and [the plan](<2024/road map.md>) or [x](other.md). [[unclosed"
+        );
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn test_rename_updates_links() {
+        let dir = std::path::absolute(std::env::temp_dir().join("test_rename")).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("notes")).unwrap();
+        let (old, new) = (dir.join("notes/plan.md"), dir.join("archive/roadmap.md"));
+        fs::write(&old, "# Plan\nSee [the index](../index.md) and [[Plan#Goals]].").unwrap();
+        fs::write(sidecar::sidecar_path(&old, "comments"), "[]").unwrap();
+        let index = dir.join("index.md");
+        fs::write(&index, "[[plan|Our plan]], [plan](notes/plan) and [other](notes/other.md)").unwrap();
+        let canvas = dir.join("notes/board.canvas");
+        let lexical = serde_json::json!({ "root": { "children": [{ "type": "paragraph", "children": [
+            { "type": "link", "url": "plan.md#goals", "children": [{ "type": "text", "text": "Plan" }] },
+            { "type": "text", "text": "and [[plan]]" }
+        ] }] } });
+        fs::write(&canvas, lexical.to_string()).unwrap();
+
+        move_document(&old, &new, false).unwrap();
+        assert!(!old.exists());
+        assert_eq!(fs::read_to_string(&new).unwrap(), "# Plan\nSee [the index](../index.md) and [[Plan#Goals]].");
+        assert!(sidecar::sidecar_path(&new, "comments").exists());
+        assert!(move_document(&old, &new, false).is_err());
+
+        let mut report = RenameReport::default();
+        let documents = BTreeSet::from([index.clone(), canvas.clone(), new.clone()]);
+        update_references(&documents, &old, &new, false, &mut report);
+        assert_eq!(fs::read_to_string(&index).unwrap(), "[[roadmap|Our plan]], [plan](archive/roadmap) and [other](notes/other.md)");
+        let canvas_content: Value = serde_json::from_str(&fs::read_to_string(&canvas).unwrap()).unwrap();
+        let children = &canvas_content["root"]["children"][0]["children"];
+        assert_eq!(children[0]["url"], "../archive/roadmap.md#goals");
+        assert_eq!(children[1]["text"], "and [[roadmap]]");
+        assert_eq!(report.updated.len(), 2);
+        assert!(report.updated.iter().all(|u| u.links == 2));
+        assert!(report.failed.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
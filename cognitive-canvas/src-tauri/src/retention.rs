@@ -0,0 +1,316 @@
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use crate::config_parser::ConfigParser;
+use crate::{document_format, document_text, sidecar, workspace};
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionConfig {
+    pub enabled: bool,
+    pub workspace_dir: String,
+    /// How many days before expiry to warn the user
+    pub warning_days: i64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            workspace_dir: String::new(),
+            warning_days: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpiryWarning {
+    pub path: String,
+    pub expires: NaiveDate,
+    pub days_left: i64,
+    pub action: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RetentionReport {
+    pub archived: Vec<String>,
+    pub trashed: Vec<String>,
+    pub warnings: Vec<ExpiryWarning>,
+}
+
+const ARCHIVE_DIR: &str = "Archive";
+const TRASH_DIR: &str = ".trash";
+
+fn get_app_data_dir(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir)
+}
+
+/// retention.conf, or None if there isn't one yet.
+fn load_parser(app_handle: &AppHandle) -> AppResult<Option<ConfigParser>> {
+    let config_path = get_app_data_dir(app_handle)?.join("retention.conf");
+    if !config_path.exists() {
+        return Ok(None);
+    }
+
+    let config_path_str = config_path.to_str()
+        .ok_or_else(|| AppError::config("Invalid config path"))?;
+    let mut parser = ConfigParser::new(config_path_str);
+    parser.set_range("warning_days", 0.0, 365.0);
+    parser.load()?;
+    Ok(Some(parser))
+}
+
+pub fn load_retention_config(app_handle: &AppHandle) -> AppResult<RetentionConfig> {
+    let Some(parser) = load_parser(app_handle)? else {
+        return Ok(RetentionConfig::default());
+    };
+
+    let defaults = RetentionConfig::default();
+    Ok(RetentionConfig {
+        enabled: parser.get_bool("enabled").unwrap_or(defaults.enabled),
+        workspace_dir: parser.get_str("workspace_dir").cloned().unwrap_or_default(),
+        warning_days: parser.get_int("warning_days").unwrap_or(defaults.warning_days),
+    })
+}
+
+/// The configured workspace with `~` and variables expanded, or None when
+/// there isn't one.
+pub fn workspace_dir(app_handle: &AppHandle) -> AppResult<Option<PathBuf>> {
+    let dir = load_parser(app_handle)?.and_then(|parser| parser.get_expanded("workspace_dir")).unwrap_or_default();
+    Ok((!dir.trim().is_empty()).then(|| PathBuf::from(dir)))
+}
+
+pub fn save_retention_config(app_handle: &AppHandle, config: &RetentionConfig) -> AppResult<()> {
+    let config_path = get_app_data_dir(app_handle)?.join("retention.conf");
+    let config_path_str = config_path.to_str()
+        .ok_or_else(|| AppError::config("Invalid config path"))?;
+
+    let mut parser = ConfigParser::new(config_path_str);
+    parser.set_bool("enabled", config.enabled);
+    parser.set_str("workspace_dir", &config.workspace_dir);
+    parser.set_range("warning_days", 0.0, 365.0);
+    parser.set_int("warning_days", config.warning_days)?;
+
+    parser.set_comment("enabled", "Archive or trash documents whose expires: date has passed");
+    parser.set_comment("warning_days", "Days of notice before a document expires");
+
+    parser.save()
+}
+
+/// Archives or trashes expired documents and warns about ones expiring
+/// soon. Documents opt in through frontmatter:
+///
+/// ```text
+/// ---
+/// expires: 2025-12-31
+/// on_expire: trash   # or archive (default)
+/// ---
+/// ```
+pub fn run_retention(app_handle: &AppHandle) -> AppResult<RetentionReport> {
+    let config = load_retention_config(app_handle)?;
+    let Some(workspace_dir) = workspace_dir(app_handle)?.filter(|_| config.enabled) else {
+        return Ok(RetentionReport::default());
+    };
+    let today = Local::now().date_naive();
+
+    // Remember who was already warned so the event fires once per document
+    let warned_path = get_app_data_dir(app_handle)?.join("retention_warned.json");
+    let mut warned: HashSet<String> = sidecar::read_json(&warned_path)?;
+
+    let report = apply_retention(&workspace_dir, today, config.warning_days, &mut warned)?;
+    sidecar::write_json(&warned_path, &warned)?;
+
+    for warning in &report.warnings {
+        let _ = app_handle.emit("retention-warning", warning);
+    }
+    if !report.archived.is_empty() || !report.trashed.is_empty() {
+        let _ = app_handle.emit("retention-applied", &report);
+    }
+
+    Ok(report)
+}
+
+/// What to do with a document once its `expires` date has passed, or None
+/// if it has no valid date.
+fn expiry(content: &str) -> Option<(NaiveDate, &'static str)> {
+    let frontmatter = document_text::frontmatter(&document_text::plain_text(content));
+    let value = |key: &str| frontmatter.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone());
+
+    let expires = NaiveDate::parse_from_str(&value("expires")?, "%Y-%m-%d").ok()?;
+    let action = match value("on_expire").as_deref() {
+        Some("trash") | Some("delete") => "trash",
+        _ => "archive",
+    };
+    Some((expires, action))
+}
+
+/// Moves documents that expired before `today` and lists the ones within
+/// `warning_days` of expiring that aren't in `warned` yet.
+fn apply_retention(
+    workspace_dir: &Path,
+    today: NaiveDate,
+    warning_days: i64,
+    warned: &mut HashSet<String>,
+) -> AppResult<RetentionReport> {
+    let mut report = RetentionReport::default();
+    let archive_root = workspace_dir.join(ARCHIVE_DIR);
+    for path in workspace::document_files(workspace_dir)? {
+        // Never re-process what retention already archived
+        if path.starts_with(&archive_root) {
+            continue;
+        }
+        let content = match document_format::read_document(&path) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+        let Some((expires, action)) = expiry(&content) else {
+            continue;
+        };
+
+        let path_str = path.to_string_lossy().to_string();
+        let days_left = (expires - today).num_days();
+
+        if days_left < 0 {
+            let target_root = workspace_dir.join(if action == "trash" { TRASH_DIR } else { ARCHIVE_DIR });
+            move_into(workspace_dir, &path, &target_root)?;
+            warned.remove(&path_str);
+            if action == "trash" {
+                report.trashed.push(path_str);
+            } else {
+                report.archived.push(path_str);
+            }
+        } else if days_left <= warning_days && !warned.contains(&path_str) {
+            report.warnings.push(ExpiryWarning {
+                path: path_str.clone(),
+                expires,
+                days_left,
+                action: action.to_string(),
+            });
+            warned.insert(path_str);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Moves `path` below `target_root`, keeping its position relative to the
+/// workspace and never overwriting an existing file.
+fn move_into(workspace: &Path, path: &Path, target_root: &Path) -> AppResult<()> {
+    let relative = path.strip_prefix(workspace).unwrap_or(path);
+    let mut target = target_root.join(relative);
+
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| AppError::io(format!("Failed to create {}: {}", parent.display(), e)))?;
+    }
+    if target.exists() {
+        let stamp = Local::now().format("%Y%m%d%H%M%S");
+        let name = target.file_name().and_then(|n| n.to_str()).unwrap_or("document").to_string();
+        target.set_file_name(format!("{}-{}", stamp, name));
+    }
+
+    std::fs::rename(path, &target)
+        .map_err(|e| AppError::io(format!("Failed to move {}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_expiry() {
+        let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+        assert_eq!(expiry("---\nexpires: 2025-01-31\n---\nBody"), Some((date("2025-01-31"), "archive")));
+        assert_eq!(expiry("---\nexpires: \"2025-01-31\"\non_expire: delete\n---\n"), Some((date("2025-01-31"), "trash")));
+        assert_eq!(expiry("---\nexpires: 2025-01-31\non_expire: keep\n---\n"), Some((date("2025-01-31"), "archive")));
+        assert_eq!(expiry("---\nexpires: next week\n---\n"), None);
+        assert_eq!(expiry("expires: 2025-01-31\n"), None);
+        assert_eq!(expiry("---\ntitle: Notes\n---\nexpires: 2025-01-31\n"), None);
+    }
+
+    #[test]
+    fn test_apply_retention() {
+        let dir = std::env::temp_dir().join("test_retention_apply");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("projects")).unwrap();
+        fs::create_dir_all(dir.join(ARCHIVE_DIR)).unwrap();
+
+        let document = |name: &str, frontmatter: &str| {
+            let path = dir.join(name);
+            fs::write(&path, format!("---\n{}\n---\nBody\n", frontmatter)).unwrap();
+            path
+        };
+        let old = document("projects/old.md", "expires: 2025-03-01");
+        let scratch = document("scratch.md", "expires: 2025-03-09\non_expire: trash");
+        let soon = document("soon.md", "expires: 2025-03-12");
+        let today = document("today.md", "expires: 2025-03-10");
+        let later = document("later.md", "expires: 2025-04-01");
+        let kept = document("kept.md", "title: Kept");
+        let archived = document("Archive/done.md", "expires: 2024-01-01");
+
+        let mut warned = HashSet::new();
+        let date = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        let report = apply_retention(&dir, date, 3, &mut warned).unwrap();
+
+        assert_eq!(report.archived, vec![old.to_string_lossy().to_string()]);
+        assert_eq!(report.trashed, vec![scratch.to_string_lossy().to_string()]);
+        assert!(dir.join("Archive/projects/old.md").exists());
+        assert!(dir.join(".trash/scratch.md").exists());
+        assert!(!old.exists() && !scratch.exists());
+        assert!(soon.exists() && today.exists() && later.exists() && kept.exists() && archived.exists());
+
+        let mut warnings: Vec<(String, i64)> = report
+            .warnings
+            .iter()
+            .map(|w| (w.path.clone(), w.days_left))
+            .collect();
+        warnings.sort();
+        assert_eq!(
+            warnings,
+            vec![(soon.to_string_lossy().to_string(), 2), (today.to_string_lossy().to_string(), 0)]
+        );
+
+        // Documents are only warned about once
+        let report = apply_retention(&dir, date, 3, &mut warned).unwrap();
+        assert!(report.warnings.is_empty() && report.archived.is_empty() && report.trashed.is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_move_into_keeps_existing() {
+        let dir = std::env::temp_dir().join("test_retention_move");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("notes")).unwrap();
+        fs::create_dir_all(dir.join("Archive/notes")).unwrap();
+        fs::write(dir.join("Archive/notes/a.md"), "earlier").unwrap();
+        fs::write(dir.join("notes/a.md"), "latest").unwrap();
+
+        move_into(&dir, &dir.join("notes/a.md"), &dir.join(ARCHIVE_DIR)).unwrap();
+
+        assert!(!dir.join("notes/a.md").exists());
+        assert_eq!(fs::read_to_string(dir.join("Archive/notes/a.md")).unwrap(), "earlier");
+        let moved: Vec<String> = fs::read_dir(dir.join("Archive/notes"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| name != "a.md")
+            .collect();
+        assert_eq!(moved.len(), 1);
+        assert!(moved[0].ends_with("-a.md"));
+        assert_eq!(fs::read_to_string(dir.join("Archive/notes").join(&moved[0])).unwrap(), "latest");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,322 @@
+use async_trait::async_trait;
+use reqwest::{Client, Method, StatusCode};
+use sha2::{Digest, Sha256};
+use crate::sync_provider::{decode_xml_entities, encode_segment, extract_elements, RemoteEntry, SyncProvider};
+use crate::error::{AppError, AppResult};
+
+/// The headers every request is signed over, sorted and lowercase.
+const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+
+/// Minimal S3 client using path-style addressing, which AWS, Backblaze B2,
+/// MinIO and most other S3-compatible stores accept.
+#[derive(Debug, Clone)]
+pub struct S3Client {
+    endpoint: String,
+    bucket: String,
+    region: String,
+    access_key_id: String,
+    secret_access_key: String,
+    http: Client,
+}
+
+impl S3Client {
+    pub fn new(endpoint: &str, bucket: &str, region: &str, access_key_id: &str, secret_access_key: &str) -> Self {
+        let endpoint = if endpoint.trim().is_empty() {
+            format!("https://s3.{}.amazonaws.com", region)
+        } else {
+            endpoint.trim_end_matches('/').to_string()
+        };
+
+        Self {
+            endpoint,
+            bucket: bucket.to_string(),
+            region: region.to_string(),
+            access_key_id: access_key_id.to_string(),
+            secret_access_key: secret_access_key.to_string(),
+            http: Client::new(),
+        }
+    }
+
+    fn host(&self) -> String {
+        let without_scheme = self.endpoint.split("://").nth(1).unwrap_or(&self.endpoint);
+        without_scheme.split('/').next().unwrap_or("").to_string()
+    }
+
+    fn canonical_uri(&self, key: &str) -> String {
+        let mut uri = format!("/{}", encode_segment(&self.bucket));
+        if !key.is_empty() {
+            uri.push('/');
+            uri.push_str(
+                &key.split('/').map(encode_segment).collect::<Vec<_>>().join("/"),
+            );
+        }
+        uri
+    }
+
+    /// Builds a request signed with AWS Signature Version 4.
+    fn signed_request(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, &str)],
+        body: &[u8],
+    ) -> reqwest::RequestBuilder {
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex::encode(Sha256::digest(body));
+        let host = self.host();
+
+        let canonical_uri = self.canonical_uri(key);
+        let mut sorted_query: Vec<_> = query
+            .iter()
+            .map(|(k, v)| (encode_segment(k), encode_segment(v)))
+            .collect();
+        sorted_query.sort();
+        let canonical_query = sorted_query
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let canonical_request =
+            canonical_request(method.as_str(), &canonical_uri, &canonical_query, &host, &payload_hash, &amz_date);
+        let signature = sign(&self.secret_access_key, &self.region, &amz_date, &canonical_request);
+
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key_id,
+            scope(&date, &self.region),
+            SIGNED_HEADERS,
+            signature
+        );
+
+        let mut url = format!("{}{}", self.endpoint, canonical_uri);
+        if !canonical_query.is_empty() {
+            url.push('?');
+            url.push_str(&canonical_query);
+        }
+
+        self.http
+            .request(method, url)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("Authorization", authorization)
+            .body(body.to_vec())
+    }
+}
+
+#[async_trait]
+impl SyncProvider for S3Client {
+    async fn list_files(&self, dir: &str) -> AppResult<Vec<RemoteEntry>> {
+        let prefix = match dir.trim_matches('/') {
+            "" => String::new(),
+            dir => format!("{}/", dir),
+        };
+
+        let mut entries = Vec::new();
+        let mut continuation: Option<String> = None;
+
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", prefix.as_str())];
+            if let Some(token) = &continuation {
+                query.push(("continuation-token", token.as_str()));
+            }
+
+            let response = self
+                .signed_request(Method::GET, "", &query, b"")
+                .send()
+                .await
+                .map_err(|e| AppError::network(format!("S3 request failed: {}", e)))?;
+
+            if !response.status().is_success() {
+                return Err(AppError::network(format!("S3 listing failed: {}", response.status())));
+            }
+
+            let xml = response
+                .text()
+                .await
+                .map_err(|e| AppError::network(format!("Failed to read S3 response: {}", e)))?;
+
+            for contents in extract_elements(&xml, "Contents") {
+                let key = match extract_elements(&contents, "Key").into_iter().next() {
+                    Some(key) => decode_xml_entities(&key),
+                    None => continue,
+                };
+                // Zero-byte "folder" markers created by some S3 browsers
+                if key.ends_with('/') {
+                    continue;
+                }
+                let etag = extract_elements(&contents, "ETag")
+                    .into_iter()
+                    .next()
+                    .map(|e| decode_xml_entities(&e).trim_matches('"').to_string());
+
+                entries.push(RemoteEntry { path: key, etag, is_dir: false });
+            }
+
+            let truncated = extract_elements(&xml, "IsTruncated")
+                .first()
+                .is_some_and(|v| v.trim() == "true");
+            continuation = extract_elements(&xml, "NextContinuationToken")
+                .into_iter()
+                .next()
+                .map(|t| decode_xml_entities(&t));
+
+            if !truncated || continuation.is_none() {
+                break;
+            }
+        }
+
+        Ok(entries)
+    }
+
+    async fn download(&self, path: &str) -> AppResult<Vec<u8>> {
+        let response = self
+            .signed_request(Method::GET, path, &[], b"")
+            .send()
+            .await
+            .map_err(|e| AppError::network(format!("S3 request failed: {}", e)))?;
+
+        if !response.status().is_success() {
+            return Err(AppError::network(format!("Failed to download {}: {}", path, response.status())));
+        }
+
+        response
+            .bytes()
+            .await
+            .map(|b| b.to_vec())
+            .map_err(|e| AppError::network(format!("Failed to download {}: {}", path, e)))
+    }
+
+    async fn upload(&self, path: &str, contents: Vec<u8>) -> AppResult<Option<String>> {
+        let response = self
+            .signed_request(Method::PUT, path, &[], &contents)
+            .send()
+            .await
+            .map_err(|e| AppError::network(format!("S3 request failed: {}", e)))?;
+
+        if response.status() == StatusCode::FORBIDDEN {
+            return Err(AppError::network("S3 rejected the credentials"));
+        }
+        if !response.status().is_success() {
+            return Err(AppError::network(format!("Failed to upload {}: {}", path, response.status())));
+        }
+
+        Ok(response
+            .headers()
+            .get("etag")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim_matches('"').to_string()))
+    }
+
+    async fn delete(&self, path: &str) -> AppResult<()> {
+        let response = self
+            .signed_request(Method::DELETE, path, &[], b"")
+            .send()
+            .await
+            .map_err(|e| AppError::network(format!("S3 request failed: {}", e)))?;
+
+        if response.status() == StatusCode::FORBIDDEN {
+            return Err(AppError::network("S3 rejected the credentials"));
+        }
+        // S3 answers 204 whether or not the key existed; others may say 404
+        if !response.status().is_success() && response.status() != StatusCode::NOT_FOUND {
+            return Err(AppError::network(format!("Failed to delete {}: {}", path, response.status())));
+        }
+        Ok(())
+    }
+}
+
+fn scope(date: &str, region: &str) -> String {
+    format!("{}/{}/s3/aws4_request", date, region)
+}
+
+/// The request as SigV4 sees it, covering `SIGNED_HEADERS` and the payload.
+fn canonical_request(method: &str, uri: &str, query: &str, host: &str, payload_hash: &str, amz_date: &str) -> String {
+    format!(
+        "{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        method, uri, query, host, payload_hash, amz_date, SIGNED_HEADERS, payload_hash
+    )
+}
+
+/// The hex SigV4 signature of `canonical_request` made at `amz_date`
+/// (`YYYYMMDDTHHMMSSZ`), with a key derived for that day and region.
+fn sign(secret_access_key: &str, region: &str, amz_date: &str, canonical_request: &str) -> String {
+    let date = &amz_date[..8];
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope(date, region),
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let scope_parts: [&[u8]; 3] = [region.as_bytes(), b"s3", b"aws4_request"];
+    let signing_key = scope_parts.iter().fold(
+        hmac_sha256(format!("AWS4{}", secret_access_key).as_bytes(), date.as_bytes()),
+        |key, part| hmac_sha256(&key, part),
+    );
+    hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()))
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    const BLOCK_SIZE: usize = 64;
+
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(block.iter().map(|b| b ^ 0x36).collect::<Vec<_>>());
+    inner.update(message);
+    let inner_hash = inner.finalize();
+
+    let mut outer = Sha256::new();
+    outer.update(block.iter().map(|b| b ^ 0x5c).collect::<Vec<_>>());
+    outer.update(inner_hash);
+    outer.finalize().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test cases 1, 2 and 6 of RFC 4231
+    #[test]
+    fn test_hmac_sha256() {
+        assert_eq!(
+            hex::encode(hmac_sha256(&[0x0b; 20], b"Hi There")),
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"
+        );
+        assert_eq!(
+            hex::encode(hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            hex::encode(hmac_sha256(&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First")),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    /// The "GET Bucket (List Objects)" example from the AWS SigV4 documentation
+    #[test]
+    fn test_sign() {
+        let empty_hash = hex::encode(Sha256::digest(b""));
+        let request = canonical_request("GET", "/", "max-keys=2&prefix=J", "examplebucket.s3.amazonaws.com", &empty_hash, "20130524T000000Z");
+        assert_eq!(
+            sign("wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY", "us-east-1", "20130524T000000Z", &request),
+            "34b48302e7b5fa45bde8084f4b7868a86f0a534bc59db6670ed5711ef69dc6f7"
+        );
+    }
+
+    #[test]
+    fn test_canonical_uri() {
+        let client = S3Client::new("https://minio.local:9000/", "my notes", "us-east-1", "key", "secret");
+        assert_eq!(client.host(), "minio.local:9000");
+        assert_eq!(client.canonical_uri(""), "/my%20notes");
+        assert_eq!(client.canonical_uri("2024/plan a.md"), "/my%20notes/2024/plan%20a.md");
+    }
+}
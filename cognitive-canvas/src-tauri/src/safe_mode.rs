@@ -0,0 +1,216 @@
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use crate::sidecar;
+use crate::error::{AppError, AppResult};
+
+/// Launching after this many crashes in a row starts in safe mode.
+const CRASHES_BEFORE_SAFE_MODE: u32 = 2;
+
+pub const SAFE_MODE_FLAG: &str = "--safe-mode";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SafeModeReason {
+    Flag,
+    RepeatedCrashes,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SafeModeStatus {
+    pub active: bool,
+    pub reason: Option<SafeModeReason>,
+    pub consecutive_crashes: u32,
+    /// What was starting up when the last run crashed, if it crashed
+    /// during startup
+    pub suspected_component: Option<String>,
+}
+
+#[derive(Default)]
+pub struct SafeModeState {
+    status: Mutex<SafeModeStatus>,
+}
+
+/// Persisted across launches. `running` is set while the app runs and
+/// cleared on a clean exit, so finding it set at launch means a crash.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CrashTracker {
+    running: bool,
+    consecutive_crashes: u32,
+    component: Option<String>,
+}
+
+fn get_tracker_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir.join("crash_tracker.json"))
+}
+
+fn update_tracker<F>(app_handle: &AppHandle, change: F) -> AppResult<CrashTracker>
+where
+    F: FnOnce(&mut CrashTracker),
+{
+    let path = get_tracker_path(app_handle)?;
+    // A corrupted tracker shouldn't keep the app from starting
+    let mut tracker: CrashTracker = sidecar::read_json(&path).unwrap_or_default();
+    change(&mut tracker);
+    sidecar::write_json(&path, &tracker)?;
+    Ok(tracker)
+}
+
+/// The tracker to persist for this launch and the resulting status, given
+/// the tracker the previous run left behind.
+fn next_launch(previous: &CrashTracker, args: &[String]) -> (CrashTracker, SafeModeStatus) {
+    let consecutive_crashes = if previous.running {
+        previous.consecutive_crashes + 1
+    } else {
+        previous.consecutive_crashes
+    };
+    let tracker = CrashTracker {
+        running: true,
+        consecutive_crashes,
+        component: None,
+    };
+
+    let reason = if args.iter().any(|arg| arg == SAFE_MODE_FLAG) {
+        Some(SafeModeReason::Flag)
+    } else if consecutive_crashes >= CRASHES_BEFORE_SAFE_MODE {
+        Some(SafeModeReason::RepeatedCrashes)
+    } else {
+        None
+    };
+
+    let status = SafeModeStatus {
+        active: reason.is_some(),
+        reason,
+        consecutive_crashes,
+        suspected_component: if previous.running { previous.component.clone() } else { None },
+    };
+    (tracker, status)
+}
+
+/// Works out whether this launch runs in safe mode, from the command line
+/// and whether the previous runs crashed, and marks the app as running.
+pub fn begin_launch(app_handle: &AppHandle, args: &[String]) -> SafeModeStatus {
+    let mut status = None;
+    let result = update_tracker(app_handle, |tracker| {
+        let (next, launch_status) = next_launch(tracker, args);
+        *tracker = next;
+        status = Some(launch_status);
+    });
+    if let Err(e) = result {
+        tracing::warn!("Failed to update crash tracker: {}", e);
+    }
+    let status = status.unwrap_or_else(|| next_launch(&CrashTracker::default(), args).1);
+
+    if let Ok(mut current) = app_handle.state::<SafeModeState>().status.lock() {
+        *current = status.clone();
+    }
+    status
+}
+
+/// Records which component is starting, so a crash can be blamed on it.
+/// `None` once startup is over.
+pub fn set_component(app_handle: &AppHandle, component: Option<&str>) {
+    if let Err(e) = update_tracker(app_handle, |tracker| tracker.component = component.map(str::to_string)) {
+        tracing::warn!("Failed to update crash tracker: {}", e);
+    }
+}
+
+/// Called on a normal shutdown; the crash count starts over.
+pub fn record_clean_exit(app_handle: &AppHandle) {
+    let result = update_tracker(app_handle, |tracker| *tracker = CrashTracker::default());
+    if let Err(e) = result {
+        tracing::warn!("Failed to update crash tracker: {}", e);
+    }
+}
+
+pub fn status(state: &SafeModeState) -> AppResult<SafeModeStatus> {
+    Ok(state.status.lock()?.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn launch(previous: &CrashTracker) -> (CrashTracker, SafeModeStatus) {
+        next_launch(previous, &[])
+    }
+
+    #[test]
+    fn test_next_launch_counts_crashes() {
+        // First launch ever
+        let (tracker, status) = launch(&CrashTracker::default());
+        assert!(tracker.running);
+        assert_eq!(tracker.consecutive_crashes, 0);
+        assert!(!status.active);
+
+        // The previous run never cleared `running`, so it crashed
+        let (tracker, status) = launch(&tracker);
+        assert!(tracker.running);
+        assert_eq!(tracker.consecutive_crashes, 1);
+        assert_eq!(status.consecutive_crashes, 1);
+        assert!(!status.active);
+
+        let (tracker, status) = launch(&tracker);
+        assert_eq!(tracker.consecutive_crashes, CRASHES_BEFORE_SAFE_MODE);
+        assert!(status.active);
+        assert_eq!(status.reason, Some(SafeModeReason::RepeatedCrashes));
+    }
+
+    #[test]
+    fn test_next_launch_after_clean_exit() {
+        let crashed = CrashTracker {
+            running: true,
+            consecutive_crashes: CRASHES_BEFORE_SAFE_MODE,
+            component: Some("plugins".to_string()),
+        };
+        let (_, status) = launch(&crashed);
+        assert!(status.active);
+
+        // record_clean_exit resets the tracker, so the next launch starts over
+        let (tracker, status) = launch(&CrashTracker::default());
+        assert_eq!(tracker.consecutive_crashes, 0);
+        assert!(!status.active);
+        assert_eq!(status.reason, None);
+    }
+
+    #[test]
+    fn test_next_launch_flag() {
+        let args = vec!["app".to_string(), SAFE_MODE_FLAG.to_string()];
+        let (tracker, status) = next_launch(&CrashTracker::default(), &args);
+        assert!(tracker.running);
+        assert!(status.active);
+        assert_eq!(status.reason, Some(SafeModeReason::Flag));
+        assert_eq!(status.consecutive_crashes, 0);
+    }
+
+    #[test]
+    fn test_next_launch_suspected_component() {
+        let crashed = CrashTracker {
+            running: true,
+            consecutive_crashes: 0,
+            component: Some("search_index".to_string()),
+        };
+        let (tracker, status) = launch(&crashed);
+        assert_eq!(status.suspected_component.as_deref(), Some("search_index"));
+        // The component is cleared for the new run
+        assert_eq!(tracker.component, None);
+
+        // A component left behind by a run that exited cleanly isn't a suspect
+        let exited = CrashTracker {
+            running: false,
+            consecutive_crashes: 0,
+            component: Some("search_index".to_string()),
+        };
+        let (_, status) = launch(&exited);
+        assert_eq!(status.suspected_component, None);
+    }
+}
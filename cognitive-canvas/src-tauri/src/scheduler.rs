@@ -0,0 +1,42 @@
+use std::time::Duration;
+use tauri::AppHandle;
+use crate::idle;
+use crate::error::AppResult;
+
+/// Runs `job` every `interval` for the lifetime of the app, starting after
+/// one `initial_delay` so startup isn't slowed down. Failures are logged
+/// and the job stays scheduled.
+pub fn spawn_periodic<F>(app_handle: &AppHandle, name: &'static str, initial_delay: Duration, interval: Duration, job: F)
+where
+    F: Fn(&AppHandle) -> AppResult<()> + Send + 'static,
+{
+    spawn(app_handle, name, initial_delay, interval, false, job);
+}
+
+/// Like `spawn_periodic`, but each run waits until the user stops typing,
+/// for jobs heavy enough to make the editor stutter.
+pub fn spawn_periodic_when_idle<F>(app_handle: &AppHandle, name: &'static str, initial_delay: Duration, interval: Duration, job: F)
+where
+    F: Fn(&AppHandle) -> AppResult<()> + Send + 'static,
+{
+    spawn(app_handle, name, initial_delay, interval, true, job);
+}
+
+fn spawn<F>(app_handle: &AppHandle, name: &'static str, initial_delay: Duration, interval: Duration, when_idle: bool, job: F)
+where
+    F: Fn(&AppHandle) -> AppResult<()> + Send + 'static,
+{
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(initial_delay).await;
+        loop {
+            if when_idle {
+                idle::wait_until_idle(&app_handle).await;
+            }
+            if let Err(e) = job(&app_handle) {
+                tracing::error!("Scheduled job {} failed: {}", name, e);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
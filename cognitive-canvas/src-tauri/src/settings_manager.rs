@@ -1,13 +1,195 @@
 use serde::{Deserialize, Serialize};
-use tauri::{AppHandle, Manager};
-use std::path::PathBuf;
-use crate::config_parser::ConfigParser;
+use tauri::{AppHandle, Emitter, Manager};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+use crate::config_parser::{ConfigParser, Validator};
+use crate::{monitors, window_effects};
+use crate::error::{AppError, AppResult};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Version of the settings.conf layout this build writes. Bump it and add
+/// an entry to `MIGRATIONS` whenever keys are renamed or removed.
+const CONFIG_VERSION: i64 = 1;
+
+/// `MIGRATIONS[n]` upgrades a version `n` file to version `n + 1`.
+const MIGRATIONS: &[fn(&mut ConfigParser)] = &[
+    // 0 -> 1: the default config used to put the command palette shortcut
+    // here, but shortcuts are read from shortcuts.conf
+    |parser| {
+        parser.remove("command_palette");
+    },
+];
+
+/// How long settings changes are collected before they're written.
+const SAVE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Below this the window is too faint to find again.
+pub const MIN_WINDOW_OPACITY: f64 = 0.3;
+
+/// Keeps the window buttons within reach of the titlebar.
+const MAX_TRAFFIC_LIGHT_INSET: f64 = 200.0;
+
+pub const MIN_ZOOM_LEVEL: f64 = 0.5;
+pub const MAX_ZOOM_LEVEL: f64 = 3.0;
+
+const DEFAULT_IDLE_SAVE_DELAY: u32 = 2;
+/// Past this, unsaved work sits around too long.
+const MAX_IDLE_SAVE_DELAY: u32 = 300;
+
+/// The `language` value that means "use the OS language".
+pub const SYSTEM_LANGUAGE: &str = "system";
+
+/// Used when the OS doesn't report a locale.
+const FALLBACK_LOCALE: &str = "en-US";
+
+const DEFAULT_ACCENT_COLOR: &str = "#3b82f6";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Light,
+    Dark,
+    /// Follow the operating system's appearance
+    #[default]
+    System,
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+            Theme::System => "system",
+        })
+    }
+}
+
+impl FromStr for Theme {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "light" => Ok(Theme::Light),
+            "dark" => Ok(Theme::Dark),
+            "system" => Ok(Theme::System),
+            _ => Err(AppError::validation(format!("expected light, dark or system, got {}", value))),
+        }
+    }
+}
+
+/// The macOS material drawn behind the window's transparent areas.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Vibrancy {
+    #[default]
+    None,
+    Sidebar,
+    Titlebar,
+    Menu,
+    Popover,
+    HudWindow,
+    WindowBackground,
+    ContentBackground,
+    UnderWindowBackground,
+}
+
+impl Vibrancy {
+    pub const ALL: [Vibrancy; 9] = [
+        Vibrancy::None,
+        Vibrancy::Sidebar,
+        Vibrancy::Titlebar,
+        Vibrancy::Menu,
+        Vibrancy::Popover,
+        Vibrancy::HudWindow,
+        Vibrancy::WindowBackground,
+        Vibrancy::ContentBackground,
+        Vibrancy::UnderWindowBackground,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Vibrancy::None => "none",
+            Vibrancy::Sidebar => "sidebar",
+            Vibrancy::Titlebar => "titlebar",
+            Vibrancy::Menu => "menu",
+            Vibrancy::Popover => "popover",
+            Vibrancy::HudWindow => "hud_window",
+            Vibrancy::WindowBackground => "window_background",
+            Vibrancy::ContentBackground => "content_background",
+            Vibrancy::UnderWindowBackground => "under_window_background",
+        }
+    }
+}
+
+impl fmt::Display for Vibrancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Vibrancy {
+    type Err = AppError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.to_lowercase();
+        Vibrancy::ALL
+            .into_iter()
+            .find(|vibrancy| vibrancy.as_str() == value)
+            .ok_or_else(|| AppError::validation(format!("unknown vibrancy material {}", value)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Settings {
     pub window_decorations: bool,
     pub window_maximized: bool,
     pub window_fullscreen: bool,
+    /// Keep the window above other apps' windows
+    pub window_always_on_top: bool,
+    /// Launch with only the tray icon showing; the window opens from its menu
+    pub start_minimized_to_tray: bool,
+    /// From `MIN_WINDOW_OPACITY` (mostly see-through) to 1.0 (opaque)
+    pub window_opacity: f64,
+    /// Webview zoom factor, 1.0 being 100%
+    pub zoom_level: f64,
+    /// A BCP 47 tag like "en-US", or "system" to follow the OS
+    pub language: String,
+    pub theme: Theme,
+    /// A `#rrggbb` color
+    pub accent_color: String,
+    /// Log documents opened, created and words written to a local journal
+    pub activity_journal: bool,
+    /// Send anonymous usage statistics
+    pub telemetry: bool,
+    /// Where new documents are saved and file dialogs open; empty for the
+    /// home directory
+    pub default_directory: String,
+    /// Compress large documents with zstd when saving. Compressed files
+    /// open fine here but not in other editors
+    pub compress_documents: bool,
+    /// Seconds without typing before autosaves and indexing run, so they
+    /// don't stutter the editor; 0 to run them right away
+    pub idle_save_delay: u32,
+    /// Git repository with shared templates, snippets, prompts and shortcut
+    /// presets, kept up to date in the background; empty for none
+    pub sync_config_repo: String,
+    /// Last position and size of the restored (not maximized or fullscreen)
+    /// window in physical pixels, unknown until it's been moved or resized
+    pub window_x: Option<i32>,
+    pub window_y: Option<i32>,
+    pub window_width: Option<u32>,
+    pub window_height: Option<u32>,
+    /// Name of the display the window was last on
+    pub window_monitor: Option<String>,
+    /// Let the content extend under a see-through titlebar (macOS only)
+    pub macos_transparent_titlebar: bool,
+    /// Where the close/minimize/zoom buttons sit, in points from the
+    /// window's top left corner; the system default when unset (macOS only)
+    pub macos_traffic_light_x: Option<f64>,
+    pub macos_traffic_light_y: Option<f64>,
+    pub macos_vibrancy: Vibrancy,
 }
 
 impl Default for Settings {
@@ -16,77 +198,1118 @@ impl Default for Settings {
             window_decorations: true,
             window_maximized: true,
             window_fullscreen: false,
+            window_always_on_top: false,
+            start_minimized_to_tray: false,
+            window_opacity: 1.0,
+            zoom_level: 1.0,
+            language: SYSTEM_LANGUAGE.to_string(),
+            theme: Theme::System,
+            accent_color: DEFAULT_ACCENT_COLOR.to_string(),
+            activity_journal: false,
+            telemetry: false,
+            default_directory: String::new(),
+            compress_documents: false,
+            idle_save_delay: DEFAULT_IDLE_SAVE_DELAY,
+            sync_config_repo: String::new(),
+            window_x: None,
+            window_y: None,
+            window_width: None,
+            window_height: None,
+            window_monitor: None,
+            macos_transparent_titlebar: false,
+            macos_traffic_light_x: None,
+            macos_traffic_light_y: None,
+            macos_vibrancy: Vibrancy::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SettingType {
+    Bool,
+    Int,
+    Float,
+    String,
+    Enum,
+    Color,
+}
+
+/// How one settings.conf key is validated and presented.
+struct SettingDef {
+    key: &'static str,
+    kind: SettingType,
+    category: &'static str,
+    description: &'static str,
+    allowed_values: &'static [&'static str],
+    range: Option<(f64, f64)>,
+    validator: Option<Validator>,
+}
+
+/// Every key in settings.conf. Validation, the comments written to the
+/// file and `settings_schema` are all derived from this table.
+const SETTINGS_SCHEMA: &[SettingDef] = &[
+    SettingDef {
+        key: "window_decorations",
+        kind: SettingType::Bool,
+        category: "Window",
+        description: "Show native window title bar and decorations",
+        allowed_values: &[],
+        range: None,
+        validator: Some(validate_bool),
+    },
+    SettingDef {
+        key: "window_maximized",
+        kind: SettingType::Bool,
+        category: "Window",
+        description: "Start window in maximized state",
+        allowed_values: &[],
+        range: None,
+        validator: Some(validate_bool),
+    },
+    SettingDef {
+        key: "window_fullscreen",
+        kind: SettingType::Bool,
+        category: "Window",
+        description: "Start window in fullscreen mode (overrides maximized)",
+        allowed_values: &[],
+        range: None,
+        validator: Some(validate_bool),
+    },
+    SettingDef {
+        key: "window_always_on_top",
+        kind: SettingType::Bool,
+        category: "Window",
+        description: "Keep the window above other windows",
+        allowed_values: &[],
+        range: None,
+        validator: Some(validate_bool),
+    },
+    SettingDef {
+        key: "start_minimized_to_tray",
+        kind: SettingType::Bool,
+        category: "Window",
+        description: "Start with the window hidden and only the tray icon showing",
+        allowed_values: &[],
+        range: None,
+        validator: Some(validate_bool),
+    },
+    SettingDef {
+        key: "window_opacity",
+        kind: SettingType::Float,
+        category: "Window",
+        description: "Window opacity, from 0.3 (mostly see-through) to 1.0 (opaque)",
+        allowed_values: &[],
+        range: Some((MIN_WINDOW_OPACITY, 1.0)),
+        validator: None,
+    },
+    // Geometry ranges keep a corrupted file from opening the window off in
+    // the void or too small to grab
+    SettingDef {
+        key: "window_x",
+        kind: SettingType::Int,
+        category: "Window",
+        description: "Horizontal window position, remembered automatically",
+        allowed_values: &[],
+        range: Some((-100_000.0, 100_000.0)),
+        validator: None,
+    },
+    SettingDef {
+        key: "window_y",
+        kind: SettingType::Int,
+        category: "Window",
+        description: "Vertical window position, remembered automatically",
+        allowed_values: &[],
+        range: Some((-100_000.0, 100_000.0)),
+        validator: None,
+    },
+    SettingDef {
+        key: "window_width",
+        kind: SettingType::Int,
+        category: "Window",
+        description: "Window width, remembered automatically",
+        allowed_values: &[],
+        range: Some((200.0, 100_000.0)),
+        validator: None,
+    },
+    SettingDef {
+        key: "window_height",
+        kind: SettingType::Int,
+        category: "Window",
+        description: "Window height, remembered automatically",
+        allowed_values: &[],
+        range: Some((150.0, 100_000.0)),
+        validator: None,
+    },
+    SettingDef {
+        key: "window_monitor",
+        kind: SettingType::String,
+        category: "Window",
+        description: "Display the window was last on, remembered automatically",
+        allowed_values: &[],
+        range: None,
+        validator: None,
+    },
+    SettingDef {
+        key: "zoom_level",
+        kind: SettingType::Float,
+        category: "Appearance",
+        description: "Interface zoom, 1.0 being 100%",
+        allowed_values: &[],
+        range: Some((MIN_ZOOM_LEVEL, MAX_ZOOM_LEVEL)),
+        validator: None,
+    },
+    SettingDef {
+        key: "language",
+        kind: SettingType::String,
+        category: "Appearance",
+        description: "Interface language like en-US, or system to follow the OS",
+        allowed_values: &[],
+        range: None,
+        validator: Some(validate_language),
+    },
+    SettingDef {
+        key: "theme",
+        kind: SettingType::Enum,
+        category: "Appearance",
+        description: "Color scheme: light, dark or system",
+        allowed_values: &["light", "dark", "system"],
+        range: None,
+        validator: Some(validate_theme),
+    },
+    SettingDef {
+        key: "accent_color",
+        kind: SettingType::Color,
+        category: "Appearance",
+        description: "Accent color as #rrggbb",
+        allowed_values: &[],
+        range: None,
+        validator: Some(validate_color),
+    },
+    SettingDef {
+        key: "activity_journal",
+        kind: SettingType::Bool,
+        category: "Privacy",
+        description: "Keep a local journal of documents opened, created and words written",
+        allowed_values: &[],
+        range: None,
+        validator: Some(validate_bool),
+    },
+    SettingDef {
+        key: "telemetry",
+        kind: SettingType::Bool,
+        category: "Privacy",
+        description: "Send anonymous usage statistics",
+        allowed_values: &[],
+        range: None,
+        validator: Some(validate_bool),
+    },
+    SettingDef {
+        key: "default_directory",
+        kind: SettingType::String,
+        category: "Files",
+        description: "Absolute path where new documents are saved; empty for the home directory",
+        allowed_values: &[],
+        range: None,
+        validator: Some(validate_directory),
+    },
+    SettingDef {
+        key: "compress_documents",
+        kind: SettingType::Bool,
+        category: "Files",
+        description: "Compress large documents when saving; other editors can't open them",
+        allowed_values: &[],
+        range: None,
+        validator: Some(validate_bool),
+    },
+    SettingDef {
+        key: "idle_save_delay",
+        kind: SettingType::Int,
+        category: "Files",
+        description: "Seconds without typing before autosaving and indexing; 0 to do them right away",
+        allowed_values: &[],
+        range: Some((0.0, MAX_IDLE_SAVE_DELAY as f64)),
+        validator: None,
+    },
+    SettingDef {
+        key: "sync_config_repo",
+        kind: SettingType::String,
+        category: "Sync",
+        description: "Git repository URL to pull shared templates, snippets, prompts and shortcut presets from; empty for none",
+        allowed_values: &[],
+        range: None,
+        validator: Some(validate_config_repo),
+    },
+    SettingDef {
+        key: "macos_transparent_titlebar",
+        kind: SettingType::Bool,
+        category: "macOS",
+        description: "Draw the canvas under a transparent titlebar",
+        allowed_values: &[],
+        range: None,
+        validator: Some(validate_bool),
+    },
+    SettingDef {
+        key: "macos_traffic_light_x",
+        kind: SettingType::Float,
+        category: "macOS",
+        description: "Horizontal inset of the window buttons in points",
+        allowed_values: &[],
+        range: Some((0.0, MAX_TRAFFIC_LIGHT_INSET)),
+        validator: None,
+    },
+    SettingDef {
+        key: "macos_traffic_light_y",
+        kind: SettingType::Float,
+        category: "macOS",
+        description: "Vertical inset of the window buttons in points",
+        allowed_values: &[],
+        range: Some((0.0, MAX_TRAFFIC_LIGHT_INSET)),
+        validator: None,
+    },
+    SettingDef {
+        key: "macos_vibrancy",
+        kind: SettingType::Enum,
+        category: "macOS",
+        description: "Translucent material behind the window",
+        allowed_values: &[
+            "none",
+            "sidebar",
+            "titlebar",
+            "menu",
+            "popover",
+            "hud_window",
+            "window_background",
+            "content_background",
+            "under_window_background",
+        ],
+        range: None,
+        validator: Some(validate_vibrancy),
+    },
+];
+
+/// A setting as described to the frontend's settings screen.
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingSchema {
+    pub key: &'static str,
+    #[serde(rename = "type")]
+    pub kind: SettingType,
+    pub default: serde_json::Value,
+    pub description: &'static str,
+    pub allowed_values: &'static [&'static str],
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub category: &'static str,
+}
+
+/// Describes every setting, with defaults taken from `Settings::default()`.
+pub fn settings_schema() -> Vec<SettingSchema> {
+    let defaults = serde_json::to_value(Settings::default()).unwrap_or_default();
+    SETTINGS_SCHEMA
+        .iter()
+        .map(|def| SettingSchema {
+            key: def.key,
+            kind: def.kind,
+            default: defaults.get(def.key).cloned().unwrap_or_default(),
+            description: def.description,
+            allowed_values: def.allowed_values,
+            min: def.range.map(|(min, _)| min),
+            max: def.range.map(|(_, max)| max),
+            category: def.category,
+        })
+        .collect()
+}
+
+/// Registers the schema's validators and ranges with `parser`.
+fn apply_schema(parser: &mut ConfigParser) {
+    for def in SETTINGS_SCHEMA {
+        if let Some(validator) = def.validator {
+            parser.set_validator(def.key, validator);
+        }
+        if let Some((min, max)) = def.range {
+            parser.set_range(def.key, min, max);
         }
     }
 }
 
-fn get_config_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+/// Sent as "theme-changed" so every window restyles together.
+#[derive(Debug, Clone, Serialize)]
+struct ThemeChanged<'a> {
+    theme: Theme,
+    accent_color: &'a str,
+}
+
+/// A partial update from the frontend. Omitted fields keep their value;
+/// unknown fields are rejected so typos don't silently do nothing.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SettingsPatch {
+    pub window_decorations: Option<bool>,
+    pub window_maximized: Option<bool>,
+    pub window_fullscreen: Option<bool>,
+    pub window_always_on_top: Option<bool>,
+    pub start_minimized_to_tray: Option<bool>,
+    pub window_opacity: Option<f64>,
+    pub zoom_level: Option<f64>,
+    pub language: Option<String>,
+    pub theme: Option<Theme>,
+    pub accent_color: Option<String>,
+    pub activity_journal: Option<bool>,
+    pub telemetry: Option<bool>,
+    pub default_directory: Option<String>,
+    pub compress_documents: Option<bool>,
+    pub idle_save_delay: Option<u32>,
+    pub sync_config_repo: Option<String>,
+    pub macos_transparent_titlebar: Option<bool>,
+    pub macos_traffic_light_x: Option<f64>,
+    pub macos_traffic_light_y: Option<f64>,
+    pub macos_vibrancy: Option<Vibrancy>,
+}
+
+impl SettingsPatch {
+    pub fn is_empty(&self) -> bool {
+        self.window_decorations.is_none()
+            && self.window_maximized.is_none()
+            && self.window_fullscreen.is_none()
+            && self.window_always_on_top.is_none()
+            && self.start_minimized_to_tray.is_none()
+            && self.window_opacity.is_none()
+            && self.zoom_level.is_none()
+            && self.language.is_none()
+            && self.theme.is_none()
+            && self.accent_color.is_none()
+            && self.activity_journal.is_none()
+            && self.telemetry.is_none()
+            && self.default_directory.is_none()
+            && self.compress_documents.is_none()
+            && self.idle_save_delay.is_none()
+            && self.sync_config_repo.is_none()
+            && self.macos_transparent_titlebar.is_none()
+            && self.macos_traffic_light_x.is_none()
+            && self.macos_traffic_light_y.is_none()
+            && self.macos_vibrancy.is_none()
+    }
+
+    pub fn changes_theme(&self) -> bool {
+        self.theme.is_some() || self.accent_color.is_some()
+    }
+
+    /// Whether any field `apply_window_settings` applies is set.
+    pub fn changes_window(&self) -> bool {
+        self.window_decorations.is_some()
+            || self.window_maximized.is_some()
+            || self.window_fullscreen.is_some()
+            || self.window_always_on_top.is_some()
+            || self.window_opacity.is_some()
+            || self.zoom_level.is_some()
+            || self.macos_transparent_titlebar.is_some()
+            || self.macos_traffic_light_x.is_some()
+            || self.macos_traffic_light_y.is_some()
+            || self.macos_vibrancy.is_some()
+    }
+
+    pub fn validate(&self) -> AppResult<()> {
+        if let Some(opacity) = self.window_opacity {
+            validate_opacity(opacity)?;
+        }
+        if let Some(zoom_level) = self.zoom_level {
+            validate_zoom_level(zoom_level)?;
+        }
+        if let Some(language) = &self.language {
+            validate_language(language)?;
+        }
+        if let Some(directory) = &self.default_directory {
+            validate_directory(directory)?;
+        }
+        if let Some(delay) = self.idle_save_delay {
+            validate_idle_save_delay(delay)?;
+        }
+        if let Some(repo) = &self.sync_config_repo {
+            validate_config_repo(repo)?;
+        }
+        for inset in [self.macos_traffic_light_x, self.macos_traffic_light_y].into_iter().flatten() {
+            validate_traffic_light_inset(inset)?;
+        }
+        match &self.accent_color {
+            Some(color) => validate_color(color),
+            None => Ok(()),
+        }
+    }
+
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some(decorations) = self.window_decorations {
+            settings.window_decorations = decorations;
+        }
+        if let Some(maximized) = self.window_maximized {
+            settings.window_maximized = maximized;
+        }
+        if let Some(fullscreen) = self.window_fullscreen {
+            settings.window_fullscreen = fullscreen;
+        }
+        if let Some(always_on_top) = self.window_always_on_top {
+            settings.window_always_on_top = always_on_top;
+        }
+        if let Some(start_minimized) = self.start_minimized_to_tray {
+            settings.start_minimized_to_tray = start_minimized;
+        }
+        if let Some(opacity) = self.window_opacity {
+            settings.window_opacity = opacity;
+        }
+        if let Some(zoom_level) = self.zoom_level {
+            settings.zoom_level = zoom_level;
+        }
+        if let Some(language) = &self.language {
+            settings.language = language.clone();
+        }
+        if let Some(theme) = self.theme {
+            settings.theme = theme;
+        }
+        if let Some(accent_color) = &self.accent_color {
+            settings.accent_color = accent_color.clone();
+        }
+        if let Some(activity_journal) = self.activity_journal {
+            settings.activity_journal = activity_journal;
+        }
+        if let Some(telemetry) = self.telemetry {
+            settings.telemetry = telemetry;
+        }
+        if let Some(directory) = &self.default_directory {
+            settings.default_directory = directory.trim().to_string();
+        }
+        if let Some(compress) = self.compress_documents {
+            settings.compress_documents = compress;
+        }
+        if let Some(delay) = self.idle_save_delay {
+            settings.idle_save_delay = delay;
+        }
+        if let Some(repo) = &self.sync_config_repo {
+            settings.sync_config_repo = repo.trim().to_string();
+        }
+        if let Some(transparent) = self.macos_transparent_titlebar {
+            settings.macos_transparent_titlebar = transparent;
+        }
+        if let Some(x) = self.macos_traffic_light_x {
+            settings.macos_traffic_light_x = Some(x);
+        }
+        if let Some(y) = self.macos_traffic_light_y {
+            settings.macos_traffic_light_y = Some(y);
+        }
+        if let Some(vibrancy) = self.macos_vibrancy {
+            settings.macos_vibrancy = vibrancy;
+        }
+    }
+}
+
+/// In-memory settings shared by the commands. Changes are written to
+/// settings.conf after a short quiet period instead of on every toggle.
+#[derive(Default)]
+pub struct SettingsState {
+    inner: Mutex<PendingSettings>,
+}
+
+#[derive(Default)]
+struct PendingSettings {
+    settings: Option<Settings>,
+    dirty: bool,
+    /// Bumped on every change so only the latest debounce timer flushes
+    generation: u64,
+    /// In safe mode the defaults are used and the user's file is left alone
+    read_only: bool,
+}
+
+fn get_config_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
     let app_data_dir = app_handle
         .path()
         .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
     
     std::fs::create_dir_all(&app_data_dir)
-        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
     
     Ok(app_data_dir.join("settings.conf"))
 }
 
-pub fn load_settings(app_handle: &AppHandle) -> Result<Settings, String> {
+pub fn load_settings(app_handle: &AppHandle) -> AppResult<Settings> {
     let config_path = get_config_path(app_handle)?;
-    let config_path_str = config_path.to_str()
-        .ok_or("Invalid config path")?;
-    
-    let mut parser = ConfigParser::new(config_path_str);
-    parser.load()?;
+    let mut parser = open_config(&config_path)?;
+    apply_schema(&mut parser);
     
     let settings = Settings {
         window_decorations: parser.get_bool("window_decorations").unwrap_or(true),
         window_maximized: parser.get_bool("window_maximized").unwrap_or(true),
         window_fullscreen: parser.get_bool("window_fullscreen").unwrap_or(false),
+        window_always_on_top: parser.get_bool("window_always_on_top").unwrap_or(false),
+        start_minimized_to_tray: parser.get_bool("start_minimized_to_tray").unwrap_or(false),
+        window_opacity: parser.get_float("window_opacity").unwrap_or(1.0),
+        zoom_level: parser.get_float("zoom_level").unwrap_or(1.0),
+        language: parser
+            .get_str("language")
+            .filter(|language| validate_language(language).is_ok())
+            .cloned()
+            .unwrap_or_else(|| SYSTEM_LANGUAGE.to_string()),
+        theme: parser.get_enum("theme").unwrap_or_default(),
+        accent_color: parser
+            .get_str("accent_color")
+            .filter(|color| validate_color(color).is_ok())
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_ACCENT_COLOR.to_string()),
+        activity_journal: parser.get_bool("activity_journal").unwrap_or(false),
+        telemetry: parser.get_bool("telemetry").unwrap_or(false),
+        default_directory: parser
+            .get_str("default_directory")
+            .filter(|directory| validate_directory(directory).is_ok())
+            .map(|directory| directory.trim().to_string())
+            .unwrap_or_default(),
+        compress_documents: parser.get_bool("compress_documents").unwrap_or(false),
+        idle_save_delay: parser
+            .get_int("idle_save_delay")
+            .and_then(|v| u32::try_from(v).ok())
+            .unwrap_or(DEFAULT_IDLE_SAVE_DELAY),
+        sync_config_repo: parser
+            .get_str("sync_config_repo")
+            .filter(|repo| validate_config_repo(repo).is_ok())
+            .map(|repo| repo.trim().to_string())
+            .unwrap_or_default(),
+        window_x: parser.get_int("window_x").and_then(|v| i32::try_from(v).ok()),
+        window_y: parser.get_int("window_y").and_then(|v| i32::try_from(v).ok()),
+        window_width: parser.get_int("window_width").and_then(|v| u32::try_from(v).ok()),
+        window_height: parser.get_int("window_height").and_then(|v| u32::try_from(v).ok()),
+        window_monitor: parser.get_str("window_monitor").filter(|name| !name.is_empty()).cloned(),
+        macos_transparent_titlebar: parser.get_bool("macos_transparent_titlebar").unwrap_or(false),
+        macos_traffic_light_x: parser.get_float("macos_traffic_light_x"),
+        macos_traffic_light_y: parser.get_float("macos_traffic_light_y"),
+        macos_vibrancy: parser.get_enum("macos_vibrancy").unwrap_or_default(),
     };
     
     Ok(settings)
 }
 
-pub fn save_settings(app_handle: &AppHandle, settings: &Settings) -> Result<(), String> {
+pub fn save_settings(app_handle: &AppHandle, settings: &Settings) -> AppResult<()> {
     let config_path = get_config_path(app_handle)?;
     let config_path_str = config_path.to_str()
-        .ok_or("Invalid config path")?;
+        .ok_or_else(|| AppError::config("Invalid config path"))?;
     
     let mut parser = ConfigParser::new(config_path_str);
     parser.load()?; // Load existing config to preserve comments
+    apply_schema(&mut parser);
     
-    // Update values
-    parser.set_bool("window_decorations", settings.window_decorations);
-    parser.set_bool("window_maximized", settings.window_maximized);
-    parser.set_bool("window_fullscreen", settings.window_fullscreen);
-    
-    // Set comments if they don't exist
-    parser.set_comment("window_decorations", "Show native window title bar and decorations");
-    parser.set_comment("window_maximized", "Start window in maximized state");
-    parser.set_comment("window_fullscreen", "Start window in fullscreen mode (overrides maximized)");
-    
-    parser.save()?;
+    parser.transaction(|parser| {
+        // Update values
+        parser.set_bool("window_decorations", settings.window_decorations);
+        parser.set_bool("window_maximized", settings.window_maximized);
+        parser.set_bool("window_fullscreen", settings.window_fullscreen);
+        parser.set_bool("window_always_on_top", settings.window_always_on_top);
+        parser.set_bool("start_minimized_to_tray", settings.start_minimized_to_tray);
+        parser.set_float("window_opacity", settings.window_opacity)?;
+        parser.set_float("zoom_level", settings.zoom_level)?;
+        parser.set_str("language", &settings.language);
+        parser.set_enum("theme", settings.theme);
+        parser.set_str("accent_color", &settings.accent_color);
+        parser.set_bool("activity_journal", settings.activity_journal);
+        parser.set_bool("telemetry", settings.telemetry);
+        parser.set_str("default_directory", &settings.default_directory);
+        parser.set_bool("compress_documents", settings.compress_documents);
+        parser.set_int("idle_save_delay", i64::from(settings.idle_save_delay))?;
+        parser.set_str("sync_config_repo", &settings.sync_config_repo);
+        let geometry = [
+            ("window_x", settings.window_x.map(i64::from)),
+            ("window_y", settings.window_y.map(i64::from)),
+            ("window_width", settings.window_width.map(i64::from)),
+            ("window_height", settings.window_height.map(i64::from)),
+        ];
+        for (key, value) in geometry {
+            match value {
+                Some(value) => parser.set_int(key, value)?,
+                None => {
+                    parser.remove(key);
+                }
+            }
+        }
+        match &settings.window_monitor {
+            Some(name) => parser.set_str("window_monitor", name),
+            None => {
+                parser.remove("window_monitor");
+            }
+        }
+        parser.set_bool("macos_transparent_titlebar", settings.macos_transparent_titlebar);
+        let traffic_lights = [
+            ("macos_traffic_light_x", settings.macos_traffic_light_x),
+            ("macos_traffic_light_y", settings.macos_traffic_light_y),
+        ];
+        for (key, value) in traffic_lights {
+            match value {
+                Some(value) => parser.set_float(key, value)?,
+                None => {
+                    parser.remove(key);
+                }
+            }
+        }
+        parser.set_enum("macos_vibrancy", settings.macos_vibrancy);
+        parser.set_int("config_version", CONFIG_VERSION)?;
+        
+        // Set comments if they don't exist
+        for def in SETTINGS_SCHEMA {
+            parser.set_comment(def.key, def.description);
+        }
+        parser.set_comment("config_version", "Format version, used to upgrade this file automatically");
+        Ok(())
+    })
+}
+
+fn validate_bool(value: &str) -> AppResult<()> {
+    match value.to_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" | "false" | "0" | "no" | "off" => Ok(()),
+        _ => Err(AppError::validation(format!("expected true or false, got {}", value))),
+    }
+}
+
+fn validate_theme(value: &str) -> AppResult<()> {
+    value.parse::<Theme>().map(|_| ())
+}
+
+pub fn validate_opacity(opacity: f64) -> AppResult<()> {
+    if (MIN_WINDOW_OPACITY..=1.0).contains(&opacity) {
+        Ok(())
+    } else {
+        Err(AppError::validation(format!("Opacity must be between {} and 1.0, got {}", MIN_WINDOW_OPACITY, opacity)))
+    }
+}
+
+fn validate_idle_save_delay(delay: u32) -> AppResult<()> {
+    if delay <= MAX_IDLE_SAVE_DELAY {
+        Ok(())
+    } else {
+        Err(AppError::validation(format!("Idle save delay must be at most {} seconds, got {}", MAX_IDLE_SAVE_DELAY, delay)))
+    }
+}
+
+pub fn validate_zoom_level(zoom_level: f64) -> AppResult<()> {
+    if (MIN_ZOOM_LEVEL..=MAX_ZOOM_LEVEL).contains(&zoom_level) {
+        Ok(())
+    } else {
+        Err(AppError::validation(format!("Zoom level must be between {} and {}, got {}", MIN_ZOOM_LEVEL, MAX_ZOOM_LEVEL, zoom_level)))
+    }
+}
+
+/// Accepts "system" or a language tag made of alphanumeric subtags, like
+/// "de", "pt-BR" or "zh-Hant-TW".
+pub fn validate_language(value: &str) -> AppResult<()> {
+    if value == SYSTEM_LANGUAGE {
+        return Ok(());
+    }
+    let mut subtags = value.split('-');
+    let primary_valid = subtags
+        .next()
+        .is_some_and(|primary| (2..=3).contains(&primary.len()) && primary.chars().all(|c| c.is_ascii_alphabetic()));
+    let rest_valid = subtags.all(|subtag| (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric()));
+    if primary_valid && rest_valid {
+        Ok(())
+    } else {
+        Err(AppError::validation(format!("expected a language tag like en-US or system, got {}", value)))
+    }
+}
+
+/// Accepts an empty value, a URL git can clone (https, ssh, git, file), an
+/// scp-style `user@host:path` or an absolute path. Values starting with `-`
+/// are refused so they can't be taken as git options.
+pub fn validate_config_repo(value: &str) -> AppResult<()> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(());
+    }
+    let url = ["https://", "http://", "ssh://", "git://", "file://"].iter().any(|scheme| value.starts_with(scheme));
+    let scp = value
+        .split_once(':')
+        .is_some_and(|(host, path)| host.contains('@') && !host.contains('/') && !path.is_empty());
+    if value.starts_with('-') || value.chars().any(char::is_whitespace) || !(url || scp || Path::new(value).is_absolute()) {
+        return Err(AppError::validation(format!("expected a git repository URL or an absolute path, got {}", value)));
+    }
+    Ok(())
+}
+
+pub fn validate_directory(value: &str) -> AppResult<()> {
+    let value = value.trim();
+    if !value.is_empty() && !Path::new(value).is_absolute() {
+        return Err(AppError::validation(format!("expected an absolute path, got {}", value)));
+    }
     Ok(())
 }
 
-pub fn apply_window_settings(app_handle: &AppHandle, settings: &Settings) -> Result<(), String> {
+/// The OS locale as a BCP 47 tag. POSIX style names like "en_US.UTF-8" are
+/// normalized to "en-US".
+pub fn system_locale() -> String {
+    sys_locale::get_locale()
+        .map(|locale| normalize_locale(&locale))
+        .filter(|locale| validate_language(locale).is_ok())
+        .unwrap_or_else(|| FALLBACK_LOCALE.to_string())
+}
+
+fn normalize_locale(locale: &str) -> String {
+    // Drop the encoding and modifier: "de_DE.UTF-8@euro" -> "de_DE"
+    let locale = locale.split(['.', '@']).next().unwrap_or(locale);
+    locale.replace('_', "-")
+}
+
+fn validate_vibrancy(value: &str) -> AppResult<()> {
+    value.parse::<Vibrancy>().map(|_| ())
+}
+
+fn validate_traffic_light_inset(inset: f64) -> AppResult<()> {
+    if (0.0..=MAX_TRAFFIC_LIGHT_INSET).contains(&inset) {
+        Ok(())
+    } else {
+        Err(AppError::validation(format!("Traffic light inset must be between 0 and {}, got {}", MAX_TRAFFIC_LIGHT_INSET, inset)))
+    }
+}
+
+/// Accepts `#rrggbb` colors.
+pub fn validate_color(value: &str) -> AppResult<()> {
+    match value.strip_prefix('#') {
+        Some(hex) if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) => Ok(()),
+        _ => Err(AppError::validation(format!("expected a color like #3b82f6, got {}", value))),
+    }
+}
+
+/// Loads settings.conf, creating it when it's missing and upgrading it
+/// when it's older. A new file is already in the current layout, so it's
+/// only stamped with the version, with nothing to migrate or back up.
+fn open_config(config_path: &Path) -> AppResult<ConfigParser> {
+    let config_path_str = config_path.to_str()
+        .ok_or_else(|| AppError::config("Invalid config path"))?;
+    let created = !config_path.exists();
+
+    let mut parser = ConfigParser::new(config_path_str);
+    parser.load()?;
+    if created {
+        parser.set_int("config_version", CONFIG_VERSION)?;
+        parser.set_comment("config_version", "Format version, used to upgrade this file automatically");
+        parser.save()?;
+    } else {
+        migrate_config(config_path, &mut parser)?;
+    }
+    Ok(parser)
+}
+
+/// Upgrades an older settings file in place, keeping a copy of the original
+/// next to it as settings.conf.v{N}.bak. Files written by a newer version
+/// are left alone.
+fn migrate_config(config_path: &Path, parser: &mut ConfigParser) -> AppResult<()> {
+    let version = parser.get_int("config_version").unwrap_or(0);
+    if version >= CONFIG_VERSION {
+        if version > CONFIG_VERSION {
+            tracing::warn!("settings.conf is from a newer version ({}), not migrating", version);
+        }
+        return Ok(());
+    }
+
+    let backup_path = config_path.with_extension(format!("conf.v{}.bak", version));
+    std::fs::copy(config_path, &backup_path)
+        .map_err(|e| AppError::io(format!("Failed to back up settings before migrating: {}", e)))?;
+
+    for migration in MIGRATIONS.iter().skip(version.max(0) as usize) {
+        migration(parser);
+    }
+
+    parser.set_int("config_version", CONFIG_VERSION)?;
+    parser.set_comment("config_version", "Format version, used to upgrade this file automatically");
+    parser.save()
+}
+
+pub fn apply_window_settings(app_handle: &AppHandle, settings: &Settings) -> AppResult<()> {
     if let Some(window) = app_handle.get_webview_window("main") {
         // Apply decorations
-        window.set_decorations(settings.window_decorations).map_err(|e| e.to_string())?;
+        window.set_decorations(settings.window_decorations)?;
+        window.set_always_on_top(settings.window_always_on_top)?;
+        window_effects::set_opacity(&window, settings.window_opacity)?;
+        window.set_zoom(settings.zoom_level)?;
+        window_effects::apply_macos_style(&window, settings)?;
         
         // Apply fullscreen or maximized state
         if settings.window_fullscreen {
-            window.set_fullscreen(true).map_err(|e| e.to_string())?;
+            window.set_fullscreen(true)?;
         } else {
-            window.set_fullscreen(false).map_err(|e| e.to_string())?;
+            window.set_fullscreen(false)?;
             if settings.window_maximized {
-                window.maximize().map_err(|e| e.to_string())?;
+                window.maximize()?;
             } else {
-                window.unmaximize().map_err(|e| e.to_string())?;
+                window.unmaximize()?;
+                restore_window_geometry(&window, settings)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn restore_window_geometry(window: &tauri::WebviewWindow, settings: &Settings) -> AppResult<()> {
+    if let (Some(width), Some(height)) = (settings.window_width, settings.window_height) {
+        window
+            .set_size(tauri::PhysicalSize::new(width, height))
+            ?;
+    }
+    if let (Some(x), Some(y)) = (settings.window_x, settings.window_y) {
+        let available = window.available_monitors()?;
+        let monitor_connected = settings
+            .window_monitor
+            .as_ref()
+            .is_none_or(|name| available.iter().any(|m| m.name() == Some(name)));
+        let on_screen = available.iter().any(|m| monitors::contains(m, x, y));
+
+        // The display it was on may have been unplugged or rearranged since
+        if monitor_connected && on_screen {
+            window
+                .set_position(tauri::PhysicalPosition::new(x, y))
+                ?;
+        } else {
+            window.center()?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the main window's actual state back to the settings after it
+/// was moved, resized, maximized or made fullscreen by the user, so the
+/// next launch matches what they last had. The maximized and fullscreen
+/// geometry isn't recorded, so un-maximizing returns to the user's own
+/// placement.
+pub fn record_window_state(window: &tauri::Window) -> AppResult<()> {
+    // A minimized window reports a meaningless position and size
+    if window.label() != "main" || window.is_minimized().unwrap_or(false) {
+        return Ok(());
+    }
+
+    let maximized = window.is_maximized()?;
+    let fullscreen = window.is_fullscreen()?;
+
+    let geometry = if !maximized && !fullscreen {
+        let monitor = window.current_monitor()?.and_then(|m| m.name().cloned());
+        Some((window.outer_position()?, window.inner_size()?, monitor))
+    } else {
+        None
+    };
+
+    // Only the window's own fields, so changes made meanwhile are kept
+    let record = |settings: &mut Settings| {
+        settings.window_fullscreen = fullscreen;
+        // Fullscreen windows on macOS report being maximized as well
+        if !fullscreen {
+            settings.window_maximized = maximized;
+        }
+        if let Some((position, size, monitor)) = &geometry {
+            settings.window_x = Some(position.x);
+            settings.window_y = Some(position.y);
+            settings.window_width = Some(size.width);
+            settings.window_height = Some(size.height);
+            settings.window_monitor = monitor.clone();
+        }
+    };
+
+    let app_handle = window.app_handle();
+    let state = app_handle.state::<SettingsState>();
+    let current = current_settings(app_handle, &state)?;
+    let mut updated = current.clone();
+    record(&mut updated);
+    if updated == current {
+        return Ok(());
+    }
+    update_settings(app_handle, &state, record).map(|_| ())
+}
+
+/// Puts the traffic lights back where the settings want them; AppKit resets
+/// them whenever the window is resized.
+pub fn refresh_traffic_lights(window: &tauri::Window) -> AppResult<()> {
+    let app_handle = window.app_handle();
+    let state = app_handle.state::<SettingsState>();
+    let settings = current_settings(app_handle, &state)?;
+    window_effects::position_traffic_lights(window, &settings)
+}
+
+/// Tells every window about the current theme.
+pub fn emit_theme_changed(app_handle: &AppHandle, settings: &Settings) -> AppResult<()> {
+    app_handle
+        .emit("theme-changed", &ThemeChanged { theme: settings.theme, accent_color: &settings.accent_color })
+        .map_err(|e| AppError::internal(format!("Failed to emit theme change: {}", e)))
+}
+
+/// Returns the current settings, including changes not yet written.
+pub fn current_settings(app_handle: &AppHandle, state: &SettingsState) -> AppResult<Settings> {
+    let mut pending = state.inner.lock()?;
+    if pending.settings.is_none() {
+        pending.settings = Some(load_settings(app_handle)?);
+    }
+    Ok(pending.settings.clone().unwrap_or_default())
+}
+
+/// Applies `change` to the in-memory settings and schedules a write. The
+/// change is made under the lock, so concurrent updates can't undo each
+/// other.
+pub fn update_settings<F>(app_handle: &AppHandle, state: &SettingsState, change: F) -> AppResult<Settings>
+where
+    F: FnOnce(&mut Settings),
+{
+    let (settings, generation) = {
+        let mut pending = state.inner.lock()?;
+        if pending.settings.is_none() {
+            pending.settings = Some(load_settings(app_handle)?);
+        }
+        let settings = pending.settings.get_or_insert_with(Settings::default);
+        change(settings);
+        let settings = settings.clone();
+        pending.dirty = true;
+        pending.generation += 1;
+        (settings, pending.generation)
+    };
+
+    let app_handle = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(SAVE_DEBOUNCE).await;
+        let state = app_handle.state::<SettingsState>();
+        let latest = state.inner.lock().map(|p| p.generation == generation).unwrap_or(false);
+        if latest {
+            if let Err(e) = flush_settings(&app_handle, &state) {
+                tracing::error!("Failed to save settings: {}", e);
             }
         }
+    });
+
+    Ok(settings)
+}
+
+/// Switches to default settings for this run without touching settings.conf.
+/// Changes made in safe mode last until the app quits.
+pub fn enter_safe_mode(state: &SettingsState) -> AppResult<()> {
+    let mut pending = state.inner.lock()?;
+    pending.settings = Some(Settings::default());
+    pending.dirty = false;
+    pending.read_only = true;
+    Ok(())
+}
+
+/// Re-reads settings.conf after it changed on disk. Returns the new
+/// settings if they differ from the ones in memory; an edit on disk wins
+/// over changes still waiting to be written.
+pub fn reload_settings(app_handle: &AppHandle, state: &SettingsState) -> AppResult<Option<Settings>> {
+    let settings = load_settings(app_handle)?;
+    let mut pending = state.inner.lock()?;
+
+    if pending.settings.as_ref() == Some(&settings) {
+        return Ok(None);
     }
+    pending.settings = Some(settings.clone());
+    pending.dirty = false;
+    pending.generation += 1;
+    Ok(Some(settings))
+}
+
+/// Writes pending settings changes to disk right away. Called on shutdown
+/// so nothing within the debounce window is lost.
+pub fn flush_settings(app_handle: &AppHandle, state: &SettingsState) -> AppResult<()> {
+    let mut pending = state.inner.lock()?;
+    if !pending.dirty || pending.read_only {
+        return Ok(());
+    }
+    if let Some(settings) = &pending.settings {
+        save_settings(app_handle, settings)?;
+    }
+    pending.dirty = false;
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_migrates_unversioned_config() {
+        let dir = std::env::temp_dir().join("test_settings_migration");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("settings.conf");
+        let original = "window_maximized=false\ncommand_palette=Cmd+P\n";
+        fs::write(&config_path, original).unwrap();
+
+        let mut parser = ConfigParser::new(config_path.to_str().unwrap());
+        parser.load().unwrap();
+        migrate_config(&config_path, &mut parser).unwrap();
+
+        assert_eq!(fs::read_to_string(dir.join("settings.conf.v0.bak")).unwrap(), original);
+        let migrated = fs::read_to_string(&config_path).unwrap();
+        assert!(migrated.starts_with("window_maximized=false\nconfig_version=1"));
+        assert!(!migrated.contains("command_palette"));
+
+        // Already current: nothing to do
+        fs::remove_file(dir.join("settings.conf.v0.bak")).unwrap();
+        migrate_config(&config_path, &mut parser).unwrap();
+        assert!(!dir.join("settings.conf.v0.bak").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_new_config_is_current() {
+        let dir = std::env::temp_dir().join("test_settings_new_config");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let config_path = dir.join("settings.conf");
+
+        let parser = open_config(&config_path).unwrap();
+        assert_eq!(parser.get_int("config_version"), Some(CONFIG_VERSION));
+        assert!(parser.get_str("command_palette").is_none());
+        let entries: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(entries, vec!["settings.conf"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_language_tags() {
+        assert_eq!(normalize_locale("de_DE.UTF-8@euro"), "de-DE");
+        assert_eq!(normalize_locale("en-US"), "en-US");
+        assert!(validate_language("system").is_ok());
+        assert!(validate_language("zh-Hant-TW").is_ok());
+        assert!(validate_language("C").is_err());
+        assert!(validate_language("en-").is_err());
+    }
+
+    #[test]
+    fn test_config_repo_urls() {
+        assert!(validate_config_repo("").is_ok());
+        assert!(validate_config_repo("https://github.com/team/canvas-config.git").is_ok());
+        assert!(validate_config_repo("git@github.com:team/canvas-config.git").is_ok());
+        assert!(validate_config_repo("--upload-pack=touch /tmp/x").is_err());
+        assert!(validate_config_repo("team/canvas-config").is_err());
+    }
+
+    #[test]
+    fn test_patch_changes_window() {
+        let theme_only = SettingsPatch { theme: Some(Theme::Dark), idle_save_delay: Some(5), ..Default::default() };
+        assert!(!theme_only.changes_window());
+        assert!(theme_only.changes_theme());
+
+        let zoom = SettingsPatch { zoom_level: Some(1.2), ..Default::default() };
+        assert!(zoom.changes_window());
+    }
+
+    #[test]
+    fn test_schema_covers_settings() {
+        let schema = settings_schema();
+        let defaults = serde_json::to_value(Settings::default()).unwrap();
+        let fields = defaults.as_object().unwrap();
+        assert_eq!(schema.len(), fields.len());
+        for setting in &schema {
+            assert!(fields.contains_key(setting.key), "{} isn't a setting", setting.key);
+        }
+
+        let theme = schema.iter().find(|s| s.key == "theme").unwrap();
+        assert_eq!(theme.default, "system");
+        for value in theme.allowed_values {
+            assert!(value.parse::<Theme>().is_ok());
+        }
+
+        let vibrancy = schema.iter().find(|s| s.key == "macos_vibrancy").unwrap();
+        assert_eq!(vibrancy.allowed_values.len(), Vibrancy::ALL.len());
+        for value in vibrancy.allowed_values {
+            assert_eq!(value.parse::<Vibrancy>().unwrap().to_string(), *value);
+        }
+    }
+}
@@ -1,66 +1,755 @@
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Manager};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::accelerator::{Accelerator, KeySequence};
 use crate::config_parser::ConfigParser;
+use crate::config_repo::{self, ConfigLayer};
+use crate::error::{AppError, AppResult};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Every bindable action as (action id, default accelerator, description).
+/// shortcuts.conf only needs to list the ones the user changed. Defaults
+/// use CmdOrCtrl and are resolved for the platform on load.
+const DEFAULT_KEYMAP: &[(&str, &str, &str)] = &[
+    ("command_palette", "CmdOrCtrl+P", "Open the command palette"),
+    ("new_document", "CmdOrCtrl+N", "Create a new document"),
+    ("open", "CmdOrCtrl+O", "Open a document"),
+    ("save", "CmdOrCtrl+S", "Save the current document"),
+    ("close_document", "CmdOrCtrl+W", "Close the current document"),
+    ("find", "CmdOrCtrl+F", "Find in the current document"),
+    ("search", "CmdOrCtrl+Shift+F", "Search the workspace"),
+    ("undo", "CmdOrCtrl+Z", "Undo"),
+    ("redo", "CmdOrCtrl+Shift+Z", "Redo"),
+    ("zoom_in", "CmdOrCtrl+=", "Zoom in"),
+    ("zoom_out", "CmdOrCtrl+-", "Zoom out"),
+    ("zoom_reset", "CmdOrCtrl+0", "Reset zoom"),
+    ("toggle_sidebar", "CmdOrCtrl+\\", "Show or hide the sidebar"),
+    ("toggle_fullscreen", "CmdOrCtrl+Alt+F", "Toggle fullscreen"),
+];
+
+/// A keymap preset: bindings that replace the defaults. Actions a preset
+/// doesn't list keep their default.
+#[derive(Debug, Clone)]
+struct ShortcutPreset {
+    name: String,
+    label: String,
+    description: String,
+    bindings: Vec<(String, String)>,
+}
+
+const DEFAULT_PRESET: &str = "default";
+
+/// Config key holding the name of the active preset.
+const PRESET_KEY: &str = "preset";
+
+/// (action id, accelerator) pairs of a preset compiled into the app.
+type PresetBindings = &'static [(&'static str, &'static str)];
+
+/// Presets compiled into the app as (name, label, description, bindings).
+/// The default preset comes first.
+const BUILTIN_PRESETS: &[(&str, &str, &str, PresetBindings)] = &[
+    (DEFAULT_PRESET, "Default", "The app's own shortcuts", &[]),
+    (
+        "vim",
+        "Vim",
+        "Ctrl bindings familiar from Vim",
+        &[
+            ("command_palette", "Ctrl+Shift+;"),
+            ("open", "Ctrl+P"),
+            ("find", "Ctrl+/"),
+            ("search", "Ctrl+Shift+/"),
+            ("redo", "Ctrl+R"),
+        ],
+    ),
+    (
+        "emacs",
+        "Emacs",
+        "Ctrl+X chords and Emacs search and undo",
+        &[
+            ("command_palette", "Alt+X"),
+            ("open", "Ctrl+X Ctrl+F"),
+            ("save", "Ctrl+X Ctrl+S"),
+            ("close_document", "Ctrl+X Ctrl+K"),
+            ("find", "Ctrl+S"),
+            ("undo", "Ctrl+/"),
+            ("redo", "Ctrl+Shift+/"),
+            ("zoom_in", "Ctrl+X Ctrl+="),
+            ("zoom_out", "Ctrl+X Ctrl+-"),
+            ("zoom_reset", "Ctrl+X Ctrl+0"),
+        ],
+    ),
+    (
+        "vscode",
+        "VS Code",
+        "Quick open, command palette and sidebar as in VS Code",
+        &[
+            ("command_palette", "CmdOrCtrl+Shift+P"),
+            ("open", "CmdOrCtrl+P"),
+            ("toggle_sidebar", "CmdOrCtrl+B"),
+            ("toggle_fullscreen", "F11"),
+        ],
+    ),
+];
+
+fn builtin_presets() -> Vec<ShortcutPreset> {
+    BUILTIN_PRESETS
+        .iter()
+        .map(|(name, label, description, bindings)| ShortcutPreset {
+            name: name.to_string(),
+            label: label.to_string(),
+            description: description.to_string(),
+            bindings: bindings.iter().map(|(a, k)| (a.to_string(), k.to_string())).collect(),
+        })
+        .collect()
+}
+
+/// The built-in presets followed by the `.conf` files in the shared config
+/// repository's shortcut-presets folder. Shared presets can't replace
+/// built-in ones.
+fn available_presets(app_handle: &AppHandle) -> Vec<ShortcutPreset> {
+    let mut presets = builtin_presets();
+    for path in config_repo::layer_files(app_handle, ConfigLayer::ShortcutPresets, "conf") {
+        match read_preset_file(&path) {
+            Ok(preset) if find_preset(&presets, &preset.name).is_none() => presets.push(preset),
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Skipping shortcut preset {}: {}", path.display(), e),
+        }
+    }
+    presets
+}
+
+/// Reads a preset written like shortcuts.conf, named after the file, with
+/// optional `label` and `description` keys.
+fn read_preset_file(path: &Path) -> AppResult<ShortcutPreset> {
+    let name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| AppError::validation("Invalid preset file name"))?
+        .to_string();
+    let mut parser = ConfigParser::new(path.to_str().ok_or_else(|| AppError::validation("Invalid preset path"))?);
+    parser.load()?;
+
+    Ok(ShortcutPreset {
+        label: parser.get_str("label").cloned().unwrap_or_else(|| name.clone()),
+        description: parser.get_str("description").cloned().unwrap_or_default(),
+        bindings: DEFAULT_KEYMAP
+            .iter()
+            .filter_map(|(action, _, _)| Some((action.to_string(), parser.get_str(action)?.clone())))
+            .collect(),
+        name,
+    })
+}
+
+fn find_preset<'a>(presets: &'a [ShortcutPreset], name: &str) -> Option<&'a ShortcutPreset> {
+    presets.iter().find(|preset| preset.name == name)
+}
+
+/// The preset named in shortcuts.conf, or the default one.
+fn active_preset<'a>(parser: &ConfigParser, presets: &'a [ShortcutPreset]) -> &'a ShortcutPreset {
+    parser
+        .get_str(PRESET_KEY)
+        .and_then(|name| find_preset(presets, name.trim()))
+        .unwrap_or(&presets[0])
+}
+
+fn preset_keymap(preset: &ShortcutPreset) -> Shortcuts {
+    let mut shortcuts = Shortcuts::default();
+    for (action, accelerator) in &preset.bindings {
+        if let Some(binding) = shortcuts.bindings.get_mut(action) {
+            *binding = accelerator.clone();
+        }
+    }
+    shortcuts
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PresetInfo {
+    pub name: String,
+    pub label: String,
+    pub description: String,
+    pub bindings: Shortcuts,
+    pub active: bool,
+}
+
+/// Actions that can be bound system-wide under `[global]` in shortcuts.conf.
+/// They're unbound by default, since a global shortcut takes the key away
+/// from every other app.
+pub const GLOBAL_ACTIONS: &[(&str, &str)] = &[
+    ("quick_capture", "Capture a note from any app"),
+    ("toggle_window", "Show or hide the window"),
+];
+
+/// How long a started chord waits for its next step.
+const CHORD_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Action id to accelerator for every action in `DEFAULT_KEYMAP`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Shortcuts {
-    pub command_palette: String,
+    bindings: BTreeMap<String, String>,
 }
 
 impl Default for Shortcuts {
     fn default() -> Self {
         Self {
-            command_palette: "Cmd+P".to_string(),
+            bindings: DEFAULT_KEYMAP
+                .iter()
+                .map(|(action, accelerator, _)| (action.to_string(), accelerator.to_string()))
+                .collect(),
         }
     }
 }
 
-fn get_config_path(app_handle: &AppHandle) -> Result<PathBuf, String> {
+impl Shortcuts {
+    /// The accelerator bound to `action`, or `None` for an unknown action.
+    pub fn get(&self, action: &str) -> Option<&str> {
+        self.bindings.get(action).map(|s| s.as_str())
+    }
+
+    /// Every (action, accelerator) pair in action order.
+    pub fn bindings(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.bindings.iter().map(|(action, accelerator)| (action.as_str(), accelerator.as_str()))
+    }
+}
+
+/// Every action the keymap binds, as (action id, description) pairs.
+pub fn actions() -> impl Iterator<Item = (&'static str, &'static str)> {
+    DEFAULT_KEYMAP.iter().map(|(action, _, description)| (*action, *description))
+}
+
+/// One binding `set_shortcuts` refused, so the frontend can point at it.
+#[derive(Debug, Clone, Serialize)]
+pub struct InvalidShortcut {
+    pub action: String,
+    pub accelerator: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictReason {
+    /// Several actions share the shortcut
+    Duplicate,
+    /// The OS takes the shortcut before the app sees it
+    Reserved,
+    /// One shortcut is the start of a chord, so the chord can't be typed
+    Prefix,
+}
+
+/// Bindings that parse but can't all work, e.g. two actions on Ctrl+S.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShortcutConflict {
+    pub accelerator: String,
+    pub actions: Vec<String>,
+    pub reason: ConflictReason,
+}
+
+fn get_config_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
     let app_data_dir = app_handle
         .path()
         .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
     
     std::fs::create_dir_all(&app_data_dir)
-        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
     
     Ok(app_data_dir.join("shortcuts.conf"))
 }
 
-pub fn load_shortcuts(app_handle: &AppHandle) -> Result<Shortcuts, String> {
+pub fn load_shortcuts(app_handle: &AppHandle) -> AppResult<Shortcuts> {
     let config_path = get_config_path(app_handle)?;
     let config_path_str = config_path.to_str()
-        .ok_or("Invalid config path")?;
+        .ok_or_else(|| AppError::config("Invalid config path"))?;
     
     let mut parser = ConfigParser::new(config_path_str);
     parser.load()?;
     
-    let mut command_palette = parser.get_str("command_palette").unwrap_or(&"Cmd+P".to_string()).to_string();
-    if command_palette.trim().is_empty() {
-        command_palette = "Cmd+P".to_string();
+    Ok(shortcuts_from(&parser, &available_presets(app_handle), std::env::consts::OS))
+}
+
+/// Reads the keymap from `parser`, falling back to the active preset for
+/// actions that are missing or left empty. Keys that aren't actions are
+/// ignored. Bindings are spelled out for `platform`, e.g. CmdOrCtrl+P
+/// becomes Ctrl+P on Linux, and ones saved on macOS get Ctrl in place of
+/// Cmd elsewhere.
+fn shortcuts_from(parser: &ConfigParser, presets: &[ShortcutPreset], platform: &str) -> Shortcuts {
+    let mut shortcuts = preset_keymap(active_preset(parser, presets));
+    for (action, accelerator) in shortcuts.bindings.iter_mut() {
+        if let Some(value) = parser.get_str(action).filter(|v| !v.trim().is_empty()) {
+            *accelerator = value.clone();
+        }
+        *accelerator = resolve_binding(accelerator, platform);
+    }
+    shortcuts
+}
+
+/// Invalid bindings are kept as written so set_shortcuts can point at them.
+fn resolve_binding(accelerator: &str, platform: &str) -> String {
+    KeySequence::parse_portable(accelerator, platform)
+        .map(|parsed| parsed.resolve(platform).to_string())
+        .unwrap_or_else(|_| accelerator.to_string())
+}
+
+/// The keymap after switching from the `previous` preset's keymap to
+/// `preset`. Bindings that differ from `previous` were set by the user and
+/// are kept.
+fn switch_preset(current: &Shortcuts, previous: &Shortcuts, preset: &ShortcutPreset, platform: &str) -> Shortcuts {
+    let next = preset_keymap(preset);
+    let mut shortcuts = current.clone();
+    for (action, binding) in shortcuts.bindings.iter_mut() {
+        let was_preset = previous
+            .get(action)
+            .is_some_and(|default| resolve_binding(default, platform) == resolve_binding(binding, platform));
+        if let (true, Some(value)) = (was_preset, next.get(action)) {
+            *binding = resolve_binding(value, platform);
+        }
+    }
+    shortcuts
+}
+
+pub fn list_presets(app_handle: &AppHandle) -> AppResult<Vec<PresetInfo>> {
+    let config_path = get_config_path(app_handle)?;
+    let config_path_str = config_path.to_str()
+        .ok_or_else(|| AppError::config("Invalid config path"))?;
+
+    let mut parser = ConfigParser::new(config_path_str);
+    parser.load()?;
+
+    let platform = std::env::consts::OS;
+    let presets = available_presets(app_handle);
+    let active = &active_preset(&parser, &presets).name;
+    Ok(presets
+        .iter()
+        .map(|preset| {
+            let mut bindings = preset_keymap(preset);
+            for binding in bindings.bindings.values_mut() {
+                *binding = resolve_binding(binding, platform);
+            }
+            PresetInfo {
+                name: preset.name.clone(),
+                label: preset.label.clone(),
+                description: preset.description.clone(),
+                bindings,
+                active: &preset.name == active,
+            }
+        })
+        .collect())
+}
+
+/// Switches to the preset `name`, keeping bindings the user changed from
+/// the previous preset. Nothing is saved if a kept binding would clash with
+/// the new preset.
+pub fn apply_preset(app_handle: &AppHandle, name: &str) -> AppResult<Shortcuts> {
+    let presets = available_presets(app_handle);
+    let preset = find_preset(&presets, name).ok_or_else(|| AppError::not_found(format!("Unknown keymap preset {}", name)))?;
+    let config_path = get_config_path(app_handle)?;
+    let config_path_str = config_path.to_str()
+        .ok_or_else(|| AppError::config("Invalid config path"))?;
+
+    let mut parser = ConfigParser::new(config_path_str);
+    parser.load()?;
+
+    let platform = std::env::consts::OS;
+    let previous = preset_keymap(active_preset(&parser, &presets));
+    let shortcuts = switch_preset(&shortcuts_from(&parser, &presets, platform), &previous, preset, platform);
+
+    let conflicts = find_conflicts(&shortcuts, platform);
+    if !conflicts.is_empty() {
+        return Err(AppError::conflict(format!("Keymap preset {} conflicts with your shortcuts", preset.name))
+            .with_details(serde_json::json!({ "conflicts": conflicts })));
+    }
+
+    write_shortcuts(app_handle, &shortcuts, Some(&preset.name))?;
+    Ok(shortcuts)
+}
+
+/// How a binding is written to shortcuts.conf: with the platform's command
+/// key as CmdOrCtrl, so the file still works when moved to another OS.
+fn stored_form(accelerator: &str, platform: &str) -> String {
+    KeySequence::parse_portable(accelerator, platform)
+        .map(|parsed| parsed.portable(platform).to_string())
+        .unwrap_or_else(|_| accelerator.to_string())
+}
+
+/// The `[global]` bindings from shortcuts.conf, leaving out empty ones.
+pub fn load_global_shortcuts(app_handle: &AppHandle) -> AppResult<BTreeMap<String, String>> {
+    let config_path = get_config_path(app_handle)?;
+    let config_path_str = config_path.to_str()
+        .ok_or_else(|| AppError::config("Invalid config path"))?;
+
+    let mut parser = ConfigParser::new(config_path_str);
+    parser.load()?;
+
+    for action in unknown_global_actions(&parser) {
+        tracing::warn!("Ignoring [global] binding for {}, which can't be triggered globally", action);
     }
-    let shortcuts = Shortcuts {
-        command_palette,
+    Ok(global_shortcuts_from(&parser))
+}
+
+/// Keys in `[global]` that aren't global actions, usually typos or in-app
+/// actions put in the wrong section.
+fn unknown_global_actions(parser: &ConfigParser) -> Vec<String> {
+    parser
+        .section_keys("global")
+        .into_iter()
+        .filter(|key| !GLOBAL_ACTIONS.iter().any(|(action, _)| action == key))
+        .collect()
+}
+
+fn global_shortcuts_from(parser: &ConfigParser) -> BTreeMap<String, String> {
+    GLOBAL_ACTIONS
+        .iter()
+        .filter_map(|(action, _)| {
+            let accelerator = parser.get_str(&format!("global.{}", action))?;
+            (!accelerator.trim().is_empty()).then(|| (action.to_string(), accelerator.clone()))
+        })
+        .collect()
+}
+
+/// Parses every binding in `updates`, returning the keymap with them applied
+/// in normalized form, or every binding that's invalid on `platform`.
+fn apply_updates(
+    current: &Shortcuts,
+    updates: &BTreeMap<String, String>,
+    platform: &str,
+) -> Result<Shortcuts, Vec<InvalidShortcut>> {
+    let mut shortcuts = current.clone();
+    let mut errors = Vec::new();
+
+    for (action, accelerator) in updates {
+        let invalid = |message: String| InvalidShortcut {
+            action: action.clone(),
+            accelerator: accelerator.clone(),
+            message,
+        };
+        let Some(binding) = shortcuts.bindings.get_mut(action) else {
+            errors.push(invalid(format!("Unknown action {}", action)));
+            continue;
+        };
+        match KeySequence::parse(accelerator, platform) {
+            Ok(parsed) => *binding = parsed.to_string(),
+            Err(error) => errors.push(invalid(error.to_string())),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(shortcuts)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Every binding that parses on `platform`, spelled out for it.
+fn resolved_bindings(shortcuts: &Shortcuts, platform: &str) -> Vec<(String, KeySequence)> {
+    shortcuts
+        .bindings
+        .iter()
+        .filter_map(|(action, binding)| {
+            let sequence = KeySequence::parse(binding, platform).ok()?;
+            Some((action.clone(), sequence.resolve(platform)))
+        })
+        .collect()
+}
+
+/// Finds actions sharing a shortcut on `platform`, shortcuts the OS
+/// reserves and shortcuts that start a chord. Bindings that don't parse
+/// are left to `apply_updates`.
+fn find_conflicts(shortcuts: &Shortcuts, platform: &str) -> Vec<ShortcutConflict> {
+    let resolved = resolved_bindings(shortcuts, platform);
+    let mut by_sequence: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut conflicts = Vec::new();
+
+    for (action, sequence) in &resolved {
+        if sequence.is_reserved(platform) {
+            conflicts.push(ShortcutConflict {
+                accelerator: shortcuts.bindings[action].clone(),
+                actions: vec![action.clone()],
+                reason: ConflictReason::Reserved,
+            });
+        }
+        by_sequence.entry(sequence.to_string()).or_default().push(action.clone());
+    }
+
+    conflicts.extend(
+        by_sequence
+            .into_iter()
+            .filter(|(_, actions)| actions.len() > 1)
+            .map(|(accelerator, actions)| ShortcutConflict { accelerator, actions, reason: ConflictReason::Duplicate }),
+    );
+
+    for (action, sequence) in &resolved {
+        for (chord_action, chord) in &resolved {
+            if chord.steps.len() > sequence.steps.len() && chord.starts_with(sequence) {
+                conflicts.push(ShortcutConflict {
+                    accelerator: sequence.to_string(),
+                    actions: vec![action.clone(), chord_action.clone()],
+                    reason: ConflictReason::Prefix,
+                });
+            }
+        }
+    }
+    conflicts
+}
+
+/// What the keys typed so far amount to.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ChordMatch {
+    /// A complete shortcut
+    Matched { action: String },
+    /// The start of one or more chords; the next step decides
+    Pending { sequence: String, actions: Vec<String> },
+    NoMatch,
+}
+
+/// The chord steps typed so far, shared between `match_step` calls.
+#[derive(Default)]
+pub struct ChordState {
+    pending: Mutex<Option<(KeySequence, Instant)>>,
+}
+
+fn match_sequence(shortcuts: &Shortcuts, typed: &KeySequence, platform: &str) -> ChordMatch {
+    let mut continuations = Vec::new();
+    for (action, sequence) in resolved_bindings(shortcuts, platform) {
+        if sequence == *typed {
+            return ChordMatch::Matched { action };
+        }
+        if sequence.starts_with(typed) {
+            continuations.push(action);
+        }
+    }
+
+    if continuations.is_empty() {
+        ChordMatch::NoMatch
+    } else {
+        ChordMatch::Pending { sequence: typed.to_string(), actions: continuations }
+    }
+}
+
+/// Feeds one keystroke such as "Ctrl+K" to the chord matcher. A keystroke
+/// within `CHORD_TIMEOUT` of a pending step continues that chord; anything
+/// other than `Pending` ends it.
+pub fn match_step(app_handle: &AppHandle, state: &ChordState, step: &str) -> AppResult<ChordMatch> {
+    let platform = std::env::consts::OS;
+    let mut pending = state.pending.lock()?;
+    let previous = pending.take().filter(|(_, at)| at.elapsed() < CHORD_TIMEOUT);
+
+    // Keys that can't be shortcuts, like a plain letter, just end the chord
+    let Ok(step) = Accelerator::parse(step, platform) else {
+        return Ok(ChordMatch::NoMatch);
     };
-    
+    let mut typed = previous.map(|(sequence, _)| sequence).unwrap_or(KeySequence { steps: Vec::new() });
+    typed.steps.push(step.resolve(platform));
+
+    let result = match_sequence(&load_shortcuts(app_handle)?, &typed, platform);
+    if matches!(result, ChordMatch::Pending { .. }) {
+        *pending = Some((typed, Instant::now()));
+    }
+    Ok(result)
+}
+
+pub fn cancel_chord(state: &ChordState) -> AppResult<()> {
+    *state.pending.lock()? = None;
+    Ok(())
+}
+
+/// Validates and saves the given bindings; actions left out keep theirs.
+/// Nothing is saved if any binding is invalid or the keymap would end up
+/// ambiguous.
+pub fn set_shortcuts(app_handle: &AppHandle, updates: &BTreeMap<String, String>) -> AppResult<Shortcuts> {
+    let platform = std::env::consts::OS;
+    let current = load_shortcuts(app_handle)?;
+    let shortcuts = apply_updates(&current, updates, platform)
+        .map_err(|errors| {
+            AppError::validation("Some shortcuts are invalid").with_details(serde_json::json!({ "errors": errors }))
+        })?;
+
+    let conflicts = find_conflicts(&shortcuts, platform);
+    if !conflicts.is_empty() {
+        return Err(AppError::conflict("Some shortcuts are bound more than once")
+            .with_details(serde_json::json!({ "conflicts": conflicts })));
+    }
+
+    save_shortcuts(app_handle, &shortcuts)?;
     Ok(shortcuts)
 }
 
-pub fn save_shortcuts(app_handle: &AppHandle, shortcuts: &Shortcuts) -> Result<(), String> {
+pub fn save_shortcuts(app_handle: &AppHandle, shortcuts: &Shortcuts) -> AppResult<()> {
+    write_shortcuts(app_handle, shortcuts, None)
+}
+
+/// Saves the keymap and, if given, the name of the active preset.
+fn write_shortcuts(app_handle: &AppHandle, shortcuts: &Shortcuts, preset: Option<&str>) -> AppResult<()> {
     let config_path = get_config_path(app_handle)?;
     let config_path_str = config_path.to_str()
-        .ok_or("Invalid config path")?;
+        .ok_or_else(|| AppError::config("Invalid config path"))?;
     
     let mut parser = ConfigParser::new(config_path_str);
     parser.load()?; // Load existing config to preserve comments
     
-    // Update values
-    parser.set_str("command_palette", &shortcuts.command_palette);
-    
-    // Set comments if they don't exist
-    parser.set_comment("command_palette", "Open the command palette");
+    for (action, _, _) in DEFAULT_KEYMAP {
+        parser.set_validator(action, |value| {
+            if value.trim().is_empty() {
+                Err(AppError::validation("shortcut can't be empty"))
+            } else {
+                Ok(())
+            }
+        });
+    }
     
-    parser.save()?;
-    Ok(())
+    let platform = std::env::consts::OS;
+    parser.transaction(|parser| {
+        if let Some(preset) = preset {
+            parser.set_str(PRESET_KEY, preset);
+            parser.set_comment(PRESET_KEY, "Keymap preset: default, vim, emacs or vscode");
+        }
+        for (action, _, description) in DEFAULT_KEYMAP {
+            // Update values
+            if let Some(accelerator) = shortcuts.get(action) {
+                parser.set_str(action, &stored_form(accelerator, platform));
+            }
+            
+            // Set comments if they don't exist
+            parser.set_comment(action, description);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_keymap_falls_back_to_defaults() {
+        let path = std::env::temp_dir().join("test_shortcuts.conf");
+        fs::write(&path, "save=Ctrl+S\nsearch=\nunknown=Cmd+U\n").unwrap();
+
+        let mut parser = ConfigParser::new(path.to_str().unwrap());
+        parser.load().unwrap();
+        let shortcuts = shortcuts_from(&parser, &builtin_presets(), "linux");
+
+        assert_eq!(shortcuts.get("save"), Some("Ctrl+S"));
+        assert_eq!(shortcuts.get("search"), Some("Ctrl+Shift+F"));
+        assert_eq!(shortcuts.get("zoom_in"), Some("Ctrl+="));
+        assert_eq!(shortcuts.get("unknown"), None);
+        assert_eq!(shortcuts_from(&parser, &builtin_presets(), "macos").get("zoom_in"), Some("Cmd+="));
+        assert!(global_shortcuts_from(&parser).is_empty());
+
+        fs::write(&path, "save=Ctrl+S\n[global]\nquick_capture=Ctrl+Alt+N\ntoggle_window=\nsave=Ctrl+Alt+S\n").unwrap();
+        parser.load().unwrap();
+        let global = global_shortcuts_from(&parser);
+        assert_eq!(global, BTreeMap::from([("quick_capture".to_string(), "Ctrl+Alt+N".to_string())]));
+        assert_eq!(unknown_global_actions(&parser), vec!["save".to_string()]);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_bindings_move_between_platforms() {
+        let path = std::env::temp_dir().join("test_shortcuts_portable.conf");
+        fs::write(&path, "save=Cmd+S\nopen=Cmd+Shift+Hyper\n").unwrap();
+
+        let mut parser = ConfigParser::new(path.to_str().unwrap());
+        parser.load().unwrap();
+        let shortcuts = shortcuts_from(&parser, &builtin_presets(), "windows");
+        assert_eq!(shortcuts.get("save"), Some("Ctrl+S"));
+        assert_eq!(shortcuts.get("open"), Some("Cmd+Shift+Hyper"));
+
+        assert_eq!(stored_form("Ctrl+S", "windows"), "CmdOrCtrl+S");
+        assert_eq!(stored_form("Cmd+S", "macos"), "CmdOrCtrl+S");
+        assert_eq!(stored_form("Ctrl+Tab", "macos"), "Ctrl+Tab");
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_updates_report_every_invalid_binding() {
+        let current = Shortcuts::default();
+        let updates = BTreeMap::from([
+            ("save".to_string(), "ctrl+shift+s".to_string()),
+            ("open".to_string(), "Shift+O".to_string()),
+            ("fly".to_string(), "Ctrl+Y".to_string()),
+        ]);
+        let errors = apply_updates(&current, &updates, "linux").unwrap_err();
+        let actions: Vec<&str> = errors.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(actions, vec!["fly", "open"]);
+
+        let updates = BTreeMap::from([("save".to_string(), "ctrl+shift+s".to_string())]);
+        let shortcuts = apply_updates(&current, &updates, "linux").unwrap();
+        assert_eq!(shortcuts.get("save"), Some("Ctrl+Shift+S"));
+    }
+
+    #[test]
+    fn test_conflicts() {
+        assert!(find_conflicts(&Shortcuts::default(), "macos").is_empty());
+        assert!(find_conflicts(&Shortcuts::default(), "windows").is_empty());
+
+        let updates = BTreeMap::from([
+            ("open".to_string(), "Cmd+S".to_string()),
+            ("close_document".to_string(), "CmdOrCtrl+Q".to_string()),
+        ]);
+        let shortcuts = apply_updates(&Shortcuts::default(), &updates, "macos").unwrap();
+        let conflicts = find_conflicts(&shortcuts, "macos");
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].reason, ConflictReason::Reserved);
+        assert_eq!(conflicts[1].accelerator, "Cmd+S");
+        assert_eq!(conflicts[1].actions, vec!["open", "save"]);
+    }
+
+    #[test]
+    fn test_presets() {
+        for platform in ["linux", "macos", "windows"] {
+            for preset in &builtin_presets() {
+                let keymap = preset_keymap(preset);
+                assert!(preset.bindings.iter().all(|(action, _)| Shortcuts::default().get(action).is_some()));
+                assert!(resolved_bindings(&keymap, platform).len() == DEFAULT_KEYMAP.len(), "{} on {}", preset.name, platform);
+                assert!(find_conflicts(&keymap, platform).is_empty(), "{} on {}", preset.name, platform);
+            }
+        }
+
+        // The user moved find away from the default; it survives the switch
+        let presets = builtin_presets();
+        let default = preset_keymap(&presets[0]);
+        let updates = BTreeMap::from([("find".to_string(), "Ctrl+Alt+G".to_string())]);
+        let current = apply_updates(&default, &updates, "linux").unwrap();
+        let emacs = switch_preset(&current, &default, find_preset(&presets, "emacs").unwrap(), "linux");
+        assert_eq!(emacs.get("find"), Some("Ctrl+Alt+G"));
+        assert_eq!(emacs.get("save"), Some("Ctrl+X Ctrl+S"));
+        assert_eq!(emacs.get("new_document"), Some("Ctrl+N"));
+
+        let back = switch_preset(&emacs, &preset_keymap(find_preset(&presets, "emacs").unwrap()), &presets[0], "linux");
+        assert_eq!(back.get("save"), Some("Ctrl+S"));
+        assert_eq!(back.get("find"), Some("Ctrl+Alt+G"));
+    }
+
+    #[test]
+    fn test_chords() {
+        let updates = BTreeMap::from([
+            ("save".to_string(), "Ctrl+K Ctrl+S".to_string()),
+            ("close_document".to_string(), "ctrl+k ctrl+w".to_string()),
+        ]);
+        let shortcuts = apply_updates(&Shortcuts::default(), &updates, "linux").unwrap();
+        assert_eq!(shortcuts.get("close_document"), Some("Ctrl+K Ctrl+W"));
+        assert!(find_conflicts(&shortcuts, "linux").is_empty());
+        assert_eq!(stored_form("Ctrl+K Ctrl+S", "linux"), "CmdOrCtrl+K CmdOrCtrl+S");
+
+        let typed = |value: &str| KeySequence::parse(value, "linux").unwrap();
+        assert_eq!(
+            match_sequence(&shortcuts, &typed("Ctrl+K"), "linux"),
+            ChordMatch::Pending { sequence: "Ctrl+K".to_string(), actions: vec!["close_document".to_string(), "save".to_string()] }
+        );
+        assert_eq!(match_sequence(&shortcuts, &typed("Ctrl+K Ctrl+S"), "linux"), ChordMatch::Matched { action: "save".to_string() });
+        assert_eq!(match_sequence(&shortcuts, &typed("Ctrl+K Ctrl+P"), "linux"), ChordMatch::NoMatch);
+        assert_eq!(match_sequence(&shortcuts, &typed("Ctrl+P"), "linux"), ChordMatch::Matched { action: "command_palette".to_string() });
+
+        let updates = BTreeMap::from([("open".to_string(), "Ctrl+K".to_string())]);
+        let shortcuts = apply_updates(&shortcuts, &updates, "linux").unwrap();
+        let conflicts = find_conflicts(&shortcuts, "linux");
+        assert_eq!(conflicts.len(), 2);
+        assert!(conflicts.iter().all(|c| c.reason == ConflictReason::Prefix && c.actions[0] == "open"));
+    }
 }
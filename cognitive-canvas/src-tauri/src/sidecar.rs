@@ -0,0 +1,98 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::error::{AppError, AppResult};
+
+/// Returns the hidden file next to a document that stores `kind` data for
+/// it, e.g. `notes.canvas` -> `.notes.canvas.comments.json`.
+pub fn sidecar_path(document_path: &Path, kind: &str) -> PathBuf {
+    let file_name = document_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("document");
+    document_path.with_file_name(format!(".{}.{}.json", file_name, kind))
+}
+
+/// The sidecars that exist for a document, with their kind.
+pub fn sidecars(document_path: &Path) -> Vec<(String, PathBuf)> {
+    let Some(file_name) = document_path.file_name().and_then(|name| name.to_str()) else {
+        return Vec::new();
+    };
+    let prefix = format!(".{}.", file_name);
+    let dir = document_path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut found: Vec<(String, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let kind = name.strip_prefix(&prefix)?.strip_suffix(".json")?;
+            // `.notes.canvas.bak.meta.json` belongs to notes.canvas.bak
+            if kind.is_empty() || kind.contains('.') {
+                return None;
+            }
+            Some((kind.to_string(), document_path.with_file_name(&name)))
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+/// Reads a JSON sidecar, treating a missing file as empty.
+pub fn read_json<T: DeserializeOwned + Default>(path: &Path) -> AppResult<T> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| AppError::io(format!("Failed to read {}: {}", path.display(), e)))?;
+    serde_json::from_str(&content).map_err(|e| AppError::validation(format!("Failed to parse {}: {}", path.display(), e)))
+}
+
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> AppResult<()> {
+    let content = serde_json::to_string_pretty(value)
+        .map_err(|e| AppError::io(format!("Failed to serialize {}: {}", path.display(), e)))?;
+    std::fs::write(path, content).map_err(|e| AppError::io(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// Remembers which document owns `id` in an app-wide index, so records
+/// stored in sidecars can later be addressed by id alone.
+pub fn register_owner(index_path: &Path, id: &str, document_path: &Path) -> AppResult<()> {
+    let mut index: HashMap<String, String> = read_json(index_path)?;
+    index.insert(id.to_string(), document_path.to_string_lossy().to_string());
+    write_json(index_path, &index)
+}
+
+pub fn find_owner(index_path: &Path, id: &str) -> AppResult<PathBuf> {
+    let index: HashMap<String, String> = read_json(index_path)?;
+    index
+        .get(id)
+        .map(PathBuf::from)
+        .ok_or_else(|| AppError::not_found(format!("No document found for {}", id)))
+}
+
+/// Points the ids owned by a document that was renamed at its new path.
+pub fn move_owners(index_path: &Path, old_path: &Path, new_path: &Path) -> AppResult<()> {
+    let mut index: HashMap<String, String> = read_json(index_path)?;
+    let (old, new) = (old_path.to_string_lossy(), new_path.to_string_lossy());
+    let mut changed = false;
+    for owner in index.values_mut().filter(|owner| **owner == old) {
+        *owner = new.to_string();
+        changed = true;
+    }
+    if changed {
+        write_json(index_path, &index)?;
+    }
+    Ok(())
+}
+
+pub fn remove_owner(index_path: &Path, id: &str) -> AppResult<()> {
+    let mut index: HashMap<String, String> = read_json(index_path)?;
+    if index.remove(id).is_some() {
+        write_json(index_path, &index)?;
+    }
+    Ok(())
+}
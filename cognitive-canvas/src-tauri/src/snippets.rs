@@ -0,0 +1,216 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use std::path::{Path, PathBuf};
+use crate::fuzzy::fuzzy_match;
+use crate::sidecar;
+use crate::error::{AppError, AppResult};
+
+/// How many results `search_snippets` returns unless asked for more.
+pub const DEFAULT_LIMIT: usize = 20;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnippetKind {
+    /// Inserted as typed text
+    #[default]
+    Text,
+    /// Serialized Lexical nodes, pasted into a canvas as they are
+    Canvas,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snippet {
+    pub name: String,
+    #[serde(default)]
+    pub kind: SnippetKind,
+    pub content: String,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewSnippet {
+    pub name: String,
+    #[serde(default)]
+    pub kind: SnippetKind,
+    pub content: String,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SnippetMatch {
+    #[serde(flatten)]
+    pub snippet: Snippet,
+    pub score: i64,
+    /// Character positions in `name` that matched the query, for highlighting
+    pub matched: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SnippetsFile {
+    snippets: Vec<Snippet>,
+}
+
+pub fn get_snippets_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir.join("snippets.json"))
+}
+
+/// Every snippet, by name.
+pub fn list_snippets(path: &Path) -> AppResult<Vec<Snippet>> {
+    let file: SnippetsFile = sidecar::read_json(path)?;
+    Ok(file.snippets)
+}
+
+/// Adds a snippet, or replaces the one with the same name (ignoring case)
+/// keeping when it was created.
+pub fn save_snippet(path: &Path, snippet: NewSnippet) -> AppResult<Snippet> {
+    let name = snippet.name.trim().to_string();
+    if name.is_empty() {
+        return Err(AppError::validation("A snippet needs a name"));
+    }
+    if snippet.kind == SnippetKind::Canvas {
+        serde_json::from_str::<serde_json::Value>(&snippet.content)
+            .map_err(|e| AppError::validation(format!("Canvas snippet {} isn't valid JSON: {}", name, e)))?;
+    }
+
+    let mut file: SnippetsFile = sidecar::read_json(path)?;
+    let now = Utc::now();
+    let created_at = file
+        .snippets
+        .iter()
+        .find(|s| s.name.eq_ignore_ascii_case(&name))
+        .map_or(now, |s| s.created_at);
+    file.snippets.retain(|s| !s.name.eq_ignore_ascii_case(&name));
+
+    let saved = Snippet {
+        name,
+        kind: snippet.kind,
+        content: snippet.content,
+        keywords: snippet.keywords.into_iter().map(|k| k.trim().to_string()).filter(|k| !k.is_empty()).collect(),
+        created_at,
+        updated_at: now,
+    };
+    file.snippets.push(saved.clone());
+    file.snippets.sort_by_key(|s| s.name.to_lowercase());
+
+    sidecar::write_json(path, &file)?;
+    Ok(saved)
+}
+
+/// Returns whether there was a snippet called `name`.
+pub fn delete_snippet(path: &Path, name: &str) -> AppResult<bool> {
+    let mut file: SnippetsFile = sidecar::read_json(path)?;
+    let before = file.snippets.len();
+    file.snippets.retain(|s| !s.name.eq_ignore_ascii_case(name.trim()));
+    if file.snippets.len() == before {
+        return Ok(false);
+    }
+    sidecar::write_json(path, &file)?;
+    Ok(true)
+}
+
+/// Snippets matching `query`, best first, scored like palette commands:
+/// the name counts fully, keywords and the start of the content at a
+/// discount. An empty query lists every snippet by name.
+pub fn search_snippets(snippets: Vec<Snippet>, query: &str, limit: usize) -> Vec<SnippetMatch> {
+    let query = query.trim();
+    let mut matches: Vec<SnippetMatch> = snippets
+        .into_iter()
+        .filter_map(|snippet| {
+            if query.is_empty() {
+                return Some(SnippetMatch { snippet, score: 0, matched: Vec::new() });
+            }
+            let (score, matched) = match_snippet(query, &snippet)?;
+            Some(SnippetMatch { snippet, score, matched })
+        })
+        .collect();
+
+    matches.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.snippet.name.cmp(&b.snippet.name)));
+    matches.truncate(limit);
+    matches
+}
+
+fn match_snippet(query: &str, snippet: &Snippet) -> Option<(i64, Vec<usize>)> {
+    let name = fuzzy_match(query, &snippet.name);
+    // Canvas JSON is all keys and braces, so only text content is searched
+    let preview = match snippet.kind {
+        SnippetKind::Text => snippet.content.lines().find(|line| !line.trim().is_empty()).map(str::to_string),
+        SnippetKind::Canvas => None,
+    };
+    let other = snippet
+        .keywords
+        .iter()
+        .cloned()
+        .chain(preview)
+        .filter_map(|text| fuzzy_match(query, &text))
+        .map(|(score, _)| score / 2)
+        .max();
+
+    match (name, other) {
+        (Some((score, _)), Some(other)) if other > score => Some((other, Vec::new())),
+        (Some(name), _) => Some(name),
+        (None, other) => other.map(|score| (score, Vec::new())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn new_snippet(name: &str, kind: SnippetKind, content: &str, keywords: &[&str]) -> NewSnippet {
+        NewSnippet {
+            name: name.to_string(),
+            kind,
+            content: content.to_string(),
+            keywords: keywords.iter().map(|k| k.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_snippets() {
+        let dir = std::env::temp_dir().join("test_snippets");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("snippets.json");
+
+        let signature = save_snippet(&path, new_snippet("Signature", SnippetKind::Text, "Best,\nJordan", &["email", " "])).unwrap();
+        assert_eq!(signature.keywords, vec!["email"]);
+        save_snippet(&path, new_snippet("Todo list", SnippetKind::Canvas, r#"{"type":"list","children":[]}"#, &["checklist"])).unwrap();
+        save_snippet(&path, new_snippet("Standup", SnippetKind::Text, "Yesterday:\nToday:\nBlockers:", &[])).unwrap();
+        assert!(save_snippet(&path, new_snippet("Broken", SnippetKind::Canvas, "{not json", &[])).is_err());
+        assert!(save_snippet(&path, new_snippet("  ", SnippetKind::Text, "x", &[])).is_err());
+
+        let updated = save_snippet(&path, new_snippet("signature", SnippetKind::Text, "Cheers,\nJordan", &[])).unwrap();
+        assert_eq!(updated.created_at, signature.created_at);
+        let names: Vec<String> = list_snippets(&path).unwrap().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["signature", "Standup", "Todo list"]);
+
+        let results = search_snippets(list_snippets(&path).unwrap(), "std", DEFAULT_LIMIT);
+        assert_eq!(results[0].snippet.name, "Standup");
+        assert_eq!(results[0].matched, vec![0, 1, 4]);
+        let results = search_snippets(list_snippets(&path).unwrap(), "check", DEFAULT_LIMIT);
+        assert_eq!(results[0].snippet.name, "Todo list");
+        assert!(results[0].matched.is_empty());
+        assert!(search_snippets(list_snippets(&path).unwrap(), "yesterday", DEFAULT_LIMIT).iter().any(|m| m.snippet.name == "Standup"));
+        assert_eq!(search_snippets(list_snippets(&path).unwrap(), "", 2).len(), 2);
+
+        assert!(delete_snippet(&path, "STANDUP").unwrap());
+        assert!(!delete_snippet(&path, "Standup").unwrap());
+        assert_eq!(list_snippets(&path).unwrap().len(), 2);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
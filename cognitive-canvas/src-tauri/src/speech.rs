@@ -0,0 +1,188 @@
+use serde::Serialize;
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use crate::error::{AppError, AppResult};
+
+/// Words per minute at rate 1.0 for the engines that take a speed in wpm.
+const NORMAL_WPM: f64 = 175.0;
+const MIN_RATE: f64 = 0.25;
+const MAX_RATE: f64 = 4.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Voice {
+    /// What to pass as `voice` to `speak_text`
+    pub id: String,
+    pub name: String,
+    pub language: String,
+}
+
+/// The speech being played, so it can be stopped or replaced.
+#[derive(Default)]
+pub struct SpeechState {
+    child: Mutex<Option<Child>>,
+}
+
+/// The platform's speech synthesizer: `say` on macOS, System.Speech through
+/// PowerShell on Windows and espeak-ng elsewhere. All read the text from
+/// stdin, so it never has to be quoted.
+fn speak_command(voice: Option<&str>, rate: f64) -> Command {
+    let wpm = (NORMAL_WPM * rate).round().to_string();
+    if cfg!(target_os = "macos") {
+        let mut command = Command::new("say");
+        command.args(["-r", &wpm, "-f", "-"]);
+        if let Some(voice) = voice {
+            command.args(["-v", voice]);
+        }
+        command
+    } else if cfg!(windows) {
+        // System.Speech rates go from -10 to 10, 0 being normal
+        let rate = ((rate - 1.0) * 10.0).round().clamp(-10.0, 10.0);
+        let select = voice.map(|v| format!("$s.SelectVoice('{}');", v.replace('\'', "''"))).unwrap_or_default();
+        let mut command = Command::new("powershell");
+        command.args([
+            "-NoProfile",
+            "-Command",
+            &format!(
+                "Add-Type -AssemblyName System.Speech; $s = New-Object System.Speech.Synthesis.SpeechSynthesizer; {} $s.Rate = {}; $s.Speak([Console]::In.ReadToEnd())",
+                select, rate
+            ),
+        ]);
+        command
+    } else {
+        let mut command = Command::new("espeak-ng");
+        command.args(["-s", &wpm, "--stdin"]);
+        if let Some(voice) = voice {
+            command.args(["-v", voice]);
+        }
+        command
+    }
+}
+
+/// Reads `text` aloud, stopping whatever was being read before. `rate` is
+/// relative to the normal speed, 1.0 by default.
+pub fn speak_text(state: &SpeechState, text: &str, voice: Option<&str>, rate: Option<f64>) -> AppResult<()> {
+    stop_speaking(state)?;
+    if text.trim().is_empty() {
+        return Ok(());
+    }
+
+    let rate = rate.unwrap_or(1.0).clamp(MIN_RATE, MAX_RATE);
+    let mut child = speak_command(voice, rate)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| AppError::external(format!("Failed to start text-to-speech: {}", e)))?;
+
+    // Written from another thread so a long text can't block on a full pipe
+    if let Some(mut stdin) = child.stdin.take() {
+        let text = text.to_string();
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(text.as_bytes());
+        });
+    }
+    *state.child.lock()? = Some(child);
+    Ok(())
+}
+
+pub fn stop_speaking(state: &SpeechState) -> AppResult<()> {
+    if let Some(mut child) = state.child.lock()?.take() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    Ok(())
+}
+
+pub fn is_speaking(state: &SpeechState) -> bool {
+    state
+        .child
+        .lock()
+        .is_ok_and(|mut child| child.as_mut().is_some_and(|c| c.try_wait().is_ok_and(|status| status.is_none())))
+}
+
+/// `say -v ?` lines look like `Bad News            en_US    # The light you see...`.
+fn parse_say_voices(output: &str) -> Vec<Voice> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (left, _) = line.split_once('#')?;
+            let (name, language) = left.trim().rsplit_once(char::is_whitespace)?;
+            let name = name.trim().to_string();
+            Some(Voice { id: name.clone(), name, language: language.to_string() })
+        })
+        .collect()
+}
+
+/// `espeak-ng --voices` prints a table: Pty, Language, Age/Gender,
+/// VoiceName, File, Other Languages.
+fn parse_espeak_voices(output: &str) -> Vec<Voice> {
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            let (language, name) = (columns.get(1)?, columns.get(3)?);
+            Some(Voice { id: language.to_string(), name: name.replace('_', " "), language: language.to_string() })
+        })
+        .collect()
+}
+
+/// PowerShell prints one `name|culture` line per voice.
+fn parse_windows_voices(output: &str) -> Vec<Voice> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, language) = line.trim().split_once('|')?;
+            Some(Voice { id: name.to_string(), name: name.to_string(), language: language.to_string() })
+        })
+        .collect()
+}
+
+/// The voices installed for the platform's synthesizer.
+pub fn list_voices() -> AppResult<Vec<Voice>> {
+    let (mut command, parse): (Command, fn(&str) -> Vec<Voice>) = if cfg!(target_os = "macos") {
+        let mut command = Command::new("say");
+        command.args(["-v", "?"]);
+        (command, parse_say_voices)
+    } else if cfg!(windows) {
+        let mut command = Command::new("powershell");
+        command.args([
+            "-NoProfile",
+            "-Command",
+            "Add-Type -AssemblyName System.Speech; (New-Object System.Speech.Synthesis.SpeechSynthesizer).GetInstalledVoices() | ForEach-Object { $_.VoiceInfo.Name + '|' + $_.VoiceInfo.Culture }",
+        ]);
+        (command, parse_windows_voices)
+    } else {
+        let mut command = Command::new("espeak-ng");
+        command.arg("--voices");
+        (command, parse_espeak_voices)
+    };
+
+    let output = command.output().map_err(|e| AppError::io(format!("Failed to list voices: {}", e)))?;
+    if !output.status.success() {
+        return Err(AppError::io(format!("Failed to list voices: {}", String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    Ok(parse(&String::from_utf8_lossy(&output.stdout)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_voices() {
+        let say = "Alex                en_US    # Most people recognize me by my voice.\nBad News            en_US    # The light you see at the end of the tunnel";
+        let voices = parse_say_voices(say);
+        assert_eq!(voices.len(), 2);
+        assert_eq!(voices[1], Voice { id: "Bad News".to_string(), name: "Bad News".to_string(), language: "en_US".to_string() });
+
+        let espeak = "Pty Language       Age/Gender VoiceName          File                 Other Languages\n 5  af              --/M      Afrikaans          gmw/af\n 5  en-us           --/M      English_(America)  gmw/en-US            (en 3)";
+        let voices = parse_espeak_voices(espeak);
+        assert_eq!(voices[1].id, "en-us");
+        assert_eq!(voices[1].name, "English (America)");
+
+        let windows = "Microsoft David Desktop|en-US\r\nMicrosoft Zira Desktop|en-US\r\n";
+        assert_eq!(parse_windows_voices(windows)[1].name, "Microsoft Zira Desktop");
+    }
+}
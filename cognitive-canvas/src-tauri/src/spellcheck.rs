@@ -0,0 +1,298 @@
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use crate::sidecar;
+use crate::error::{AppError, AppResult};
+
+/// Suggestions further than this many edits away aren't offered.
+const MAX_EDIT_DISTANCE: usize = 2;
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Endings accepted on a dictionary word, since affix rules from the
+/// `.aff` file aren't applied: "notes", "tagged", "linking".
+const SUFFIXES: &[&str] = &["s", "es", "ed", "d", "ing", "'s", "ly", "er", "est"];
+
+/// A word list with a symmetric-delete index: every word is also filed
+/// under each way of deleting one of its letters, so candidates for a
+/// misspelling are found by looking up its own deletes instead of
+/// comparing it with every word.
+pub struct Dictionary {
+    words: HashSet<String>,
+    /// Word minus one letter -> words
+    deletes: HashMap<String, Vec<String>>,
+    /// Dictionary order, used to break ties between suggestions since
+    /// word lists tend to put common words first
+    rank: HashMap<String, usize>,
+}
+
+fn deletes(word: &str) -> Vec<String> {
+    let chars: Vec<char> = word.chars().collect();
+    (0..chars.len())
+        .map(|i| chars[..i].iter().chain(&chars[i + 1..]).collect())
+        .collect()
+}
+
+/// Optimal string alignment distance: insertions, deletions,
+/// substitutions and swaps of neighbours each count as one edit.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+impl Dictionary {
+    pub fn new<I: IntoIterator<Item = String>>(words: I) -> Self {
+        let mut dictionary = Dictionary { words: HashSet::new(), deletes: HashMap::new(), rank: HashMap::new() };
+        for word in words {
+            dictionary.insert(&word.to_lowercase());
+        }
+        dictionary
+    }
+
+    fn insert(&mut self, word: &str) {
+        if word.is_empty() || !self.words.insert(word.to_string()) {
+            return;
+        }
+        self.rank.insert(word.to_string(), self.rank.len());
+        for delete in deletes(word) {
+            self.deletes.entry(delete).or_default().push(word.to_string());
+        }
+    }
+
+    /// Parses a hunspell `.dic` file: a word count, then one word per line
+    /// with optional `/FLAGS` after it.
+    pub fn from_hunspell(content: &str) -> Self {
+        Self::new(
+            content
+                .lines()
+                .skip(1)
+                .filter_map(|line| line.split(['/', '\t']).next())
+                .map(|word| word.trim().to_string()),
+        )
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        let word = word.to_lowercase();
+        self.words.contains(&word)
+            || SUFFIXES.iter().any(|suffix| {
+                word.strip_suffix(suffix).is_some_and(|stem| {
+                    stem.chars().count() > 1
+                        && (self.words.contains(stem)
+                            // "tagged", "running"
+                            || {
+                                let mut chars = stem.chars();
+                                let last = chars.next_back();
+                                chars.as_str().ends_with(|c| Some(c) == last) && self.words.contains(chars.as_str())
+                            })
+                })
+            })
+    }
+
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let word = word.to_lowercase();
+        let mut candidates: HashSet<&String> = HashSet::new();
+        for key in std::iter::once(word.clone()).chain(deletes(&word)) {
+            if let Some(found) = self.words.get(&key) {
+                candidates.insert(found);
+            }
+            candidates.extend(self.deletes.get(&key).into_iter().flatten());
+        }
+
+        let mut ranked: Vec<(usize, usize, &String)> = candidates
+            .into_iter()
+            .map(|candidate| (edit_distance(&word, candidate), self.rank.get(candidate).copied().unwrap_or(usize::MAX), candidate))
+            .filter(|(distance, _, _)| *distance > 0 && *distance <= MAX_EDIT_DISTANCE)
+            .collect();
+        ranked.sort();
+        ranked.into_iter().take(MAX_SUGGESTIONS).map(|(_, _, word)| word.clone()).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Misspelling {
+    pub word: String,
+    /// Character offsets into the checked text
+    pub start: usize,
+    pub end: usize,
+    pub suggestions: Vec<String>,
+}
+
+/// Words in `text` with their character offsets. Words with digits,
+/// acronyms and anything in a URL or email address are left out.
+fn words(text: &str) -> Vec<(usize, String)> {
+    let mut found = Vec::new();
+    let mut offset = 0;
+    for chunk in text.split_inclusive(char::is_whitespace) {
+        let length = chunk.chars().count();
+        if chunk.contains("://") || chunk.contains('@') || chunk.starts_with("www.") {
+            offset += length;
+            continue;
+        }
+
+        let chars: Vec<char> = chunk.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            if !chars[i].is_alphanumeric() {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            // Apostrophes inside a word belong to it: "don't"
+            while i < chars.len() && (chars[i].is_alphanumeric() || (chars[i] == '\'' && chars.get(i + 1).is_some_and(|c| c.is_alphabetic()))) {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            let letters = word.chars().filter(|c| c.is_alphabetic()).count();
+            let acronym = word.chars().all(|c| !c.is_lowercase());
+            if letters > 1 && !acronym && !word.chars().any(|c| c.is_numeric()) {
+                found.push((offset + start, word));
+            }
+        }
+        offset += length;
+    }
+    found
+}
+
+pub fn check(dictionary: &Dictionary, user_words: &BTreeSet<String>, text: &str) -> Vec<Misspelling> {
+    words(text)
+        .into_iter()
+        .filter(|(_, word)| !dictionary.contains(word) && !user_words.contains(&word.to_lowercase()))
+        .map(|(start, word)| Misspelling {
+            start,
+            end: start + word.chars().count(),
+            suggestions: dictionary.suggest(&word),
+            word,
+        })
+        .collect()
+}
+
+/// Loaded dictionaries by language, as the first check in a language
+/// takes a moment to build the index.
+#[derive(Default)]
+pub struct SpellcheckState {
+    dictionaries: Mutex<HashMap<String, Arc<Dictionary>>>,
+}
+
+fn get_app_data_dir(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir)
+}
+
+/// Where a hunspell dictionary for `lang` (e.g. "en_US") may be: the app's
+/// own dictionaries folder first, then where Linux distributions and macOS
+/// users put them.
+fn dictionary_paths(app_handle: &AppHandle, lang: &str) -> Vec<PathBuf> {
+    let file = format!("{}.dic", lang);
+    let mut paths = Vec::new();
+    if let Ok(dir) = get_app_data_dir(app_handle) {
+        paths.push(dir.join("dictionaries").join(&file));
+    }
+    if let Some(home) = std::env::var_os("HOME") {
+        paths.push(PathBuf::from(home).join("Library/Spelling").join(&file));
+    }
+    for dir in ["/usr/share/hunspell", "/usr/share/myspell", "/usr/share/myspell/dicts"] {
+        paths.push(PathBuf::from(dir).join(&file));
+    }
+    paths
+}
+
+fn dictionary(app_handle: &AppHandle, state: &SpellcheckState, lang: &str) -> AppResult<Arc<Dictionary>> {
+    let lang = lang.replace('-', "_");
+    if let Some(dictionary) = state.dictionaries.lock()?.get(&lang) {
+        return Ok(dictionary.clone());
+    }
+
+    let paths = dictionary_paths(app_handle, &lang);
+    let path = paths
+        .iter()
+        .find(|path| path.is_file())
+        .ok_or_else(|| AppError::not_found(format!("No dictionary for {}; add a hunspell {}.dic to {}", lang, lang, paths[0].display())))?;
+    // Dictionaries aren't always UTF-8
+    let bytes = std::fs::read(path).map_err(|e| AppError::io(format!("Failed to read {}: {}", path.display(), e)))?;
+    let dictionary = Arc::new(Dictionary::from_hunspell(&String::from_utf8_lossy(&bytes)));
+
+    state.dictionaries.lock()?.insert(lang, dictionary.clone());
+    Ok(dictionary)
+}
+
+fn get_user_dictionary_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    Ok(get_app_data_dir(app_handle)?.join("user_dictionary.json"))
+}
+
+pub fn check_text(app_handle: &AppHandle, state: &SpellcheckState, text: &str, lang: &str) -> AppResult<Vec<Misspelling>> {
+    let dictionary = dictionary(app_handle, state, lang)?;
+    let user_words: BTreeSet<String> = sidecar::read_json(&get_user_dictionary_path(app_handle)?)?;
+    Ok(check(&dictionary, &user_words, text))
+}
+
+/// Stops `word` from being reported in any language.
+pub fn add_to_dictionary(app_handle: &AppHandle, word: &str) -> AppResult<()> {
+    let word = word.trim().to_lowercase();
+    if word.is_empty() {
+        return Err(AppError::validation("Word can't be empty"));
+    }
+    let path = get_user_dictionary_path(app_handle)?;
+    let mut user_words: BTreeSet<String> = sidecar::read_json(&path)?;
+    user_words.insert(word);
+    sidecar::write_json(&path, &user_words)
+}
+
+pub fn remove_from_dictionary(app_handle: &AppHandle, word: &str) -> AppResult<()> {
+    let path = get_user_dictionary_path(app_handle)?;
+    let mut user_words: BTreeSet<String> = sidecar::read_json(&path)?;
+    user_words.remove(&word.trim().to_lowercase());
+    sidecar::write_json(&path, &user_words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let dictionary = Dictionary::from_hunspell("8\nthe\nquick/S\nbrown\nfox\njump/DSG\nover\ndog/SM\ntag/S\n");
+        assert!(dictionary.contains("Jumped"));
+        assert!(dictionary.contains("tagged"));
+        assert!(!dictionary.contains("qick"));
+
+        let user_words = BTreeSet::from(["tauri".to_string()]);
+        let text = "The qiuck brown fox jumsp over the NASA dog, see https://exmaple.com Tauri v2 don't";
+        let misspellings = check(&dictionary, &user_words, text);
+        let found: Vec<(&str, usize, usize)> = misspellings.iter().map(|m| (m.word.as_str(), m.start, m.end)).collect();
+        assert_eq!(found, vec![("qiuck", 4, 9), ("jumsp", 20, 25), ("see", 45, 48), ("don't", 78, 83)]);
+        assert_eq!(misspellings[0].suggestions, vec!["quick"]);
+        assert_eq!(misspellings[1].suggestions, vec!["jump"]);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("qiuck", "quick"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "ab"), 2);
+    }
+}
@@ -0,0 +1,169 @@
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+use std::sync::Mutex;
+use std::time::Duration;
+use crate::{config_watcher, retention, safe_mode, settings_manager, shortcuts_manager, workspace};
+use crate::error::AppResult;
+use crate::operations::{Operation, OperationKind};
+
+/// The window is shown after this long even if the frontend never reports
+/// ready, so a broken frontend can't leave the app invisible.
+const SHOW_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Tracks the handshake that decides when the main window is shown: only
+/// once both the backend's startup work and the frontend's first render
+/// are done, so there's no white flash or half-initialized UI.
+#[derive(Default)]
+pub struct StartupState {
+    inner: Mutex<Readiness>,
+}
+
+#[derive(Default)]
+struct Readiness {
+    frontend_ready: bool,
+    backend_ready: bool,
+    shown: bool,
+    completed_phases: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StartupStatus {
+    pub backend_ready: bool,
+    pub completed_phases: Vec<&'static str>,
+}
+
+/// Sent as "startup-phase" when a phase starts and again when it's done.
+#[derive(Debug, Clone, Serialize)]
+struct StartupPhase<'a> {
+    phase: &'static str,
+    done: bool,
+    error: Option<&'a str>,
+}
+
+/// Runs the backend's startup work in the background, reporting each phase,
+/// and emits "backend-ready" when it's finished. In safe mode only what's
+/// needed to show the window runs.
+pub fn run(app_handle: &AppHandle, safe_mode: bool) {
+    let app = app_handle.clone();
+    std::thread::spawn(move || {
+        phase(&app, "settings", || {
+            // Loading also migrates an older settings file; in safe mode the
+            // defaults are already in place and nothing is read
+            let state = app.state::<settings_manager::SettingsState>();
+            let settings = settings_manager::current_settings(&app, &state)?;
+            settings_manager::apply_window_settings(&app, &settings)
+        });
+        if !safe_mode {
+            phase(&app, "shortcuts", || shortcuts_manager::load_shortcuts(&app).map(|_| ()));
+            #[cfg(desktop)]
+            phase(&app, "global-shortcuts", || crate::global_shortcuts::register_all(&app));
+            phase(&app, "config-watcher", || config_watcher::start(&app));
+            phase(&app, "workspace-scan", || start_workspace_scan(&app));
+        }
+        safe_mode::set_component(&app, None);
+
+        let state = app.state::<StartupState>();
+        if let Ok(mut readiness) = state.inner.lock() {
+            readiness.backend_ready = true;
+            show_if_ready(&app, &mut readiness);
+        }
+        let _ = app.emit("backend-ready", ());
+    });
+
+    let app = app_handle.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(SHOW_TIMEOUT).await;
+        let state = app.state::<StartupState>();
+        let Ok(mut readiness) = state.inner.lock() else {
+            return;
+        };
+        if !readiness.shown {
+            tracing::warn!("Startup handshake timed out, showing the window anyway");
+            readiness.frontend_ready = true;
+            readiness.backend_ready = true;
+            show_if_ready(&app, &mut readiness);
+        }
+    });
+}
+
+/// Called by the frontend once it has rendered. Returns how far the backend
+/// got, since phase events sent before the frontend listened are lost.
+pub fn frontend_ready(app_handle: &AppHandle, state: &StartupState) -> AppResult<StartupStatus> {
+    let mut readiness = state.inner.lock()?;
+    readiness.frontend_ready = true;
+    show_if_ready(app_handle, &mut readiness);
+
+    Ok(StartupStatus {
+        backend_ready: readiness.backend_ready,
+        completed_phases: readiness.completed_phases.clone(),
+    })
+}
+
+/// A failing phase is reported but doesn't stop startup; the app is still
+/// usable with default settings or without hot reload.
+fn phase<F>(app_handle: &AppHandle, name: &'static str, work: F)
+where
+    F: FnOnce() -> AppResult<()>,
+{
+    let _ = app_handle.emit("startup-phase", StartupPhase { phase: name, done: false, error: None });
+    safe_mode::set_component(app_handle, Some(name));
+
+    let result = work();
+    if let Err(e) = &result {
+        tracing::error!("Startup phase {} failed: {}", name, e);
+    }
+
+    let state = app_handle.state::<StartupState>();
+    if let Ok(mut readiness) = state.inner.lock() {
+        readiness.completed_phases.push(name);
+    }
+    let _ = app_handle.emit(
+        "startup-phase",
+        StartupPhase { phase: name, done: true, error: result.as_ref().err().map(|e| e.message()) },
+    );
+}
+
+/// Starts scanning the configured workspace without waiting for it; the
+/// results arrive as "workspace-scan-batch" events.
+fn start_workspace_scan(app_handle: &AppHandle) -> AppResult<()> {
+    let Some(root) = retention::workspace_dir(app_handle)? else {
+        return Ok(());
+    };
+    let app = app_handle.clone();
+    let operation = Operation::start(app_handle, OperationKind::WorkspaceScan, None)?;
+    std::thread::spawn(move || {
+        if let Err(e) = workspace::scan_workspace(&app, &root, &operation) {
+            tracing::warn!("Startup workspace scan failed: {}", e);
+        }
+    });
+    Ok(())
+}
+
+fn show_if_ready(app_handle: &AppHandle, readiness: &mut Readiness) {
+    if readiness.shown || !readiness.frontend_ready || !readiness.backend_ready {
+        return;
+    }
+    if starts_in_tray(app_handle) {
+        readiness.shown = true;
+        return;
+    }
+    if let Some(window) = app_handle.get_webview_window("main") {
+        if let Err(e) = window.show().and_then(|_| window.set_focus()) {
+            tracing::error!("Failed to show window: {}", e);
+            return;
+        }
+        readiness.shown = true;
+    }
+}
+
+/// Whether the window stays hidden behind the tray icon. Without the icon
+/// there would be no way to open it, so it's shown after all.
+fn starts_in_tray(app_handle: &AppHandle) -> bool {
+    #[cfg(desktop)]
+    let has_tray = app_handle.tray_by_id(crate::tray::TRAY_ID).is_some();
+    #[cfg(not(desktop))]
+    let has_tray = false;
+
+    let state = app_handle.state::<settings_manager::SettingsState>();
+    has_tray && settings_manager::current_settings(app_handle, &state).is_ok_and(|settings| settings.start_minimized_to_tray)
+}
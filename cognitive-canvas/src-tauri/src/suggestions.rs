@@ -0,0 +1,248 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use std::path::{Path, PathBuf};
+use crate::{document_text, sidecar};
+use crate::error::{AppError, AppResult};
+
+/// A proposed replacement against the canonical document content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Suggestion {
+    pub id: String,
+    pub author: String,
+    pub created_at: DateTime<Utc>,
+    /// Byte offset into the canonical content at proposal time
+    pub offset: usize,
+    pub removed: String,
+    pub inserted: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SuggestionsFile {
+    enabled: bool,
+    suggestions: Vec<Suggestion>,
+}
+
+fn get_index_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir.join("suggestions_index.json"))
+}
+
+fn load(document_path: &Path) -> AppResult<(PathBuf, SuggestionsFile)> {
+    let path = sidecar::sidecar_path(document_path, "suggestions");
+    let file = sidecar::read_json(&path)?;
+    Ok((path, file))
+}
+
+/// Keeps suggestions addressable by id after their document was renamed.
+pub fn rename_document(app_handle: &AppHandle, old_path: &Path, new_path: &Path) -> AppResult<()> {
+    sidecar::move_owners(&get_index_path(app_handle)?, old_path, new_path)
+}
+
+pub fn is_enabled(document_path: &Path) -> AppResult<bool> {
+    Ok(load(document_path)?.1.enabled)
+}
+
+pub fn set_enabled(document_path: &Path, enabled: bool) -> AppResult<()> {
+    let (path, mut file) = load(document_path)?;
+    file.enabled = enabled;
+    sidecar::write_json(&path, &file)
+}
+
+pub fn list_suggestions(document_path: &Path) -> AppResult<Vec<Suggestion>> {
+    Ok(load(document_path)?.1.suggestions)
+}
+
+/// Records the difference between the document on disk and `proposed` as a
+/// pending suggestion, leaving the document itself untouched.
+pub fn propose_edit(
+    app_handle: &AppHandle,
+    document_path: &Path,
+    author: &str,
+    proposed: &str,
+) -> AppResult<Option<Suggestion>> {
+    propose(&get_index_path(app_handle)?, document_path, author, proposed)
+}
+
+/// Applies a suggestion to the canonical document and returns the new
+/// content. Other edits may have shifted the text since it was proposed, so
+/// the replaced span is located again, preferring the match nearest its
+/// original offset.
+pub fn accept_suggestion(app_handle: &AppHandle, id: &str) -> AppResult<String> {
+    accept(&get_index_path(app_handle)?, id)
+}
+
+pub fn reject_suggestion(app_handle: &AppHandle, id: &str) -> AppResult<()> {
+    reject(&get_index_path(app_handle)?, id)
+}
+
+fn propose(index_path: &Path, document_path: &Path, author: &str, proposed: &str) -> AppResult<Option<Suggestion>> {
+    let canonical = std::fs::read_to_string(document_path)
+        .map_err(|e| AppError::io(format!("Failed to load document: {}", e)))?;
+
+    let (offset, removed_len, inserted) = match document_text::changed_range(&canonical, proposed) {
+        Some(change) => change,
+        None => return Ok(None),
+    };
+
+    let suggestion = Suggestion {
+        id: format!("suggestion-{}", Utc::now().timestamp_nanos_opt().unwrap_or_default()),
+        author: author.to_string(),
+        created_at: Utc::now(),
+        offset,
+        removed: canonical[offset..offset + removed_len].to_string(),
+        inserted: inserted.to_string(),
+    };
+
+    let (path, mut file) = load(document_path)?;
+    file.suggestions.push(suggestion.clone());
+    sidecar::write_json(&path, &file)?;
+    sidecar::register_owner(index_path, &suggestion.id, document_path)?;
+
+    Ok(Some(suggestion))
+}
+
+fn accept(index_path: &Path, id: &str) -> AppResult<String> {
+    let document_path = sidecar::find_owner(index_path, id)?;
+    let (path, mut file) = load(&document_path)?;
+
+    let position = file
+        .suggestions
+        .iter()
+        .position(|s| s.id == id)
+        .ok_or_else(|| AppError::not_found(format!("Suggestion not found: {}", id)))?;
+    let suggestion = file.suggestions[position].clone();
+
+    let mut content = std::fs::read_to_string(&document_path)
+        .map_err(|e| AppError::io(format!("Failed to load document: {}", e)))?;
+
+    let start = locate(&content, &suggestion)
+        .ok_or_else(|| AppError::conflict("The text this suggestion changes has since been edited"))?;
+    content.replace_range(start..start + suggestion.removed.len(), &suggestion.inserted);
+
+    std::fs::write(&document_path, &content)
+        .map_err(|e| AppError::io(format!("Failed to save document: {}", e)))?;
+
+    file.suggestions.remove(position);
+    sidecar::write_json(&path, &file)?;
+    sidecar::remove_owner(index_path, id)?;
+
+    Ok(content)
+}
+
+fn reject(index_path: &Path, id: &str) -> AppResult<()> {
+    let document_path = sidecar::find_owner(index_path, id)?;
+    let (path, mut file) = load(&document_path)?;
+
+    file.suggestions.retain(|s| s.id != id);
+    sidecar::write_json(&path, &file)?;
+    sidecar::remove_owner(index_path, id)
+}
+
+fn locate(content: &str, suggestion: &Suggestion) -> Option<usize> {
+    let end = suggestion.offset + suggestion.removed.len();
+    if content.get(suggestion.offset..end) == Some(suggestion.removed.as_str()) {
+        return Some(suggestion.offset);
+    }
+
+    // A pure insertion has nothing to search for
+    if suggestion.removed.is_empty() {
+        return (suggestion.offset <= content.len() && content.is_char_boundary(suggestion.offset))
+            .then_some(suggestion.offset);
+    }
+
+    content
+        .match_indices(&suggestion.removed)
+        .map(|(i, _)| i)
+        .min_by_key(|i| i.abs_diff(suggestion.offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_propose_accept_reject() {
+        let dir = std::env::temp_dir().join("test_suggestions_review");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let (index, document) = (dir.join("suggestions_index.json"), dir.join("draft.md"));
+        fs::write(&document, "The quick fox jumps.").unwrap();
+
+        assert!(!is_enabled(&document).unwrap());
+        set_enabled(&document, true).unwrap();
+        assert!(is_enabled(&document).unwrap());
+
+        assert!(propose(&index, &document, "Ana", "The quick fox jumps.").unwrap().is_none());
+        let brown = propose(&index, &document, "Ana", "The quick brown fox jumps.").unwrap().unwrap();
+        assert_eq!((brown.offset, brown.removed.as_str(), brown.inserted.as_str()), (10, "", "brown "));
+        let leaps = propose(&index, &document, "Ben", "The quick fox leaps.").unwrap().unwrap();
+        assert_eq!(list_suggestions(&document).unwrap().len(), 2);
+        // Proposing never touches the document itself
+        assert_eq!(fs::read_to_string(&document).unwrap(), "The quick fox jumps.");
+
+        // Accepting the insertion shifts the text the other suggestion replaces
+        assert_eq!(accept(&index, &brown.id).unwrap(), "The quick brown fox jumps.");
+        assert_eq!(accept(&index, &leaps.id).unwrap(), "The quick brown fox leaps.");
+        assert_eq!(fs::read_to_string(&document).unwrap(), "The quick brown fox leaps.");
+        assert!(list_suggestions(&document).unwrap().is_empty());
+        assert!(is_enabled(&document).unwrap());
+
+        let stale = propose(&index, &document, "Ana", "The quick brown fox sleeps.").unwrap().unwrap();
+        reject(&index, &stale.id).unwrap();
+        assert!(list_suggestions(&document).unwrap().is_empty());
+        assert!(matches!(accept(&index, &stale.id), Err(AppError::NotFound(_))));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_accept_after_rename() {
+        let dir = std::env::temp_dir().join("test_suggestions_rename");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let index = dir.join("suggestions_index.json");
+        let (old, new) = (dir.join("draft.md"), dir.join("final.md"));
+        fs::write(&old, "Hello world").unwrap();
+
+        let suggestion = propose(&index, &old, "Ana", "Hello there").unwrap().unwrap();
+
+        fs::rename(&old, &new).unwrap();
+        fs::rename(sidecar::sidecar_path(&old, "suggestions"), sidecar::sidecar_path(&new, "suggestions")).unwrap();
+        sidecar::move_owners(&index, &old, &new).unwrap();
+
+        assert_eq!(accept(&index, &suggestion.id).unwrap(), "Hello there");
+        assert_eq!(fs::read_to_string(&new).unwrap(), "Hello there");
+        assert!(!old.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_locate() {
+        let suggestion = |offset: usize, removed: &str| Suggestion {
+            id: "s".to_string(),
+            author: "Ana".to_string(),
+            created_at: Utc::now(),
+            offset,
+            removed: removed.to_string(),
+            inserted: String::new(),
+        };
+
+        assert_eq!(locate("abc abc abc", &suggestion(4, "abc")), Some(4));
+        // Moved text resolves to the occurrence nearest the original offset
+        assert_eq!(locate("xx abc abc abc", &suggestion(6, "abc")), Some(7));
+        assert_eq!(locate("abc", &suggestion(0, "xyz")), None);
+        assert_eq!(locate("héllo", &suggestion(2, "")), None);
+        assert_eq!(locate("héllo", &suggestion(6, "")), Some(6));
+        assert_eq!(locate("hi", &suggestion(5, "")), None);
+    }
+}
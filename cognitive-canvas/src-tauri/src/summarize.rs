@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+use crate::ai::{self, AiConfig, CompletionOptions, TokenUsage};
+use crate::{document_format, document_text, document_version, sidecar};
+use crate::error::{AppError, AppResult};
+
+/// Text longer than this is summarized in parts first, which keeps each
+/// request well inside small local models' context windows.
+const CHUNK_CHARS: usize = 12_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SummaryLength {
+    Short,
+    #[default]
+    Medium,
+    Long,
+}
+
+impl SummaryLength {
+    fn key(self) -> &'static str {
+        match self {
+            SummaryLength::Short => "short",
+            SummaryLength::Medium => "medium",
+            SummaryLength::Long => "long",
+        }
+    }
+
+    /// Words in the summary and how many key points to ask for.
+    fn targets(self) -> (usize, usize) {
+        match self {
+            SummaryLength::Short => (50, 3),
+            SummaryLength::Medium => (150, 5),
+            SummaryLength::Long => (400, 8),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub summary: String,
+    pub key_points: Vec<String>,
+    /// Of the document content that was summarized
+    pub content_hash: String,
+    pub model: String,
+    /// Tokens used making it, across all parts; nothing when read from the cache
+    pub usage: TokenUsage,
+    pub cached: bool,
+}
+
+fn summary_prompt(text: &str, length: SummaryLength) -> String {
+    let (words, points) = length.targets();
+    format!(
+        "Summarize the following document in about {} words and list its {} most important points. \
+         Answer with only a JSON object like {{\"summary\": \"...\", \"key_points\": [\"...\"]}}.\n\n---\n{}",
+        words, points, text
+    )
+}
+
+/// For the parts of a long document, whose summaries are then combined.
+fn part_prompt(text: &str, part: usize, parts: usize) -> String {
+    format!(
+        "This is part {} of {} of a long document. Summarize it in a paragraph, keeping names, numbers and decisions.\n\n---\n{}",
+        part, parts, text
+    )
+}
+
+/// Reads the model's answer, which should be JSON but may be wrapped in a
+/// code fence or be plain prose with a bulleted list.
+fn parse_summary(text: &str) -> (String, Vec<String>) {
+    let json = text
+        .find('{')
+        .zip(text.rfind('}'))
+        .and_then(|(start, end)| serde_json::from_str::<Value>(&text[start..=end]).ok());
+    if let Some(value) = json {
+        if let Some(summary) = value["summary"].as_str() {
+            let key_points = value["key_points"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|point| point.as_str().map(|p| p.trim().to_string()))
+                .filter(|point| !point.is_empty())
+                .collect();
+            return (summary.trim().to_string(), key_points);
+        }
+    }
+
+    let mut summary = Vec::new();
+    let mut key_points = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        match line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")).or_else(|| line.strip_prefix("• ")) {
+            Some(point) => key_points.push(point.trim().to_string()),
+            None => summary.push(line),
+        }
+    }
+    (summary.join("\n"), key_points)
+}
+
+fn add_usage(total: &mut TokenUsage, usage: &TokenUsage) {
+    total.input_tokens += usage.input_tokens;
+    total.output_tokens += usage.output_tokens;
+}
+
+async fn generate(config: &AiConfig, text: &str, length: SummaryLength) -> AppResult<Summary> {
+    let options = CompletionOptions { temperature: Some(0.2), ..Default::default() };
+    let mut usage = TokenUsage::default();
+
+    let chunks = document_text::chunk_text(text, CHUNK_CHARS);
+    let text = if chunks.len() > 1 {
+        let mut parts = Vec::new();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let completion = ai::complete(config, &part_prompt(chunk, i + 1, chunks.len()), &options).await?;
+            add_usage(&mut usage, &completion.usage);
+            parts.push(completion.text);
+        }
+        parts.join("\n\n")
+    } else {
+        text.to_string()
+    };
+
+    let completion = ai::complete(config, &summary_prompt(&text, length), &options).await?;
+    add_usage(&mut usage, &completion.usage);
+    let (summary, key_points) = parse_summary(&completion.text);
+    Ok(Summary { summary, key_points, content_hash: String::new(), model: completion.model, usage, cached: false })
+}
+
+/// A summary and key points of the document at `path`, by the configured
+/// AI provider. Kept in a `.summary` sidecar per length until the content
+/// changes.
+pub async fn summarize_document(config: &AiConfig, path: &Path, length: SummaryLength) -> AppResult<Summary> {
+    let content = document_format::read_document_async(path).await?;
+    let content_hash = document_version::content_hash(&content);
+    let sidecar_path = sidecar::sidecar_path(path, "summary");
+    let mut cache: BTreeMap<String, Summary> = sidecar::read_json(&sidecar_path)?;
+    if let Some(cached) = cache.get(length.key()).filter(|s| s.content_hash == content_hash) {
+        return Ok(Summary { usage: TokenUsage::default(), cached: true, ..cached.clone() });
+    }
+
+    let text = document_text::plain_text(&content);
+    if text.trim().is_empty() {
+        return Err(AppError::validation("The document is empty"));
+    }
+    let mut summary = generate(config, &text, length).await?;
+    summary.content_hash = content_hash.clone();
+
+    // Summaries of an older version are no use any more
+    cache.retain(|_, cached| cached.content_hash == content_hash);
+    cache.insert(length.key().to_string(), summary.clone());
+    sidecar::write_json(&sidecar_path, &cache)?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_summary() {
+        let fenced = "```json\n{\"summary\": \"Q3 plan.\", \"key_points\": [\"Hire two\", \" \", \"Ship v2\"]}\n```";
+        assert_eq!(parse_summary(fenced), ("Q3 plan.".to_string(), vec!["Hire two".to_string(), "Ship v2".to_string()]));
+
+        let prose = "The team agreed on a plan.\n\n- Hire two engineers\n* Ship v2 in March";
+        let (summary, key_points) = parse_summary(prose);
+        assert_eq!(summary, "The team agreed on a plan.");
+        assert_eq!(key_points, vec!["Hire two engineers", "Ship v2 in March"]);
+    }
+}
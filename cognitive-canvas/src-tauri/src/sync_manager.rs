@@ -0,0 +1,826 @@
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use crate::{archive, sidecar, trash};
+use crate::config_parser::{expand_value, ConfigParser};
+use crate::ignore_rules::IgnoreRules;
+use crate::s3::S3Client;
+use crate::sync_provider::SyncProvider;
+use crate::webdav::WebDavClient;
+use crate::error::{AppError, AppResult};
+use crate::operations::Operation;
+
+/// Everything about sync except passwords and keys, which only
+/// `set_credentials` writes and which never go back to the frontend.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncConfig {
+    /// Either "webdav" or "s3"
+    pub provider: String,
+    pub local_dir: String,
+    pub remote_dir: String,
+    pub server_url: String,
+    pub username: String,
+    pub s3_endpoint: String,
+    pub s3_bucket: String,
+    pub s3_region: String,
+    pub s3_access_key_id: String,
+    /// Patterns left out of sync on top of the workspace's ignore files
+    #[serde(default)]
+    pub ignore: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+pub enum SyncCredentials {
+    Webdav {
+        server_url: String,
+        username: String,
+        password: String,
+    },
+    S3 {
+        endpoint: String,
+        bucket: String,
+        region: String,
+        access_key_id: String,
+        secret_access_key: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncDirection {
+    Push,
+    Pull,
+    Both,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileSyncStatus {
+    Synced,
+    LocalOnly,
+    RemoteOnly,
+    LocalModified,
+    RemoteModified,
+    /// Synced before and since deleted locally, so it's removed remotely
+    LocalDeleted,
+    /// Synced before and since deleted remotely, so it's trashed locally
+    RemoteDeleted,
+    Conflict,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileSyncEntry {
+    pub path: String,
+    pub status: FileSyncStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncConflict {
+    pub path: String,
+    pub local_modified: u64,
+    pub remote_etag: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncReport {
+    pub uploaded: Vec<String>,
+    pub downloaded: Vec<String>,
+    pub deleted_remotely: Vec<String>,
+    pub deleted_locally: Vec<String>,
+    pub conflicts: Vec<SyncConflict>,
+}
+
+/// Kept in their own file, readable only by the user, rather than in
+/// sync.conf.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SyncSecrets {
+    #[serde(default)]
+    password: String,
+    #[serde(default)]
+    s3_secret_access_key: String,
+}
+
+/// What each file looked like the last time both sides agreed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SyncedFileRecord {
+    local_modified: u64,
+    remote_etag: Option<String>,
+}
+
+fn get_app_data_dir(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir)
+}
+
+fn get_config_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    Ok(get_app_data_dir(app_handle)?.join("sync.conf"))
+}
+
+fn get_secrets_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    Ok(get_app_data_dir(app_handle)?.join("sync_secrets.json"))
+}
+
+fn load_secrets(app_handle: &AppHandle) -> AppResult<SyncSecrets> {
+    sidecar::read_json(&get_secrets_path(app_handle)?)
+}
+
+fn save_secrets(path: &Path, secrets: &SyncSecrets) -> AppResult<()> {
+    use std::io::Write;
+
+    let content = serde_json::to_string_pretty(secrets)
+        .map_err(|e| AppError::io(format!("Failed to serialize sync credentials: {}", e)))?;
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .map_err(|e| AppError::io(format!("Failed to write {}: {}", path.display(), e)))?;
+    // The mode above only applies to a new file
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| AppError::io(format!("Failed to restrict {}: {}", path.display(), e)))?;
+    }
+    file.write_all(content.as_bytes())
+        .map_err(|e| AppError::io(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// Moves secrets an older version wrote into sync.conf over to the
+/// secrets file.
+fn migrate_secrets(app_handle: &AppHandle, parser: &mut ConfigParser) -> AppResult<()> {
+    let password = parser.remove("password");
+    let s3_secret_access_key = parser.remove("s3_secret_access_key");
+    if password.is_none() && s3_secret_access_key.is_none() {
+        return Ok(());
+    }
+    let secrets_path = get_secrets_path(app_handle)?;
+    let mut secrets: SyncSecrets = sidecar::read_json(&secrets_path)?;
+    if secrets.password.is_empty() {
+        secrets.password = password.unwrap_or_default();
+    }
+    if secrets.s3_secret_access_key.is_empty() {
+        secrets.s3_secret_access_key = s3_secret_access_key.unwrap_or_default();
+    }
+    save_secrets(&secrets_path, &secrets)?;
+    parser.save()
+}
+
+fn get_state_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    Ok(get_app_data_dir(app_handle)?.join("sync_state.json"))
+}
+
+pub fn load_sync_config(app_handle: &AppHandle) -> AppResult<SyncConfig> {
+    let config_path = get_config_path(app_handle)?;
+    let config_path_str = config_path.to_str()
+        .ok_or_else(|| AppError::config("Invalid config path"))?;
+
+    if !config_path.exists() {
+        return Ok(SyncConfig::default());
+    }
+
+    let mut parser = ConfigParser::new(config_path_str);
+    parser.load()?;
+    migrate_secrets(app_handle, &mut parser)?;
+
+    let value = |key: &str| parser.get_str(key).cloned().unwrap_or_default();
+    let provider = value("provider");
+    Ok(SyncConfig {
+        provider: if provider.is_empty() { "webdav".to_string() } else { provider },
+        local_dir: value("local_dir"),
+        remote_dir: value("remote_dir"),
+        server_url: value("server_url"),
+        username: value("username"),
+        s3_endpoint: value("s3_endpoint"),
+        s3_bucket: value("s3_bucket"),
+        s3_region: value("s3_region"),
+        s3_access_key_id: value("s3_access_key_id"),
+        ignore: parser.get_list("ignore").unwrap_or_default(),
+    })
+}
+
+pub fn save_sync_config(app_handle: &AppHandle, config: &SyncConfig) -> AppResult<()> {
+    let config_path = get_config_path(app_handle)?;
+    let config_path_str = config_path.to_str()
+        .ok_or_else(|| AppError::config("Invalid config path"))?;
+
+    // Start from an empty parser so the app's default settings don't leak into sync.conf
+    let mut parser = ConfigParser::new(config_path_str);
+
+    parser.set_str("provider", &config.provider);
+    parser.set_str("local_dir", &config.local_dir);
+    parser.set_str("remote_dir", &config.remote_dir);
+    parser.set_str("server_url", &config.server_url);
+    parser.set_str("username", &config.username);
+    parser.set_str("s3_endpoint", &config.s3_endpoint);
+    parser.set_str("s3_bucket", &config.s3_bucket);
+    parser.set_str("s3_region", &config.s3_region);
+    parser.set_str("s3_access_key_id", &config.s3_access_key_id);
+    parser.set_list("ignore", &config.ignore);
+
+    parser.set_comment("provider", "Sync backend: webdav or s3");
+    parser.set_comment("local_dir", "Workspace folder mirrored to the remote");
+    parser.set_comment("remote_dir", "Folder on the remote, relative to its root");
+    parser.set_comment("server_url", "WebDAV endpoint, e.g. https://cloud.example.com/remote.php/dav/files/me");
+    parser.set_comment("s3_endpoint", "Leave empty for AWS; e.g. https://s3.us-west-004.backblazeb2.com for Backblaze");
+    parser.set_comment("ignore", "Extra patterns not to sync, like .canvasignore lines");
+
+    parser.save()
+}
+
+/// Stores credentials for one provider and makes it the active one. The
+/// only way passwords and keys are written.
+pub fn set_credentials(app_handle: &AppHandle, credentials: SyncCredentials) -> AppResult<()> {
+    let mut config = load_sync_config(app_handle)?;
+    let mut secrets = load_secrets(app_handle)?;
+
+    match credentials {
+        SyncCredentials::Webdav { server_url, username, password } => {
+            config.provider = "webdav".to_string();
+            config.server_url = server_url;
+            config.username = username;
+            secrets.password = password;
+        }
+        SyncCredentials::S3 { endpoint, bucket, region, access_key_id, secret_access_key } => {
+            config.provider = "s3".to_string();
+            config.s3_endpoint = endpoint;
+            config.s3_bucket = bucket;
+            config.s3_region = region;
+            config.s3_access_key_id = access_key_id;
+            secrets.s3_secret_access_key = secret_access_key;
+        }
+    }
+
+    save_secrets(&get_secrets_path(app_handle)?, &secrets)?;
+    save_sync_config(app_handle, &config)
+}
+
+fn create_provider(config: &SyncConfig, secrets: &SyncSecrets) -> AppResult<Box<dyn SyncProvider>> {
+    match config.provider.as_str() {
+        "webdav" | "" => {
+            if config.server_url.is_empty() {
+                return Err(AppError::config("WebDAV server URL is not configured"));
+            }
+            Ok(Box::new(WebDavClient::new(&config.server_url, &config.username, &secrets.password)))
+        }
+        "s3" => {
+            if config.s3_bucket.is_empty() || config.s3_access_key_id.is_empty() {
+                return Err(AppError::config("S3 bucket and credentials are not configured"));
+            }
+            let region = if config.s3_region.is_empty() { "us-east-1" } else { &config.s3_region };
+            Ok(Box::new(S3Client::new(
+                &config.s3_endpoint,
+                &config.s3_bucket,
+                region,
+                &config.s3_access_key_id,
+                &secrets.s3_secret_access_key,
+            )))
+        }
+        other => Err(AppError::config(format!("Unknown sync provider: {}", other))),
+    }
+}
+
+fn load_sync_state(state_path: &Path) -> AppResult<HashMap<String, SyncedFileRecord>> {
+    if !state_path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let content = std::fs::read_to_string(state_path)
+        .map_err(|e| AppError::io(format!("Failed to read sync state: {}", e)))?;
+    serde_json::from_str(&content).map_err(|e| AppError::validation(format!("Failed to parse sync state: {}", e)))
+}
+
+fn save_sync_state(state_path: &Path, state: &HashMap<String, SyncedFileRecord>) -> AppResult<()> {
+    let content = serde_json::to_string_pretty(state)
+        .map_err(|e| AppError::io(format!("Failed to serialize sync state: {}", e)))?;
+    std::fs::write(state_path, content).map_err(|e| AppError::io(format!("Failed to write sync state: {}", e)))
+}
+
+/// The ignore rules that apply in each folder of a workspace: the
+/// defaults and the configured patterns, plus the ignore files from the
+/// root down. Read on first use.
+struct WorkspaceIgnores {
+    root: PathBuf,
+    base: IgnoreRules,
+    dirs: HashMap<PathBuf, IgnoreRules>,
+}
+
+impl WorkspaceIgnores {
+    fn new(root: &Path, patterns: &[String]) -> Self {
+        let mut base = IgnoreRules::default();
+        base.add(root, &patterns.join("\n"));
+        Self { root: root.to_path_buf(), base, dirs: HashMap::new() }
+    }
+
+    fn rules_for(&mut self, dir: &Path) -> &IgnoreRules {
+        if !self.dirs.contains_key(dir) {
+            let mut rules = match dir.parent().filter(|_| dir != self.root) {
+                Some(parent) => self.rules_for(parent).clone(),
+                None => self.base.clone(),
+            };
+            rules.read_dir_files(dir);
+            self.dirs.insert(dir.to_path_buf(), rules);
+        }
+        &self.dirs[dir]
+    }
+
+    /// Whether a `/`-separated path below the root, or a folder it's in,
+    /// is ignored.
+    fn is_ignored(&mut self, relative: &str) -> bool {
+        let parts: Vec<&str> = relative.split('/').collect();
+        let mut dir = self.root.clone();
+        for (i, part) in parts.iter().enumerate() {
+            let path = dir.join(part);
+            if self.rules_for(&dir).is_ignored(&path, i + 1 < parts.len()) {
+                return true;
+            }
+            dir = path;
+        }
+        false
+    }
+}
+
+/// Walks `root` and returns every file that isn't ignored, keyed by its
+/// `/`-separated relative path.
+fn scan_local_files(root: &Path, ignores: &mut WorkspaceIgnores) -> AppResult<HashMap<String, u64>> {
+    let mut files = HashMap::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|e| AppError::io(format!("Failed to read {}: {}", dir.display(), e)))?;
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_dir = path.is_dir();
+            if ignores.rules_for(&dir).is_ignored(&path, is_dir) {
+                continue;
+            }
+            if is_dir {
+                pending.push(path);
+                continue;
+            }
+
+            let relative = match path.strip_prefix(root) {
+                Ok(relative) => relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().to_string())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+                Err(_) => continue,
+            };
+            files.insert(relative, modified_millis(&path));
+        }
+    }
+
+    Ok(files)
+}
+
+fn modified_millis(path: &Path) -> u64 {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn remote_path(remote_dir: &str, relative: &str) -> String {
+    if remote_dir.is_empty() {
+        relative.to_string()
+    } else {
+        format!("{}/{}", remote_dir, relative)
+    }
+}
+
+/// `path` from a remote listing relative to `remote_dir`, or None when it
+/// isn't below it or could lead outside the workspace once pulled.
+fn remote_relative_path(remote_dir: &str, path: &str) -> Option<String> {
+    let path = path.trim_start_matches('/');
+    let relative = if remote_dir.is_empty() {
+        path
+    } else {
+        path.strip_prefix(remote_dir)?.strip_prefix('/')?
+    };
+    archive::safe_entry_path(relative)?;
+    Some(relative.trim_end_matches('/').to_string())
+}
+
+/// Lists the remote files, leaving out those the workspace ignores so
+/// they're never pulled over local ones.
+async fn scan_remote_files(
+    provider: &dyn SyncProvider,
+    remote_dir: &str,
+    ignores: &mut WorkspaceIgnores,
+) -> AppResult<HashMap<String, Option<String>>> {
+    let mut files = HashMap::new();
+    for entry in provider.list_files(remote_dir).await? {
+        match remote_relative_path(remote_dir, &entry.path) {
+            Some(relative) if ignores.is_ignored(&relative) => {}
+            Some(relative) => {
+                files.insert(relative, entry.etag);
+            }
+            None => tracing::warn!("Skipping remote file {} outside {}", entry.path, remote_dir),
+        }
+    }
+    Ok(files)
+}
+
+/// A file missing on one side that has a record was synced before, so it
+/// was deleted there, unless it was changed on the other side since.
+fn classify(
+    local: Option<u64>,
+    remote: Option<&Option<String>>,
+    previous: Option<&SyncedFileRecord>,
+) -> FileSyncStatus {
+    let local_changed = match (local, previous) {
+        (Some(modified), Some(prev)) => modified != prev.local_modified,
+        (Some(_), None) => true,
+        _ => false,
+    };
+    let remote_changed = match (remote, previous) {
+        (Some(etag), Some(prev)) => *etag != prev.remote_etag,
+        (Some(_), None) => true,
+        _ => false,
+    };
+
+    match (local.is_some(), remote.is_some()) {
+        (true, false) if previous.is_some() && !local_changed => FileSyncStatus::RemoteDeleted,
+        (true, false) => FileSyncStatus::LocalOnly,
+        (false, true) if previous.is_some() && !remote_changed => FileSyncStatus::LocalDeleted,
+        (false, true) => FileSyncStatus::RemoteOnly,
+        _ if local_changed && remote_changed => FileSyncStatus::Conflict,
+        _ if local_changed => FileSyncStatus::LocalModified,
+        _ if remote_changed => FileSyncStatus::RemoteModified,
+        _ => FileSyncStatus::Synced,
+    }
+}
+
+fn workspace_paths(config: &SyncConfig) -> AppResult<(PathBuf, String)> {
+    if config.local_dir.is_empty() {
+        return Err(AppError::config("Sync is not configured"));
+    }
+    Ok((PathBuf::from(expand_value(&config.local_dir)), config.remote_dir.trim_matches('/').to_string()))
+}
+
+/// Reports how every file in the workspace compares to the remote.
+pub async fn get_sync_status(app_handle: &AppHandle) -> AppResult<Vec<FileSyncEntry>> {
+    let config = load_sync_config(app_handle)?;
+    let (local_root, remote_dir) = workspace_paths(&config)?;
+    let provider = create_provider(&config, &load_secrets(app_handle)?)?;
+
+    let mut ignores = WorkspaceIgnores::new(&local_root, &config.ignore);
+    let local_files = scan_local_files(&local_root, &mut ignores)?;
+    let remote_files = scan_remote_files(provider.as_ref(), &remote_dir, &mut ignores).await?;
+    let state = load_sync_state(&get_state_path(app_handle)?)?;
+
+    let all_paths: BTreeSet<&String> = local_files.keys().chain(remote_files.keys()).collect();
+    Ok(all_paths
+        .into_iter()
+        .map(|path| FileSyncEntry {
+            path: path.clone(),
+            status: classify(local_files.get(path).copied(), remote_files.get(path), state.get(path)),
+        })
+        .collect())
+}
+
+/// A sync between listing both sides and saving what was transferred.
+struct SyncRun<'a> {
+    provider: &'a dyn SyncProvider,
+    local_root: &'a Path,
+    remote_dir: &'a str,
+    local_files: HashMap<String, u64>,
+    remote_files: HashMap<String, Option<String>>,
+    state: HashMap<String, SyncedFileRecord>,
+    report: SyncReport,
+}
+
+impl SyncRun<'_> {
+    /// Pushes, pulls or deletes one file as its status calls for, and
+    /// records the result. `trash` moves a file deleted remotely aside.
+    async fn sync_file(
+        &mut self,
+        relative: &str,
+        direction: SyncDirection,
+        trash: &(dyn Fn(&Path) -> AppResult<()> + Sync),
+    ) -> AppResult<()> {
+        let push = direction != SyncDirection::Pull;
+        let pull = direction != SyncDirection::Push;
+        let local = self.local_files.get(relative).copied();
+        let remote = self.remote_files.get(relative);
+        let local_path = self.local_root.join(relative);
+        let remote_file = remote_path(self.remote_dir, relative);
+
+        match classify(local, remote, self.state.get(relative)) {
+            FileSyncStatus::Conflict => {
+                // Both sides changed since the last sync - let the user decide
+                self.report.conflicts.push(SyncConflict {
+                    path: relative.to_string(),
+                    local_modified: local.unwrap_or(0),
+                    remote_etag: remote.cloned().flatten(),
+                });
+            }
+            FileSyncStatus::LocalOnly | FileSyncStatus::LocalModified if push => {
+                let contents = std::fs::read(&local_path)
+                    .map_err(|e| AppError::io(format!("Failed to read {}: {}", relative, e)))?;
+                if let Some(parent) = Path::new(relative).parent().and_then(|p| p.to_str()) {
+                    if !parent.is_empty() {
+                        self.provider.ensure_dir(&remote_path(self.remote_dir, parent)).await?;
+                    }
+                }
+                let etag = self.provider.upload(&remote_file, contents).await?;
+                self.state.insert(relative.to_string(), SyncedFileRecord {
+                    local_modified: local.unwrap_or(0),
+                    remote_etag: etag,
+                });
+                self.report.uploaded.push(relative.to_string());
+            }
+            FileSyncStatus::RemoteOnly | FileSyncStatus::RemoteModified if pull => {
+                let remote_etag = remote.cloned().flatten();
+                let contents = self.provider.download(&remote_file).await?;
+                if let Some(parent) = local_path.parent() {
+                    std::fs::create_dir_all(parent)
+                        .map_err(|e| AppError::io(format!("Failed to create {}: {}", parent.display(), e)))?;
+                }
+                std::fs::write(&local_path, contents)
+                    .map_err(|e| AppError::io(format!("Failed to write {}: {}", relative, e)))?;
+                self.state.insert(relative.to_string(), SyncedFileRecord {
+                    local_modified: modified_millis(&local_path),
+                    remote_etag,
+                });
+                self.report.downloaded.push(relative.to_string());
+            }
+            FileSyncStatus::LocalDeleted if push => {
+                self.provider.delete(&remote_file).await?;
+                self.state.remove(relative);
+                self.report.deleted_remotely.push(relative.to_string());
+            }
+            FileSyncStatus::RemoteDeleted if pull => {
+                // Into the trash rather than gone, as the remote side may
+                // have deleted it by mistake. One open elsewhere is kept
+                // and tried again next time.
+                match trash(&local_path) {
+                    Ok(()) => {
+                        self.state.remove(relative);
+                        self.report.deleted_locally.push(relative.to_string());
+                    }
+                    Err(e) => tracing::warn!("Keeping {} deleted remotely: {}", relative, e),
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Goes through every file until one fails or `proceed` says to stop,
+    /// then saves the records to `state_path` either way, so files already
+    /// transferred aren't taken for conflicts next time. The first error
+    /// is returned after saving.
+    async fn transfer_all(
+        &mut self,
+        state_path: &Path,
+        direction: SyncDirection,
+        ignores: &mut WorkspaceIgnores,
+        mut proceed: impl FnMut(usize, usize, &str) -> bool,
+        trash: &(dyn Fn(&Path) -> AppResult<()> + Sync),
+    ) -> AppResult<()> {
+        let all_paths: BTreeSet<String> = self.local_files.keys().chain(self.remote_files.keys()).cloned().collect();
+
+        let total = all_paths.len();
+        let mut result = Ok(());
+        for (index, relative) in all_paths.iter().enumerate() {
+            if !proceed(index, total, relative) {
+                break;
+            }
+            if let Err(e) = self.sync_file(relative, direction, trash).await {
+                result = Err(e);
+                break;
+            }
+        }
+
+        // Records of files gone from both sides have nothing left to compare
+        let (local_files, remote_files) = (&self.local_files, &self.remote_files);
+        self.state.retain(|relative, _| local_files.contains_key(relative) || remote_files.contains_key(relative));
+
+        // Not every provider returns an ETag from an upload, so pick them
+        // up from a fresh listing. After a failure the records are saved
+        // without them rather than risk a second one.
+        let missing_etags = self.report.uploaded.iter().any(|p| self.state.get(p).is_none_or(|s| s.remote_etag.is_none()));
+        if result.is_ok() && missing_etags {
+            match scan_remote_files(self.provider, self.remote_dir, ignores).await {
+                Ok(listing) => {
+                    for (relative, etag) in listing {
+                        if let Some(record) = self.state.get_mut(&relative) {
+                            if record.remote_etag.is_none() {
+                                record.remote_etag = etag;
+                            }
+                        }
+                    }
+                }
+                Err(e) => result = Err(e),
+            }
+        }
+
+        save_sync_state(state_path, &self.state)?;
+        result
+    }
+}
+
+/// Pushes and/or pulls every changed file, reporting each one to
+/// `operation`. When it's cancelled or a transfer fails, the files
+/// transferred so far are still remembered as synced, so the next sync
+/// doesn't mistake them for conflicts.
+pub async fn sync(app_handle: &AppHandle, direction: SyncDirection, operation: &Operation) -> AppResult<SyncReport> {
+    let config = load_sync_config(app_handle)?;
+    let (local_root, remote_dir) = workspace_paths(&config)?;
+    let provider = create_provider(&config, &load_secrets(app_handle)?)?;
+
+    operation.report(None, "Comparing files");
+    provider.ensure_dir(&remote_dir).await?;
+
+    let mut ignores = WorkspaceIgnores::new(&local_root, &config.ignore);
+    let local_files = scan_local_files(&local_root, &mut ignores)?;
+    let remote_files = scan_remote_files(provider.as_ref(), &remote_dir, &mut ignores).await?;
+
+    let state_path = get_state_path(app_handle)?;
+    let mut run = SyncRun {
+        provider: provider.as_ref(),
+        local_root: &local_root,
+        remote_dir: &remote_dir,
+        local_files,
+        remote_files,
+        state: load_sync_state(&state_path)?,
+        report: SyncReport::default(),
+    };
+
+    let trash_state = app_handle.state::<trash::TrashState>();
+    let trash_dir = trash::get_trash_dir(app_handle)?;
+    let trash = |path: &Path| trash::trash_document(&trash_state, &trash_dir, path).map(|_| ());
+    let proceed = |index: usize, total: usize, relative: &str| {
+        if operation.is_cancelled() {
+            return false;
+        }
+        operation.step(index, total, relative);
+        true
+    };
+    let result = run.transfer_all(&state_path, direction, &mut ignores, proceed, &trash).await;
+
+    for conflict in &run.report.conflicts {
+        let _ = app_handle.emit("sync-conflict", conflict);
+    }
+    result?;
+    operation.check_cancelled()?;
+    let _ = app_handle.emit("sync-completed", &run.report);
+
+    Ok(run.report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_relative_path() {
+        assert_eq!(remote_relative_path("notes", "notes/plan.md"), Some("plan.md".to_string()));
+        assert_eq!(remote_relative_path("notes", "/notes/2024/plan.md"), Some("2024/plan.md".to_string()));
+        assert_eq!(remote_relative_path("", "plan.md"), Some("plan.md".to_string()));
+        // A sibling folder sharing the prefix isn't below it
+        assert_eq!(remote_relative_path("notes", "notes2/plan.md"), None);
+        assert_eq!(remote_relative_path("notes", "notes"), None);
+    }
+
+    #[test]
+    fn test_remote_relative_path_stays_in_workspace() {
+        for path in ["notes/../../.ssh/authorized_keys", "notes//etc/passwd", "notes/C:/Windows/win.ini", "notes/a\\..\\b"] {
+            assert_eq!(remote_relative_path("notes", path), None, "{}", path);
+        }
+        assert_eq!(remote_relative_path("", "../outside.md"), None);
+    }
+
+    #[test]
+    fn test_classify_deletions() {
+        let record = SyncedFileRecord { local_modified: 10, remote_etag: Some("a".to_string()) };
+        let etag = |tag: &str| Some(tag.to_string());
+
+        assert_eq!(classify(Some(10), None, None), FileSyncStatus::LocalOnly);
+        assert_eq!(classify(None, Some(&etag("a")), None), FileSyncStatus::RemoteOnly);
+        assert_eq!(classify(Some(10), Some(&etag("a")), Some(&record)), FileSyncStatus::Synced);
+
+        assert_eq!(classify(None, Some(&etag("a")), Some(&record)), FileSyncStatus::LocalDeleted);
+        assert_eq!(classify(Some(10), None, Some(&record)), FileSyncStatus::RemoteDeleted);
+        // An edit on the other side since the last sync wins over the delete
+        assert_eq!(classify(None, Some(&etag("b")), Some(&record)), FileSyncStatus::RemoteOnly);
+        assert_eq!(classify(Some(11), None, Some(&record)), FileSyncStatus::LocalOnly);
+    }
+
+    #[test]
+    fn test_scan_skips_ignored_files() {
+        let dir = std::env::temp_dir().join("test_sync_ignores");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("notes/drafts")).unwrap();
+        std::fs::create_dir_all(dir.join("node_modules")).unwrap();
+        for file in ["plan.md", "sync.log", ".plan.md.comments.json", "notes/a.md", "notes/a.tmp", "notes/drafts/b.md", "node_modules/x.js"] {
+            std::fs::write(dir.join(file), "x").unwrap();
+        }
+        std::fs::write(dir.join("notes/.canvasignore"), "drafts/\n").unwrap();
+
+        let mut ignores = WorkspaceIgnores::new(&dir, &["*.log".to_string()]);
+        let files: BTreeSet<String> = scan_local_files(&dir, &mut ignores).unwrap().into_keys().collect();
+        assert_eq!(files, BTreeSet::from(["notes/a.md".to_string(), "plan.md".to_string()]));
+
+        // Remote paths are checked against the same rules
+        assert!(ignores.is_ignored("notes/drafts/c.md"));
+        assert!(ignores.is_ignored("notes/a.tmp"));
+        assert!(ignores.is_ignored("notes/old.log"));
+        assert!(ignores.is_ignored("node_modules/y.js"));
+        assert!(!ignores.is_ignored("notes/c.md"));
+        assert!(!ignores.is_ignored("drafts/c.md"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Uploads everything except paths containing `fail`, which error as
+    /// if the connection dropped.
+    struct FailingProvider {
+        fail: &'static str,
+    }
+
+    #[async_trait::async_trait]
+    impl SyncProvider for FailingProvider {
+        async fn list_files(&self, _dir: &str) -> AppResult<Vec<crate::sync_provider::RemoteEntry>> {
+            Ok(Vec::new())
+        }
+
+        async fn download(&self, path: &str) -> AppResult<Vec<u8>> {
+            Err(AppError::network(format!("Failed to download {}", path)))
+        }
+
+        async fn upload(&self, path: &str, _contents: Vec<u8>) -> AppResult<Option<String>> {
+            if path.contains(self.fail) {
+                return Err(AppError::network(format!("Failed to upload {}", path)));
+            }
+            Ok(Some(format!("etag-{}", path)))
+        }
+
+        async fn delete(&self, _path: &str) -> AppResult<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_failed_transfer_keeps_earlier_records() {
+        let dir = std::env::temp_dir().join("test_sync_failure");
+        let _ = std::fs::remove_dir_all(&dir);
+        let root = dir.join("workspace");
+        std::fs::create_dir_all(&root).unwrap();
+        for file in ["a.md", "b.md", "c.md"] {
+            std::fs::write(root.join(file), "x").unwrap();
+        }
+        let state_path = dir.join("sync_state.json");
+
+        let provider = FailingProvider { fail: "b.md" };
+        let mut ignores = WorkspaceIgnores::new(&root, &[]);
+        let mut run = SyncRun {
+            provider: &provider,
+            local_root: &root,
+            remote_dir: "notes",
+            local_files: scan_local_files(&root, &mut ignores).unwrap(),
+            remote_files: HashMap::new(),
+            state: HashMap::new(),
+            report: SyncReport::default(),
+        };
+        let no_trash = |_: &Path| -> AppResult<()> { Ok(()) };
+        let result = tauri::async_runtime::block_on(run.transfer_all(
+            &state_path,
+            SyncDirection::Both,
+            &mut ignores,
+            |_, _, _| true,
+            &no_trash,
+        ));
+
+        assert!(result.is_err());
+        assert_eq!(run.report.uploaded, vec!["a.md"]);
+        let saved = load_sync_state(&state_path).unwrap();
+        assert_eq!(saved.keys().collect::<Vec<_>>(), vec!["a.md"]);
+        assert_eq!(saved["a.md"].remote_etag.as_deref(), Some("etag-notes/a.md"));
+
+        // So the next run sees a.md as synced rather than as a conflict
+        let remote = Some("etag-notes/a.md".to_string());
+        assert_eq!(classify(run.local_files.get("a.md").copied(), Some(&remote), saved.get("a.md")), FileSyncStatus::Synced);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,149 @@
+use async_trait::async_trait;
+use crate::error::AppResult;
+
+#[derive(Debug, Clone)]
+pub struct RemoteEntry {
+    pub path: String,
+    pub etag: Option<String>,
+    pub is_dir: bool,
+}
+
+/// A remote store a workspace folder can be mirrored to. Paths are always
+/// `/`-separated and relative to the provider's root.
+#[async_trait]
+pub trait SyncProvider: Send + Sync {
+    /// Lists every file below `dir`, recursively.
+    async fn list_files(&self, dir: &str) -> AppResult<Vec<RemoteEntry>>;
+
+    async fn download(&self, path: &str) -> AppResult<Vec<u8>>;
+
+    /// Uploads `contents`, returning the new ETag if the provider reported one.
+    async fn upload(&self, path: &str, contents: Vec<u8>) -> AppResult<Option<String>>;
+
+    /// Removes a file. One that's already gone isn't an error.
+    async fn delete(&self, path: &str) -> AppResult<()>;
+
+    /// Makes sure `dir` exists. Stores without real folders can ignore this.
+    async fn ensure_dir(&self, _dir: &str) -> AppResult<()> {
+        Ok(())
+    }
+}
+
+/// Returns the inner text of every element with the given local name,
+/// ignoring whatever namespace prefix the server chose (`d:`, `D:`, none).
+pub fn extract_elements(xml: &str, local_name: &str) -> Vec<String> {
+    let mut results = Vec::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        // Comments can hold markup that isn't part of the document
+        if let Some(comment) = rest.strip_prefix("!--") {
+            match comment.find("-->") {
+                Some(end) => {
+                    rest = &comment[end + 3..];
+                    continue;
+                }
+                None => break,
+            }
+        }
+        let tag_end = match rest.find('>') {
+            Some(pos) => pos,
+            None => break,
+        };
+        let tag = &rest[..tag_end];
+        if tag.starts_with('/') || tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+
+        let name = tag.split_whitespace().next().unwrap_or("").trim_end_matches('/');
+        let (prefix, local) = match name.split_once(':') {
+            Some((prefix, local)) => (Some(prefix), local),
+            None => (None, name),
+        };
+        if local != local_name {
+            continue;
+        }
+
+        if tag.ends_with('/') {
+            results.push(String::new());
+            rest = &rest[tag_end + 1..];
+            continue;
+        }
+
+        let closing = match prefix {
+            Some(prefix) => format!("</{}:{}>", prefix, local),
+            None => format!("</{}>", local),
+        };
+        let body = &rest[tag_end + 1..];
+        match body.find(&closing) {
+            Some(end) => {
+                results.push(body[..end].to_string());
+                rest = &body[end + closing.len()..];
+            }
+            None => break,
+        }
+    }
+
+    results
+}
+
+pub fn decode_xml_entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Percent-encodes a single path segment per RFC 3986 unreserved characters.
+pub fn encode_segment(segment: &str) -> String {
+    let mut encoded = String::new();
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_elements() {
+        let xml = r#"<?xml version="1.0"?>
+<d:multistatus xmlns:d="DAV:">
+  <d:response><d:href>/notes/a.md</d:href><d:getetag>"1"</d:getetag></d:response>
+  <D:response><D:href>/notes/b.md</D:href><D:resourcetype/></D:response>
+  <!-- <d:href>/commented.md</d:href> -->
+  <response xmlns="DAV:"><href>/notes/c.md</href></response>
+</d:multistatus>"#;
+        assert_eq!(extract_elements(xml, "href"), vec!["/notes/a.md", "/notes/b.md", "/notes/c.md"]);
+        assert_eq!(extract_elements(xml, "resourcetype"), vec![""]);
+        assert_eq!(extract_elements(xml, "getetag"), vec!["\"1\""]);
+
+        let responses = extract_elements(xml, "response");
+        assert_eq!(responses.len(), 3);
+        assert_eq!(extract_elements(&responses[1], "href"), vec!["/notes/b.md"]);
+        // A prefix that only shares the local name's start doesn't count
+        assert!(extract_elements("<hrefs>x</hrefs>", "href").is_empty());
+        assert!(extract_elements("<d:href>unclosed", "href").is_empty());
+    }
+
+    #[test]
+    fn test_decode_xml_entities() {
+        assert_eq!(decode_xml_entities("&quot;Q&amp;A&quot; &lt;draft&gt;"), "\"Q&A\" <draft>");
+        assert_eq!(decode_xml_entities("&amp;lt;"), "&lt;");
+    }
+
+    #[test]
+    fn test_encode_segment() {
+        assert_eq!(encode_segment("plan a.md"), "plan%20a.md");
+        assert_eq!(encode_segment("café~1"), "caf%C3%A9~1");
+    }
+}
@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use crate::document_metadata::{self, MetadataPatch};
+use crate::{document_text, sidecar};
+use crate::error::{AppError, AppResult};
+
+/// Which documents carry each tag, so lookups don't read every sidecar.
+/// Tags compare case-insensitively and keep the first spelling seen.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct TagIndex {
+    tags: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl TagIndex {
+    fn key(&self, tag: &str) -> Option<String> {
+        self.tags.keys().find(|key| key.eq_ignore_ascii_case(tag)).cloned()
+    }
+
+    /// Makes `tags` the tags of `path`.
+    fn set_document(&mut self, path: &str, tags: &[String]) {
+        for documents in self.tags.values_mut() {
+            documents.remove(path);
+        }
+        for tag in tags {
+            let key = self.key(tag).unwrap_or_else(|| tag.clone());
+            self.tags.entry(key).or_default().insert(path.to_string());
+        }
+        self.tags.retain(|_, documents| !documents.is_empty());
+    }
+
+    fn documents(&self, tag: &str) -> BTreeSet<String> {
+        self.key(tag).and_then(|key| self.tags.get(&key).cloned()).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+fn get_index_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir.join("tag_index.json"))
+}
+
+/// Records the tags `path` has now, e.g. after its metadata was edited.
+pub fn index_document(app_handle: &AppHandle, path: &Path, tags: &[String]) -> AppResult<()> {
+    let index_path = get_index_path(app_handle)?;
+    let mut index: TagIndex = sidecar::read_json(&index_path)?;
+    index.set_document(&path.to_string_lossy(), tags);
+    sidecar::write_json(&index_path, &index)
+}
+
+pub fn rename_document(app_handle: &AppHandle, old_path: &Path, new_path: &Path) -> AppResult<()> {
+    let index_path = get_index_path(app_handle)?;
+    let mut index: TagIndex = sidecar::read_json(&index_path)?;
+    let (old, new) = (old_path.to_string_lossy(), new_path.to_string_lossy().to_string());
+    for documents in index.tags.values_mut() {
+        if documents.remove(old.as_ref()) {
+            documents.insert(new.clone());
+        }
+    }
+    sidecar::write_json(&index_path, &index)
+}
+
+fn set_tags(app_handle: &AppHandle, path: &Path, tags: Vec<String>) -> AppResult<Vec<String>> {
+    let metadata = document_metadata::update(path, MetadataPatch { tags: Some(tags), ..Default::default() })?;
+    index_document(app_handle, path, &metadata.tags)?;
+    Ok(metadata.tags)
+}
+
+/// Adds `tag` to the document and returns its tags.
+pub fn add_tag(app_handle: &AppHandle, path: &Path, tag: &str) -> AppResult<Vec<String>> {
+    let tag = tag.trim().trim_start_matches('#');
+    if tag.is_empty() {
+        return Err(AppError::validation("Tag can't be empty"));
+    }
+    let mut tags = document_metadata::load(path)?.tags;
+    tags.push(tag.to_string());
+    set_tags(app_handle, path, tags)
+}
+
+/// Removes `tag` from the document and returns its tags.
+pub fn remove_tag(app_handle: &AppHandle, path: &Path, tag: &str) -> AppResult<Vec<String>> {
+    let tag = tag.trim().trim_start_matches('#');
+    let mut tags = document_metadata::load(path)?.tags;
+    tags.retain(|t| !t.eq_ignore_ascii_case(tag));
+    set_tags(app_handle, path, tags)
+}
+
+/// Every tag with how many documents carry it, by name.
+pub fn list_tags(app_handle: &AppHandle) -> AppResult<Vec<TagCount>> {
+    let index: TagIndex = sidecar::read_json(&get_index_path(app_handle)?)?;
+    Ok(index
+        .tags
+        .iter()
+        .map(|(tag, documents)| TagCount { tag: tag.clone(), count: documents.len() })
+        .collect())
+}
+
+fn matching_documents(index: &TagIndex, query: &str) -> AppResult<Vec<String>> {
+    let mut required = Vec::new();
+    let mut excluded = Vec::new();
+    for word in query.split_whitespace() {
+        match word.strip_prefix('-') {
+            Some(tag) => excluded.push(tag.trim_start_matches('#')),
+            None => required.push(word.trim_start_matches('#')),
+        }
+    }
+    let Some((first, rest)) = required.split_first() else {
+        return Err(AppError::validation("Name at least one tag to look for"));
+    };
+
+    let mut documents = index.documents(first);
+    for tag in rest {
+        let tagged = index.documents(tag);
+        documents.retain(|path| tagged.contains(path));
+    }
+    for tag in excluded {
+        let tagged = index.documents(tag);
+        documents.retain(|path| !tagged.contains(path));
+    }
+    Ok(documents.into_iter().collect())
+}
+
+/// Documents matching a tag query: every tag listed is required and
+/// `-tag` excludes, e.g. `work urgent -done`. Documents that were deleted
+/// since they were tagged are dropped from the index.
+pub fn find_documents_by_tag(app_handle: &AppHandle, query: &str) -> AppResult<Vec<String>> {
+    let index_path = get_index_path(app_handle)?;
+    let mut index: TagIndex = sidecar::read_json(&index_path)?;
+
+    let (found, missing): (Vec<String>, Vec<String>) = matching_documents(&index, query)?
+        .into_iter()
+        .partition(|path| Path::new(path).exists());
+    if !missing.is_empty() {
+        for path in &missing {
+            index.set_document(path, &[]);
+        }
+        sidecar::write_json(&index_path, &index)?;
+    }
+    Ok(found)
+}
+
+/// A document's own tags followed by the ones in its frontmatter.
+pub fn document_tags(path: &Path, text: &str) -> Vec<String> {
+    let mut tags = document_metadata::load(path).map(|metadata| metadata.tags).unwrap_or_default();
+    tags.extend(document_text::tags(text));
+    document_metadata::normalize_tags(&tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_queries() {
+        let mut index = TagIndex::default();
+        index.set_document("a.md", &["Work".to_string(), "urgent".to_string()]);
+        index.set_document("b.md", &["work".to_string(), "done".to_string()]);
+        index.set_document("c.md", &["home".to_string()]);
+
+        assert_eq!(index.tags.keys().collect::<Vec<_>>(), vec!["Work", "done", "home", "urgent"]);
+        assert_eq!(matching_documents(&index, "work").unwrap(), vec!["a.md", "b.md"]);
+        assert_eq!(matching_documents(&index, "WORK -done").unwrap(), vec!["a.md"]);
+        assert_eq!(matching_documents(&index, "#work #urgent").unwrap(), vec!["a.md"]);
+        assert!(matching_documents(&index, "-done").is_err());
+
+        index.set_document("c.md", &[]);
+        assert!(!index.tags.contains_key("home"));
+    }
+}
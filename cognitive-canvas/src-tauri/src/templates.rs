@@ -0,0 +1,272 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+use std::path::{Path, PathBuf};
+use crate::config_repo::{self, ConfigLayer};
+use crate::{document_format, workspace};
+use crate::error::{AppError, AppResult};
+
+const TEMPLATES_DIR: &str = "templates";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateSource {
+    /// Saved on this machine
+    Local,
+    /// From the shared config repository
+    Shared,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TemplateInfo {
+    /// The file name without extension
+    pub name: String,
+    pub path: String,
+    pub source: TemplateSource,
+    /// The `{{variables}}` it uses
+    pub variables: Vec<String>,
+}
+
+pub fn get_templates_dir(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    let dir = app_data_dir.join(TEMPLATES_DIR);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| AppError::io(format!("Failed to create templates directory: {}", e)))?;
+
+    Ok(dir)
+}
+
+fn template_name(path: &Path) -> String {
+    path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+/// Letters, digits, spaces, `-` and `_`, as for new document names.
+fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric() || *c == ' ' || *c == '-' || *c == '_')
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn local_templates(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && workspace::is_document(path))
+        .collect();
+    files.sort();
+    files
+}
+
+fn shared_templates(app_handle: &AppHandle) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = workspace::DOCUMENT_EXTENSIONS
+        .iter()
+        .flat_map(|extension| config_repo::layer_files(app_handle, ConfigLayer::Templates, extension))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Calls `replace` with each `{{name}}` or `{{name:format}}` in `text` and
+/// puts in what it returns; unknown variables are left as they are.
+fn substitute(text: &str, replace: &dyn Fn(&str, Option<&str>) -> Option<String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}").map(|end| start + 2 + end) else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let inner = rest[start + 2..end].trim();
+        let (name, format) = match inner.split_once(':') {
+            Some((name, format)) => (name.trim(), Some(format)),
+            None => (inner, None),
+        };
+        match replace(&name.to_lowercase(), format) {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[start..end + 2]),
+        }
+        rest = &rest[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The variables a template uses, in order of first use.
+fn variables(content: &str) -> Vec<String> {
+    let found = std::cell::RefCell::new(Vec::new());
+    substitute(content, &|name, _| {
+        let mut found = found.borrow_mut();
+        if !found.iter().any(|n: &String| n == name) {
+            found.push(name.to_string());
+        }
+        None
+    });
+    found.into_inner()
+}
+
+/// `now` in a strftime `format`, or None if the format is invalid.
+fn format_time(now: &DateTime<Local>, format: &str) -> Option<String> {
+    let items: Vec<Item> = StrftimeItems::new(format).collect();
+    if items.contains(&Item::Error) {
+        return None;
+    }
+    Some(now.format_with_items(items.into_iter()).to_string())
+}
+
+fn replace_strings(value: &mut Value, replace: &dyn Fn(&str, Option<&str>) -> Option<String>) {
+    match value {
+        Value::String(text) if text.contains("{{") => *text = substitute(text, replace),
+        Value::Array(items) => items.iter_mut().for_each(|item| replace_strings(item, replace)),
+        Value::Object(map) => map.values_mut().for_each(|item| replace_strings(item, replace)),
+        _ => {}
+    }
+}
+
+/// Fills in `{{title}}`, `{{date}}`, `{{time}}` and `{{datetime}}`, the
+/// last three optionally with a strftime format like `{{date:%A %e %B}}`.
+/// In canvases only text is changed, so a title with quotes can't break
+/// the JSON.
+pub fn render(content: &str, title: &str, now: &DateTime<Local>) -> String {
+    let replace = |name: &str, format: Option<&str>| match name {
+        "title" => Some(title.to_string()),
+        "date" => format_time(now, format.unwrap_or("%Y-%m-%d")),
+        "time" => format_time(now, format.unwrap_or("%H:%M")),
+        "datetime" => format_time(now, format.unwrap_or("%Y-%m-%d %H:%M")),
+        _ => None,
+    };
+    match serde_json::from_str::<Value>(content) {
+        Ok(mut value) if value.get("root").is_some() => {
+            replace_strings(&mut value, &replace);
+            serde_json::to_string(&value).unwrap_or_else(|_| content.to_string())
+        }
+        _ => substitute(content, &replace),
+    }
+}
+
+/// Local templates, then shared ones whose name isn't already taken.
+pub fn list_templates(local: &[PathBuf], shared: &[PathBuf]) -> Vec<TemplateInfo> {
+    let mut templates: Vec<TemplateInfo> = Vec::new();
+    let sources = local.iter().map(|path| (path, TemplateSource::Local)).chain(shared.iter().map(|path| (path, TemplateSource::Shared)));
+    for (path, source) in sources {
+        let name = template_name(path);
+        if templates.iter().any(|template| template.name.eq_ignore_ascii_case(&name)) {
+            continue;
+        }
+        let content = document_format::read_document(path).unwrap_or_default();
+        templates.push(TemplateInfo { name, path: path.to_string_lossy().to_string(), source, variables: variables(&content) });
+    }
+    templates
+}
+
+/// Copies a document into `dir` as the template `name`, in the same format.
+pub fn save_as_template(dir: &Path, document: &Path, name: &str) -> AppResult<TemplateInfo> {
+    let name = sanitize_name(name);
+    if name.is_empty() {
+        return Err(AppError::validation("Template names need at least one letter or digit"));
+    }
+    if local_templates(dir).iter().any(|path| template_name(path).eq_ignore_ascii_case(&name)) {
+        return Err(AppError::conflict(format!("A template named {} already exists", name)));
+    }
+    let extension = document.extension().and_then(|e| e.to_str()).unwrap_or("md");
+    let dest = dir.join(format!("{}.{}", name, extension));
+    std::fs::copy(document, &dest).map_err(|e| AppError::io(format!("Failed to save template: {}", e)))?;
+
+    let content = document_format::read_document(&dest)?;
+    Ok(TemplateInfo { name, path: dest.to_string_lossy().to_string(), source: TemplateSource::Local, variables: variables(&content) })
+}
+
+/// Creates a document titled `title` in `dest_dir` from a template,
+/// numbering the file name if it's taken. Returns its path.
+pub fn create_from_template(template: &Path, dest_dir: &Path, title: &str, compress: bool) -> AppResult<PathBuf> {
+    let content = render(&document_format::read_document(template)?, title, &Local::now());
+    let name = sanitize_name(title);
+    let name = if name.is_empty() { "Untitled".to_string() } else { name };
+    let extension = template.extension().and_then(|e| e.to_str()).unwrap_or("md");
+
+    let mut path = dest_dir.join(format!("{}.{}", name, extension));
+    let mut n = 2;
+    while path.exists() {
+        path = dest_dir.join(format!("{} {}.{}", name, n, extension));
+        n += 1;
+    }
+    std::fs::create_dir_all(dest_dir).map_err(|e| AppError::io(format!("Failed to create {}: {}", dest_dir.display(), e)))?;
+    let bytes = document_format::encode(&path, &content, compress)?;
+    std::fs::write(&path, bytes).map_err(|e| AppError::io(format!("Failed to create {}: {}", path.display(), e)))?;
+    Ok(path)
+}
+
+/// The local and shared templates available.
+pub fn available_templates(app_handle: &AppHandle) -> AppResult<Vec<TemplateInfo>> {
+    Ok(list_templates(&local_templates(&get_templates_dir(app_handle)?), &shared_templates(app_handle)))
+}
+
+pub fn find_template(app_handle: &AppHandle, name: &str) -> AppResult<PathBuf> {
+    available_templates(app_handle)?
+        .into_iter()
+        .find(|template| template.name.eq_ignore_ascii_case(name))
+        .map(|template| PathBuf::from(template.path))
+        .ok_or_else(|| AppError::not_found(format!("No template named {}", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::fs;
+
+    #[test]
+    fn test_render() {
+        let now = Local.with_ymd_and_hms(2024, 3, 9, 14, 5, 0).unwrap();
+        assert_eq!(
+            render("# {{title}}\n{{ date }} {{time}} ({{date:%A}}) {{unknown}} {{date:%Q}} {{", "Standup", &now),
+            "# Standup\n2024-03-09 14:05 (Saturday) {{unknown}} {{date:%Q}} {{"
+        );
+
+        let canvas = r#"{"root":{"children":[{"type":"heading","children":[{"type":"text","text":"{{title}}"}]}]}}"#;
+        let rendered: Value = serde_json::from_str(&render(canvas, "The \"big\" one", &now)).unwrap();
+        assert_eq!(rendered["root"]["children"][0]["children"][0]["text"], "The \"big\" one");
+
+        assert_eq!(variables("{{title}} {{Date:%Y}} {{title}} {{project}}"), vec!["title", "date", "project"]);
+    }
+
+    #[test]
+    fn test_templates() {
+        let dir = std::env::temp_dir().join("test_templates");
+        let _ = fs::remove_dir_all(&dir);
+        let (local, shared, workspace) = (dir.join("templates"), dir.join("shared"), dir.join("workspace"));
+        fs::create_dir_all(&local).unwrap();
+        fs::create_dir_all(&shared).unwrap();
+        fs::create_dir_all(&workspace).unwrap();
+        let meeting = workspace.join("meeting.md");
+        fs::write(&meeting, "# {{title}}\nDate: {{date}}\n").unwrap();
+        fs::write(shared.join("Meeting.md"), "# Shared meeting").unwrap();
+        fs::write(shared.join("Weekly.md"), "# Week of {{date}}").unwrap();
+
+        let saved = save_as_template(&local, &meeting, "Meeting!").unwrap();
+        assert_eq!(saved.name, "Meeting");
+        assert_eq!(saved.variables, vec!["title", "date"]);
+        assert!(save_as_template(&local, &meeting, "meeting").is_err());
+
+        let templates = list_templates(&local_templates(&local), &local_templates(&shared));
+        let names: Vec<(&str, TemplateSource)> = templates.iter().map(|t| (t.name.as_str(), t.source)).collect();
+        assert_eq!(names, vec![("Meeting", TemplateSource::Local), ("Weekly", TemplateSource::Shared)]);
+
+        let first = create_from_template(Path::new(&saved.path), &workspace, "Team sync", false).unwrap();
+        let second = create_from_template(Path::new(&saved.path), &workspace, "Team sync", false).unwrap();
+        assert_eq!(first, workspace.join("Team sync.md"));
+        assert_eq!(second, workspace.join("Team sync 2.md"));
+        assert!(fs::read_to_string(&first).unwrap().starts_with("# Team sync\nDate: 20"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,71 @@
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+use std::path::{Path, PathBuf};
+use crate::document_version;
+use crate::error::{AppError, AppResult};
+
+pub const DEFAULT_THUMBNAIL_SIZE: u32 = 256;
+const MIN_THUMBNAIL_SIZE: u32 = 16;
+const MAX_THUMBNAIL_SIZE: u32 = 1024;
+
+fn get_cache_dir(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    let dir = app_data_dir.join("thumbnails");
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| AppError::io(format!("Failed to create thumbnail directory: {}", e)))?;
+
+    Ok(dir)
+}
+
+/// The cache file for a thumbnail. The source's mtime is part of the key,
+/// so an edited image gets a new thumbnail instead of a stale one.
+fn cache_name(source: &Path, modified: u64, max_size: u32) -> String {
+    let key = format!("{}\n{}\n{}", source.to_string_lossy(), modified, max_size);
+    format!("{}.png", &hex::encode(Sha256::digest(key.as_bytes()))[..32])
+}
+
+fn generate(source: &Path, dest: &Path, max_size: u32) -> AppResult<()> {
+    let image = image::open(source).map_err(|e| AppError::io(format!("Failed to make a thumbnail of {}: {}", source.display(), e)))?;
+    image
+        .thumbnail(max_size, max_size)
+        .save_with_format(dest, image::ImageFormat::Png)
+        .map_err(|e| AppError::io(format!("Failed to write thumbnail {}: {}", dest.display(), e)))
+}
+
+/// A PNG preview of `source` fitting in `max_size` pixels square, made on
+/// first request and read from the cache after that.
+pub fn get_thumbnail(app_handle: &AppHandle, source: &Path, max_size: Option<u32>) -> AppResult<Vec<u8>> {
+    let max_size = max_size.unwrap_or(DEFAULT_THUMBNAIL_SIZE).clamp(MIN_THUMBNAIL_SIZE, MAX_THUMBNAIL_SIZE);
+    let modified = document_version::modified_millis(source).ok_or_else(|| AppError::not_found(format!("{} doesn't exist", source.display())))?;
+
+    let dest = get_cache_dir(app_handle)?.join(cache_name(source, modified, max_size));
+    if !dest.exists() {
+        generate(source, &dest, max_size)?;
+    }
+    std::fs::read(&dest).map_err(|e| AppError::io(format!("Failed to read thumbnail {}: {}", dest.display(), e)))
+}
+
+/// Empties the thumbnail cache; thumbnails are made again as needed.
+pub fn clear_thumbnails(app_handle: &AppHandle) -> AppResult<()> {
+    let dir = get_cache_dir(app_handle)?;
+    std::fs::remove_dir_all(&dir).map_err(|e| AppError::io(format!("Failed to clear thumbnails: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_name() {
+        let photo = Path::new("/workspace/assets/photo.png");
+        let name = cache_name(photo, 1, 256);
+        assert_eq!(name, cache_name(photo, 1, 256));
+        assert_ne!(name, cache_name(photo, 2, 256));
+        assert_ne!(name, cache_name(photo, 1, 128));
+        assert!(name.ends_with(".png"));
+    }
+}
@@ -0,0 +1,85 @@
+use serde::Serialize;
+use tauri::WebviewWindow;
+use crate::error::{AppError, AppResult};
+
+/// Height of the custom titlebar in logical pixels.
+const TITLEBAR_HEIGHT: u32 = 32;
+
+/// Windows 11 reports itself as 10.0 with a build number from here on.
+#[cfg(target_os = "windows")]
+const WINDOWS_11_BUILD: u32 = 22000;
+
+/// What the frontend needs to draw its own titlebar when native
+/// decorations are off.
+#[derive(Debug, Clone, Serialize)]
+pub struct TitlebarConfig {
+    pub platform: &'static str,
+    pub height: u32,
+    /// "left" on macOS, "right" elsewhere
+    pub controls_side: &'static str,
+    /// Whether the frontend has to draw minimize/maximize/close itself
+    pub custom_controls: bool,
+    /// Whether hovering the maximize button should offer snap layouts
+    pub snap_layouts: bool,
+    /// Elements with this attribute move the window when dragged and
+    /// toggle maximize on double click
+    pub drag_region_attribute: &'static str,
+}
+
+pub fn titlebar_config(decorations: bool) -> TitlebarConfig {
+    let macos = cfg!(target_os = "macos");
+    TitlebarConfig {
+        platform: std::env::consts::OS,
+        height: TITLEBAR_HEIGHT,
+        controls_side: if macos { "left" } else { "right" },
+        // macOS keeps its traffic lights with an overlay titlebar
+        custom_controls: !decorations && !macos,
+        snap_layouts: !decorations && supports_snap_layouts(),
+        drag_region_attribute: "data-tauri-drag-region",
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn supports_snap_layouts() -> bool {
+    // `ver` prints e.g. "Microsoft Windows [Version 10.0.22631.4317]"
+    let output = match std::process::Command::new("cmd").args(["/C", "ver"]).output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).to_string(),
+        Err(_) => return false,
+    };
+    output
+        .split(|c: char| !c.is_ascii_digit() && c != '.')
+        .find(|part| part.starts_with("10.0."))
+        .and_then(|version| version.split('.').nth(2))
+        .and_then(|build| build.parse::<u32>().ok())
+        .is_some_and(|build| build >= WINDOWS_11_BUILD)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn supports_snap_layouts() -> bool {
+    false
+}
+
+/// Starts moving the window with the mouse, for titlebar elements that
+/// handle `mousedown` themselves instead of using the drag region attribute.
+pub fn start_drag(window: &WebviewWindow) -> AppResult<()> {
+    window
+        .start_dragging()
+        .map_err(|e| AppError::internal(format!("Failed to start window drag: {}", e)))
+}
+
+/// Maximizes or restores the window like a double click on a native
+/// titlebar. Returns whether the window is now maximized.
+pub fn toggle_maximize(window: &WebviewWindow) -> AppResult<bool> {
+    // Double clicking a fullscreen window's titlebar does nothing natively
+    if window.is_fullscreen()? {
+        return Ok(false);
+    }
+
+    let maximized = window.is_maximized()?;
+    if maximized {
+        window.unmaximize()?;
+    } else {
+        window.maximize()?;
+    }
+    Ok(!maximized)
+}
@@ -0,0 +1,232 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::{document_lock, sidecar};
+use crate::error::{AppError, AppResult};
+
+/// Kept out of the workspace so trashed documents don't turn up in
+/// searches or sync.
+const TRASH_DIR: &str = "trash";
+const INDEX_FILE: &str = "index.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub id: String,
+    /// File name of the document
+    pub name: String,
+    /// Where it's restored to
+    pub original_path: String,
+    pub trashed_at: DateTime<Utc>,
+    pub size: u64,
+}
+
+/// Held while `index.json` is read and rewritten, so concurrent trash
+/// operations don't drop each other's entries.
+#[derive(Default)]
+pub struct TrashState {
+    index: Mutex<()>,
+}
+
+pub fn get_trash_dir(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    let dir = app_data_dir.join(TRASH_DIR);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| AppError::io(format!("Failed to create trash directory: {}", e)))?;
+
+    Ok(dir)
+}
+
+/// Renames `from` to `to`, copying instead when they're on different
+/// drives.
+fn move_file(from: &Path, to: &Path) -> AppResult<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    std::fs::copy(from, to).map_err(|e| AppError::io(format!("Failed to move {}: {}", from.display(), e)))?;
+    std::fs::remove_file(from).map_err(|e| AppError::io(format!("Failed to move {}: {}", from.display(), e)))
+}
+
+fn read_index(trash_dir: &Path) -> AppResult<Vec<TrashEntry>> {
+    sidecar::read_json(&trash_dir.join(INDEX_FILE))
+}
+
+fn write_index(trash_dir: &Path, entries: &[TrashEntry]) -> AppResult<()> {
+    sidecar::write_json(&trash_dir.join(INDEX_FILE), &entries)
+}
+
+/// Moves a document and its sidecars into the trash. Its lock isn't kept,
+/// as it would be stale by the time the document is restored. A document
+/// another instance has open is refused.
+pub fn trash_document(state: &TrashState, trash_dir: &Path, path: &Path) -> AppResult<TrashEntry> {
+    let metadata = std::fs::metadata(path).map_err(|e| AppError::io(format!("Failed to trash {}: {}", path.display(), e)))?;
+    if !metadata.is_file() {
+        return Err(AppError::validation(format!("{} is not a document", path.display())));
+    }
+    if let Some(lock) = document_lock::foreign_lock(path) {
+        return Err(AppError::conflict(format!("{} is open on {}", path.display(), lock.hostname)));
+    }
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("document").to_string();
+    let original_path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let trashed_at = Utc::now();
+    let key = format!("{}\n{}", original_path.to_string_lossy(), trashed_at.timestamp_nanos_opt().unwrap_or_default());
+    let id = hex::encode(&Sha256::digest(key.as_bytes())[..8]);
+
+    let entry_dir = trash_dir.join(&id);
+    std::fs::create_dir_all(&entry_dir).map_err(|e| AppError::io(format!("Failed to create {}: {}", entry_dir.display(), e)))?;
+    move_file(path, &entry_dir.join(&name))?;
+    for (kind, sidecar_path) in sidecar::sidecars(path) {
+        if kind == "lock" {
+            let _ = std::fs::remove_file(&sidecar_path);
+        } else if let Some(file_name) = sidecar_path.file_name() {
+            move_file(&sidecar_path, &entry_dir.join(file_name))?;
+        }
+    }
+
+    let entry = TrashEntry {
+        id,
+        name,
+        original_path: original_path.to_string_lossy().to_string(),
+        trashed_at,
+        size: metadata.len(),
+    };
+    let _guard = state.index.lock()?;
+    let mut entries = read_index(trash_dir)?;
+    entries.push(entry.clone());
+    write_index(trash_dir, &entries)?;
+    Ok(entry)
+}
+
+/// Everything in the trash, most recently trashed first.
+pub fn list_trash(trash_dir: &Path) -> AppResult<Vec<TrashEntry>> {
+    let mut entries = read_index(trash_dir)?;
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.trashed_at));
+    Ok(entries)
+}
+
+/// Where to restore to: the original path, or `name 2.ext` and so on when
+/// another document has taken its place since.
+fn restore_target(original: &Path) -> PathBuf {
+    if !original.exists() {
+        return original.to_path_buf();
+    }
+    let stem = original.file_stem().and_then(|s| s.to_str()).unwrap_or("document");
+    let extension = original.extension().and_then(|e| e.to_str()).map(|e| format!(".{}", e)).unwrap_or_default();
+    let mut n = 2;
+    loop {
+        let candidate = original.with_file_name(format!("{} {}{}", stem, n, extension));
+        if !candidate.exists() {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+/// Puts a trashed document and its sidecars back, recreating its folder if
+/// needed. Returns where it was restored to.
+pub fn restore_from_trash(state: &TrashState, trash_dir: &Path, id: &str) -> AppResult<PathBuf> {
+    let _guard = state.index.lock()?;
+    let mut entries = read_index(trash_dir)?;
+    let index = entries
+        .iter()
+        .position(|entry| entry.id == id)
+        .ok_or_else(|| AppError::not_found(format!("Nothing in the trash with id {}", id)))?;
+    let entry = &entries[index];
+
+    let target = restore_target(Path::new(&entry.original_path));
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| AppError::io(format!("Failed to create {}: {}", parent.display(), e)))?;
+    }
+    let entry_dir = trash_dir.join(&entry.id);
+    move_file(&entry_dir.join(&entry.name), &target)?;
+    for (kind, sidecar_path) in sidecar::sidecars(&entry_dir.join(&entry.name)) {
+        move_file(&sidecar_path, &sidecar::sidecar_path(&target, &kind))?;
+    }
+    let _ = std::fs::remove_dir_all(&entry_dir);
+
+    entries.remove(index);
+    write_index(trash_dir, &entries)?;
+    Ok(target)
+}
+
+/// Permanently deletes what's in the trash, or only what was trashed more
+/// than `older_than_days` ago. Returns how many documents were deleted.
+pub fn empty_trash(state: &TrashState, trash_dir: &Path, older_than_days: Option<i64>) -> AppResult<usize> {
+    let _guard = state.index.lock()?;
+    let cutoff = older_than_days.map(|days| Utc::now() - chrono::Duration::days(days));
+    let (expired, kept): (Vec<TrashEntry>, Vec<TrashEntry>) = read_index(trash_dir)?
+        .into_iter()
+        .partition(|entry| cutoff.is_none_or(|cutoff| entry.trashed_at < cutoff));
+
+    for entry in &expired {
+        let entry_dir = trash_dir.join(&entry.id);
+        if entry_dir.exists() {
+            std::fs::remove_dir_all(&entry_dir).map_err(|e| AppError::io(format!("Failed to delete {}: {}", entry.name, e)))?;
+        }
+    }
+    write_index(trash_dir, &kept)?;
+    Ok(expired.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_trash_and_restore() {
+        let dir = std::env::temp_dir().join("test_trash");
+        let _ = fs::remove_dir_all(&dir);
+        let (workspace, trash_dir) = (dir.join("workspace/notes"), dir.join("trash"));
+        fs::create_dir_all(&workspace).unwrap();
+        fs::create_dir_all(&trash_dir).unwrap();
+        let state = TrashState::default();
+
+        let plan = workspace.join("plan.md");
+        fs::write(&plan, "# Plan").unwrap();
+        fs::write(sidecar::sidecar_path(&plan, "comments"), "[]").unwrap();
+        fs::write(sidecar::sidecar_path(&plan, "lock"), "{}").unwrap();
+        // Another document's sidecar that only looks similar
+        fs::write(workspace.join(".plan.md.bak.meta.json"), "{}").unwrap();
+
+        let entry = trash_document(&state, &trash_dir, &plan).unwrap();
+        assert!(!plan.exists());
+        assert!(!sidecar::sidecar_path(&plan, "comments").exists());
+        assert!(!sidecar::sidecar_path(&plan, "lock").exists());
+        assert!(workspace.join(".plan.md.bak.meta.json").exists());
+        assert_eq!(list_trash(&trash_dir).unwrap(), vec![entry.clone()]);
+
+        // Restoring after the folder was removed and a new plan.md was made
+        fs::remove_dir_all(&workspace).unwrap();
+        fs::create_dir_all(&workspace).unwrap();
+        fs::write(&plan, "# New plan").unwrap();
+        let restored = restore_from_trash(&state, &trash_dir, &entry.id).unwrap();
+        assert_eq!(restored, workspace.join("plan 2.md"));
+        assert_eq!(fs::read_to_string(&restored).unwrap(), "# Plan");
+        assert!(sidecar::sidecar_path(&restored, "comments").exists());
+        assert!(list_trash(&trash_dir).unwrap().is_empty());
+        assert!(restore_from_trash(&state, &trash_dir, &entry.id).is_err());
+
+        trash_document(&state, &trash_dir, &plan).unwrap();
+        assert_eq!(empty_trash(&state, &trash_dir, Some(30)).unwrap(), 0);
+        assert_eq!(empty_trash(&state, &trash_dir, None).unwrap(), 1);
+        assert!(list_trash(&trash_dir).unwrap().is_empty());
+
+        // A document open in another instance stays where it is
+        fs::write(&plan, "# Plan").unwrap();
+        let now = Utc::now();
+        let lock = serde_json::json!({ "pid": 1, "hostname": "elsewhere", "acquired_at": now, "refreshed_at": now });
+        fs::write(sidecar::sidecar_path(&plan, "lock"), lock.to_string()).unwrap();
+        assert!(matches!(trash_document(&state, &trash_dir, &plan), Err(AppError::Conflict(_))));
+        assert!(plan.exists());
+        assert!(list_trash(&trash_dir).unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
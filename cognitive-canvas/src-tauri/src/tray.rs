@@ -0,0 +1,49 @@
+use tauri::menu::{Menu, MenuItem, PredefinedMenuItem};
+use tauri::tray::TrayIconBuilder;
+use tauri::{AppHandle, Emitter, Manager};
+use crate::cli;
+use crate::error::AppResult;
+
+pub const TRAY_ID: &str = "main";
+
+/// Adds the tray icon. Every menu choice is sent as "tray-action" with its
+/// id; new documents and quick capture are left to the frontend.
+pub fn build(app_handle: &AppHandle) -> AppResult<()> {
+    let menu = Menu::with_items(
+        app_handle,
+        &[
+            &MenuItem::with_id(app_handle, "new_document", "New Document", true, None::<&str>)?,
+            &MenuItem::with_id(app_handle, "quick_capture", "Quick Capture", true, None::<&str>)?,
+            &PredefinedMenuItem::separator(app_handle)?,
+            &MenuItem::with_id(app_handle, "toggle_window", "Show/Hide Window", true, None::<&str>)?,
+            &PredefinedMenuItem::separator(app_handle)?,
+            &MenuItem::with_id(app_handle, "quit", "Quit", true, None::<&str>)?,
+        ],
+    )?;
+
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .menu(&menu)
+        .tooltip(app_handle.package_info().name.clone())
+        .on_menu_event(|app, event| trigger(app, event.id().as_ref()));
+    if let Some(icon) = app_handle.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app_handle)?;
+    Ok(())
+}
+
+fn trigger(app_handle: &AppHandle, action: &str) {
+    let _ = app_handle.emit("tray-action", action);
+    match action {
+        // The menu has focus by now, so only visibility says whether the
+        // window is showing
+        "toggle_window" => match app_handle.get_webview_window("main") {
+            Some(window) if window.is_visible().unwrap_or(false) => {
+                let _ = window.hide();
+            }
+            _ => cli::focus_main_window(app_handle),
+        },
+        "quit" => app_handle.exit(0),
+        _ => cli::focus_main_window(app_handle),
+    }
+}
@@ -0,0 +1,156 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use crate::sidecar;
+use crate::error::{AppError, AppResult};
+
+/// Oldest batches are dropped past either limit.
+const MAX_BATCHES: usize = 500;
+const MAX_BYTES: usize = 1024 * 1024;
+
+/// One undoable step as the editor recorded it. `ops` is whatever the
+/// frontend needs to reverse and replay the step; it isn't looked into.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UndoBatch {
+    pub ops: Value,
+    /// Shown in the Edit menu, like "Typing" or "Delete node"
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default = "Utc::now")]
+    pub timestamp: DateTime<Utc>,
+}
+
+/// What's kept in the `.undo.json` sidecar, most recent batch last.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UndoHistory {
+    pub undo: Vec<UndoBatch>,
+    pub redo: Vec<UndoBatch>,
+}
+
+impl UndoHistory {
+    fn size(&self) -> usize {
+        self.undo.iter().chain(&self.redo).map(|batch| batch.ops.to_string().len()).sum()
+    }
+
+    /// Drops the oldest undo batches, then the furthest redo ones, until
+    /// the history fits the caps.
+    fn trim(&mut self) {
+        let mut size = self.size();
+        while self.undo.len() + self.redo.len() > MAX_BATCHES || size > MAX_BYTES {
+            let dropped = if self.undo.is_empty() { self.redo.remove(0) } else { self.undo.remove(0) };
+            size -= dropped.ops.to_string().len();
+        }
+    }
+}
+
+fn sidecar_path(document_path: &Path) -> PathBuf {
+    sidecar::sidecar_path(document_path, "undo")
+}
+
+pub fn get_undo_history(document_path: &Path) -> AppResult<UndoHistory> {
+    sidecar::read_json(&sidecar_path(document_path))
+}
+
+/// Records a new step. Like in any editor, this forgets what could be
+/// redone.
+pub fn push_undo_batch(document_path: &Path, batch: UndoBatch) -> AppResult<()> {
+    let path = sidecar_path(document_path);
+    let mut history: UndoHistory = sidecar::read_json(&path)?;
+    history.undo.push(batch);
+    history.redo.clear();
+    history.trim();
+    sidecar::write_json(&path, &history)
+}
+
+/// Moves the latest undo batch to the redo stack and returns it for the
+/// editor to reverse, or None when there's nothing to undo.
+pub fn undo(document_path: &Path) -> AppResult<Option<UndoBatch>> {
+    move_batch(document_path, true)
+}
+
+/// The opposite of `undo`.
+pub fn redo(document_path: &Path) -> AppResult<Option<UndoBatch>> {
+    move_batch(document_path, false)
+}
+
+fn move_batch(document_path: &Path, undo: bool) -> AppResult<Option<UndoBatch>> {
+    let path = sidecar_path(document_path);
+    let mut history: UndoHistory = sidecar::read_json(&path)?;
+    let (from, to) = if undo { (&mut history.undo, &mut history.redo) } else { (&mut history.redo, &mut history.undo) };
+    let Some(batch) = from.pop() else {
+        return Ok(None);
+    };
+    to.push(batch.clone());
+    sidecar::write_json(&path, &history)?;
+    Ok(Some(batch))
+}
+
+/// For when the document changed outside the editor and the recorded
+/// steps no longer apply.
+pub fn clear_undo_history(document_path: &Path) -> AppResult<()> {
+    let path = sidecar_path(document_path);
+    if path.exists() {
+        std::fs::remove_file(&path).map_err(|e| AppError::io(format!("Failed to remove {}: {}", path.display(), e)))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn batch(text: &str) -> UndoBatch {
+        UndoBatch { ops: serde_json::json!([{ "insert": text }]), label: Some("Typing".to_string()), timestamp: Utc::now() }
+    }
+
+    #[test]
+    fn test_undo_history() {
+        let dir = std::env::temp_dir().join("test_undo_history");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let document = dir.join("notes.canvas");
+        fs::write(&document, "{}").unwrap();
+
+        assert_eq!(get_undo_history(&document).unwrap(), UndoHistory::default());
+        assert_eq!(undo(&document).unwrap(), None);
+
+        push_undo_batch(&document, batch("a")).unwrap();
+        push_undo_batch(&document, batch("b")).unwrap();
+        assert_eq!(undo(&document).unwrap().unwrap().ops, batch("b").ops);
+        let history = get_undo_history(&document).unwrap();
+        assert_eq!((history.undo.len(), history.redo.len()), (1, 1));
+
+        assert_eq!(redo(&document).unwrap().unwrap().ops, batch("b").ops);
+        undo(&document).unwrap();
+        // A new step after undoing forgets the redo stack
+        push_undo_batch(&document, batch("c")).unwrap();
+        let history = get_undo_history(&document).unwrap();
+        let ops: Vec<&Value> = history.undo.iter().map(|b| &b.ops).collect();
+        assert_eq!(ops, vec![&batch("a").ops, &batch("c").ops]);
+        assert!(history.redo.is_empty());
+
+        clear_undo_history(&document).unwrap();
+        assert!(!sidecar_path(&document).exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_trim() {
+        let mut history = UndoHistory::default();
+        for i in 0..MAX_BATCHES + 10 {
+            history.undo.push(batch(&i.to_string()));
+        }
+        history.trim();
+        assert_eq!(history.undo.len(), MAX_BATCHES);
+        assert_eq!(history.undo[0].ops, batch("10").ops);
+
+        let big = "x".repeat(MAX_BYTES / 3);
+        let mut history = UndoHistory { undo: vec![batch(&big), batch(&big)], redo: vec![batch(&big), batch("small")] };
+        history.trim();
+        assert_eq!(history.undo.len(), 1);
+        assert_eq!(history.redo.len(), 2);
+    }
+}
@@ -0,0 +1,306 @@
+use async_trait::async_trait;
+use reqwest::{Client, Method, StatusCode};
+use crate::sync_provider::{decode_xml_entities, encode_segment, extract_elements, RemoteEntry, SyncProvider};
+use crate::error::{AppError, AppResult};
+
+#[derive(Debug, Clone)]
+pub struct WebDavClient {
+    base_url: String,
+    username: String,
+    password: String,
+    http: Client,
+}
+
+impl WebDavClient {
+    pub fn new(base_url: &str, username: &str, password: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            http: Client::new(),
+        }
+    }
+
+    fn url_for(&self, path: &str) -> String {
+        let encoded = path
+            .trim_start_matches('/')
+            .split('/')
+            .map(encode_segment)
+            .collect::<Vec<_>>()
+            .join("/");
+        format!("{}/{}", self.base_url, encoded)
+    }
+
+    fn request(&self, method: Method, path: &str) -> reqwest::RequestBuilder {
+        self.http
+            .request(method, self.url_for(path))
+            .basic_auth(&self.username, Some(&self.password))
+    }
+
+    /// Walks `dir` one level at a time since many servers (Nextcloud
+    /// included) reject `Depth: infinity`.
+    pub async fn list_recursive(&self, dir: &str) -> AppResult<Vec<RemoteEntry>> {
+        let mut files = Vec::new();
+        let mut pending = vec![dir.trim_matches('/').to_string()];
+
+        while let Some(current) = pending.pop() {
+            for entry in self.list(&current).await? {
+                if entry.is_dir {
+                    pending.push(entry.path);
+                } else {
+                    files.push(entry);
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Lists the direct children of `dir`. Returned paths are relative to the base URL.
+    pub async fn list(&self, dir: &str) -> AppResult<Vec<RemoteEntry>> {
+        let body = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:getetag/><d:resourcetype/></d:prop></d:propfind>"#;
+
+        let method = Method::from_bytes(b"PROPFIND").map_err(|e| AppError::internal(e.to_string()))?;
+        let response = self
+            .request(method, &format!("{}/", dir.trim_matches('/')))
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| AppError::network(format!("WebDAV request failed: {}", e)))?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        if !response.status().is_success() {
+            return Err(AppError::network(format!("WebDAV listing failed: {}", response.status())));
+        }
+
+        let xml = response
+            .text()
+            .await
+            .map_err(|e| AppError::network(format!("Failed to read WebDAV response: {}", e)))?;
+
+        Ok(parse_propfind(&xml, &url_path(&self.base_url), dir))
+    }
+
+    pub async fn get(&self, path: &str) -> AppResult<Vec<u8>> {
+        let response = self
+            .request(Method::GET, path)
+            .send()
+            .await
+            .map_err(|e| AppError::network(format!("WebDAV request failed: {}", e)))?;
+
+        if !response.status().is_success() {
+            return Err(AppError::network(format!("Failed to download {}: {}", path, response.status())));
+        }
+
+        response
+            .bytes()
+            .await
+            .map(|b| b.to_vec())
+            .map_err(|e| AppError::network(format!("Failed to download {}: {}", path, e)))
+    }
+
+    /// Uploads `contents`, returning the new ETag if the server reported one.
+    pub async fn put(&self, path: &str, contents: Vec<u8>) -> AppResult<Option<String>> {
+        let response = self
+            .request(Method::PUT, path)
+            .body(contents)
+            .send()
+            .await
+            .map_err(|e| AppError::network(format!("WebDAV request failed: {}", e)))?;
+
+        if !response.status().is_success() {
+            return Err(AppError::network(format!("Failed to upload {}: {}", path, response.status())));
+        }
+
+        Ok(response
+            .headers()
+            .get("etag")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim_matches('"').to_string()))
+    }
+
+    pub async fn delete(&self, path: &str) -> AppResult<()> {
+        let response = self
+            .request(Method::DELETE, path)
+            .send()
+            .await
+            .map_err(|e| AppError::network(format!("WebDAV request failed: {}", e)))?;
+
+        let status = response.status();
+        if !status.is_success() && status != StatusCode::NOT_FOUND {
+            return Err(AppError::network(format!("Failed to delete {}: {}", path, status)));
+        }
+        Ok(())
+    }
+
+    /// Creates `dir` and any missing parents.
+    pub async fn mkcol_all(&self, dir: &str) -> AppResult<()> {
+        let mut current = String::new();
+        for segment in dir.split('/').filter(|s| !s.is_empty()) {
+            if !current.is_empty() {
+                current.push('/');
+            }
+            current.push_str(segment);
+
+            let method = Method::from_bytes(b"MKCOL").map_err(|e| AppError::internal(e.to_string()))?;
+            let response = self
+                .request(method, &format!("{}/", current))
+                .send()
+                .await
+                .map_err(|e| AppError::network(format!("WebDAV request failed: {}", e)))?;
+
+            // 405 means the collection already exists
+            let status = response.status();
+            if !status.is_success() && status != StatusCode::METHOD_NOT_ALLOWED {
+                return Err(AppError::io(format!("Failed to create remote folder {}: {}", current, status)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SyncProvider for WebDavClient {
+    async fn list_files(&self, dir: &str) -> AppResult<Vec<RemoteEntry>> {
+        self.list_recursive(dir).await
+    }
+
+    async fn download(&self, path: &str) -> AppResult<Vec<u8>> {
+        self.get(path).await
+    }
+
+    async fn upload(&self, path: &str, contents: Vec<u8>) -> AppResult<Option<String>> {
+        self.put(path, contents).await
+    }
+
+    async fn delete(&self, path: &str) -> AppResult<()> {
+        WebDavClient::delete(self, path).await
+    }
+
+    async fn ensure_dir(&self, dir: &str) -> AppResult<()> {
+        self.mkcol_all(dir).await
+    }
+}
+
+/// The entries of a PROPFIND response for `dir`, relative to `base_path`.
+/// Hrefs may be percent-encoded or not depending on the server, so both
+/// sides are decoded before they're compared.
+fn parse_propfind(xml: &str, base_path: &str, dir: &str) -> Vec<RemoteEntry> {
+    let base_path = decode_path(base_path);
+    let requested = format!("{}/{}", base_path, dir.trim_matches('/'))
+        .trim_end_matches('/')
+        .to_string();
+
+    let mut entries = Vec::new();
+    for response_xml in extract_elements(xml, "response") {
+        let href = match extract_elements(&response_xml, "href").into_iter().next() {
+            Some(href) => decode_path(&url_path(decode_xml_entities(href.trim()).as_str())),
+            None => continue,
+        };
+        let href = href.trim_end_matches('/');
+
+        // The first entry is the collection itself
+        if href == requested {
+            continue;
+        }
+        let Some(relative) = href.strip_prefix(base_path.as_str()).and_then(|rest| rest.strip_prefix('/')) else {
+            continue;
+        };
+
+        let is_dir = extract_elements(&response_xml, "resourcetype")
+            .iter()
+            .any(|r| !extract_elements(r, "collection").is_empty());
+        let etag = extract_elements(&response_xml, "getetag")
+            .into_iter()
+            .next()
+            .map(|e| decode_xml_entities(e.trim()).trim_matches('"').to_string());
+
+        entries.push(RemoteEntry { path: relative.to_string(), etag, is_dir });
+    }
+    entries
+}
+
+/// Strips scheme and host from a URL, leaving just the path.
+fn url_path(url: &str) -> String {
+    let without_scheme = match url.find("://") {
+        Some(pos) => &url[pos + 3..],
+        None => return url.trim_end_matches('/').to_string(),
+    };
+    match without_scheme.find('/') {
+        Some(pos) => without_scheme[pos..].trim_end_matches('/').to_string(),
+        None => String::new(),
+    }
+}
+
+fn decode_path(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LISTING: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:multistatus xmlns:d="DAV:">
+  <d:response>
+    <d:href>/remote.php/dav/files/J%C3%BCrgen%20K/My%20Notes/</d:href>
+    <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>https://cloud.example.com/remote.php/dav/files/J%C3%BCrgen%20K/My%20Notes/Q%26A.md</d:href>
+    <d:propstat><d:prop><d:getetag>&quot;5f2a&quot;</d:getetag><d:resourcetype/></d:prop></d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/remote.php/dav/files/J%C3%BCrgen%20K/My%20Notes/Drafts/</d:href>
+    <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop></d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>/remote.php/dav/files/J%C3%BCrgen%20K/Other/x.md</d:href>
+  </d:response>
+</d:multistatus>"#;
+
+    #[test]
+    fn test_parse_propfind() {
+        // The same folder whether the base URL was entered encoded or not
+        for base_url in ["https://cloud.example.com/remote.php/dav/files/Jürgen K", "https://cloud.example.com/remote.php/dav/files/J%C3%BCrgen%20K/"] {
+            let entries = parse_propfind(LISTING, &url_path(base_url), "My Notes");
+            let paths: Vec<(&str, bool)> = entries.iter().map(|entry| (entry.path.as_str(), entry.is_dir)).collect();
+            assert_eq!(paths, vec![("My Notes/Q&A.md", false), ("My Notes/Drafts", true), ("Other/x.md", false)], "{}", base_url);
+            assert_eq!(entries[0].etag.as_deref(), Some("5f2a"));
+            assert_eq!(entries[1].etag, None);
+        }
+    }
+
+    #[test]
+    fn test_parse_propfind_outside_base() {
+        let entries = parse_propfind(LISTING, "/remote.php/dav/files/J%C3%BCrgen", "");
+        assert!(entries.is_empty());
+    }
+
+    #[test]
+    fn test_url_path() {
+        assert_eq!(url_path("https://dav.example.com/webdav/"), "/webdav");
+        assert_eq!(url_path("https://dav.example.com"), "");
+        assert_eq!(decode_path("/a%20b/%E2%9C%93/100%"), "/a b/✓/100%");
+    }
+}
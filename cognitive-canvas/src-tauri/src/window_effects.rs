@@ -0,0 +1,188 @@
+use tauri::WebviewWindow;
+use crate::settings_manager::Settings;
+use crate::error::{AppError, AppResult};
+#[cfg(target_os = "macos")]
+use crate::settings_manager::Vibrancy;
+
+/// Sets the opacity of the whole window, 0.0 being invisible. Tauri has no
+/// cross-platform API for this, so each platform's native window is used.
+#[cfg(target_os = "macos")]
+pub fn set_opacity(window: &WebviewWindow, opacity: f64) -> AppResult<()> {
+    use objc::{msg_send, sel, sel_impl};
+
+    let ns_window = window.ns_window()? as *mut objc::runtime::Object;
+    let window = window.clone();
+    // AppKit may only be touched from the main thread
+    window
+        .run_on_main_thread(move || unsafe {
+            let _: () = msg_send![ns_window, setAlphaValue: opacity];
+        })
+        .map_err(AppError::from)
+}
+
+#[cfg(target_os = "windows")]
+pub fn set_opacity(window: &WebviewWindow, opacity: f64) -> AppResult<()> {
+    use windows::Win32::Foundation::{COLORREF, HWND};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        GetWindowLongPtrW, SetLayeredWindowAttributes, SetWindowLongPtrW, GWL_EXSTYLE, LWA_ALPHA,
+        WS_EX_LAYERED,
+    };
+
+    let hwnd = HWND(window.hwnd()?.0);
+    unsafe {
+        // Only layered windows can be translucent
+        let style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+        SetWindowLongPtrW(hwnd, GWL_EXSTYLE, style | WS_EX_LAYERED.0 as isize);
+        SetLayeredWindowAttributes(hwnd, COLORREF(0), (opacity * 255.0).round() as u8, LWA_ALPHA)
+            .map_err(|e| AppError::internal(format!("Failed to set window opacity: {}", e)))
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+pub fn set_opacity(window: &WebviewWindow, opacity: f64) -> AppResult<()> {
+    use gtk::prelude::WidgetExt;
+
+    // GTK may only be touched from the main thread, and its windows can't
+    // be sent there, so it's looked up once there
+    let handle = window.clone();
+    window
+        .run_on_main_thread(move || {
+            if let Ok(gtk_window) = handle.gtk_window() {
+                gtk_window.set_opacity(opacity);
+            }
+        })
+        .map_err(AppError::from)
+}
+
+#[cfg(not(any(
+    target_os = "macos",
+    target_os = "windows",
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+pub fn set_opacity(_window: &WebviewWindow, _opacity: f64) -> AppResult<()> {
+    Ok(())
+}
+
+/// Applies the macOS titlebar style, traffic light position and vibrancy
+/// from `settings`. Does nothing on other platforms.
+#[cfg(target_os = "macos")]
+pub fn apply_macos_style(window: &WebviewWindow, settings: &Settings) -> AppResult<()> {
+    use tauri::window::{Effect, EffectsBuilder};
+    use tauri::{Manager, TitleBarStyle};
+
+    let style = if settings.macos_transparent_titlebar {
+        TitleBarStyle::Overlay
+    } else {
+        TitleBarStyle::Visible
+    };
+    window.set_title_bar_style(style)?;
+
+    let effect = match settings.macos_vibrancy {
+        Vibrancy::None => None,
+        Vibrancy::Sidebar => Some(Effect::Sidebar),
+        Vibrancy::Titlebar => Some(Effect::Titlebar),
+        Vibrancy::Menu => Some(Effect::Menu),
+        Vibrancy::Popover => Some(Effect::Popover),
+        Vibrancy::HudWindow => Some(Effect::HudWindow),
+        Vibrancy::WindowBackground => Some(Effect::WindowBackground),
+        Vibrancy::ContentBackground => Some(Effect::ContentBackground),
+        Vibrancy::UnderWindowBackground => Some(Effect::UnderWindowBackground),
+    };
+    window
+        .set_effects(effect.map(|effect| EffectsBuilder::new().effect(effect).build()))
+        .map_err(|e| AppError::internal(format!("Failed to set window vibrancy: {}", e)))?;
+
+    let ns_window = window.ns_window()?;
+    move_traffic_lights(window.app_handle(), ns_window as usize, settings)
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn apply_macos_style(_window: &WebviewWindow, _settings: &Settings) -> AppResult<()> {
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct NSPoint {
+    x: f64,
+    y: f64,
+}
+
+#[cfg(target_os = "macos")]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct NSSize {
+    width: f64,
+    height: f64,
+}
+
+#[cfg(target_os = "macos")]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct NSRect {
+    origin: NSPoint,
+    size: NSSize,
+}
+
+/// Moves the close/minimize/zoom buttons to the inset from `settings`, if
+/// one is set. Does nothing on other platforms.
+#[cfg(target_os = "macos")]
+pub fn position_traffic_lights(window: &tauri::Window, settings: &Settings) -> AppResult<()> {
+    use tauri::Manager;
+
+    let ns_window = window.ns_window()?;
+    move_traffic_lights(window.app_handle(), ns_window as usize, settings)
+}
+
+/// Raw pointers aren't Send, so the window travels to the main thread as an
+/// address.
+#[cfg(target_os = "macos")]
+fn move_traffic_lights(app_handle: &tauri::AppHandle, ns_window: usize, settings: &Settings) -> AppResult<()> {
+    use objc::runtime::Object;
+    use objc::{msg_send, sel, sel_impl};
+
+    let (Some(x), Some(y)) = (settings.macos_traffic_light_x, settings.macos_traffic_light_y) else {
+        return Ok(());
+    };
+
+    app_handle
+        .run_on_main_thread(move || unsafe {
+            let ns_window = ns_window as *mut Object;
+            // NSWindowCloseButton, NSWindowMiniaturizeButton, NSWindowZoomButton
+            let close: *mut Object = msg_send![ns_window, standardWindowButton: 0u64];
+            let miniaturize: *mut Object = msg_send![ns_window, standardWindowButton: 1u64];
+            let zoom: *mut Object = msg_send![ns_window, standardWindowButton: 2u64];
+            if close.is_null() || miniaturize.is_null() || zoom.is_null() {
+                return;
+            }
+
+            // Grow the titlebar container so the buttons aren't clipped
+            let superview: *mut Object = msg_send![close, superview];
+            let container: *mut Object = msg_send![superview, superview];
+            let close_frame: NSRect = msg_send![close, frame];
+            let window_frame: NSRect = msg_send![ns_window, frame];
+            let mut container_frame: NSRect = msg_send![container, frame];
+            container_frame.size.height = close_frame.size.height + y;
+            container_frame.origin.y = window_frame.size.height - container_frame.size.height;
+            let _: () = msg_send![container, setFrame: container_frame];
+
+            let miniaturize_frame: NSRect = msg_send![miniaturize, frame];
+            let spacing = miniaturize_frame.origin.x - close_frame.origin.x;
+            for (i, button) in [close, miniaturize, zoom].into_iter().enumerate() {
+                let mut frame: NSRect = msg_send![button, frame];
+                frame.origin.x = x + i as f64 * spacing;
+                let _: () = msg_send![button, setFrameOrigin: frame.origin];
+            }
+        })
+        .map_err(AppError::from)
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn position_traffic_lights(_window: &tauri::Window, _settings: &Settings) -> AppResult<()> {
+    Ok(())
+}
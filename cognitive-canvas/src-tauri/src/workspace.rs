@@ -0,0 +1,288 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::ignore_rules::{IgnoreRules, IGNORE_FILES};
+use crate::sidecar;
+use crate::error::{AppError, AppResult};
+use crate::operations::Operation;
+
+pub const DOCUMENT_EXTENSIONS: &[&str] = &["canvas", "canvasb", "md", "markdown", "txt"];
+
+/// Upper bound on directories or documents read at the same time, so a
+/// huge workspace on a slow or network disk isn't flooded with requests.
+const SCAN_CONCURRENCY: usize = 8;
+
+/// Directories changed this recently aren't cached: a second change within
+/// the mtime resolution would otherwise go unnoticed.
+const CACHE_SETTLE_TIME: Duration = Duration::from_secs(2);
+
+pub fn is_document(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| DOCUMENT_EXTENSIONS.contains(&e))
+}
+
+/// Bumped when listings start keeping something new, so old caches are
+/// read again.
+const CACHE_VERSION: u32 = 1;
+
+/// The listing of one directory as of its mtime. A directory's mtime
+/// changes whenever entries are added, removed or renamed in it, so an
+/// unchanged mtime means the listing can be reused without reading it.
+/// Ignore rules are applied to it afterwards, as editing an ignore file
+/// doesn't change the mtime.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedDir {
+    modified: u64,
+    documents: Vec<PathBuf>,
+    subdirs: Vec<PathBuf>,
+    /// Whether the directory has a `.canvasignore` or `.gitignore`
+    #[serde(default)]
+    has_ignore_file: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanCache {
+    #[serde(default)]
+    version: u32,
+    dirs: HashMap<PathBuf, CachedDir>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ScanBatch<'a> {
+    root: &'a Path,
+    documents: &'a [PathBuf],
+}
+
+pub(crate) fn scan_pool() -> &'static rayon::ThreadPool {
+    static POOL: OnceLock<rayon::ThreadPool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(SCAN_CONCURRENCY)
+            .thread_name(|i| format!("workspace-scan-{}", i))
+            .build()
+            .expect("failed to build workspace scan pool")
+    })
+}
+
+fn modified_millis(metadata: &std::fs::Metadata) -> Option<u64> {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+}
+
+fn read_dir_listing(dir: &Path, cache: &ScanCache) -> AppResult<(CachedDir, bool)> {
+    let metadata = std::fs::metadata(dir)
+        .map_err(|e| AppError::io(format!("Failed to read {}: {}", dir.display(), e)))?;
+    let modified = modified_millis(&metadata);
+
+    if let (Some(modified), Some(cached)) = (modified, cache.dirs.get(dir)) {
+        if cached.modified == modified {
+            return Ok((cached.clone(), true));
+        }
+    }
+
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| AppError::io(format!("Failed to read {}: {}", dir.display(), e)))?;
+
+    let mut listing = CachedDir {
+        modified: modified.unwrap_or(0),
+        documents: Vec::new(),
+        subdirs: Vec::new(),
+        has_ignore_file: false,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            listing.subdirs.push(path);
+        } else if is_document(&path) {
+            listing.documents.push(path);
+        } else if IGNORE_FILES.iter().any(|name| entry.file_name() == *name) {
+            listing.has_ignore_file = true;
+        }
+    }
+
+    let settled = metadata
+        .modified()
+        .ok()
+        .and_then(|t| SystemTime::now().duration_since(t).ok())
+        .is_some_and(|age| age >= CACHE_SETTLE_TIME);
+
+    Ok((listing, settled))
+}
+
+/// Lists every document below `root`, reading directories in parallel one
+/// level at a time. `on_batch` receives the documents of each level as soon
+/// as it's done. Listings in `cache` whose directory is unchanged are
+/// reused, and `cache` is updated with what was read. What the default
+/// ignore rules and the ignore files below `root` exclude is skipped.
+pub fn scan<F>(root: &Path, cache: &mut ScanCache, on_batch: F) -> AppResult<Vec<PathBuf>>
+where
+    F: FnMut(&[PathBuf]),
+{
+    scan_from(root, IgnoreRules::default(), cache, on_batch)
+}
+
+/// `scan` starting with `rules`, e.g. those of the folders above `root`.
+pub fn scan_from<F>(root: &Path, rules: IgnoreRules, cache: &mut ScanCache, mut on_batch: F) -> AppResult<Vec<PathBuf>>
+where
+    F: FnMut(&[PathBuf]),
+{
+    if cache.version != CACHE_VERSION {
+        *cache = ScanCache { version: CACHE_VERSION, ..Default::default() };
+    }
+
+    let mut documents = Vec::new();
+    let mut visited = HashMap::new();
+    let mut level = vec![(root.to_path_buf(), Arc::new(rules))];
+
+    while !level.is_empty() {
+        let results: Vec<_> = scan_pool().install(|| {
+            level
+                .par_iter()
+                .map(|(dir, rules)| {
+                    read_dir_listing(dir, cache).map(|(listing, cacheable)| (dir.clone(), rules.clone(), listing, cacheable))
+                })
+                .collect()
+        });
+
+        let mut next_level = Vec::new();
+        let mut batch = Vec::new();
+        for result in results {
+            let (dir, mut rules, listing, cacheable) = result?;
+            if listing.has_ignore_file {
+                Arc::make_mut(&mut rules).read_dir_files(&dir);
+            }
+            batch.extend(listing.documents.iter().filter(|path| !rules.is_ignored(path, false)).cloned());
+            next_level.extend(
+                listing
+                    .subdirs
+                    .iter()
+                    .filter(|path| !rules.is_ignored(path, true))
+                    .map(|path| (path.clone(), rules.clone())),
+            );
+            if cacheable {
+                visited.insert(dir, listing);
+            }
+        }
+
+        if !batch.is_empty() {
+            on_batch(&batch);
+        }
+        documents.append(&mut batch);
+        level = next_level;
+    }
+
+    // Drop entries for directories below root that no longer exist
+    cache.dirs.retain(|dir, _| !dir.starts_with(root));
+    cache.dirs.extend(visited);
+
+    documents.sort();
+    Ok(documents)
+}
+
+/// Recursively lists every document below `root`, without caching.
+pub fn document_files(root: &Path) -> AppResult<Vec<PathBuf>> {
+    scan(root, &mut ScanCache::default(), |_| {})
+}
+
+fn get_cache_path(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    let app_data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::io(format!("Failed to get app data directory: {}", e)))?;
+
+    std::fs::create_dir_all(&app_data_dir)
+        .map_err(|e| AppError::io(format!("Failed to create app data directory: {}", e)))?;
+
+    Ok(app_data_dir.join("scan_cache.json"))
+}
+
+/// Scans a workspace using the persisted cache, emitting
+/// "workspace-scan-batch" as results come in. How many documents were
+/// found so far goes to `operation`, as the total isn't known up front.
+pub fn scan_workspace(app_handle: &AppHandle, root: &Path, operation: &Operation) -> AppResult<Vec<PathBuf>> {
+    let cache_path = get_cache_path(app_handle)?;
+    let mut cache: ScanCache = sidecar::read_json(&cache_path).unwrap_or_default();
+
+    let mut found = 0;
+    let documents = scan(root, &mut cache, |documents| {
+        found += documents.len();
+        let _ = app_handle.emit("workspace-scan-batch", &ScanBatch { root, documents });
+        operation.report(None, format!("{} documents found", found));
+    })?;
+
+    sidecar::write_json(&cache_path, &cache)?;
+    Ok(documents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_scan_reuses_unchanged_directories() {
+        let root = std::env::temp_dir().join("test_workspace_scan");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("notes/deep")).unwrap();
+        fs::create_dir_all(root.join(".trash")).unwrap();
+        fs::write(root.join("a.canvas"), "").unwrap();
+        fs::write(root.join("notes/deep/b.md"), "").unwrap();
+        fs::write(root.join("notes/image.png"), "").unwrap();
+        fs::write(root.join(".trash/c.md"), "").unwrap();
+
+        let mut cache = ScanCache::default();
+        let mut batches = 0;
+        let documents = scan(&root, &mut cache, |_| batches += 1).unwrap();
+        assert_eq!(documents, vec![root.join("a.canvas"), root.join("notes/deep/b.md")]);
+        assert_eq!(batches, 2);
+
+        // Freshly written directories haven't settled, so nothing is cached
+        assert!(cache.dirs.is_empty());
+
+        // A cached listing is trusted while the directory's mtime matches
+        let deep = root.join("notes/deep");
+        let modified = modified_millis(&fs::metadata(&deep).unwrap()).unwrap();
+        cache.dirs.insert(deep.clone(), CachedDir { modified, documents: Vec::new(), subdirs: Vec::new(), has_ignore_file: false });
+        assert_eq!(scan(&root, &mut cache, |_| {}).unwrap(), vec![root.join("a.canvas")]);
+
+        cache.dirs.get_mut(&deep).unwrap().modified -= 1;
+        assert_eq!(scan(&root, &mut cache, |_| {}).unwrap().len(), 2);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_scan_skips_ignored() {
+        let root = std::env::temp_dir().join("test_workspace_ignore");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("app/node_modules/pkg")).unwrap();
+        fs::create_dir_all(root.join("notes/drafts")).unwrap();
+        fs::write(root.join(".canvasignore"), "drafts/\n*.bak.md\n").unwrap();
+        fs::write(root.join("notes/.gitignore"), "!keep.bak.md\n").unwrap();
+        fs::write(root.join("app/node_modules/pkg/README.md"), "").unwrap();
+        fs::write(root.join("notes/drafts/idea.md"), "").unwrap();
+        fs::write(root.join("notes/plan.md"), "").unwrap();
+        fs::write(root.join("notes/plan.bak.md"), "").unwrap();
+        fs::write(root.join("notes/keep.bak.md"), "").unwrap();
+        fs::write(root.join("notes/.plan.md.swp"), "").unwrap();
+        fs::write(root.join("notes/~$plan.md"), "").unwrap();
+
+        let documents = document_files(&root).unwrap();
+        assert_eq!(documents, vec![root.join("notes/keep.bak.md"), root.join("notes/plan.md")]);
+
+        // Starting below the ignore file still applies it when given
+        let notes = root.join("notes");
+        let rules = IgnoreRules::for_dir(Some(&root), &notes);
+        assert_eq!(scan_from(&notes, rules, &mut ScanCache::default(), |_| {}).unwrap().len(), 2);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -3,14 +3,15 @@ import React from "react";
 import ReactDOM from "react-dom/client";
 import { initializeContentTypes } from "./content/registry";
 
-// Show window as soon as Tauri API is available
-async function showWindowWhenTauriReady() {
+// Tell the backend the first render is done; it shows the window once its
+// own startup work has finished too
+async function reportFrontendReady() {
   try {
     const { invoke } = await import('@tauri-apps/api/core');
-    await invoke('show_window_when_ready');
-    console.log('🚀 Window shown immediately when Tauri API ready');
+    const status = await invoke<{ backend_ready: boolean; completed_phases: string[] }>('frontend_ready');
+    console.log('🚀 Frontend ready, backend startup phases done:', status.completed_phases);
   } catch (error) {
-    console.log('Not running in Tauri or window show command unavailable:', error);
+    console.log('Not running in Tauri or frontend_ready command unavailable:', error);
   }
 }
 
@@ -28,11 +29,11 @@ if (typeof window !== 'undefined') {
   console.log('🛠️ Debug: Use clearAppStorage() in console to clear all data');
 }
 
-// Call immediately since main.tsx only loads when Tauri API is ready
-showWindowWhenTauriReady();
-
 ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
   <React.StrictMode>
     <App />
   </React.StrictMode>,
 );
+
+// Wait for the first frame to be painted before asking to be shown
+requestAnimationFrame(() => requestAnimationFrame(() => reportFrontendReady()));
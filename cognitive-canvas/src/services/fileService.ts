@@ -1,4 +1,4 @@
-import { invoke } from '@tauri-apps/api/core';
+import { Channel, invoke } from '@tauri-apps/api/core';
 import { open, save } from '@tauri-apps/plugin-dialog';
 
 export interface DocumentData {
@@ -6,6 +6,53 @@ export interface DocumentData {
   title: string;
   content: string;
   file_path?: string;
+  modified?: number;
+  content_hash?: string;
+  metadata?: Record<string, unknown>;
+  properties?: DocumentMetadata;
+  schema?: SchemaReport;
+  // Set when another instance or machine has the document open
+  lock?: DocumentLock;
+}
+
+export interface DocumentLock {
+  pid: number;
+  hostname: string;
+  acquired_at: string;
+  refreshed_at: string;
+}
+
+export interface SchemaReport {
+  schema_version?: number;
+  errors: { path: string; expected: string; found: string }[];
+  repairs: { path: string; description: string }[];
+}
+
+export interface DocumentMetadata {
+  created?: string;
+  modified?: string;
+  tags: string[];
+  fields: Record<string, unknown>;
+}
+
+export interface MetadataPatch {
+  tags?: string[];
+  // A field set to null is removed
+  fields?: Record<string, unknown>;
+}
+
+export interface DocumentHeader {
+  title: string;
+  file_path: string;
+  size: number;
+  modified?: number;
+  content_hash: string;
+}
+
+export interface SavedDocument {
+  file_path: string;
+  modified: number;
+  content_hash: string;
 }
 
 class FileService {
@@ -37,16 +84,21 @@ class FileService {
         }
       }
 
-      const result = await invoke<string>('save_document', { 
+      const result = await invoke<SavedDocument>('save_document', { 
         document: {
           id: document.id,
           title: document.title,
           content: document.content,
-          file_path: filePath
+          file_path: filePath,
+          modified: document.modified,
+          content_hash: document.content_hash
         }
       });
       
-      return result;
+      // Track the version we just wrote so the next save can detect external edits
+      document.modified = result.modified;
+      document.content_hash = result.content_hash;
+      return result.file_path;
     } catch (error) {
       console.error('Save error:', error);
       throw error;
@@ -61,7 +113,7 @@ class FileService {
       const filePath = await open({
         filters: [{
           name: 'Canvas Files',
-          extensions: ['canvas', 'txt']
+          extensions: ['canvas', 'canvasb', 'txt']
         }],
         multiple: false
       });
@@ -89,6 +141,110 @@ class FileService {
     }
   }
 
+  // Streams the file as raw byte chunks instead of one large JSON string.
+  async loadDocumentChunked(path: string, onProgress?: (loaded: number) => void): Promise<DocumentData> {
+    try {
+      const decoder = new TextDecoder();
+      const parts: string[] = [];
+      let loaded = 0;
+
+      const onChunk = new Channel<ArrayBuffer>();
+      onChunk.onmessage = (chunk) => {
+        loaded += chunk.byteLength;
+        parts.push(decoder.decode(chunk, { stream: true }));
+        onProgress?.(loaded);
+      };
+
+      const header = await invoke<DocumentHeader>('load_document_chunked', { path, onChunk });
+      parts.push(decoder.decode());
+
+      return {
+        id: `doc-${Date.now()}`,
+        title: header.title,
+        content: parts.join(''),
+        file_path: header.file_path,
+        modified: header.modified,
+        content_hash: header.content_hash
+      };
+    } catch (error) {
+      console.error('Chunked load error:', error);
+      throw error;
+    }
+  }
+
+  // Pulls a file a chunk at a time; stop early by passing an aborted signal
+  async readFileStreamed(path: string, onChunk: (chunk: ArrayBuffer) => void, signal?: AbortSignal): Promise<void> {
+    const { id } = await invoke<{ id: number; size: number }>('open_read_stream', { path });
+    while (true) {
+      if (signal?.aborted) {
+        await invoke('cancel_stream', { id });
+        return;
+      }
+      const chunk = await invoke<ArrayBuffer>('read_chunk', { id });
+      if (chunk.byteLength === 0) {
+        return;
+      }
+      onChunk(chunk);
+    }
+  }
+
+  // Sends a large file in chunks; the file only appears once all of it was written
+  async writeFileStreamed(path: string, data: Blob, onProgress?: (written: number) => void, signal?: AbortSignal): Promise<void> {
+    const chunkSize = 1024 * 1024;
+    const id = await invoke<number>('open_write_stream', { path, overwrite: true });
+    try {
+      for (let offset = 0; offset < data.size; offset += chunkSize) {
+        if (signal?.aborted) {
+          throw new Error('Write cancelled');
+        }
+        const chunk = new Uint8Array(await data.slice(offset, offset + chunkSize).arrayBuffer());
+        const written = await invoke<number>('write_chunk', chunk, { headers: { 'stream-id': String(id) } });
+        onProgress?.(written);
+      }
+      await invoke('close_stream', { id });
+    } catch (error) {
+      await invoke('cancel_stream', { id }).catch(() => {});
+      throw error;
+    }
+  }
+
+  // Call when a document is closed
+  async releaseDocumentLock(path: string): Promise<void> {
+    try {
+      await invoke('release_document_lock', { path });
+    } catch (error) {
+      console.error('Release lock error:', error);
+    }
+  }
+
+  async updateDocumentMetadata(path: string, patch: MetadataPatch): Promise<DocumentMetadata> {
+    try {
+      return await invoke<DocumentMetadata>('update_document_metadata', { path, patch });
+    } catch (error) {
+      console.error('Update metadata error:', error);
+      throw error;
+    }
+  }
+
+  // Writes a .canvasb copy of a .canvas document or the other way round
+  async convertDocument(path: string): Promise<string> {
+    try {
+      return await invoke<string>('convert_document', { path });
+    } catch (error) {
+      console.error('Convert document error:', error);
+      throw error;
+    }
+  }
+
+  async readAsset(path: string): Promise<ArrayBuffer> {
+    try {
+      return await invoke<ArrayBuffer>('read_asset', { path });
+    } catch (error) {
+      console.error('Read asset error:', error);
+      throw error;
+    }
+  }
+
   async saveFile(path: string, contents: string): Promise<void> {
     try {
       await invoke('save_file', { path, contents });